@@ -14,22 +14,65 @@ use crate::{
 use color_eyre::Result;
 use crossterm::event::{Event, KeyCode, KeyEvent};
 use ratatui::{prelude::*, widgets::*};
+use sn_transfers::{RewardId, RewardIdKind};
+use std::str::FromStr;
 use tui_input::{backend::crossterm::EventHandler, Input};
 
+/// Input box for the identifier the rewards are tracked with, a Discord username by default.
+/// The type of identifier is cycled through with Tab.
 pub struct DiscordUsernameInputBox {
     show_scene: bool,
+    kind: RewardIdKind,
     discord_input_filed: Input,
     // cache the old value incase user presses Esc.
-    old_value: String,
+    old_value: (RewardIdKind, String),
 }
 
 impl DiscordUsernameInputBox {
+    /// `username` is in the `RewardId` format, i.e. `<type>:<value>` or a plain Discord username
     pub fn new(username: String) -> Self {
+        let (kind, value) = match RewardId::from_str(&username) {
+            Ok(reward_id) => (reward_id.kind, reward_id.value),
+            Err(_) => (RewardIdKind::Discord, username),
+        };
         Self {
             show_scene: false,
-            discord_input_filed: Input::default().with_value(username),
-            old_value: Default::default(),
+            kind,
+            discord_input_filed: Input::default().with_value(value),
+            old_value: (RewardIdKind::Discord, Default::default()),
+        }
+    }
+
+    /// The value to store, in the `RewardId` format
+    fn reward_id(&self) -> String {
+        let value = self.discord_input_filed.value();
+        if value.is_empty() {
+            return String::new();
         }
+        RewardId::new(self.kind, value).to_string()
+    }
+
+    fn max_len(&self) -> usize {
+        match self.kind {
+            // max 32 limit as per discord docs
+            RewardIdKind::Discord => 32,
+            RewardIdKind::Email => 254,
+            RewardIdKind::Wallet | RewardIdKind::Opaque | RewardIdKind::Other(_) => 128,
+        }
+    }
+
+    /// The next type that the rewards can be tracked with, only Discord without the
+    /// `typed-reward-tracking` feature
+    fn next_kind(&self) -> RewardIdKind {
+        let kinds = RewardIdKind::ALL
+            .into_iter()
+            .filter(|kind| kind.can_be_sent())
+            .collect::<Vec<_>>();
+        let index = kinds
+            .iter()
+            .position(|kind| *kind == self.kind)
+            .unwrap_or_default();
+        kinds[(index + 1) % kinds.len()]
     }
 }
 
@@ -38,23 +81,29 @@ impl Component for DiscordUsernameInputBox {
         // while in entry mode, keybinds are not captured, so gotta exit entry mode from here
         let send_back = match key.code {
             KeyCode::Enter => {
-                let username = self.discord_input_filed.value().to_string();
-                debug!("Got Enter, saving the discord username {username:?} and switching scene",);
+                let username = self.reward_id();
+                debug!("Got Enter, saving the reward id {username:?} and switching scene",);
                 vec![
-                    Action::StoreDiscordUserName(self.discord_input_filed.value().to_string()),
+                    Action::StoreDiscordUserName(username),
                     Action::SwitchScene(Scene::Home),
                 ]
             }
+            KeyCode::Tab => {
+                self.kind = self.next_kind();
+                debug!("Got Tab, switching the reward id type to {}", self.kind);
+                vec![]
+            }
             KeyCode::Esc => {
                 debug!(
-                    "Got Esc, restoring the old value {} and switching to home",
+                    "Got Esc, restoring the old value {:?} and switching to home",
                     self.old_value
                 );
                 // reset to old value
+                self.kind = self.old_value.0;
                 self.discord_input_filed = self
                     .discord_input_filed
                     .clone()
-                    .with_value(self.old_value.clone());
+                    .with_value(self.old_value.1.clone());
                 vec![Action::SwitchScene(Scene::Home)]
             }
             KeyCode::Char(' ') => vec![],
@@ -64,8 +113,7 @@ impl Component for DiscordUsernameInputBox {
                 vec![]
             }
             _ => {
-                // max 32 limit as per discord docs
-                if self.discord_input_filed.value().len() >= 32 {
+                if self.discord_input_filed.value().len() >= self.max_len() {
                     return Ok(vec![]);
                 }
                 self.discord_input_filed.handle_event(&Event::Key(key));
@@ -80,7 +128,7 @@ impl Component for DiscordUsernameInputBox {
             Action::SwitchScene(scene) => match scene {
                 Scene::DiscordUsernameInputBox => {
                     self.show_scene = true;
-                    self.old_value = self.discord_input_filed.value().to_string();
+                    self.old_value = (self.kind, self.discord_input_filed.value().to_string());
                     // set to entry input mode as we want to handle everything within our handle_key_events
                     // so by default if this scene is active, we capture inputs.
                     Some(Action::SwitchInputMode(InputMode::Entry))
@@ -121,7 +169,7 @@ impl Component for DiscordUsernameInputBox {
                 .borders(Borders::ALL)
                 .border_type(BorderType::Double)
                 .border_style(Style::new().bold())
-                .title("Enter Discord Username"),
+                .title(format!("Enter Reward ID ({})", self.kind)),
         );
         f.render_widget(Clear, layer_zero);
         f.render_widget(pop_up_border, layer_zero);
@@ -36,7 +36,7 @@ impl Component for Footer {
                 "[A]dd node, [S]tart node, [K]ill node, [R]emove node, [D]iscord Username, [Q]uit"
             }
             Scene::Options => "none",
-            Scene::DiscordUsernameInputBox => "⏎ Accept, [Esc] Cancel",
+            Scene::DiscordUsernameInputBox => "⏎ Accept, [Tab] Type, [Esc] Cancel",
         };
 
         f.render_widget(
@@ -94,6 +94,7 @@ impl Component for Home {
                         peers,
                         None,
                         None,
+                        false,
                         None,
                         None,
                         None,
@@ -0,0 +1,451 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use async_trait::async_trait;
+use color_eyre::eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use sn_client::transfers::SignedSpend;
+use sn_client::SpendFault;
+use std::{
+    io::Write,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{dag_db::fault_kind, dag_store::SpendDagStore, metrics::AuditorMetrics};
+
+/// The number of attempts made to deliver alerts to a webhook
+const WEBHOOK_ATTEMPTS: u32 = 3;
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A fault newly detected in the DAG
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FaultAlert {
+    /// When the fault was detected, in seconds since the UNIX epoch
+    pub detected_at: u64,
+    /// One of `FAULT_KINDS`
+    pub kind: String,
+    pub description: String,
+    /// The address of the faulty spend
+    pub address: String,
+    /// The other addresses involved in the fault, e.g. the missing or double spent ancestor
+    pub related_addresses: Vec<String>,
+    /// The spends found at the faulty address, more than one for a double spend
+    pub spends: Vec<AlertSpend>,
+    /// The sum of the amounts of the spends, in nanos
+    pub total_amount: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AlertSpend {
+    pub address: String,
+    /// In nanos
+    pub amount: u64,
+}
+
+impl FaultAlert {
+    /// Create an alert for a fault, with the spends found at the faulty address
+    pub fn new(fault: &SpendFault, spends: &[SignedSpend]) -> Self {
+        let address = fault.spend_address();
+        let related_addresses = match fault {
+            SpendFault::MissingAncestry { ancestor, .. }
+            | SpendFault::DoubleSpentAncestor { ancestor, .. } => vec![ancestor.to_hex()],
+            SpendFault::OrphanSpend { src, .. } => vec![src.to_hex()],
+            SpendFault::DoubleSpend(_)
+            | SpendFault::InvalidTransaction(..)
+            | SpendFault::PoisonedAncestry(..) => vec![],
+        };
+        let spends: Vec<AlertSpend> = spends
+            .iter()
+            .map(|spend| AlertSpend {
+                address: spend.address().to_hex(),
+                amount: spend.spend.amount.as_nano(),
+            })
+            .collect();
+
+        Self {
+            detected_at: unix_timestamp(),
+            kind: fault_kind(fault).to_string(),
+            description: fault.to_string(),
+            address: address.to_hex(),
+            related_addresses,
+            total_amount: spends.iter().map(|s| s.amount).sum(),
+            spends,
+        }
+    }
+}
+
+/// A destination for fault alerts
+#[async_trait]
+pub trait AlertSink: Send + Sync {
+    /// A short name to identify the sink in the logs
+    fn name(&self) -> String;
+
+    /// Deliver a batch of alerts
+    async fn send(&self, alerts: &[FaultAlert]) -> Result<()>;
+}
+
+/// POSTs the alerts as JSON to a URL: `{"alerts": [...]}`
+pub struct WebhookSink {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl WebhookSink {
+    pub fn new(url: String) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .map_err(|e| eyre!("Failed to create webhook client: {e}"))?;
+        Ok(Self { url, client })
+    }
+}
+
+#[async_trait]
+impl AlertSink for WebhookSink {
+    fn name(&self) -> String {
+        format!("webhook {}", self.url)
+    }
+
+    async fn send(&self, alerts: &[FaultAlert]) -> Result<()> {
+        let payload = serde_json::json!({ "alerts": alerts });
+        let mut last_error = None;
+        for attempt in 1..=WEBHOOK_ATTEMPTS {
+            match self
+                .client
+                .post(&self.url)
+                .json(&payload)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+            {
+                Ok(_) => return Ok(()),
+                Err(e) => {
+                    warn!(
+                        "Attempt {attempt} to POST alerts to {} failed: {e}",
+                        self.url
+                    );
+                    last_error = Some(e);
+                    tokio::time::sleep(Duration::from_secs(u64::from(attempt))).await;
+                }
+            }
+        }
+        Err(eyre!(
+            "Failed to POST alerts to {}: {last_error:?}",
+            self.url
+        ))
+    }
+}
+
+/// Appends the alerts to a local file, one JSON object per line
+pub struct FaultLogSink {
+    path: PathBuf,
+}
+
+impl FaultLogSink {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+#[async_trait]
+impl AlertSink for FaultLogSink {
+    fn name(&self) -> String {
+        format!("fault log {:?}", self.path)
+    }
+
+    async fn send(&self, alerts: &[FaultAlert]) -> Result<()> {
+        let mut lines = Vec::new();
+        for alert in alerts {
+            serde_json::to_writer(&mut lines, alert)?;
+            lines.push(b'\n');
+        }
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(&lines)?;
+        file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// Counts the alerts per fault kind, exposed in the Prometheus text format
+struct MetricsSink {
+    metrics: AuditorMetrics,
+}
+
+#[async_trait]
+impl AlertSink for MetricsSink {
+    fn name(&self) -> String {
+        "metrics".to_string()
+    }
+
+    async fn send(&self, alerts: &[FaultAlert]) -> Result<()> {
+        for alert in alerts {
+            self.metrics.record_fault(&alert.kind);
+        }
+        Ok(())
+    }
+}
+
+/// Fans out the newly detected faults to all the configured sinks.
+/// The fault counters of the metrics are always updated.
+#[derive(Clone)]
+pub struct FaultAlerter {
+    sinks: Vec<Arc<dyn AlertSink>>,
+}
+
+impl FaultAlerter {
+    pub fn new(mut sinks: Vec<Box<dyn AlertSink>>, metrics: AuditorMetrics) -> Self {
+        sinks.insert(0, Box::new(MetricsSink { metrics }));
+        Self {
+            sinks: sinks.into_iter().map(Arc::from).collect(),
+        }
+    }
+
+    /// Send alerts for the given faults to all the sinks in the background, each in its own task so
+    /// a slow sink doesn't hold up the others
+    pub fn notify(&self, faults: &[SpendFault], store: &SpendDagStore) {
+        if faults.is_empty() {
+            return;
+        }
+        let alerts: Vec<FaultAlert> = faults
+            .iter()
+            .map(|f| {
+                let spends = store.get_spends(&f.spend_address()).unwrap_or_else(|e| {
+                    warn!("Failed to read the spends of {f:?} from the store: {e}");
+                    vec![]
+                });
+                FaultAlert::new(f, &spends)
+            })
+            .collect();
+        for alert in &alerts {
+            warn!("Detected {}: {}", alert.kind, alert.description);
+        }
+        println!("Detected {} new faults in the spend DAG", alerts.len());
+
+        let alerts = Arc::new(alerts);
+        for sink in &self.sinks {
+            let sink = sink.clone();
+            let alerts = alerts.clone();
+            let _handle = tokio::spawn(async move {
+                if let Err(e) = sink.send(&alerts).await {
+                    error!(
+                        "Failed to send {} alerts to {}: {e}",
+                        alerts.len(),
+                        sink.name()
+                    );
+                }
+            });
+        }
+    }
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_client::transfers::{
+        bls::SecretKey, rand, DerivationIndex, MainSecretKey, NanoTokens, OfflineTransfer,
+        SpendAddress, SpendReason, GENESIS_CASHNOTE, GENESIS_CASHNOTE_SK,
+    };
+    use std::sync::Mutex;
+
+    /// Spend genesis to a new key, a different `amount` giving a different spend
+    fn genesis_spend(amount: u64) -> Result<SignedSpend> {
+        let genesis_sk = MainSecretKey::new(SecretKey::from_hex(GENESIS_CASHNOTE_SK)?);
+        let key = GENESIS_CASHNOTE.derived_key(&genesis_sk)?;
+        let transfer = OfflineTransfer::new(
+            vec![(GENESIS_CASHNOTE.clone(), Some(key))],
+            vec![(
+                NanoTokens::from(amount),
+                Default::default(),
+                MainSecretKey::new(SecretKey::random()).main_pubkey(),
+                DerivationIndex::random(&mut rand::thread_rng()),
+            )],
+            genesis_sk.main_pubkey(),
+            SpendReason::default(),
+        )?;
+        transfer
+            .all_spend_requests
+            .into_iter()
+            .next()
+            .ok_or_else(|| eyre!("The transfer should have a spend"))
+    }
+
+    fn alert(kind: &str) -> FaultAlert {
+        FaultAlert {
+            detected_at: 1,
+            kind: kind.to_string(),
+            description: format!("a {kind}"),
+            address: "00".to_string(),
+            related_addresses: vec![],
+            spends: vec![],
+            total_amount: 0,
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingSink {
+        received: Arc<Mutex<Vec<FaultAlert>>>,
+    }
+
+    #[async_trait]
+    impl AlertSink for RecordingSink {
+        fn name(&self) -> String {
+            "recording".to_string()
+        }
+
+        async fn send(&self, alerts: &[FaultAlert]) -> Result<()> {
+            self.received
+                .lock()
+                .map_err(|e| eyre!("Failed to lock: {e}"))?
+                .extend_from_slice(alerts);
+            Ok(())
+        }
+    }
+
+    /// A sink that never finishes sending, like an unresponsive webhook
+    struct StalledSink;
+
+    #[async_trait]
+    impl AlertSink for StalledSink {
+        fn name(&self) -> String {
+            "stalled".to_string()
+        }
+
+        async fn send(&self, _alerts: &[FaultAlert]) -> Result<()> {
+            std::future::pending().await
+        }
+    }
+
+    #[test]
+    fn alert_should_describe_the_spends_at_the_faulty_address() -> Result<()> {
+        let (spend1, spend2) = (genesis_spend(1000)?, genesis_spend(2000)?);
+        let address = spend1.address();
+        let alert = FaultAlert::new(
+            &SpendFault::DoubleSpend(address),
+            &[spend1.clone(), spend2.clone()],
+        );
+
+        assert_eq!(alert.kind, fault_kind(&SpendFault::DoubleSpend(address)));
+        assert_eq!(alert.address, address.to_hex());
+        assert!(alert.related_addresses.is_empty());
+        assert_eq!(alert.spends.len(), 2);
+        assert!(alert.spends.iter().all(|s| s.address == address.to_hex()));
+        assert_eq!(
+            alert.total_amount,
+            spend1.spend.amount.as_nano() + spend2.spend.amount.as_nano()
+        );
+
+        // the ancestor of the fault is listed as related
+        let ancestor = SpendAddress::new(xor_name::XorName([7; 32]));
+        let alert = FaultAlert::new(
+            &SpendFault::MissingAncestry {
+                addr: address,
+                ancestor,
+            },
+            &[],
+        );
+        assert_eq!(alert.related_addresses, vec![ancestor.to_hex()]);
+        assert!(alert.spends.is_empty());
+        assert_eq!(alert.total_amount, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fault_log_sink_should_append_one_json_alert_per_line() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("alerts").join("faults.jsonl");
+        let sink = FaultLogSink::new(path.clone());
+
+        sink.send(&[alert("double_spend")]).await?;
+        sink.send(&[alert("orphan_spend"), alert("missing_ancestry")])
+            .await?;
+
+        let kinds = std::fs::read_to_string(&path)?
+            .lines()
+            .map(|line| Ok(serde_json::from_str::<FaultAlert>(line)?.kind))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            kinds,
+            vec!["double_spend", "orphan_spend", "missing_ancestry"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn notify_should_send_the_faults_to_every_sink() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = SpendDagStore::open(dir.path().to_path_buf())?;
+        let (first, second) = (RecordingSink::default(), RecordingSink::default());
+        let received = [first.received.clone(), second.received.clone()];
+        let alerter = FaultAlerter::new(
+            vec![Box::new(first), Box::new(second)],
+            AuditorMetrics::new(),
+        );
+
+        let address = genesis_spend(1000)?.address();
+        alerter.notify(&[], &store);
+        alerter.notify(&[SpendFault::DoubleSpend(address)], &store);
+
+        // the alerts are sent in the background
+        for _ in 0..50 {
+            if received
+                .iter()
+                .all(|r| r.lock().map(|r| !r.is_empty()).unwrap_or(false))
+            {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        for received in received {
+            let received = received.lock().map_err(|e| eyre!("Failed to lock: {e}"))?;
+            assert_eq!(received.len(), 1);
+            assert_eq!(received[0].address, address.to_hex());
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn notify_should_not_let_a_stalled_sink_hold_up_the_others() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = SpendDagStore::open(dir.path().to_path_buf())?;
+        let sink = RecordingSink::default();
+        let received = sink.received.clone();
+        let alerter = FaultAlerter::new(
+            vec![Box::new(StalledSink), Box::new(sink)],
+            AuditorMetrics::new(),
+        );
+
+        let address = genesis_spend(1000)?.address();
+        alerter.notify(&[SpendFault::DoubleSpend(address)], &store);
+
+        for _ in 0..50 {
+            if received.lock().map(|r| !r.is_empty()).unwrap_or(false) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let received = received.lock().map_err(|e| eyre!("Failed to lock: {e}"))?;
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].address, address.to_hex());
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! The JSON API of the auditor, served under `/api/v1`.
+//! The routes are described by the OpenAPI document served at `/api/v1/openapi.json`.
+
+use color_eyre::eyre::Result;
+use serde::Serialize;
+use sn_client::transfers::{DerivationIndex, MainPubkey, SpendAddress};
+use std::{collections::BTreeMap, io::Cursor, str::FromStr};
+use tiny_http::{Header, Method, Request, Response};
+
+use crate::dag_db::{SpendDagDb, FAULT_KINDS};
+use crate::royalties::ROYALTY_ISSUE_KINDS;
+
+pub(crate) const API_PREFIX: &str = "/api/v1";
+
+const OPENAPI_SPEC: &str = include_str!("openapi.json");
+
+/// The number of items returned by default by the paginated routes
+const DEFAULT_PAGE_LIMIT: usize = 100;
+/// The maximum number of items returned by the paginated routes
+const MAX_PAGE_LIMIT: usize = 1000;
+/// The number of generations returned by default by the ancestry routes
+const DEFAULT_DEPTH: usize = 5;
+/// The maximum number of generations returned by the ancestry routes
+const MAX_DEPTH: usize = 100;
+
+type ApiResponse = Response<Cursor<Vec<u8>>>;
+
+#[derive(Debug)]
+enum ApiError {
+    BadRequest(String),
+    NotFound(String),
+    Internal(color_eyre::Report),
+}
+
+impl From<color_eyre::Report> for ApiError {
+    fn from(error: color_eyre::Report) -> Self {
+        Self::Internal(error)
+    }
+}
+
+type ApiResult<T> = std::result::Result<T, ApiError>;
+
+/// The URL decoded query parameters of a request
+type Query = BTreeMap<String, String>;
+
+pub(crate) fn handle(dag: &SpendDagDb, request: &Request) -> Result<ApiResponse> {
+    if request.method() != &Method::Get {
+        return Ok(error_response(405, "Only GET requests are supported"));
+    }
+
+    let (path, query) = match request.url().split_once('?') {
+        Some((path, query)) => (path, parse_query(query)),
+        None => (request.url(), Query::new()),
+    };
+    let response = match route(dag, path, &query) {
+        Ok(json) => Response::from_string(json).with_header(json_content_type()),
+        Err(ApiError::BadRequest(message)) => error_response(400, &message),
+        Err(ApiError::NotFound(message)) => error_response(404, &message),
+        Err(ApiError::Internal(e)) => {
+            error!("Failed to handle API request {:?}: {e}", request.url());
+            error_response(500, &e.to_string())
+        }
+    };
+    Ok(response)
+}
+
+fn route(dag: &SpendDagDb, path: &str, query: &Query) -> ApiResult<String> {
+    let route: Vec<&str> = path
+        .trim_start_matches(API_PREFIX)
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+
+    match route.as_slice() {
+        ["openapi.json"] => Ok(OPENAPI_SPEC.to_string()),
+        ["stats"] => to_json(&dag.stats()?),
+        ["utxos"] => {
+            let (offset, limit) = pagination(query)?;
+            to_json(&dag.utxos(offset, limit)?)
+        }
+        ["spends", addr] => to_json(&dag.spend_details(parse_spend_address(addr)?)?),
+        ["spends", addr, "ancestors"] => {
+            to_json(&dag.ancestors(parse_spend_address(addr)?, depth(query)?)?)
+        }
+        ["spends", addr, "descendants"] => {
+            to_json(&dag.descendants(parse_spend_address(addr)?, depth(query)?)?)
+        }
+        ["faults"] => {
+            let (offset, limit) = pagination(query)?;
+            let kind = query.get("kind").map(String::as_str);
+            if let Some(kind) = kind {
+                if !FAULT_KINDS.contains(&kind) {
+                    return Err(ApiError::BadRequest(format!(
+                        "Unknown fault kind {kind:?}, expected one of {FAULT_KINDS:?}"
+                    )));
+                }
+            }
+            to_json(&dag.faults(kind, offset, limit)?)
+        }
+        ["totals"] => to_json(&dag.purpose_totals()?),
+        ["supply"] => to_json(&dag.supply_report()?),
+        ["rewards"] => to_json(&dag.reward_tracking_totals()?),
+        ["royalties"] => to_json(&dag.royalties_summary()?),
+        ["royalties", "issues"] => {
+            let (offset, limit) = pagination(query)?;
+            let kind = query.get("kind").map(String::as_str);
+            if let Some(kind) = kind {
+                if !ROYALTY_ISSUE_KINDS.contains(&kind) {
+                    return Err(ApiError::BadRequest(format!(
+                        "Unknown royalty issue kind {kind:?}, expected one of {ROYALTY_ISSUE_KINDS:?}"
+                    )));
+                }
+            }
+            to_json(&dag.royalty_issues(kind, offset, limit)?)
+        }
+        ["pubkeys", pubkey, "history"] => {
+            let main_pubkey = MainPubkey::from_hex(pubkey)
+                .map_err(|e| ApiError::BadRequest(format!("Failed to parse MainPubkey: {e}")))?;
+            let derivation_indexes = parse_derivation_indexes(query)?;
+            let (offset, limit) = pagination(query)?;
+            to_json(&dag.pubkey_history(main_pubkey, &derivation_indexes, offset, limit)?)
+        }
+        _ => Err(ApiError::NotFound(format!(
+            "Unknown route, see {API_PREFIX}/openapi.json"
+        ))),
+    }
+}
+
+fn parse_query(query: &str) -> Query {
+    form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect()
+}
+
+fn parse_spend_address(addr: &str) -> ApiResult<SpendAddress> {
+    SpendAddress::from_str(addr)
+        .map_err(|e| ApiError::BadRequest(format!("Failed to parse address: {e}")))
+}
+
+fn parse_derivation_indexes(query: &Query) -> ApiResult<Vec<DerivationIndex>> {
+    let Some(indexes) = query.get("derivation_indexes") else {
+        return Ok(vec![]);
+    };
+    indexes
+        .split(',')
+        .filter(|idx| !idx.is_empty())
+        .map(|idx| {
+            hex::decode(idx)
+                .ok()
+                .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                .map(DerivationIndex)
+                .ok_or_else(|| {
+                    ApiError::BadRequest(format!(
+                        "Invalid derivation index {idx:?}, expected 32 hex encoded bytes"
+                    ))
+                })
+        })
+        .collect()
+}
+
+fn pagination(query: &Query) -> ApiResult<(usize, usize)> {
+    let offset = parse_usize(query, "offset")?.unwrap_or(0);
+    let limit = parse_usize(query, "limit")?
+        .unwrap_or(DEFAULT_PAGE_LIMIT)
+        .min(MAX_PAGE_LIMIT);
+    Ok((offset, limit))
+}
+
+fn depth(query: &Query) -> ApiResult<usize> {
+    Ok(parse_usize(query, "depth")?
+        .unwrap_or(DEFAULT_DEPTH)
+        .min(MAX_DEPTH))
+}
+
+fn parse_usize(query: &Query, key: &str) -> ApiResult<Option<usize>> {
+    query
+        .get(key)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|e| ApiError::BadRequest(format!("Invalid {key} {value:?}: {e}")))
+        })
+        .transpose()
+}
+
+fn to_json<T: Serialize>(value: &T) -> ApiResult<String> {
+    serde_json::to_string(value).map_err(|e| ApiError::Internal(e.into()))
+}
+
+fn error_response(status: u16, message: &str) -> ApiResponse {
+    let body = serde_json::json!({ "error": message }).to_string();
+    Response::from_string(body)
+        .with_status_code(status)
+        .with_header(json_content_type())
+}
+
+fn json_content_type() -> Header {
+    Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+        .expect("Static header to be valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_parameters_should_be_url_decoded() -> ApiResult<()> {
+        let index = hex::encode([1u8; 32]);
+        let query = parse_query(&format!(
+            "kind=double%5Fspend&derivation_indexes={index}%2C{index}&offset=10&limit"
+        ));
+        assert_eq!(query.get("kind").map(String::as_str), Some("double_spend"));
+        assert_eq!(parse_derivation_indexes(&query)?.len(), 2);
+        assert_eq!(parse_usize(&query, "offset")?, Some(10));
+        assert!(parse_usize(&query, "limit").is_err());
+        Ok(())
+    }
+}
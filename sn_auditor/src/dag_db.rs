@@ -10,25 +10,49 @@ use color_eyre::eyre::{eyre, Result};
 use graphviz_rust::{cmd::Format, exec, parse, printer::PrinterContext};
 use serde::{Deserialize, Serialize};
 use sn_client::networking::NetworkError;
-use sn_client::transfers::{SignedSpend, SpendAddress, GENESIS_CASHNOTE};
+use sn_client::transfers::{
+    DerivationIndex, Hash, MainPubkey, RewardIdKind, SignedSpend, SpendAddress, SpendReason,
+    UniquePubkey, GENESIS_CASHNOTE, NETWORK_ROYALTIES_PK,
+};
 use sn_client::Error as ClientError;
-use sn_client::{Client, SpendDag, SpendDagGet};
+use sn_client::{
+    Client, CrawlerCfg, SpendDag, SpendDagCrawler, SpendDagGet, SpendFault, SupplyReport,
+};
+
+use crate::alerts::FaultAlerter;
+use crate::dag_store::{SpendDagStore, SPEND_DAG_STORE_DIRNAME};
+use crate::metrics::AuditorMetrics;
+use crate::royalties::{RoyaltiesSummary, RoyaltyIssue, RoyaltyTracker, ROYALTIES_LEDGER_FILENAME};
 use std::fmt::Write;
 use std::{
+    collections::{BTreeMap, BTreeSet},
     path::PathBuf,
-    sync::{Arc, RwLock},
+    sync::{Arc, RwLock, RwLockReadGuard},
 };
+use tempfile::TempDir;
 
 pub const SPEND_DAG_FILENAME: &str = "spend_dag";
 pub const SPEND_DAG_SVG_FILENAME: &str = "spend_dag.svg";
+pub const CRAWLER_FRONTIER_FILENAME: &str = "crawler_frontier";
+
+/// The largest DAG rendered as svg when following the Network, rendering needs the whole DAG in memory
+const SVG_MAX_SPENDS: usize = 10_000;
 
 /// Abstraction for the Spend DAG database
-/// Currently in memory, with disk backup, but should probably be a real DB at scale
+/// The DAG lives in a `SpendDagStore` on disk, the crawler only gathers the new spends in memory
+/// before they are appended to the store
+/// The queries are served from the store
 #[derive(Clone)]
 pub struct SpendDagDb {
     client: Option<Client>,
     path: PathBuf,
-    dag: Arc<RwLock<SpendDag>>,
+    store: Arc<RwLock<SpendDagStore>>,
+    /// The temporary directory holding the store in offline mode, removed when dropped
+    _offline_dir: Option<Arc<TempDir>>,
+    alerter: FaultAlerter,
+    metrics: AuditorMetrics,
+    crawler_cfg: CrawlerCfg,
+    royalties: Arc<RwLock<RoyaltyTracker>>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -39,52 +63,197 @@ struct SpendJsonResponse {
     spends: Vec<SignedSpend>,
 }
 
+/// The kinds of `SpendFault`, as used to filter the faults
+pub const FAULT_KINDS: [&str; 6] = [
+    "double_spend",
+    "missing_ancestry",
+    "double_spent_ancestor",
+    "invalid_transaction",
+    "poisoned_ancestry",
+    "orphan_spend",
+];
+
+/// A page of a larger list of items
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub items: Vec<T>,
+}
+
+impl<T> Page<T> {
+    fn new(items: impl IntoIterator<Item = T>, offset: usize, limit: usize) -> Self {
+        let mut total = 0;
+        let mut page = Vec::new();
+        for item in items {
+            if total >= offset && page.len() < limit {
+                page.push(item);
+            }
+            total += 1;
+        }
+        Self {
+            total,
+            offset,
+            limit,
+            items: page,
+        }
+    }
+}
+
+/// A spend and what the DAG knows about it
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SpendDetails {
+    pub address: String,
+    /// One of "spend", "double_spend", "utxo" or "not_found"
+    pub status: String,
+    /// The purpose and amount of the output that created this spend
+    pub purpose: Option<String>,
+    pub amount: Option<u64>,
+    pub faults: Vec<FaultDetails>,
+    pub spends: Vec<SignedSpend>,
+}
+
+/// A spend related to another one, at `depth` generations from it
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Relative {
+    pub address: String,
+    pub depth: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FaultDetails {
+    pub address: String,
+    pub kind: String,
+    pub description: String,
+}
+
+/// The number of outputs and the total amount created for a purpose
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PurposeTotal {
+    pub purpose: String,
+    pub count: usize,
+    pub amount: u64,
+}
+
+/// The number of spends and the amount spent with a reward tracking reason, by type of identifier
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RewardTrackingTotal {
+    /// The type of identifier, see `RewardIdKind`
+    pub kind: String,
+    /// The version of the reason, 0 for the beta Discord only reason
+    pub version: u8,
+    pub spends: usize,
+    pub amount: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DagStats {
+    pub source: String,
+    pub spends: usize,
+    pub double_spends: usize,
+    pub utxos: usize,
+    pub not_gathered_yet: usize,
+    pub faults: usize,
+}
+
+/// An output that was sent to a MainPubkey
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PubkeyHistoryEntry {
+    /// The address of the output, which is the address it is spent at
+    pub address: String,
+    pub amount: u64,
+    pub purpose: String,
+    /// The address of the spend that created the output
+    pub created_by: String,
+    pub spent: bool,
+    pub royalty: bool,
+}
+
 impl SpendDagDb {
     /// Create a new SpendDagDb
-    /// If a local spend DAG file is found, it will be loaded
+    /// If a local spend DAG store is found, it is used as is
+    /// Else if a local spend DAG file (from older versions) is found, it will be loaded and imported into the store
     /// Else a new DAG will be created containing only Genesis
-    pub async fn new(path: PathBuf, client: Client) -> Result<Self> {
-        let dag_path = path.join(SPEND_DAG_FILENAME);
-        let dag = match SpendDag::load_from_file(&dag_path) {
+    /// The faults detected from then on are sent to the `alerter`
+    /// The DAG is updated with a crawler configured by `crawler_cfg`, its frontier is kept in `path`
+    pub async fn new(
+        path: PathBuf,
+        client: Client,
+        alerter: FaultAlerter,
+        metrics: AuditorMetrics,
+        crawler_cfg: CrawlerCfg,
+    ) -> Result<Self> {
+        let store = SpendDagStore::open(path.join(SPEND_DAG_STORE_DIRNAME))?;
+        let royalties = RoyaltyTracker::new(Some(path.join(ROYALTIES_LEDGER_FILENAME)))?;
+        metrics.record_royalties(&royalties.summary());
+        let crawler_cfg = CrawlerCfg {
+            frontier_path: Some(path.join(CRAWLER_FRONTIER_FILENAME)),
+            ..crawler_cfg
+        };
+        let has_source = store.meta().source.is_some();
+        let db = Self {
+            client: Some(client.clone()),
+            path,
+            store: Arc::new(RwLock::new(store)),
+            _offline_dir: None,
+            alerter,
+            metrics,
+            crawler_cfg,
+            royalties: Arc::new(RwLock::new(royalties)),
+        };
+
+        if has_source {
+            println!("Found a local spend DAG store");
+            return Ok(db);
+        }
+        let dag = match SpendDag::load_from_file(db.path.join(SPEND_DAG_FILENAME)) {
             Ok(d) => {
-                println!("Found a local spend DAG file");
+                println!("Found a local spend DAG file, importing it into the store");
                 d
             }
             Err(_) => {
-                println!("Found no local spend DAG file, starting from Genesis");
+                println!("Found no local spend DAG, starting from Genesis");
                 new_dag_with_genesis_only(&client).await?
             }
         };
-
-        Ok(Self {
-            client: Some(client),
-            path,
-            dag: Arc::new(RwLock::new(dag)),
-        })
+        // the faults found in the history are not news, later runs only alert on the faults that are
+        // not in the faults log of the store yet
+        db.persist_without_alerts(&dag)?;
+        Ok(db)
     }
 
     /// Create a new SpendDagDb from a local file and no network connection
+    /// The store backing the queries is created in a temporary directory
     pub fn offline(dag_path: PathBuf) -> Result<Self> {
         let path = dag_path
             .parent()
             .ok_or_else(|| eyre!("Failed to get parent path"))?
             .to_path_buf();
         let dag = SpendDag::load_from_file(&dag_path)?;
-        Ok(Self {
+
+        let offline_dir = tempfile::tempdir()?;
+        let store = SpendDagStore::open(offline_dir.path().join(SPEND_DAG_STORE_DIRNAME))?;
+        let metrics = AuditorMetrics::new();
+        let db = Self {
             client: None,
             path,
-            dag: Arc::new(RwLock::new(dag)),
-        })
+            store: Arc::new(RwLock::new(store)),
+            _offline_dir: Some(Arc::new(offline_dir)),
+            alerter: FaultAlerter::new(vec![], metrics.clone()),
+            metrics,
+            crawler_cfg: CrawlerCfg::default(),
+            royalties: Arc::new(RwLock::new(RoyaltyTracker::new(None)?)),
+        };
+        db.persist_without_alerts(&dag)?;
+        Ok(db)
     }
 
     /// Get info about a single spend in JSON format
     pub fn spend_json(&self, address: SpendAddress) -> Result<String> {
-        let dag_ref = self.dag.clone();
-        let r_handle = dag_ref
-            .read()
-            .map_err(|e| eyre!("Failed to get read lock: {e}"))?;
-        let spend = r_handle.get_spend(&address);
-        let faults = r_handle.get_spend_faults(&address);
+        let store = self.read_store()?;
+        let spends = store.get_spends(&address)?;
+        let faults = store.get_spend_faults(&address);
         let fault = if faults.is_empty() {
             "none".to_string()
         } else {
@@ -94,11 +263,11 @@ impl SpendDagDb {
             })
         };
 
-        let (spend_type, spends) = match spend {
-            SpendDagGet::SpendNotFound => ("SpendNotFound", vec![]),
-            SpendDagGet::Utxo => ("Utxo", vec![]),
-            SpendDagGet::DoubleSpend(vs) => ("DoubleSpend", vs),
-            SpendDagGet::Spend(s) => ("Spend", vec![*s]),
+        let spend_type = match spends.len() {
+            0 if store.meta().utxos.contains_key(&address) => "Utxo",
+            0 => "SpendNotFound",
+            1 => "Spend",
+            _ => "DoubleSpend",
         };
 
         let spend_json = SpendJsonResponse {
@@ -112,18 +281,295 @@ impl SpendDagDb {
         Ok(json)
     }
 
-    /// Dump DAG to disk
-    pub fn dump(&self) -> Result<()> {
-        std::fs::create_dir_all(&self.path)?;
-        let dag_path = self.path.join(SPEND_DAG_FILENAME);
-        let dag_ref = self.dag.clone();
-        let r_handle = dag_ref
+    /// Get a spend along with its status, creation reason and faults
+    pub fn spend_details(&self, address: SpendAddress) -> Result<SpendDetails> {
+        let store = self.read_store()?;
+        let spends = store.get_spends(&address)?;
+        let utxo_reason = store.meta().utxos.get(&address);
+        let status = match spends.len() {
+            0 if utxo_reason.is_some() => "utxo",
+            0 => "not_found",
+            1 => "spend",
+            _ => "double_spend",
+        };
+        // the creation reason of a spend is found in the outputs of its parent transaction
+        let creation_reason = spends
+            .iter()
+            .flat_map(|s| s.spend.parent_tx.outputs.iter())
+            .find(|o| SpendAddress::from_unique_pubkey(&o.unique_pubkey) == address)
+            .map(|o| (o.purpose.clone(), o.amount))
+            .or_else(|| utxo_reason.cloned());
+
+        Ok(SpendDetails {
+            address: address.to_hex(),
+            status: status.to_string(),
+            purpose: creation_reason.as_ref().map(|(purpose, _)| purpose.clone()),
+            amount: creation_reason.map(|(_, amount)| amount.as_nano()),
+            faults: store
+                .get_spend_faults(&address)
+                .iter()
+                .map(fault_details)
+                .collect(),
+            spends,
+        })
+    }
+
+    /// Get the UTXOs of the DAG, ordered by address
+    pub fn utxos(&self, offset: usize, limit: usize) -> Result<Page<String>> {
+        let store = self.read_store()?;
+        Ok(Page::new(
+            store.meta().utxos.keys().map(|addr| addr.to_hex()),
+            offset,
+            limit,
+        ))
+    }
+
+    /// Get the ancestors of a spend, up to `depth` generations back
+    pub fn ancestors(&self, address: SpendAddress, depth: usize) -> Result<Vec<Relative>> {
+        let store = self.read_store()?;
+        let source = store.meta().source;
+        let relatives = walk_relatives(&store, address, depth, |spend| {
+            if Some(spend.address()) == source {
+                return vec![];
+            }
+            spend
+                .spend
+                .parent_tx
+                .inputs
+                .iter()
+                .map(|i| SpendAddress::from_unique_pubkey(&i.unique_pubkey))
+                .collect()
+        })?;
+        Ok(to_relatives(relatives))
+    }
+
+    /// Get the descendants of a spend, up to `depth` generations forward
+    pub fn descendants(&self, address: SpendAddress, depth: usize) -> Result<Vec<Relative>> {
+        let store = self.read_store()?;
+        let relatives = walk_relatives(&store, address, depth, |spend| {
+            spend
+                .spend
+                .spent_tx
+                .outputs
+                .iter()
+                .map(|o| SpendAddress::from_unique_pubkey(&o.unique_pubkey))
+                .collect()
+        })?;
+        Ok(to_relatives(relatives))
+    }
+
+    /// Get the faults recorded in the DAG, optionally only the ones of a given kind (see `FAULT_KINDS`)
+    pub fn faults(
+        &self,
+        kind: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Page<FaultDetails>> {
+        let store = self.read_store()?;
+        let faults = store
+            .faults()
+            .values()
+            .flatten()
+            .filter(|fault| match kind {
+                Some(kind) => fault_kind(fault) == kind,
+                None => true,
+            })
+            .map(fault_details);
+        Ok(Page::new(faults, offset, limit))
+    }
+
+    /// Get the number of outputs and the total amount created for each purpose
+    pub fn purpose_totals(&self) -> Result<Vec<PurposeTotal>> {
+        let store = self.read_store()?;
+        Ok(store
+            .meta()
+            .purpose_totals
+            .iter()
+            .map(|(purpose, (count, amount))| PurposeTotal {
+                purpose: purpose.clone(),
+                count: *count,
+                amount: amount.as_nano(),
+            })
+            .collect())
+    }
+
+    /// Get the spends made with a reward tracking reason, by type of identifier and version of the reason.
+    /// The identifiers themselves are encrypted to the Foundation and not decrypted here.
+    pub fn reward_tracking_totals(&self) -> Result<Vec<RewardTrackingTotal>> {
+        let store = self.read_store()?;
+        let mut totals: BTreeMap<(RewardIdKind, u8), (usize, u64)> = BTreeMap::new();
+        store.for_each_spend(|spend| {
+            let key = match &spend.spend.reason {
+                SpendReason::BetaRewardTracking(_) => (RewardIdKind::Discord, 0),
+                SpendReason::RewardTracking(cipher) => (cipher.kind(), cipher.version()),
+                _ => return,
+            };
+            let (spends, amount) = totals.entry(key).or_default();
+            *spends += 1;
+            *amount = amount.saturating_add(spend.spend.amount.as_nano());
+        })?;
+        Ok(totals
+            .into_iter()
+            .map(|((kind, version), (spends, amount))| RewardTrackingTotal {
+                kind: kind.to_string(),
+                version,
+                spends,
+                amount,
+            })
+            .collect())
+    }
+
+    /// Get statistics about the content of the DAG
+    pub fn stats(&self) -> Result<DagStats> {
+        let store = self.read_store()?;
+        let meta = store.meta();
+        Ok(DagStats {
+            source: meta.source.map(|s| s.to_hex()).unwrap_or_default(),
+            spends: store.spends_count(),
+            double_spends: store.double_spends_count(),
+            utxos: meta.utxos.len(),
+            not_gathered_yet: meta.not_gathered_yet,
+            faults: store.faults().values().map(|faults| faults.len()).sum(),
+        })
+    }
+
+    /// Reconcile the supply of tokens in the DAG against Genesis, see `SpendDag::supply_report`
+    pub fn supply_report(&self) -> Result<SupplyReport> {
+        let store = self.read_store()?;
+        supply_report(&store)
+    }
+
+    /// The royalties paid by the storage payments of the DAG, and the royalties income by day
+    pub fn royalties_summary(&self) -> Result<RoyaltiesSummary> {
+        let royalties = self
+            .royalties
+            .read()
+            .map_err(|e| eyre!("Failed to get royalties read lock: {e}"))?;
+        Ok(royalties.summary())
+    }
+
+    /// The storage payments missing or underpaying their royalties, optionally filtered by kind
+    pub fn royalty_issues(
+        &self,
+        kind: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Page<RoyaltyIssue>> {
+        let royalties = self
+            .royalties
             .read()
-            .map_err(|e| eyre!("Failed to get read lock: {e}"))?;
-        r_handle.dump_to_file(dag_path)?;
+            .map_err(|e| eyre!("Failed to get royalties read lock: {e}"))?;
+        let issues = royalties
+            .issues()
+            .iter()
+            .filter(|issue| match kind {
+                Some(kind) => issue.kind == kind,
+                None => true,
+            })
+            .cloned();
+        Ok(Page::new(issues, offset, limit))
+    }
+
+    /// Get the outputs sent to a MainPubkey.
+    ///
+    /// The keys used on the Network are derived from the MainPubkey with random derivation indexes that only the
+    /// sender and the recipient know. Hence only the outputs with a public derivation index (the network royalties)
+    /// or with one of the given `derivation_indexes` can be found.
+    pub fn pubkey_history(
+        &self,
+        main_pubkey: MainPubkey,
+        derivation_indexes: &[DerivationIndex],
+        offset: usize,
+        limit: usize,
+    ) -> Result<Page<PubkeyHistoryEntry>> {
+        let store = self.read_store()?;
+        let known_keys: BTreeSet<UniquePubkey> = derivation_indexes
+            .iter()
+            .map(|idx| main_pubkey.new_unique_pubkey(idx))
+            .collect();
+        let is_royalties_pk = main_pubkey == *NETWORK_ROYALTIES_PK;
+
+        // go through the spends one by one from disk
+        let mut history = Vec::new();
+        store.for_each_spend(|spend| {
+            let royalty_keys: BTreeSet<UniquePubkey> = if is_royalties_pk {
+                spend
+                    .spend
+                    .network_royalties
+                    .iter()
+                    .map(|idx| main_pubkey.new_unique_pubkey(idx))
+                    .collect()
+            } else {
+                BTreeSet::new()
+            };
+            for output in spend.spend.spent_tx.outputs.iter() {
+                let royalty = royalty_keys.contains(&output.unique_pubkey);
+                if !royalty && !known_keys.contains(&output.unique_pubkey) {
+                    continue;
+                }
+                let address = SpendAddress::from_unique_pubkey(&output.unique_pubkey);
+                history.push(PubkeyHistoryEntry {
+                    address: address.to_hex(),
+                    amount: output.amount.as_nano(),
+                    purpose: output.purpose.clone(),
+                    created_by: spend.address().to_hex(),
+                    spent: store.contains(&address),
+                    royalty,
+                });
+            }
+        })?;
+        Ok(Page::new(history, offset, limit))
+    }
+
+    fn read_store(&self) -> Result<RwLockReadGuard<'_, SpendDagStore>> {
+        self.store
+            .read()
+            .map_err(|e| eyre!("Failed to get store read lock: {e}"))
+    }
+
+    /// Append the spends of the DAG that are not in the store yet
+    /// Alerts are sent for the faults that were not in the store yet
+    fn persist(&self, dag: &SpendDag) -> Result<()> {
+        self.persist_spends(dag, true)
+    }
+
+    /// Append the spends of the DAG that are not in the store yet, e.g. when importing a DAG
+    /// The faults are recorded in the store, so they are not alerted on later on either
+    fn persist_without_alerts(&self, dag: &SpendDag) -> Result<()> {
+        self.persist_spends(dag, false)
+    }
+
+    fn persist_spends(&self, dag: &SpendDag, alert: bool) -> Result<()> {
+        let spends: Vec<SignedSpend> = dag.all_spends().into_iter().cloned().collect();
+        let mut store = self
+            .store
+            .write()
+            .map_err(|e| eyre!("Failed to get store write lock: {e}"))?;
+        let summary = store.persist(dag.source(), spends.iter().cloned())?;
+        info!(
+            "Persisted {} new spends to the store, with {} new faults",
+            summary.appended,
+            summary.new_faults.len()
+        );
+        if alert {
+            self.alerter.notify(&summary.new_faults, &store);
+        }
+        std::mem::drop(store);
+
+        let mut royalties = self
+            .royalties
+            .write()
+            .map_err(|e| eyre!("Failed to get royalties write lock: {e}"))?;
+        royalties.update(&spends)?;
+        self.metrics.record_royalties(&royalties.summary());
         Ok(())
     }
 
+    /// The metrics of the auditor in the Prometheus text format
+    pub fn encode_metrics(&self) -> Result<String> {
+        self.metrics.encode()
+    }
+
     /// Load current DAG svg from disk
     pub fn load_svg(&self) -> Result<Vec<u8>> {
         let svg_path = self.path.join(SPEND_DAG_SVG_FILENAME);
@@ -132,46 +578,77 @@ impl SpendDagDb {
     }
 
     /// Dump current DAG as svg to disk
+    /// The DAG is rebuilt from the store for this, so only DAGs up to `SVG_MAX_SPENDS` spends
+    /// are rendered when following the Network
     pub fn dump_dag_svg(&self) -> Result<()> {
+        let dag = {
+            let store = self.read_store()?;
+            if self.client.is_some() && store.spends_count() > SVG_MAX_SPENDS {
+                info!(
+                    "Not dumping the DAG to svg, it has more than {SVG_MAX_SPENDS} spends: {}",
+                    store.spends_count()
+                );
+                return Ok(());
+            }
+            match store.load_dag()? {
+                Some(dag) => dag,
+                None => return Ok(()),
+            }
+        };
         info!("Dumping DAG to svg...");
         std::fs::create_dir_all(&self.path)?;
         let svg_path = self.path.join(SPEND_DAG_SVG_FILENAME);
-        let dag_ref = self.dag.clone();
-        let r_handle = dag_ref
-            .read()
-            .map_err(|e| eyre!("Failed to get read lock: {e}"))?;
-        let svg = dag_to_svg(&r_handle)?;
+        let svg = dag_to_svg(&dag)?;
         std::fs::write(svg_path.clone(), svg)?;
         info!("Successfully dumped DAG to {svg_path:?}...");
         Ok(())
     }
 
     /// Update DAG from Network
+    /// The spends found from the UTXOs of the store are gathered in a new DAG, then appended to the store
     pub async fn update(&mut self) -> Result<()> {
-        // read current DAG
-        let mut dag = {
-            self.dag
-                .clone()
-                .read()
-                .map_err(|e| eyre!("Failed to get read lock: {e}"))?
-                .clone()
+        let (source, utxos) = {
+            let store = self.read_store()?;
+            let source = store
+                .meta()
+                .source
+                .ok_or(eyre!("The spend DAG store has no source"))?;
+            let utxos: BTreeSet<SpendAddress> = store.meta().utxos.keys().copied().collect();
+            (source, utxos)
         };
 
-        // update that copy 10 generations further
+        // gather the spends 10 generations further
         const NEXT_10_GEN: u32 = 10;
-        self.client
+        let client = self
+            .client
             .clone()
-            .ok_or(eyre!("Cannot update in offline mode"))?
-            .spend_dag_continue_from_utxos(&mut dag, Some(NEXT_10_GEN))
-            .await?;
+            .ok_or(eyre!("Cannot update in offline mode"))?;
+        let mut crawler = SpendDagCrawler::new(
+            client,
+            CrawlerCfg {
+                max_depth: Some(NEXT_10_GEN),
+                ..self.crawler_cfg.clone()
+            },
+        );
+        let mut progress_receiver = crawler.get_progress_receiver();
+        let metrics = self.metrics.clone();
+        let progress_handle = tokio::spawn(async move {
+            while let Some(progress) = progress_receiver.recv().await {
+                metrics.record_crawl_progress(&progress);
+            }
+        });
+        let mut new_spends = SpendDag::new(source);
+        let progress = crawler.crawl_from(&mut new_spends, utxos).await?;
+        // dropping the crawler closes the progress channel
+        std::mem::drop(crawler);
+        let _ = progress_handle.await;
+        self.metrics.record_crawl_progress(&progress);
+        info!(
+            "Crawled {} spends with {} requests in {}ms, {} left in the frontier",
+            progress.spends_gathered, progress.requests, progress.elapsed_ms, progress.frontier
+        );
 
-        // write update to DAG
-        let dag_ref = self.dag.clone();
-        let mut w_handle = dag_ref
-            .write()
-            .map_err(|e| eyre!("Failed to get write lock: {e}"))?;
-        *w_handle = dag;
-        std::mem::drop(w_handle);
+        self.persist(&new_spends)?;
 
         // update and save svg to file in a background thread so we don't block
         let self_clone = self.clone();
@@ -188,12 +665,7 @@ impl SpendDagDb {
     /// This can be used to enrich our DAG with a DAG from another node to avoid costly computations
     /// Make sure to verify the other DAG is trustworthy before calling this function to merge it in
     pub fn merge(&mut self, other: SpendDag) -> Result<()> {
-        let dag_ref = self.dag.clone();
-        let mut w_handle = dag_ref
-            .write()
-            .map_err(|e| eyre!("Failed to get write lock: {e}"))?;
-        w_handle.merge(other)?;
-        Ok(())
+        self.persist(&other)
     }
 }
 
@@ -217,6 +689,113 @@ pub async fn new_dag_with_genesis_only(client: &Client) -> Result<SpendDag> {
     Ok(dag)
 }
 
+/// Reconcile the supply of the DAG in the store like `SpendDag::supply_report` does, reading the
+/// spends from disk
+fn supply_report(store: &SpendDagStore) -> Result<SupplyReport> {
+    let meta = store.meta();
+    let single_spend = |addr: &SpendAddress| -> Result<Option<SignedSpend>> {
+        match store.get_spends(addr)?.as_slice() {
+            [spend] => Ok(Some(spend.clone())),
+            _ => Ok(None),
+        }
+    };
+    let source_spend = match meta.source {
+        Some(source) => single_spend(&source)?,
+        None => None,
+    };
+    let faucet = SupplyReport::faucet_outputs(source_spend.as_ref(), single_spend)?;
+
+    // the same transaction is shared by all the spends of its inputs
+    let mut burned = 0u64;
+    let mut seen_txs: BTreeSet<Hash> = BTreeSet::new();
+    store.for_each_spend(|spend| {
+        let tx = &spend.spend.spent_tx;
+        if store.is_double_spend(&spend.address()) || !seen_txs.insert(tx.hash()) {
+            return;
+        }
+        burned = burned.saturating_add(SupplyReport::burned_by(tx));
+    })?;
+
+    let utxos = meta.utxos.iter().map(|(utxo, (purpose, amount))| {
+        (
+            *utxo,
+            Some((purpose.as_str(), amount.as_nano())),
+            store.faults().get(utxo),
+        )
+    });
+    Ok(SupplyReport::from_utxos(
+        source_spend
+            .as_ref()
+            .map(|s| s.spend.amount.as_nano())
+            .unwrap_or_default(),
+        utxos,
+        &faucet,
+        burned,
+    ))
+}
+
+/// The snake_case name of the kind of a fault, one of `FAULT_KINDS`
+pub fn fault_kind(fault: &SpendFault) -> &'static str {
+    match fault {
+        SpendFault::DoubleSpend(_) => FAULT_KINDS[0],
+        SpendFault::MissingAncestry { .. } => FAULT_KINDS[1],
+        SpendFault::DoubleSpentAncestor { .. } => FAULT_KINDS[2],
+        SpendFault::InvalidTransaction(..) => FAULT_KINDS[3],
+        SpendFault::PoisonedAncestry(..) => FAULT_KINDS[4],
+        SpendFault::OrphanSpend { .. } => FAULT_KINDS[5],
+    }
+}
+
+fn fault_details(fault: &SpendFault) -> FaultDetails {
+    FaultDetails {
+        address: fault.spend_address().to_hex(),
+        kind: fault_kind(fault).to_string(),
+        description: fault.to_string(),
+    }
+}
+
+/// Breadth first walk through the spends of the store, `next` returns the relatives of a spend
+fn walk_relatives(
+    store: &SpendDagStore,
+    address: SpendAddress,
+    max_depth: usize,
+    next: impl Fn(&SignedSpend) -> Vec<SpendAddress>,
+) -> Result<BTreeMap<SpendAddress, usize>> {
+    let mut relatives = BTreeMap::new();
+    let mut current_gen = vec![address];
+    for depth in 1..=max_depth {
+        let mut next_gen = Vec::new();
+        for addr in current_gen {
+            for spend in store.get_spends(&addr)? {
+                for relative in next(&spend) {
+                    if relative == address || relatives.contains_key(&relative) {
+                        continue;
+                    }
+                    let _ = relatives.insert(relative, depth);
+                    next_gen.push(relative);
+                }
+            }
+        }
+        if next_gen.is_empty() {
+            break;
+        }
+        current_gen = next_gen;
+    }
+    Ok(relatives)
+}
+
+fn to_relatives(relatives: BTreeMap<SpendAddress, usize>) -> Vec<Relative> {
+    let mut relatives: Vec<_> = relatives
+        .into_iter()
+        .map(|(addr, depth)| Relative {
+            address: addr.to_hex(),
+            depth,
+        })
+        .collect();
+    relatives.sort_by_key(|relative| relative.depth);
+    relatives
+}
+
 fn dag_to_svg(dag: &SpendDag) -> Result<Vec<u8>> {
     let dot = dag.dump_dot_format();
     let graph = parse(&dot).map_err(|err| eyre!("Failed to parse dag from dot: {err}"))?;
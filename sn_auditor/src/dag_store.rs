@@ -0,0 +1,913 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use color_eyre::eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use sn_client::transfers::{
+    is_genesis_spend, is_network_royalties_purpose, NanoTokens, SignedSpend, SpendAddress,
+    CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES,
+};
+use sn_client::{SpendDag, SpendFault};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fs::{File, OpenOptions},
+    io::{BufReader, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+pub const SPEND_DAG_STORE_DIRNAME: &str = "spend_dag_store";
+const SPENDS_LOG_FILENAME: &str = "spends.log";
+const FAULTS_LOG_FILENAME: &str = "faults.log";
+const META_FILENAME: &str = "meta";
+
+/// The size of the header of a record in the spends log: the spend address and the length of the spend
+const SPEND_HEADER_LEN: usize = 32 + 4;
+
+/// The number of times the faults of a spend can be checked again during a single `persist`.
+/// The faults only depend on the ancestors of a spend so they settle quickly, unless the spends form a cycle.
+const MAX_FAULT_CHECKS_PER_SPEND: usize = 16;
+
+/// The part of the DAG that is rewritten on every checkpoint.
+/// It only grows with the number of UTXOs and purposes, not with the number of spends.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct StoreMeta {
+    pub source: Option<SpendAddress>,
+    /// The UTXOs along with the purpose and amount they were created with
+    pub utxos: BTreeMap<SpendAddress, (String, NanoTokens)>,
+    /// The number of outputs and total amount created for each purpose
+    pub purpose_totals: BTreeMap<String, (usize, NanoTokens)>,
+    /// The number of addresses that are refered to by known spends but were not gathered yet
+    pub not_gathered_yet: usize,
+}
+
+/// What changed in the store on a call to `SpendDagStore::persist`
+pub struct PersistSummary {
+    /// The number of spends appended to the store
+    pub appended: usize,
+    /// The faults that were not in the store before
+    pub new_faults: Vec<SpendFault>,
+}
+
+/// Append-only on disk storage for a SpendDag, the source of truth of the auditor.
+///
+/// Spends are appended to a log as they are discovered and never rewritten. Only the offsets of the spends are
+/// kept in memory, so that spends can be read back individually without loading the whole DAG.
+/// The faults of the new spends are checked against their ancestors read back from the log, and the change is
+/// carried over to their descendants. Faults are appended to a separate log every time the faults at an address
+/// change, the last record wins.
+///
+/// Layout of a record in the spends log: `[spend address (32 bytes)][length (u32 LE)][msgpack SignedSpend]`
+/// Layout of a record in the faults log: `[length (u32 LE)][msgpack (SpendAddress, BTreeSet<SpendFault>)]`
+pub struct SpendDagStore {
+    path: PathBuf,
+    spends_log: File,
+    faults_log: File,
+    /// Offsets of the spends in the spends log, more than one for double spends
+    index: BTreeMap<SpendAddress, Vec<u64>>,
+    spends_count: usize,
+    faults: BTreeMap<SpendAddress, BTreeSet<SpendFault>>,
+    meta: StoreMeta,
+}
+
+impl SpendDagStore {
+    /// Open the store at the given directory, creating it if it does not exist.
+    /// Only the headers of the spends log are read, truncating a partially written record at the end if any.
+    pub fn open(path: PathBuf) -> Result<Self> {
+        std::fs::create_dir_all(&path)?;
+        let spends_log_path = path.join(SPENDS_LOG_FILENAME);
+        let faults_log_path = path.join(FAULTS_LOG_FILENAME);
+
+        let (index, spends_count) = read_spends_index(&spends_log_path)?;
+        let faults = read_faults(&faults_log_path)?;
+        let meta = match std::fs::read(path.join(META_FILENAME)) {
+            Ok(bytes) => rmp_serde::from_slice(&bytes)?,
+            Err(e) if e.kind() == ErrorKind::NotFound => StoreMeta::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let spends_log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&spends_log_path)?;
+        let faults_log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&faults_log_path)?;
+
+        info!("Opened spend DAG store at {path:?} with {spends_count} spends");
+        Ok(Self {
+            path,
+            spends_log,
+            faults_log,
+            index,
+            spends_count,
+            faults,
+            meta,
+        })
+    }
+
+    pub fn meta(&self) -> &StoreMeta {
+        &self.meta
+    }
+
+    /// The number of spends in the store, counting each spend of a double spend
+    pub fn spends_count(&self) -> usize {
+        self.spends_count
+    }
+
+    /// The number of addresses with more than one spend
+    pub fn double_spends_count(&self) -> usize {
+        self.index
+            .values()
+            .filter(|offsets| offsets.len() > 1)
+            .count()
+    }
+
+    pub fn contains(&self, addr: &SpendAddress) -> bool {
+        self.index.contains_key(addr)
+    }
+
+    pub fn is_double_spend(&self, addr: &SpendAddress) -> bool {
+        self.index
+            .get(addr)
+            .is_some_and(|offsets| offsets.len() > 1)
+    }
+
+    pub fn faults(&self) -> &BTreeMap<SpendAddress, BTreeSet<SpendFault>> {
+        &self.faults
+    }
+
+    pub fn get_spend_faults(&self, addr: &SpendAddress) -> BTreeSet<SpendFault> {
+        self.faults.get(addr).cloned().unwrap_or_default()
+    }
+
+    /// Read the spends at an address from disk, more than one for double spends
+    pub fn get_spends(&self, addr: &SpendAddress) -> Result<Vec<SignedSpend>> {
+        let offsets = match self.index.get(addr) {
+            Some(offsets) => offsets,
+            None => return Ok(vec![]),
+        };
+        let mut file = File::open(self.path.join(SPENDS_LOG_FILENAME))?;
+        let mut spends = Vec::with_capacity(offsets.len());
+        for offset in offsets {
+            file.seek(SeekFrom::Start(*offset))?;
+            let mut header = [0u8; SPEND_HEADER_LEN];
+            file.read_exact(&mut header)?;
+            let mut payload = vec![0u8; payload_len(&header)];
+            file.read_exact(&mut payload)?;
+            spends.push(rmp_serde::from_slice(&payload)?);
+        }
+        Ok(spends)
+    }
+
+    /// Call `f` on every spend of the store in the order they were added, reading them one by one from disk
+    pub fn for_each_spend(&self, mut f: impl FnMut(SignedSpend)) -> Result<()> {
+        let mut reader = BufReader::new(File::open(self.path.join(SPENDS_LOG_FILENAME))?);
+        for _ in 0..self.spends_count {
+            let mut header = [0u8; SPEND_HEADER_LEN];
+            reader.read_exact(&mut header)?;
+            let mut payload = vec![0u8; payload_len(&header)];
+            reader.read_exact(&mut payload)?;
+            f(rmp_serde::from_slice(&payload)?);
+        }
+        Ok(())
+    }
+
+    /// Rebuild a SpendDag from all the spends in the store.
+    /// This holds the whole DAG in memory, the store itself never needs it.
+    pub fn load_dag(&self) -> Result<Option<SpendDag>> {
+        let source = match self.meta.source {
+            Some(source) => source,
+            None => return Ok(None),
+        };
+        let mut dag = SpendDag::new(source);
+        self.for_each_spend(|spend| {
+            let _ = dag.insert(spend.address(), spend);
+        })?;
+        if let Err(e) = dag.record_faults(&source) {
+            warn!("Failed to record faults of the DAG loaded from the store: {e}");
+        }
+        Ok(Some(dag))
+    }
+
+    /// Append the spends that are not in the store yet, then update the faults and the meta data.
+    /// Only the new spends, their ancestors and the descendants whose faults change are read back from disk.
+    pub fn persist(
+        &mut self,
+        source: SpendAddress,
+        spends: impl IntoIterator<Item = SignedSpend>,
+    ) -> Result<PersistSummary> {
+        match self.meta.source {
+            None => self.meta.source = Some(source),
+            Some(stored) if stored != source => {
+                warn!("Persisting spends from a DAG with source {source:?} into a store with source {stored:?}");
+            }
+            Some(_) => {}
+        }
+
+        // append the new spends
+        let mut added = BTreeSet::new();
+        for spend in spends {
+            let addr = spend.address();
+            let stored = self.get_spends(&addr)?;
+            if stored.contains(&spend) {
+                continue;
+            }
+            self.append_spend(&addr, &spend)?;
+            self.record_outputs(&addr, &spend, &stored);
+            let _ = added.insert(addr);
+        }
+        self.spends_log.sync_data()?;
+
+        // check the faults of the new spends and carry the changes over to their descendants
+        let new_faults = self.update_faults(&added)?;
+        self.faults_log.sync_data()?;
+
+        // rewrite the meta data
+        let missing_ancestors: BTreeSet<&SpendAddress> = self
+            .faults
+            .values()
+            .flatten()
+            .filter_map(|fault| match fault {
+                SpendFault::MissingAncestry { ancestor, .. } if !self.contains(ancestor) => {
+                    Some(ancestor)
+                }
+                _ => None,
+            })
+            .collect();
+        self.meta.not_gathered_yet = self.meta.utxos.len() + missing_ancestors.len();
+        let tmp_path = self.path.join(format!("{META_FILENAME}.tmp"));
+        std::fs::write(&tmp_path, rmp_serde::to_vec(&self.meta)?)?;
+        std::fs::rename(tmp_path, self.path.join(META_FILENAME))?;
+
+        debug!(
+            "Appended {} spends and {} new faults to the spend DAG store",
+            added.len(),
+            new_faults.len()
+        );
+        Ok(PersistSummary {
+            appended: added.len(),
+            new_faults,
+        })
+    }
+
+    /// Update the UTXOs and purpose totals with a new spend, `stored` being the spends that were
+    /// already at its address
+    fn record_outputs(&mut self, addr: &SpendAddress, spend: &SignedSpend, stored: &[SignedSpend]) {
+        let _ = self.meta.utxos.remove(addr);
+        for output in spend.spend.spent_tx.outputs.iter() {
+            let output_addr = SpendAddress::from_unique_pubkey(&output.unique_pubkey);
+            // the outputs shared with the other spends of a double spend are only counted once
+            let already_created = stored.iter().any(|s| {
+                s.spend
+                    .spent_tx
+                    .outputs
+                    .iter()
+                    .any(|o| o.unique_pubkey == output.unique_pubkey)
+            });
+            if !already_created {
+                // the royalties are counted together, whichever data address they paid for
+                let purpose = if is_network_royalties_purpose(&output.purpose) {
+                    CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES.to_string()
+                } else {
+                    output.purpose.clone()
+                };
+                let (count, amount) = self
+                    .meta
+                    .purpose_totals
+                    .entry(purpose)
+                    .or_insert((0, NanoTokens::zero()));
+                *count += 1;
+                *amount =
+                    NanoTokens::from(amount.as_nano().saturating_add(output.amount.as_nano()));
+            }
+            if !self.contains(&output_addr) {
+                let _ = self
+                    .meta
+                    .utxos
+                    .insert(output_addr, (output.purpose.clone(), output.amount));
+            }
+        }
+    }
+
+    /// Check the faults of the newly added spends, then of their descendants for as long as the
+    /// faults keep changing. Returns the faults that were not recorded before.
+    fn update_faults(&mut self, added: &BTreeSet<SpendAddress>) -> Result<Vec<SpendFault>> {
+        let mut queue: VecDeque<SpendAddress> = added.iter().copied().collect();
+        // a new spend descending from a double spend can make its siblings fork
+        for addr in added {
+            for spend in self.get_spends(addr)? {
+                for input in spend.spend.parent_tx.inputs.iter() {
+                    let ancestor = SpendAddress::from_unique_pubkey(&input.unique_pubkey);
+                    if self.is_double_spend(&ancestor) {
+                        for sibling in self.output_addresses(&ancestor)? {
+                            if sibling != *addr && self.contains(&sibling) {
+                                queue.push_back(sibling);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut checks: BTreeMap<SpendAddress, usize> = BTreeMap::new();
+        let mut new_faults = BTreeSet::new();
+        while let Some(addr) = queue.pop_front() {
+            let count = checks.entry(addr).or_default();
+            *count += 1;
+            if *count > MAX_FAULT_CHECKS_PER_SPEND {
+                warn!("Faults of {addr:?} keep changing, are the spends forming a cycle?");
+                continue;
+            }
+            let first_check_of_new_spend = *count == 1 && added.contains(&addr);
+
+            let spends = self.get_spends(&addr)?;
+            let faults = self.check_spend(&addr, &spends)?;
+            let changed = self.set_faults(addr, faults, &mut new_faults)?;
+            if !changed && !first_check_of_new_spend {
+                continue;
+            }
+
+            // the descendants inherit some of the faults of their ancestors
+            for output in self.output_addresses(&addr)? {
+                if self.contains(&output) {
+                    queue.push_back(output);
+                } else {
+                    let faults = self.inherited_faults(output, &addr, &spends);
+                    let _ = self.set_faults(output, faults, &mut new_faults)?;
+                }
+            }
+        }
+
+        // a fault that came and went during the update is not new
+        Ok(new_faults
+            .into_iter()
+            .filter(|f| {
+                self.faults
+                    .get(&f.spend_address())
+                    .is_some_and(|faults| faults.contains(f))
+            })
+            .collect())
+    }
+
+    /// The faults of the spends at an address, given the spends and faults of their ancestors in the store
+    fn check_spend(
+        &self,
+        addr: &SpendAddress,
+        spends: &[SignedSpend],
+    ) -> Result<BTreeSet<SpendFault>> {
+        let mut faults = BTreeSet::new();
+        if spends.len() > 1 {
+            let _ = faults.insert(SpendFault::DoubleSpend(*addr));
+        }
+        let source = match self.meta.source {
+            Some(source) => source,
+            None => return Ok(faults),
+        };
+        // we don't know the ancestors of the source
+        if *addr == source {
+            return Ok(faults);
+        }
+
+        let ancestors: BTreeSet<SpendAddress> = spends
+            .iter()
+            .flat_map(|s| s.spend.parent_tx.inputs.iter())
+            .map(|input| SpendAddress::from_unique_pubkey(&input.unique_pubkey))
+            .collect();
+        let mut descends_from_source = false;
+        for ancestor in ancestors {
+            let ancestor_spends = self.get_spends(&ancestor)?;
+            if ancestor_spends.len() > 1 && self.has_forking_branches(&ancestor_spends)? {
+                let _ = faults.insert(SpendFault::PoisonedAncestry(
+                    *addr,
+                    format!(
+                        "spend is on one of multiple branches of a double spent ancestor: {ancestor:?}"
+                    ),
+                ));
+            }
+            let is_child = ancestor_spends.iter().any(|s| {
+                s.spend
+                    .spent_tx
+                    .outputs
+                    .iter()
+                    .any(|o| SpendAddress::from_unique_pubkey(&o.unique_pubkey) == *addr)
+            });
+            let ancestor_is_orphan = self.faults.get(&ancestor).is_some_and(|faults| {
+                faults
+                    .iter()
+                    .any(|f| matches!(f, SpendFault::OrphanSpend { .. }))
+            });
+            if is_child && !ancestor_is_orphan {
+                descends_from_source = true;
+            }
+            faults.extend(self.inherited_faults(*addr, &ancestor, &ancestor_spends));
+        }
+        if !descends_from_source {
+            let _ = faults.insert(SpendFault::OrphanSpend {
+                addr: *addr,
+                src: source,
+            });
+        }
+
+        // the transaction of a double spend is not verified, the double spend is the fault
+        if let [spend] = spends {
+            if !is_genesis_spend(spend) {
+                faults.extend(self.verify_parent_tx(spend)?);
+            }
+        }
+        Ok(faults)
+    }
+
+    /// The faults an address inherits from one of its ancestors, whether it is spent or not
+    fn inherited_faults(
+        &self,
+        addr: SpendAddress,
+        ancestor: &SpendAddress,
+        ancestor_spends: &[SignedSpend],
+    ) -> BTreeSet<SpendFault> {
+        let mut faults = BTreeSet::new();
+        if ancestor_spends.len() > 1 {
+            let _ = faults.insert(SpendFault::DoubleSpentAncestor {
+                addr,
+                ancestor: *ancestor,
+            });
+        }
+        for fault in self.faults.get(ancestor).into_iter().flatten() {
+            let poison = match fault {
+                SpendFault::MissingAncestry { ancestor, .. } => {
+                    format!("missing ancestor at: {ancestor:?}")
+                }
+                SpendFault::InvalidTransaction(at, e) => {
+                    format!("ancestor transaction was poisoned at: {at:?}: {e}")
+                }
+                SpendFault::PoisonedAncestry(_, poison) => poison.clone(),
+                SpendFault::OrphanSpend { src, .. } if !self.contains(&addr) => {
+                    // UTXOs are not checked themselves, they inherit the orphan status
+                    let _ = faults.insert(SpendFault::OrphanSpend { addr, src: *src });
+                    continue;
+                }
+                _ => continue,
+            };
+            let _ = faults.insert(SpendFault::PoisonedAncestry(addr, poison));
+        }
+        faults
+    }
+
+    /// Verify the parent transaction of a spend against the spends of its inputs
+    fn verify_parent_tx(&self, spend: &SignedSpend) -> Result<BTreeSet<SpendFault>> {
+        let addr = spend.address();
+        let mut ancestors = BTreeSet::new();
+        for input in spend.spend.parent_tx.inputs.iter() {
+            let ancestor_addr = SpendAddress::from_unique_pubkey(&input.unique_pubkey);
+            let mut ancestor_spends = self.get_spends(&ancestor_addr)?;
+            // of a double spent ancestor, use the spend that made our parent transaction
+            if ancestor_spends.len() > 1 {
+                ancestor_spends.retain(|s| s.spend.spent_tx.hash() == spend.spend.parent_tx.hash());
+            }
+            match ancestor_spends.into_iter().next() {
+                Some(ancestor_spend) => {
+                    let _ = ancestors.insert(ancestor_spend);
+                }
+                None => {
+                    return Ok(BTreeSet::from([SpendFault::MissingAncestry {
+                        addr,
+                        ancestor: ancestor_addr,
+                    }]))
+                }
+            }
+        }
+
+        let mut faults = BTreeSet::new();
+        if let Err(e) = spend
+            .spend
+            .parent_tx
+            .verify_against_inputs_spent(&ancestors)
+        {
+            warn!("Parent Tx verfication failed for spend at: {addr:?}: {e}");
+            let _ = faults.insert(SpendFault::InvalidTransaction(addr, format!("{e}")));
+        }
+        Ok(faults)
+    }
+
+    /// Whether the spends of a double spend have descendants on diverging branches
+    fn has_forking_branches(&self, spends: &[SignedSpend]) -> Result<bool> {
+        let mut branches = Vec::with_capacity(spends.len());
+        for spend in spends {
+            let mut living_descendants = BTreeSet::new();
+            for output in spend.spend.spent_tx.outputs.iter() {
+                let output_addr = SpendAddress::from_unique_pubkey(&output.unique_pubkey);
+                living_descendants.extend(self.get_spends(&output_addr)?);
+            }
+            branches.push(living_descendants);
+        }
+        Ok(branches.iter().any(|branch1| {
+            branches
+                .iter()
+                .any(|branch2| !branch1.is_subset(branch2) && !branch2.is_subset(branch1))
+        }))
+    }
+
+    /// The addresses of the outputs of the spends at an address
+    fn output_addresses(&self, addr: &SpendAddress) -> Result<BTreeSet<SpendAddress>> {
+        Ok(self
+            .get_spends(addr)?
+            .iter()
+            .flat_map(|s| s.spend.spent_tx.outputs.iter())
+            .map(|o| SpendAddress::from_unique_pubkey(&o.unique_pubkey))
+            .collect())
+    }
+
+    /// Record the faults of an address if they changed, adding the ones it didn't have to `new_faults`
+    fn set_faults(
+        &mut self,
+        addr: SpendAddress,
+        faults: BTreeSet<SpendFault>,
+        new_faults: &mut BTreeSet<SpendFault>,
+    ) -> Result<bool> {
+        let stored = self.faults.get(&addr);
+        let unchanged = match stored {
+            Some(stored) => *stored == faults,
+            None => faults.is_empty(),
+        };
+        if unchanged {
+            return Ok(false);
+        }
+        new_faults.extend(
+            faults
+                .iter()
+                .filter(|f| !stored.is_some_and(|stored| stored.contains(f)))
+                .cloned(),
+        );
+        self.append_faults(addr, faults)?;
+        Ok(true)
+    }
+
+    fn append_spend(&mut self, addr: &SpendAddress, spend: &SignedSpend) -> Result<()> {
+        let payload = rmp_serde::to_vec(spend)?;
+        let len = u32::try_from(payload.len()).map_err(|_| eyre!("Spend {addr:?} is too large"))?;
+        let offset = self.spends_log.seek(SeekFrom::End(0))?;
+        let mut record = Vec::with_capacity(SPEND_HEADER_LEN + payload.len());
+        record.extend_from_slice(&addr.xorname().0);
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(&payload);
+        self.spends_log.write_all(&record)?;
+
+        self.index.entry(*addr).or_default().push(offset);
+        self.spends_count += 1;
+        Ok(())
+    }
+
+    fn append_faults(&mut self, addr: SpendAddress, faults: BTreeSet<SpendFault>) -> Result<()> {
+        let payload = rmp_serde::to_vec(&(addr, &faults))?;
+        let len = u32::try_from(payload.len())
+            .map_err(|_| eyre!("Faults record for {addr:?} is too large"))?;
+        let mut record = Vec::with_capacity(4 + payload.len());
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(&payload);
+        self.faults_log.write_all(&record)?;
+
+        if faults.is_empty() {
+            let _ = self.faults.remove(&addr);
+        } else {
+            let _ = self.faults.insert(addr, faults);
+        }
+        Ok(())
+    }
+}
+
+fn payload_len(header: &[u8; SPEND_HEADER_LEN]) -> usize {
+    let mut len = [0u8; 4];
+    len.copy_from_slice(&header[32..]);
+    u32::from_le_bytes(len) as usize
+}
+
+/// Read the headers of the spends log, skipping over the spends themselves
+fn read_spends_index(path: &Path) -> Result<(BTreeMap<SpendAddress, Vec<u64>>, usize)> {
+    let mut index: BTreeMap<SpendAddress, Vec<u64>> = BTreeMap::new();
+    let mut count = 0;
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok((index, count)),
+        Err(e) => return Err(e.into()),
+    };
+    let file_len = file.metadata()?.len();
+
+    let mut offset = 0;
+    loop {
+        let mut header = [0u8; SPEND_HEADER_LEN];
+        if offset + SPEND_HEADER_LEN as u64 > file_len {
+            break;
+        }
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut header)?;
+        let record_end = offset + (SPEND_HEADER_LEN + payload_len(&header)) as u64;
+        if record_end > file_len {
+            break;
+        }
+        let mut xorname = [0u8; 32];
+        xorname.copy_from_slice(&header[..32]);
+        index
+            .entry(SpendAddress::new(xor_name::XorName(xorname)))
+            .or_default()
+            .push(offset);
+        count += 1;
+        offset = record_end;
+    }
+
+    if offset < file_len {
+        warn!("Truncating partially written record at the end of {path:?}");
+        OpenOptions::new().write(true).open(path)?.set_len(offset)?;
+    }
+    Ok((index, count))
+}
+
+/// Replay the faults log, the last record of an address wins
+fn read_faults(path: &Path) -> Result<BTreeMap<SpendAddress, BTreeSet<SpendFault>>> {
+    let mut faults = BTreeMap::new();
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(faults),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut offset = 0;
+    while offset + 4 <= bytes.len() {
+        let mut len = [0u8; 4];
+        len.copy_from_slice(&bytes[offset..offset + 4]);
+        let record_end = offset + 4 + u32::from_le_bytes(len) as usize;
+        if record_end > bytes.len() {
+            break;
+        }
+        let (addr, addr_faults): (SpendAddress, BTreeSet<SpendFault>) =
+            rmp_serde::from_slice(&bytes[offset + 4..record_end])?;
+        if addr_faults.is_empty() {
+            let _ = faults.remove(&addr);
+        } else {
+            let _ = faults.insert(addr, addr_faults);
+        }
+        offset = record_end;
+    }
+
+    if offset < bytes.len() {
+        warn!("Truncating partially written record at the end of {path:?}");
+        OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len(offset as u64)?;
+    }
+    Ok(faults)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_client::transfers::{
+        bls::SecretKey, rand, CashNote, DerivationIndex, MainSecretKey, OfflineTransfer,
+        SpendReason, GENESIS_CASHNOTE, GENESIS_CASHNOTE_SK,
+    };
+
+    /// Spend all the cash notes to a new key, the change going back to `owner`.
+    /// Returns the spends, the cash notes of the recipient and the change.
+    fn transfer(
+        owner: &MainSecretKey,
+        cash_notes: Vec<CashNote>,
+        amount: u64,
+    ) -> Result<(Vec<SignedSpend>, Vec<CashNote>)> {
+        let recipient = MainSecretKey::new(SecretKey::random());
+        let cash_notes_with_keys = cash_notes
+            .into_iter()
+            .map(|cn| {
+                let key = cn.derived_key(owner)?;
+                Ok((cn, Some(key)))
+            })
+            .collect::<Result<_>>()?;
+        let transfer = OfflineTransfer::new(
+            cash_notes_with_keys,
+            vec![(
+                NanoTokens::from(amount),
+                Default::default(),
+                recipient.main_pubkey(),
+                DerivationIndex::random(&mut rand::thread_rng()),
+            )],
+            owner.main_pubkey(),
+            SpendReason::default(),
+        )?;
+        let mut created = transfer.cash_notes_for_recipient;
+        created.extend(transfer.change_cash_note);
+        Ok((transfer.all_spend_requests, created))
+    }
+
+    /// Genesis spent to a first key, then part of it spent from the change
+    fn genesis_and_child() -> Result<(SpendAddress, Vec<SignedSpend>, Vec<SignedSpend>)> {
+        let genesis_sk = MainSecretKey::new(SecretKey::from_hex(GENESIS_CASHNOTE_SK)?);
+        let everything = GENESIS_CASHNOTE.value()?.as_nano();
+        let (genesis_spends, created) =
+            transfer(&genesis_sk, vec![GENESIS_CASHNOTE.clone()], everything / 2)?;
+        let change: Vec<CashNote> = created
+            .into_iter()
+            .filter(|cn| cn.main_pubkey() == &genesis_sk.main_pubkey())
+            .collect();
+        let (child_spends, _) = transfer(&genesis_sk, change, 1000)?;
+        Ok((genesis_spends[0].address(), genesis_spends, child_spends))
+    }
+
+    fn file_len(path: &Path) -> Result<u64> {
+        Ok(std::fs::metadata(path)?.len())
+    }
+
+    #[test]
+    fn persist_should_only_append_new_spends_and_survive_reopening() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(SPEND_DAG_STORE_DIRNAME);
+        let (source, genesis_spends, child_spends) = genesis_and_child()?;
+
+        let mut store = SpendDagStore::open(path.clone())?;
+        let summary = store.persist(source, genesis_spends.clone())?;
+        assert_eq!(summary.appended, 1);
+        assert!(summary.new_faults.is_empty());
+        let log_len = file_len(&path.join(SPENDS_LOG_FILENAME))?;
+
+        // the spends already stored are not appended again
+        let all_spends = genesis_spends.iter().chain(child_spends.iter()).cloned();
+        let summary = store.persist(source, all_spends)?;
+        assert_eq!(summary.appended, 1);
+        assert!(summary.new_faults.is_empty());
+        assert!(file_len(&path.join(SPENDS_LOG_FILENAME))? > log_len);
+        assert_eq!(store.spends_count(), 2);
+        assert_eq!(store.meta().source, Some(source));
+        // the child spent the change of genesis, leaving the genesis payment, its payment and its change
+        assert_eq!(store.meta().utxos.len(), 3);
+        assert_eq!(store.meta().not_gathered_yet, 3);
+        assert!(store.faults().is_empty());
+
+        let reopened = SpendDagStore::open(path)?;
+        assert_eq!(reopened.spends_count(), 2);
+        assert_eq!(reopened.get_spends(&source)?, genesis_spends);
+        assert_eq!(
+            reopened.get_spends(&child_spends[0].address())?,
+            child_spends
+        );
+        assert_eq!(
+            reopened.meta().utxos.keys().collect::<Vec<_>>(),
+            store.meta().utxos.keys().collect::<Vec<_>>()
+        );
+        let mut read_back = vec![];
+        reopened.for_each_spend(|spend| read_back.push(spend))?;
+        assert_eq!(
+            read_back,
+            vec![genesis_spends[0].clone(), child_spends[0].clone()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn open_should_truncate_partially_written_records() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(SPEND_DAG_STORE_DIRNAME);
+        let (source, genesis_spends, child_spends) = genesis_and_child()?;
+
+        // a child without its parent is faulty, so both logs get a record
+        let mut store = SpendDagStore::open(path.clone())?;
+        let _ = store.persist(source, child_spends.clone())?;
+        assert!(!store.faults().is_empty());
+        let faults = store.faults().clone();
+        std::mem::drop(store);
+
+        // simulate a crash in the middle of writing a record to each log
+        let spends_log = path.join(SPENDS_LOG_FILENAME);
+        let faults_log = path.join(FAULTS_LOG_FILENAME);
+        let (spends_len, faults_len) = (file_len(&spends_log)?, file_len(&faults_log)?);
+        let spends_record = std::fs::read(&spends_log)?;
+        let faults_record = std::fs::read(&faults_log)?;
+        let mut file = OpenOptions::new().append(true).open(&spends_log)?;
+        file.write_all(&spends_record[..spends_record.len() - 10])?;
+        let mut file = OpenOptions::new().append(true).open(&faults_log)?;
+        file.write_all(&faults_record[..2])?;
+
+        let mut store = SpendDagStore::open(path.clone())?;
+        assert_eq!(file_len(&spends_log)?, spends_len);
+        assert_eq!(file_len(&faults_log)?, faults_len);
+        assert_eq!(store.spends_count(), 1);
+        assert_eq!(store.get_spends(&child_spends[0].address())?, child_spends);
+        assert_eq!(store.faults(), &faults);
+
+        // the store keeps working after the recovery
+        let _ = store.persist(source, genesis_spends)?;
+        std::mem::drop(store);
+        let store = SpendDagStore::open(path)?;
+        assert_eq!(store.spends_count(), 2);
+        assert!(store.faults().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn reopened_store_should_not_report_the_stored_faults_as_new() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(SPEND_DAG_STORE_DIRNAME);
+        let (source, _, child_spends) = genesis_and_child()?;
+
+        let mut store = SpendDagStore::open(path.clone())?;
+        let summary = store.persist(source, child_spends.clone())?;
+        assert!(!summary.new_faults.is_empty());
+        std::mem::drop(store);
+
+        // the faults are read back from the faults log, so the same DAG brings nothing new
+        let mut store = SpendDagStore::open(path)?;
+        let summary = store.persist(source, child_spends)?;
+        assert_eq!(summary.appended, 0);
+        assert!(summary.new_faults.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn faults_should_be_updated_as_spends_are_added() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (source, genesis_spends, child_spends) = genesis_and_child()?;
+        let child = child_spends[0].address();
+
+        let mut store = SpendDagStore::open(dir.path().join(SPEND_DAG_STORE_DIRNAME))?;
+        let summary = store.persist(source, child_spends.clone())?;
+        let faults = store.get_spend_faults(&child);
+        assert!(faults.contains(&SpendFault::MissingAncestry {
+            addr: child,
+            ancestor: source,
+        }));
+        assert!(faults.contains(&SpendFault::OrphanSpend {
+            addr: child,
+            src: source,
+        }));
+        assert!(summary
+            .new_faults
+            .iter()
+            .all(|f| f.spend_address() != source));
+        // the outputs of the faulty child are poisoned
+        for output in child_spends[0].spend.spent_tx.outputs.iter() {
+            let output = SpendAddress::from_unique_pubkey(&output.unique_pubkey);
+            assert!(store
+                .get_spend_faults(&output)
+                .iter()
+                .any(|f| matches!(f, SpendFault::PoisonedAncestry(..))));
+        }
+
+        // finding the missing ancestor clears the faults of its descendants
+        let summary = store.persist(source, genesis_spends)?;
+        assert!(summary.new_faults.is_empty());
+        assert!(store.faults().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn double_spends_should_be_recorded_with_their_descendants() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let genesis_sk = MainSecretKey::new(SecretKey::from_hex(GENESIS_CASHNOTE_SK)?);
+        let (genesis_spends, created) =
+            transfer(&genesis_sk, vec![GENESIS_CASHNOTE.clone()], 1000)?;
+        let source = genesis_spends[0].address();
+        let change: Vec<CashNote> = created
+            .into_iter()
+            .filter(|cn| cn.main_pubkey() == &genesis_sk.main_pubkey())
+            .collect();
+        // the change of genesis is spent twice
+        let (spend1, _) = transfer(&genesis_sk, change.clone(), 1000)?;
+        let (spend2, _) = transfer(&genesis_sk, change, 2000)?;
+        let double_spent = spend1[0].address();
+
+        let mut store = SpendDagStore::open(dir.path().join(SPEND_DAG_STORE_DIRNAME))?;
+        let _ = store.persist(source, genesis_spends.into_iter().chain(spend1.clone()))?;
+        assert!(store.faults().is_empty());
+
+        let summary = store.persist(source, spend2.clone())?;
+        assert_eq!(summary.appended, 1);
+        assert!(store.is_double_spend(&double_spent));
+        assert_eq!(store.double_spends_count(), 1);
+        assert!(summary
+            .new_faults
+            .contains(&SpendFault::DoubleSpend(double_spent)));
+        for output in spend1[0]
+            .spend
+            .spent_tx
+            .outputs
+            .iter()
+            .chain(spend2[0].spend.spent_tx.outputs.iter())
+        {
+            let output = SpendAddress::from_unique_pubkey(&output.unique_pubkey);
+            assert!(store
+                .get_spend_faults(&output)
+                .contains(&SpendFault::DoubleSpentAncestor {
+                    addr: output,
+                    ancestor: double_spent,
+                }));
+        }
+
+        // persisting the same double spend again changes nothing
+        let summary = store.persist(source, spend1.into_iter().chain(spend2))?;
+        assert_eq!(summary.appended, 0);
+        assert!(summary.new_faults.is_empty());
+        Ok(())
+    }
+}
@@ -9,21 +9,30 @@
 #[macro_use]
 extern crate tracing;
 
+mod alerts;
+mod api;
 mod dag_db;
+mod dag_store;
+mod metrics;
 mod routes;
+mod royalties;
 
+use alerts::{AlertSink, FaultAlerter, FaultLogSink, WebhookSink};
 use dag_db::SpendDagDb;
+use metrics::AuditorMetrics;
 
 use bls::SecretKey;
 use clap::Parser;
 use color_eyre::eyre::{eyre, Result};
-use sn_client::Client;
+use sn_client::{Client, CrawlerCfg, DEFAULT_CRAWLER_WORKERS};
 use sn_logging::{Level, LogBuilder, LogFormat, LogOutputDest};
 use sn_peers_acquisition::get_peers_from_args;
 use sn_peers_acquisition::PeersArgs;
 use std::path::PathBuf;
 use tiny_http::{Response, Server};
 
+const FAULT_LOG_FILENAME: &str = "faults.jsonl";
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Opt {
@@ -38,6 +47,23 @@ struct Opt {
     /// Visualize a local DAG file offline, does not connect to the Network
     #[clap(short, long, value_name = "dag_file")]
     offline_viewer: Option<PathBuf>,
+    /// POST newly detected faults as JSON to this URL. Can be repeated.
+    #[clap(long, value_name = "URL")]
+    alert_webhook: Vec<String>,
+    /// Append newly detected faults to this file, one JSON object per line.
+    ///
+    /// Defaults to 'faults.jsonl' in the auditor data directory.
+    #[clap(long, value_name = "PATH")]
+    fault_log: Option<PathBuf>,
+    /// The number of spends fetched in parallel when crawling the spend DAG
+    #[clap(long, default_value_t = DEFAULT_CRAWLER_WORKERS)]
+    crawler_workers: usize,
+    /// Limit the number of spends fetched per second when crawling the spend DAG
+    #[clap(long)]
+    max_requests_per_sec: Option<u32>,
+    /// Limit the number of requests per second sent to a single peer when crawling the spend DAG
+    #[clap(long)]
+    max_requests_per_peer_per_sec: Option<u32>,
 
     /// Specify the logging output destination.
     ///
@@ -74,11 +100,30 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
+    let mut sinks: Vec<Box<dyn AlertSink>> = vec![Box::new(FaultLogSink::new(
+        opt.fault_log
+            .unwrap_or(auditor_data_dir()?.join(FAULT_LOG_FILENAME)),
+    ))];
+    for url in opt.alert_webhook {
+        sinks.push(Box::new(WebhookSink::new(url)?));
+    }
+
+    let metrics = AuditorMetrics::new();
+    let crawler_cfg = CrawlerCfg {
+        workers: opt.crawler_workers,
+        max_requests_per_sec: opt.max_requests_per_sec,
+        max_requests_per_peer_per_sec: opt.max_requests_per_peer_per_sec,
+        ..Default::default()
+    };
+
     let client = connect_to_network(opt.peers).await?;
     let dag = initialize_background_spend_dag_collection(
         client.clone(),
         opt.force_from_genesis,
         opt.clean,
+        FaultAlerter::new(sinks, metrics.clone()),
+        metrics,
+        crawler_cfg,
     )
     .await?;
     start_server(dag).await
@@ -124,6 +169,13 @@ async fn connect_to_network(peers: PeersArgs) -> Result<Client> {
     Ok(client)
 }
 
+fn auditor_data_dir() -> Result<PathBuf> {
+    Ok(dirs_next::data_dir()
+        .ok_or(eyre!("Could not obtain data directory path"))?
+        .join("safe")
+        .join("auditor"))
+}
+
 /// Get DAG from disk or initialize it if it doesn't exist
 /// Spawn a background thread to update the DAG in the background
 /// Return a handle to the DAG
@@ -131,22 +183,37 @@ async fn initialize_background_spend_dag_collection(
     client: Client,
     force_from_genesis: bool,
     clean: bool,
+    alerter: FaultAlerter,
+    metrics: AuditorMetrics,
+    crawler_cfg: CrawlerCfg,
 ) -> Result<SpendDagDb> {
     println!("Initialize spend dag...");
-    let path = dirs_next::data_dir()
-        .ok_or(eyre!("Could not obtain data directory path"))?
-        .join("safe")
-        .join("auditor");
+    let path = auditor_data_dir()?;
 
     // clean the local spend DAG if requested
     if clean {
         println!("Cleaning local spend DAG...");
         let dag_file = path.join(dag_db::SPEND_DAG_FILENAME);
         let _ = std::fs::remove_file(dag_file).map_err(|e| eprintln!("Cleanup interrupted: {e}"));
+        let store_dir = path.join(dag_store::SPEND_DAG_STORE_DIRNAME);
+        if store_dir.exists() {
+            let _ = std::fs::remove_dir_all(store_dir)
+                .map_err(|e| eprintln!("Cleanup interrupted: {e}"));
+        }
+        for filename in [
+            dag_db::CRAWLER_FRONTIER_FILENAME,
+            royalties::ROYALTIES_LEDGER_FILENAME,
+        ] {
+            let file = path.join(filename);
+            if file.exists() {
+                let _ =
+                    std::fs::remove_file(file).map_err(|e| eprintln!("Cleanup interrupted: {e}"));
+            }
+        }
     }
 
     // initialize the DAG
-    let dag = dag_db::SpendDagDb::new(path.clone(), client.clone())
+    let dag = dag_db::SpendDagDb::new(path.clone(), client.clone(), alerter, metrics, crawler_cfg)
         .await
         .map_err(|e| eyre!("Could not create SpendDag Db: {e}"))?;
 
@@ -182,9 +249,6 @@ async fn initialize_background_spend_dag_collection(
                 .update()
                 .await
                 .map_err(|e| eprintln!("Could not update DAG: {e}"));
-            let _ = d
-                .dump()
-                .map_err(|e| eprintln!("Could not dump DAG to disk: {e}"));
             println!("Sleeping for 60 seconds...");
             tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
         }
@@ -206,7 +270,9 @@ async fn start_server(dag: SpendDagDb) -> Result<()> {
         // Dispatch the request to the appropriate handler
         let response = match request.url() {
             "/" => routes::spend_dag_svg(&dag),
+            "/metrics" => routes::metrics(&dag),
             s if s.starts_with("/spend/") => routes::spend(&dag, &request),
+            s if s.starts_with(api::API_PREFIX) => api::handle(&dag, &request),
             _ => routes::not_found(),
         };
 
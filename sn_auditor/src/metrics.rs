@@ -0,0 +1,170 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use color_eyre::eyre::{eyre, Result};
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{counter::Counter, family::Family, gauge::Gauge},
+    registry::Registry,
+};
+use sn_client::CrawlProgress;
+use std::sync::{atomic::AtomicU64, Arc};
+
+use crate::{dag_db::FAULT_KINDS, royalties::RoyaltiesSummary};
+
+#[derive(EncodeLabelSet, Hash, Clone, Eq, PartialEq, Debug)]
+struct FaultKindLabel {
+    kind: String,
+}
+
+/// The metrics of the auditor, served in the Prometheus text format at `/metrics`
+#[derive(Clone)]
+pub struct AuditorMetrics {
+    registry: Arc<Registry>,
+    faults_detected: Family<FaultKindLabel, Counter>,
+    // progress of the current (or last) crawl
+    crawl_spends_gathered: Gauge,
+    crawl_requests: Gauge,
+    crawl_errors: Gauge,
+    crawl_frontier: Gauge,
+    crawl_generation: Gauge,
+    crawl_requests_per_sec: Gauge<f64, AtomicU64>,
+    royalties_paid: Gauge,
+    royalties_expected: Gauge,
+    royalty_issues: Gauge,
+}
+
+impl Default for AuditorMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuditorMetrics {
+    pub fn new() -> Self {
+        let mut registry = Registry::default();
+        let sub_registry = registry.sub_registry_with_prefix("sn_auditor");
+
+        let faults_detected = Family::<FaultKindLabel, Counter>::default();
+        sub_registry.register(
+            "faults_detected",
+            "Number of faults detected in the spend DAG",
+            faults_detected.clone(),
+        );
+        // report all the kinds from the start, so that alerting rules can rely on them
+        for kind in FAULT_KINDS {
+            let _ = faults_detected.get_or_create(&FaultKindLabel {
+                kind: kind.to_string(),
+            });
+        }
+
+        let crawl_spends_gathered = Gauge::default();
+        sub_registry.register(
+            "crawl_spends_gathered",
+            "Number of spends gathered by the current crawl",
+            crawl_spends_gathered.clone(),
+        );
+        let crawl_requests = Gauge::default();
+        sub_registry.register(
+            "crawl_requests",
+            "Number of spend requests made by the current crawl",
+            crawl_requests.clone(),
+        );
+        let crawl_errors = Gauge::default();
+        sub_registry.register(
+            "crawl_errors",
+            "Number of spends that could not be fetched by the current crawl",
+            crawl_errors.clone(),
+        );
+        let crawl_frontier = Gauge::default();
+        sub_registry.register(
+            "crawl_frontier",
+            "Number of addresses left to crawl",
+            crawl_frontier.clone(),
+        );
+        let crawl_generation = Gauge::default();
+        sub_registry.register(
+            "crawl_generation",
+            "The generation reached by the current crawl",
+            crawl_generation.clone(),
+        );
+        let crawl_requests_per_sec = Gauge::<f64, AtomicU64>::default();
+        sub_registry.register(
+            "crawl_requests_per_sec",
+            "The average rate of spend requests of the current crawl",
+            crawl_requests_per_sec.clone(),
+        );
+
+        let royalties_paid = Gauge::default();
+        sub_registry.register(
+            "royalties_paid_nanos",
+            "The royalties paid to the Foundation by the storage payments of the DAG",
+            royalties_paid.clone(),
+        );
+        let royalties_expected = Gauge::default();
+        sub_registry.register(
+            "royalties_expected_nanos",
+            "The royalties expected from the store costs paid in the DAG",
+            royalties_expected.clone(),
+        );
+        let royalty_issues = Gauge::default();
+        sub_registry.register(
+            "royalty_issues",
+            "Number of storage payments missing or underpaying their royalty",
+            royalty_issues.clone(),
+        );
+
+        Self {
+            registry: Arc::new(registry),
+            faults_detected,
+            crawl_spends_gathered,
+            crawl_requests,
+            crawl_errors,
+            crawl_frontier,
+            crawl_generation,
+            crawl_requests_per_sec,
+            royalties_paid,
+            royalties_expected,
+            royalty_issues,
+        }
+    }
+
+    pub fn record_fault(&self, kind: &str) {
+        let _ = self
+            .faults_detected
+            .get_or_create(&FaultKindLabel {
+                kind: kind.to_string(),
+            })
+            .inc();
+    }
+
+    pub fn record_crawl_progress(&self, progress: &CrawlProgress) {
+        let _ = self
+            .crawl_spends_gathered
+            .set(progress.spends_gathered as i64);
+        let _ = self.crawl_requests.set(progress.requests as i64);
+        let _ = self.crawl_errors.set(progress.errors as i64);
+        let _ = self.crawl_frontier.set(progress.frontier as i64);
+        let _ = self.crawl_generation.set(i64::from(progress.generation));
+        let _ = self.crawl_requests_per_sec.set(progress.requests_per_sec);
+    }
+
+    pub fn record_royalties(&self, summary: &RoyaltiesSummary) {
+        let _ = self.royalties_paid.set(summary.paid as i64);
+        let _ = self.royalties_expected.set(summary.expected as i64);
+        let _ = self.royalty_issues.set(summary.issues as i64);
+    }
+
+    /// Encode the metrics in the Prometheus text format
+    pub fn encode(&self) -> Result<String> {
+        let mut buffer = String::new();
+        prometheus_client::encoding::text::encode(&mut buffer, &self.registry)
+            .map_err(|e| eyre!("Failed to encode metrics: {e}"))?;
+        Ok(buffer)
+    }
+}
@@ -47,7 +47,14 @@ pub(crate) fn spend(dag: &SpendDagDb, request: &Request) -> Result<Response<Curs
     Ok(response)
 }
 
+pub(crate) fn metrics(dag: &SpendDagDb) -> Result<Response<Cursor<Vec<u8>>>> {
+    let metrics = dag.encode_metrics()?;
+    let response = Response::from_string(metrics);
+    Ok(response)
+}
+
 pub(crate) fn not_found() -> Result<Response<Cursor<Vec<u8>>>> {
-    let response = Response::from_string("404: Try /").with_status_code(404);
+    let response =
+        Response::from_string("404: Try / or /api/v1/openapi.json").with_status_code(404);
     Ok(response)
 }
@@ -0,0 +1,620 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Joins the storage payments of the DAG to their network royalties outputs.
+//!
+//! A storage payment pays each node its store cost along with a royalty of
+//! `calculate_royalties_fee(store_cost)` to the Foundation, in the same transaction. The purpose of
+//! each royalty output names the data address it paid for, see `network_royalties_purpose`. A
+//! transaction is considered a storage payment if it has royalty outputs or if it was spent for
+//! `SpendReason::NetworkData`.
+
+use color_eyre::eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use sn_client::transfers::{
+    calculate_royalties_fee, is_network_royalties_purpose, network_royalties_data_address, Hash,
+    NanoTokens, SignedSpend, SpendAddress, SpendReason, UniquePubkey, CASHNOTE_PURPOSE_OF_CHANGE,
+    NETWORK_ROYALTIES_PK,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{File, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const ROYALTIES_LEDGER_FILENAME: &str = "royalties_ledger.log";
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+pub const ROYALTY_ISSUE_KINDS: [&str; 3] = ["missing", "underpaid", "misdirected"];
+
+/// A storage payment that did not pay its royalty as expected
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RoyaltyIssue {
+    /// One of `ROYALTY_ISSUE_KINDS`
+    pub kind: String,
+    /// The hash of the payment transaction
+    pub tx: String,
+    /// The addresses of the spends of the payment transaction
+    pub spends: Vec<String>,
+    /// The paid data address, as named by the royalty output or by a `SpendReason::NetworkData`
+    pub data_address: Option<String>,
+    /// The address of the output paying the node
+    pub payee: String,
+    /// The store cost paid to the node, in nanos
+    pub store_cost: u64,
+    /// The royalty expected for that store cost, in nanos
+    pub expected: u64,
+    /// The royalty paid to the Foundation for that store cost, in nanos
+    pub paid: u64,
+}
+
+/// The royalties first seen by the auditor on a given day
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RoyaltyIncome {
+    /// The start of the day, in seconds since the UNIX epoch
+    pub day: u64,
+    pub count: usize,
+    /// In nanos
+    pub amount: u64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RoyaltiesSummary {
+    pub storage_payments: usize,
+    /// The royalties paid to the Foundation, in nanos
+    pub paid: u64,
+    /// The royalties expected from the store costs, in nanos
+    pub expected: u64,
+    pub issues: usize,
+    /// The royalties income by day, as first seen by the auditor
+    pub income: Vec<RoyaltyIncome>,
+}
+
+/// What a call to `RoyaltyTracker::update` added to the ledger, the records of the ledger log
+#[derive(Default, Serialize, Deserialize)]
+struct LedgerRecord {
+    /// The day the royalties were first seen
+    day: u64,
+    /// The storage payment transactions reconciled
+    txs: Vec<Hash>,
+    paid: u64,
+    expected: u64,
+    issues: Vec<RoyaltyIssue>,
+    /// The royalties seen for the first time, with their amount in nanos
+    royalties: Vec<(SpendAddress, u64)>,
+}
+
+/// The royalties reconciliation and the royalty income by day
+#[derive(Default)]
+struct Ledger {
+    seen: BTreeSet<SpendAddress>,
+    income: BTreeMap<u64, (usize, u64)>,
+    /// The storage payment transactions already reconciled
+    reconciled: BTreeSet<Hash>,
+    paid: u64,
+    expected: u64,
+    issues: Vec<RoyaltyIssue>,
+}
+
+impl Ledger {
+    fn apply(&mut self, record: LedgerRecord) {
+        self.reconciled.extend(record.txs);
+        self.paid = self.paid.saturating_add(record.paid);
+        self.expected = self.expected.saturating_add(record.expected);
+        self.issues.extend(record.issues);
+        for (addr, amount) in record.royalties {
+            if self.seen.insert(addr) {
+                let (count, total) = self.income.entry(record.day).or_default();
+                *count += 1;
+                *total = total.saturating_add(amount);
+            }
+        }
+    }
+}
+
+/// Keeps the royalties reconciliation of the DAG and the history of the royalties income.
+///
+/// What each update adds to the ledger is appended to a log, which is replayed on restart, so that
+/// only the new spends need to be reconciled and nothing is ever rewritten.
+/// Layout of a record in the log: `[length (u32 LE)][msgpack LedgerRecord]`
+pub struct RoyaltyTracker {
+    log: Option<File>,
+    ledger: Ledger,
+}
+
+impl RoyaltyTracker {
+    /// Replay the ledger log at `path` if any, the ledger is kept in memory only without a `path`
+    pub fn new(path: Option<PathBuf>) -> Result<Self> {
+        let mut ledger = Ledger::default();
+        let log = match path {
+            Some(path) => {
+                for record in read_records(&path)? {
+                    ledger.apply(record);
+                }
+                Some(OpenOptions::new().create(true).append(true).open(&path)?)
+            }
+            None => None,
+        };
+        Ok(Self { log, ledger })
+    }
+
+    /// Reconcile the storage payments of the given spends that were not reconciled yet and record
+    /// the income of the royalties not seen yet
+    pub fn update(&mut self, spends: &[SignedSpend]) -> Result<()> {
+        let reconciliation = reconcile(
+            spends
+                .iter()
+                .filter(|s| !self.ledger.reconciled.contains(&s.spend.spent_tx.hash())),
+        );
+        let mut new_royalties = BTreeSet::new();
+        let record = LedgerRecord {
+            day: unix_timestamp() / SECONDS_PER_DAY * SECONDS_PER_DAY,
+            royalties: reconciliation
+                .royalties
+                .into_iter()
+                .filter(|(addr, _)| !self.ledger.seen.contains(addr) && new_royalties.insert(*addr))
+                .collect(),
+            txs: reconciliation.txs,
+            paid: reconciliation.paid,
+            expected: reconciliation.expected,
+            issues: reconciliation.issues,
+        };
+        if record.txs.is_empty() && record.royalties.is_empty() {
+            return Ok(());
+        }
+
+        info!(
+            "Reconciled {} new storage payments, found {} new royalties payments",
+            record.txs.len(),
+            record.royalties.len()
+        );
+        if let Some(log) = self.log.as_mut() {
+            let payload = rmp_serde::to_vec(&record)?;
+            let len = u32::try_from(payload.len())
+                .map_err(|_| eyre!("Royalties ledger record is too large"))?;
+            let mut bytes = Vec::with_capacity(4 + payload.len());
+            bytes.extend_from_slice(&len.to_le_bytes());
+            bytes.extend_from_slice(&payload);
+            log.write_all(&bytes)?;
+            log.sync_data()?;
+        }
+        self.ledger.apply(record);
+        Ok(())
+    }
+
+    pub fn summary(&self) -> RoyaltiesSummary {
+        RoyaltiesSummary {
+            storage_payments: self.ledger.reconciled.len(),
+            paid: self.ledger.paid,
+            expected: self.ledger.expected,
+            issues: self.ledger.issues.len(),
+            income: self
+                .ledger
+                .income
+                .iter()
+                .map(|(day, (count, amount))| RoyaltyIncome {
+                    day: *day,
+                    count: *count,
+                    amount: *amount,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn issues(&self) -> &[RoyaltyIssue] {
+        &self.ledger.issues
+    }
+}
+
+/// Read the records of the ledger log, truncating a partially written record at the end if any
+fn read_records(path: &Path) -> Result<Vec<LedgerRecord>> {
+    let bytes = match std::fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while offset + 4 <= bytes.len() {
+        let mut len = [0u8; 4];
+        len.copy_from_slice(&bytes[offset..offset + 4]);
+        let record_end = offset + 4 + u32::from_le_bytes(len) as usize;
+        if record_end > bytes.len() {
+            break;
+        }
+        records.push(rmp_serde::from_slice(&bytes[offset + 4..record_end])?);
+        offset = record_end;
+    }
+
+    if offset < bytes.len() {
+        warn!("Truncating partially written record at the end of {path:?}");
+        OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len(offset as u64)?;
+    }
+    Ok(records)
+}
+
+#[derive(Default)]
+struct Reconciliation {
+    paid: u64,
+    expected: u64,
+    issues: Vec<RoyaltyIssue>,
+    /// The royalty outputs claimable by the Foundation, with their amount in nanos
+    royalties: Vec<(SpendAddress, u64)>,
+    /// The storage payment transactions reconciled
+    txs: Vec<Hash>,
+}
+
+/// Match the payee outputs of every storage payment to a royalty output of the expected amount
+fn reconcile<'a>(spends: impl IntoIterator<Item = &'a SignedSpend>) -> Reconciliation {
+    // the spends of the same transaction, the inputs of a payment
+    let mut txs: BTreeMap<Hash, Vec<&SignedSpend>> = BTreeMap::new();
+    for spend in spends {
+        txs.entry(spend.spend.spent_tx.hash())
+            .or_default()
+            .push(spend);
+    }
+
+    let mut reconciliation = Reconciliation::default();
+    for (tx_hash, spends) in txs {
+        let Some(first) = spends.first() else {
+            continue;
+        };
+        let reason_data_address = spends.iter().find_map(|s| match &s.spend.reason {
+            SpendReason::NetworkData(xorname) => Some(hex::encode(xorname)),
+            _ => None,
+        });
+        let outputs = &first.spend.spent_tx.outputs;
+        let (royalty_outputs, payee_outputs): (Vec<_>, Vec<_>) = outputs
+            .iter()
+            .filter(|o| o.purpose != CASHNOTE_PURPOSE_OF_CHANGE)
+            .partition(|o| is_network_royalties_purpose(&o.purpose));
+        if royalty_outputs.is_empty() && reason_data_address.is_none() {
+            continue;
+        }
+        reconciliation.txs.push(tx_hash);
+
+        // royalty outputs must be claimable by the Foundation with the public derivation indexes
+        let foundation_keys: BTreeSet<UniquePubkey> = spends
+            .iter()
+            .flat_map(|s| s.spend.network_royalties.iter())
+            .map(|idx| NETWORK_ROYALTIES_PK.new_unique_pubkey(idx))
+            .collect();
+        let (mut claimable, mut misdirected): (Vec<_>, Vec<_>) = royalty_outputs
+            .into_iter()
+            .partition(|o| foundation_keys.contains(&o.unique_pubkey));
+        for royalty in claimable.iter() {
+            let amount = royalty.amount.as_nano();
+            reconciliation.paid = reconciliation.paid.saturating_add(amount);
+            reconciliation.royalties.push((
+                SpendAddress::from_unique_pubkey(&royalty.unique_pubkey),
+                amount,
+            ));
+        }
+
+        // pair each payee with a royalty, exact matches first
+        let mut unmatched = Vec::new();
+        for payee in payee_outputs {
+            let expected = calculate_royalties_fee(payee.amount);
+            reconciliation.expected = reconciliation.expected.saturating_add(expected.as_nano());
+            match claimable.iter().position(|r| r.amount == expected) {
+                Some(i) => {
+                    let _ = claimable.swap_remove(i);
+                }
+                None => unmatched.push((payee, expected)),
+            }
+        }
+        for (payee, expected) in unmatched {
+            let royalty = claimable
+                .iter()
+                .position(|r| r.amount < expected)
+                .map(|i| ("underpaid", claimable.swap_remove(i)))
+                .or_else(|| {
+                    misdirected
+                        .iter()
+                        .position(|r| r.amount >= expected)
+                        .map(|i| ("misdirected", misdirected.swap_remove(i)))
+                });
+            let (kind, paid, data_address) = match royalty {
+                Some((kind, royalty)) => (
+                    kind,
+                    royalty.amount,
+                    network_royalties_data_address(&royalty.purpose).map(hex::encode),
+                ),
+                None => ("missing", NanoTokens::zero(), None),
+            };
+            reconciliation.issues.push(RoyaltyIssue {
+                kind: kind.to_string(),
+                tx: tx_hash.to_hex(),
+                spends: spends.iter().map(|s| s.address().to_hex()).collect(),
+                data_address: data_address.or_else(|| reason_data_address.clone()),
+                payee: SpendAddress::from_unique_pubkey(&payee.unique_pubkey).to_hex(),
+                store_cost: payee.amount.as_nano(),
+                expected: expected.as_nano(),
+                paid: paid.as_nano(),
+            });
+        }
+    }
+    reconciliation
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_client::transfers::{
+        bls::SecretKey, rand, DerivationIndex, HotWallet, MainPubkey, MainSecretKey,
+        OfflineTransfer, PaymentQuote, CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES, GENESIS_CASHNOTE,
+        GENESIS_CASHNOTE_SK,
+    };
+    use xor_name::XorName;
+
+    const STORE_COST: u64 = 850_000;
+    const PURPOSE_OF_STORE_COST: &str = "store cost";
+
+    fn random_pk() -> MainPubkey {
+        MainSecretKey::new(SecretKey::random()).main_pubkey()
+    }
+
+    /// Spend genesis to the given outputs `(amount, purpose, recipient)`
+    fn pay(outputs: Vec<(u64, &str, MainPubkey)>, reason: SpendReason) -> Result<Vec<SignedSpend>> {
+        let genesis_sk = MainSecretKey::new(SecretKey::from_hex(GENESIS_CASHNOTE_SK)?);
+        let key = GENESIS_CASHNOTE.derived_key(&genesis_sk)?;
+        let transfer = OfflineTransfer::new(
+            vec![(GENESIS_CASHNOTE.clone(), Some(key))],
+            outputs
+                .into_iter()
+                .map(|(amount, purpose, recipient)| {
+                    (
+                        NanoTokens::from(amount),
+                        purpose.to_string(),
+                        recipient,
+                        DerivationIndex::random(&mut rand::thread_rng()),
+                    )
+                })
+                .collect(),
+            genesis_sk.main_pubkey(),
+            reason,
+        )?;
+        Ok(transfer.all_spend_requests)
+    }
+
+    fn royalty() -> u64 {
+        calculate_royalties_fee(NanoTokens::from(STORE_COST)).as_nano()
+    }
+
+    /// Pay for the storage of the data at the given addresses from the genesis wallet, as a client
+    /// does, each to a different node
+    fn pay_for_storage(data_addresses: &[XorName]) -> Result<Vec<SignedSpend>> {
+        let wallet_dir = tempfile::tempdir()?;
+        let genesis_sk = MainSecretKey::new(SecretKey::from_hex(GENESIS_CASHNOTE_SK)?);
+        let mut wallet = HotWallet::create_from_key(wallet_dir.path(), genesis_sk)?;
+        wallet.deposit_and_store_to_disk(&vec![GENESIS_CASHNOTE.clone()])?;
+
+        let price_map = data_addresses
+            .iter()
+            .map(|address| {
+                let mut quote = PaymentQuote::zero();
+                quote.content = *address;
+                quote.cost = NanoTokens::from(STORE_COST);
+                (*address, (random_pk(), quote, vec![]))
+            })
+            .collect();
+        let _ = wallet.local_send_storage_payment(&price_map)?;
+        Ok(wallet
+            .unconfirmed_spend_requests()
+            .iter()
+            .cloned()
+            .collect())
+    }
+
+    #[test]
+    fn storage_payments_should_name_the_data_address_of_their_royalties() -> Result<()> {
+        let data_addresses = [XorName([1; 32]), XorName([2; 32])];
+        let mut spends = pay_for_storage(&data_addresses)?;
+
+        let reconciliation = reconcile(&spends);
+        assert_eq!(reconciliation.txs.len(), 1);
+        assert_eq!(reconciliation.expected, 2 * royalty());
+        assert_eq!(reconciliation.paid, 2 * royalty());
+        assert!(reconciliation.issues.is_empty());
+        let paid_for: BTreeSet<XorName> = spends[0]
+            .spend
+            .spent_tx
+            .outputs
+            .iter()
+            .filter_map(|o| network_royalties_data_address(&o.purpose))
+            .collect();
+        assert_eq!(paid_for, BTreeSet::from(data_addresses));
+
+        // an issue with one of the royalties names the data it paid for
+        for spend in spends.iter_mut() {
+            let output = spend
+                .spend
+                .spent_tx
+                .outputs
+                .iter_mut()
+                .find(|o| network_royalties_data_address(&o.purpose) == Some(data_addresses[1]))
+                .expect("a royalty output for the data");
+            output.amount = NanoTokens::from(royalty() - 1);
+        }
+        let reconciliation = reconcile(&spends);
+        assert_eq!(reconciliation.issues.len(), 1);
+        let issue = &reconciliation.issues[0];
+        assert_eq!(issue.kind, "underpaid");
+        assert_eq!(issue.data_address, Some(hex::encode(data_addresses[1])));
+        assert_eq!(issue.paid, royalty() - 1);
+        Ok(())
+    }
+
+    #[test]
+    fn ledger_should_only_append_new_records_and_survive_restarts() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(ROYALTIES_LEDGER_FILENAME);
+        let spends = pay_for_storage(&[XorName([1; 32])])?;
+
+        let mut tracker = RoyaltyTracker::new(Some(path.clone()))?;
+        tracker.update(&spends)?;
+        let len = std::fs::metadata(&path)?.len();
+        assert!(len > 0);
+        // nothing new to record
+        tracker.update(&spends)?;
+        assert_eq!(std::fs::metadata(&path)?.len(), len);
+
+        // a partially written record is dropped on restart
+        let mut log = OpenOptions::new().append(true).open(&path)?;
+        log.write_all(&[42, 0, 0, 0, 1])?;
+        let mut tracker = RoyaltyTracker::new(Some(path.clone()))?;
+        assert_eq!(std::fs::metadata(&path)?.len(), len);
+        let summary = tracker.summary();
+        assert_eq!(summary.storage_payments, 1);
+        assert_eq!(summary.paid, royalty());
+        assert_eq!(summary.expected, royalty());
+        assert_eq!(summary.income.len(), 1);
+        assert_eq!(summary.income[0].count, 1);
+
+        // the replayed ledger knows the payment was already reconciled
+        tracker.update(&spends)?;
+        assert_eq!(std::fs::metadata(&path)?.len(), len);
+        assert_eq!(tracker.summary().storage_payments, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn royalties_matching_the_store_cost_should_not_raise_issues() -> Result<()> {
+        let spends = pay(
+            vec![
+                (STORE_COST, PURPOSE_OF_STORE_COST, random_pk()),
+                (
+                    royalty(),
+                    CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES,
+                    *NETWORK_ROYALTIES_PK,
+                ),
+            ],
+            SpendReason::default(),
+        )?;
+
+        let reconciliation = reconcile(&spends);
+        assert_eq!(reconciliation.txs.len(), 1);
+        assert_eq!(reconciliation.txs, vec![spends[0].spend.spent_tx.hash()]);
+        assert_eq!(reconciliation.expected, royalty());
+        assert_eq!(reconciliation.paid, royalty());
+        assert_eq!(reconciliation.royalties.len(), 1);
+        assert_eq!(reconciliation.royalties[0].1, royalty());
+        assert!(reconciliation.issues.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn payments_for_data_without_royalties_should_be_missing_them() -> Result<()> {
+        let data = xor_name::XorName([1; 32]);
+        let spends = pay(
+            vec![(STORE_COST, PURPOSE_OF_STORE_COST, random_pk())],
+            SpendReason::NetworkData(data),
+        )?;
+
+        let reconciliation = reconcile(&spends);
+        assert_eq!(reconciliation.txs.len(), 1);
+        assert_eq!(reconciliation.expected, royalty());
+        assert_eq!(reconciliation.paid, 0);
+        assert_eq!(reconciliation.issues.len(), 1);
+        let issue = &reconciliation.issues[0];
+        assert_eq!(issue.kind, "missing");
+        assert_eq!(issue.data_address, Some(hex::encode(data)));
+        assert_eq!(issue.store_cost, STORE_COST);
+        assert_eq!(issue.expected, royalty());
+        assert_eq!(issue.paid, 0);
+
+        // a transfer that pays no royalties and no data is not a storage payment
+        let spends = pay(
+            vec![(STORE_COST, PURPOSE_OF_STORE_COST, random_pk())],
+            SpendReason::default(),
+        )?;
+        let reconciliation = reconcile(&spends);
+        assert_eq!(reconciliation.txs.len(), 0);
+        assert!(reconciliation.txs.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn extra_royalties_should_be_counted_as_paid() -> Result<()> {
+        let spends = pay(
+            vec![
+                (STORE_COST, PURPOSE_OF_STORE_COST, random_pk()),
+                (
+                    royalty(),
+                    CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES,
+                    *NETWORK_ROYALTIES_PK,
+                ),
+                (
+                    royalty(),
+                    CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES,
+                    *NETWORK_ROYALTIES_PK,
+                ),
+            ],
+            SpendReason::default(),
+        )?;
+
+        let reconciliation = reconcile(&spends);
+        assert_eq!(reconciliation.txs.len(), 1);
+        assert_eq!(reconciliation.expected, royalty());
+        assert_eq!(reconciliation.paid, 2 * royalty());
+        assert_eq!(reconciliation.royalties.len(), 2);
+        assert!(reconciliation.issues.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn royalties_that_are_short_or_not_claimable_should_raise_issues() -> Result<()> {
+        let spends = pay(
+            vec![
+                (STORE_COST, PURPOSE_OF_STORE_COST, random_pk()),
+                (
+                    royalty() - 1,
+                    CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES,
+                    *NETWORK_ROYALTIES_PK,
+                ),
+                (2 * STORE_COST, PURPOSE_OF_STORE_COST, random_pk()),
+                (
+                    2 * royalty(),
+                    CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES,
+                    random_pk(),
+                ),
+            ],
+            SpendReason::default(),
+        )?;
+
+        let reconciliation = reconcile(&spends);
+        assert_eq!(reconciliation.txs.len(), 1);
+        // the misdirected royalty cannot be claimed by the Foundation
+        assert_eq!(reconciliation.paid, royalty() - 1);
+        let mut issues: Vec<(&str, u64)> = reconciliation
+            .issues
+            .iter()
+            .map(|issue| (issue.kind.as_str(), issue.paid))
+            .collect();
+        issues.sort();
+        assert_eq!(
+            issues,
+            vec![("misdirected", 2 * royalty()), ("underpaid", royalty() - 1)]
+        );
+        Ok(())
+    }
+}
@@ -11,7 +11,7 @@ mod change_tracking;
 use change_tracking::*;
 
 use super::{
-    files::{download_file, FilesUploader},
+    files::{download_file, FilesUploader, ProgressMode},
     ChunkManager,
 };
 
@@ -783,7 +783,8 @@ impl AccountPacket {
 
         let files_api: FilesApi = FilesApi::new(self.client.clone(), self.files_dir.clone());
         for (file_name, data_map_chunk, path) in files_to_download {
-            download_file(
+            // a failed download is reported by `download_file`, carry on with the other files.
+            let _ = download_file(
                 files_api.clone(),
                 *data_map_chunk.name(),
                 (file_name, Some(data_map_chunk)),
@@ -791,6 +792,7 @@ impl AccountPacket {
                 false,
                 batch_size,
                 retry_strategy,
+                ProgressMode::Bar,
             )
             .await;
         }
@@ -956,6 +958,7 @@ mod tests {
             show_holders: false,
             max_repayments_for_failed_data: 1,
             collect_registers: false,
+            erasure_coding: None,
         };
         let make_data_public = false;
         (cfg, make_data_public)
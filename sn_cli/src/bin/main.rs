@@ -22,6 +22,7 @@ use subcommands::{
     Opt, SubCmd,
 };
 
+use autonomi::ProgressMode;
 use bls::SecretKey;
 use clap::Parser;
 use color_eyre::Result;
@@ -68,10 +69,15 @@ async fn main() -> Result<()> {
         "safe client built with git version: {}",
         sn_build_info::git_info()
     );
-    println!(
+    // Keep stdout for the progress of the transfers if it's reported as JSON.
+    let progress = match &opt.cmd {
+        SubCmd::Files(cmds) => cmds.progress_mode(),
+        _ => ProgressMode::default(),
+    };
+    progress.println(format_args!(
         "safe client built with git version: {}",
         sn_build_info::git_info()
-    );
+    ));
 
     let client_data_dir_path = get_client_data_dir_path()?;
     // Perform actions that do not require us connecting to the network and return early
@@ -98,15 +104,15 @@ async fn main() -> Result<()> {
         }
     }
 
-    println!("Instantiating a SAFE client...");
+    progress.println("Instantiating a SAFE client...");
     let secret_key = get_client_secret_key(&client_data_dir_path)?;
 
     let bootstrap_peers = get_peers_from_args(opt.peers).await?;
 
-    println!(
+    progress.println(format_args!(
         "Connecting to the network with {} peers",
         bootstrap_peers.len(),
-    );
+    ));
 
     let bootstrap_peers = if bootstrap_peers.is_empty() {
         // empty vec is returned if `local-discovery` flag is provided
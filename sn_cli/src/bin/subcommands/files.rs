@@ -7,7 +7,8 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use autonomi::{
-    download_file, download_files, ChunkManager, Estimator, FilesUploader, UploadedFile,
+    download_file, download_files, read_manifest, read_share_token, share_file, upload_manifest,
+    verify_manifest, ChunkManager, Estimator, FilesUploader, ProgressMode, UploadedFile,
     UPLOADED_FILES,
 };
 use clap::Parser;
@@ -17,12 +18,13 @@ use color_eyre::{
 };
 use sn_client::{
     protocol::storage::{Chunk, ChunkAddress, RetryStrategy},
-    UploadCfg,
+    DownloadFilter, ErasureCodingCfg, UploadCfg, MAX_CONCURRENT_FILE_DOWNLOADS,
 };
 use sn_client::{Client, FilesApi, BATCH_SIZE};
 use std::{
     ffi::OsString,
     path::{Path, PathBuf},
+    time::Duration,
 };
 use walkdir::WalkDir;
 use xor_name::XorName;
@@ -56,6 +58,23 @@ pub enum FilesCmds {
         /// to 'persistent' (most effort).
         #[clap(long, default_value_t = RetryStrategy::Balanced, short = 'r', help = "Sets the retry strategy on upload failure. Options: 'quick' for minimal effort, 'balanced' for moderate effort, or 'persistent' for maximum effort.")]
         retry_strategy: RetryStrategy,
+        /// Create a signed manifest listing every uploaded file, and store it on the network.
+        ///
+        /// The manifest can later be used with 'files verify' to check that the whole dataset is still retrievable.
+        #[clap(long, default_value = "false")]
+        manifest: bool,
+        /// Protect each file with Reed-Solomon parity chunks, in the form '<data_shards>:<parity_shards>'.
+        ///
+        /// E.g. '4:2' generates 2 parity chunks for every 4 chunks of a file, allowing any 2 of them to be lost
+        /// while still being able to download the file. The parity chunks are paid for as ordinary chunks.
+        #[clap(long, value_name = "DATA:PARITY")]
+        erasure_coding: Option<ErasureCodingCfg>,
+        /// How to report the progress of the upload.
+        ///
+        /// 'json' prints a JSON object per line with the bytes transferred, throughput, ETA, retries and the
+        /// latency of each peer, and is meant to be consumed by scripts. Any other messages are printed to stderr.
+        #[clap(long, value_enum, default_value_t = ProgressMode::Bar)]
+        progress: ProgressMode,
     },
     Download {
         /// The name to apply to the downloaded file.
@@ -76,16 +95,105 @@ pub enum FilesCmds {
         /// Default to be not showing.
         #[clap(long, name = "show_holders", default_value = "false")]
         show_holders: bool,
-        /// The batch_size for parallel downloading
+        /// The batch_size for parallel downloading.
+        ///
+        /// When downloading all the files, this limits the number of chunks fetched at once across all the files.
         #[clap(long, default_value_t = BATCH_SIZE , short='b')]
         batch_size: usize,
+        /// The number of files to download in parallel, when downloading all the files.
+        #[clap(long, default_value_t = MAX_CONCURRENT_FILE_DOWNLOADS)]
+        concurrent_files: usize,
+        /// Only download the files whose path matches this glob pattern, e.g. 'photos/**/*.jpg'.
+        ///
+        /// Can be provided multiple times. Applies when downloading all the files.
+        #[clap(long, value_name = "GLOB")]
+        include: Vec<String>,
+        /// Do not download the files whose path matches this glob pattern.
+        ///
+        /// Can be provided multiple times. Applies when downloading all the files.
+        #[clap(long, value_name = "GLOB")]
+        exclude: Vec<String>,
         /// Set the strategy to use on downloads failure.
         ///
         /// Choose a retry strategy based on effort level, from 'quick' (least effort), through 'balanced',
         /// to 'persistent' (most effort).
         #[clap(long, default_value_t = RetryStrategy::Quick, short = 'r', help = "Sets the retry strategy on download failure. Options: 'quick' for minimal effort, 'balanced' for moderate effort, or 'persistent' for maximum effort.")]
         retry_strategy: RetryStrategy,
+        /// How to report the progress of the download.
+        ///
+        /// 'json' prints a JSON object per line with the bytes transferred, throughput, ETA and retries, and is
+        /// meant to be consumed by scripts. Any other messages are printed to stderr.
+        #[clap(long, value_enum, default_value_t = ProgressMode::Bar)]
+        progress: ProgressMode,
+    },
+    /// Verify that every chunk listed by a manifest is still stored on the network.
+    ///
+    /// The chunks are not downloaded, the close peers are asked to prove that they hold them instead.
+    /// Only the owner of the manifest can verify it, as the proofs are checked against challenges
+    /// that are encrypted to the owner's key.
+    Verify {
+        /// The manifest to verify. Can be a local manifest file or the hex address of a manifest chunk.
+        #[clap(name = "manifest")]
+        manifest: String,
+        /// The number of chunks to verify in parallel.
+        #[clap(long, default_value_t = BATCH_SIZE, short = 'b')]
+        batch_size: usize,
+    },
+    /// Share a privately uploaded file with the owner of a public key, without making it public.
+    ///
+    /// This creates a token holding the file's DataMap encrypted to the recipient, which can be opened with
+    /// 'files open'.
+    Share {
+        /// The file to share. Can be the hex address of the uploaded file, or its name.
+        #[clap(name = "file")]
+        file: String,
+        /// The hex encoded public key of the recipient, as printed by 'files share-key'.
+        #[clap(long, value_name = "PK")]
+        to: String,
+        /// A label to attach to the token, e.g. a note for the recipient.
+        #[clap(long)]
+        label: Option<String>,
+        /// The number of seconds after which the token can no longer be opened.
+        ///
+        /// Note that a recipient who has already opened the token keeps access to the file.
+        #[clap(long, value_name = "SECONDS")]
+        expires_in: Option<u64>,
+        /// Store the token on the network as a chunk and print its address, instead of printing the token.
+        #[clap(long, default_value = "false")]
+        store: bool,
+    },
+    /// Open a token created by 'files share' and download the shared file.
+    Open {
+        /// The hex encoded token, or the hex address of a token stored on the network.
+        #[clap(name = "token")]
+        token: String,
+        /// The name to save the file as. Defaults to the name provided by the sharer.
+        #[clap(name = "name")]
+        file_name: Option<OsString>,
+        /// The batch_size for parallel downloading
+        #[clap(long, default_value_t = BATCH_SIZE , short='b')]
+        batch_size: usize,
+        /// Set the strategy to use on downloads failure.
+        #[clap(long, default_value_t = RetryStrategy::Quick, short = 'r', help = "Sets the retry strategy on download failure. Options: 'quick' for minimal effort, 'balanced' for moderate effort, or 'persistent' for maximum effort.")]
+        retry_strategy: RetryStrategy,
+        /// How to report the progress of the download.
+        #[clap(long, value_enum, default_value_t = ProgressMode::Bar)]
+        progress: ProgressMode,
     },
+    /// Print the public key that others can share files with.
+    ShareKey,
+}
+
+impl FilesCmds {
+    /// How the command reports the progress of its transfers.
+    pub(crate) fn progress_mode(&self) -> ProgressMode {
+        match self {
+            FilesCmds::Upload { progress, .. }
+            | FilesCmds::Download { progress, .. }
+            | FilesCmds::Open { progress, .. } => *progress,
+            _ => ProgressMode::default(),
+        }
+    }
 }
 
 pub(crate) async fn files_cmds(
@@ -110,6 +218,9 @@ pub(crate) async fn files_cmds(
             batch_size,
             retry_strategy,
             make_data_public,
+            manifest,
+            erasure_coding,
+            progress,
         } => {
             let files_count = count_files_in_path_recursively(&file_path);
 
@@ -127,21 +238,52 @@ pub(crate) async fn files_cmds(
                 batch_size,
                 verify_store,
                 retry_strategy,
+                erasure_coding,
                 ..Default::default()
             };
             let files_uploader = FilesUploader::new(client.clone(), root_dir.to_path_buf())
                 .set_make_data_public(make_data_public)
                 .set_upload_cfg(upload_cfg)
+                .set_progress_mode(progress)
+                .set_collect_manifest_entries(manifest)
                 .insert_path(&file_path);
 
-            let _summary = files_uploader.start_upload().await?;
+            let summary = files_uploader.start_upload().await?;
+
+            if manifest {
+                if !summary.incomplete_files.is_empty() {
+                    progress.println(format_args!(
+                        "{} files were not uploaded completely and are not listed in the manifest.",
+                        summary.incomplete_files.len()
+                    ));
+                }
+                let address = upload_manifest(
+                    client,
+                    root_dir,
+                    &file_path,
+                    &summary.completed_files,
+                    &summary.manifest_entries,
+                    make_data_public,
+                    upload_cfg,
+                )
+                .await?;
+                progress.println(format_args!(
+                    "Manifest of {} files stored at {}",
+                    summary.completed_files.len(),
+                    address.to_hex()
+                ));
+            }
         }
         FilesCmds::Download {
             file_name,
             file_addr,
             show_holders,
             batch_size,
+            concurrent_files,
+            include,
+            exclude,
             retry_strategy,
+            progress,
         } => {
             if (file_name.is_some() && file_addr.is_none())
                 || (file_addr.is_some() && file_name.is_none())
@@ -221,7 +363,8 @@ pub(crate) async fn files_cmds(
                         }
                     };
 
-                    download_file(
+                    // the outcome is reported by `download_file`.
+                    let _ = download_file(
                         files_api,
                         xor_name_provided,
                         (download_file_name, local_data_map),
@@ -229,26 +372,135 @@ pub(crate) async fn files_cmds(
                         show_holders,
                         batch_size,
                         retry_strategy,
+                        progress,
                     )
-                    .await
+                    .await;
                 }
                 _ => {
-                    println!("Attempting to download all files uploaded by the current user...");
+                    progress.println(
+                        "Attempting to download all files uploaded by the current user...",
+                    );
+                    let filter = DownloadFilter::new(&include, &exclude)?;
                     download_files(
                         &files_api,
                         root_dir,
                         show_holders,
                         batch_size,
+                        concurrent_files,
                         retry_strategy,
+                        progress,
+                        &filter,
                     )
                     .await?
                 }
             }
         }
+        FilesCmds::Verify {
+            manifest,
+            batch_size,
+        } => {
+            let files_api = FilesApi::new(client.clone(), root_dir.to_path_buf());
+            let signed_manifest =
+                read_manifest(&files_api, &manifest, RetryStrategy::Quick).await?;
+            let verification = verify_manifest(&files_api, &signed_manifest, batch_size).await?;
+            if !verification.is_complete() {
+                bail!("Some chunks listed by the manifest could not be verified");
+            }
+        }
+        FilesCmds::Share {
+            file,
+            to,
+            label,
+            expires_in,
+            store,
+        } => {
+            let recipient = bls::PublicKey::from_hex(&to)
+                .map_err(|err| eyre!("Invalid recipient public key {to:?}: {err}"))?;
+            let files_api = FilesApi::new(client.clone(), root_dir.to_path_buf());
+            let upload_cfg = store.then(|| UploadCfg {
+                verify_store,
+                ..Default::default()
+            });
+            let token = share_file(
+                &files_api,
+                root_dir,
+                &file,
+                recipient,
+                label,
+                expires_in.map(Duration::from_secs),
+                upload_cfg,
+            )
+            .await?;
+            if store {
+                println!("Share token stored at {token}");
+            } else {
+                println!("Share token:\n{token}");
+            }
+        }
+        FilesCmds::Open {
+            token,
+            file_name,
+            batch_size,
+            retry_strategy,
+            progress,
+        } => {
+            let token = read_share_token(client, &token, retry_strategy).await?;
+            let download_dir = std::env::current_dir().unwrap_or(root_dir.to_path_buf());
+            let files_api = FilesApi::new(client.clone(), download_dir.clone());
+            let shared_file = files_api.open_share_token(&token)?;
+            progress.println(format_args!(
+                "Opened a file shared by {}",
+                token.sharer.to_hex()
+            ));
+            if let Some(label) = &shared_file.label {
+                progress.println(format_args!("Label: {label}"));
+            }
+
+            // The name in the token comes from the sharer, so it must not be able to escape the
+            // download directory.
+            let shared_file_name = shared_file
+                .file_name
+                .as_deref()
+                .map(sanitize_shared_file_name)
+                .transpose()?;
+            let file_name = file_name
+                .or(shared_file_name)
+                .unwrap_or_else(|| OsString::from(shared_file.head_address.to_hex()));
+            download_file(
+                files_api,
+                *shared_file.head_address.xorname(),
+                (file_name, Some(shared_file.data_map_chunk)),
+                &download_dir,
+                false,
+                batch_size,
+                retry_strategy,
+                progress,
+            )
+            .await?;
+        }
+        FilesCmds::ShareKey => {
+            println!("{}", client.signer_pk().to_hex());
+        }
     }
     Ok(())
 }
 
+/// Check the file name given by the sharer of a file is a plain file name, without any directory
+/// components that would place the download outside of the download directory.
+fn sanitize_shared_file_name(name: &str) -> Result<OsString> {
+    let path = Path::new(name);
+    let is_plain_name = !name.contains(['/', '\\'])
+        && !path.has_root()
+        && path.file_name() == Some(path.as_os_str());
+    if is_plain_name {
+        Ok(OsString::from(name))
+    } else {
+        Err(eyre!(
+            "The shared file name {name:?} is not a plain file name, give a name to save it as"
+        ))
+    }
+}
+
 fn count_files_in_path_recursively(file_path: &PathBuf) -> u32 {
     let entries_iterator = WalkDir::new(file_path).into_iter().flatten();
     let mut count = 0;
@@ -260,3 +512,32 @@ fn count_files_in_path_recursively(file_path: &PathBuf) -> u32 {
     });
     count
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_file_names_should_not_escape_the_download_dir() {
+        assert_eq!(
+            sanitize_shared_file_name("report.pdf").ok(),
+            Some(OsString::from("report.pdf"))
+        );
+        assert_eq!(
+            sanitize_shared_file_name("..hidden").ok(),
+            Some(OsString::from("..hidden"))
+        );
+        for name in [
+            "",
+            ".",
+            "..",
+            "../../.bashrc",
+            "/etc/cron.d/x",
+            "dir/file",
+            "..\\..\\file",
+            "C:\\Windows\\x",
+        ] {
+            assert!(sanitize_shared_file_name(name).is_err(), "{name:?}");
+        }
+    }
+}
@@ -92,6 +92,16 @@ pub enum WatchOnlyWalletCmds {
         #[clap(long, name = "force", default_value = "false")]
         force: bool,
     },
+    /// Sync watch-only wallets with the Network.
+    ///
+    /// The CashNotes spent on the Network are removed from the wallet, and the CashNotes sent to the wallet
+    /// by the transactions it knows of are added to it. Only the network royalties and the change of the
+    /// transactions built with the 'transaction' cmd can be found this way, other CashNotes have to be deposited.
+    Sync {
+        /// The hex-encoded public key of an existing watch-only wallet, all of them are synced if not provided.
+        #[clap(name = "public key")]
+        pk: Option<String>,
+    },
     /// Verify a spend on the Network.
     Verify {
         /// The Network address or hex encoded UniquePubkey of the Spend to verify
@@ -176,10 +186,35 @@ pub(crate) async fn wo_wallet_cmds_without_client(
 pub(crate) async fn wo_wallet_cmds(
     cmds: WatchOnlyWalletCmds,
     client: &Client,
-    _root_dir: &Path,
+    root_dir: &Path,
     verify_store: bool,
 ) -> Result<()> {
     match cmds {
+        WatchOnlyWalletCmds::Sync { pk } => {
+            let wallets = if let Some(pk) = pk {
+                let main_pk = MainPubkey::from_hex(&pk)?;
+                vec![(watch_only_wallet_from_pk(main_pk, root_dir)?, pk)]
+            } else {
+                get_watch_only_wallets(root_dir)?
+            };
+            for (mut wo_wallet, name) in wallets {
+                println!("Syncing watch-only wallet {name}...");
+                let summary = client.sync_watch_only_wallet(&mut wo_wallet).await?;
+                println!(
+                    "{} CashNotes spent, {} new CashNotes found, balance {}",
+                    summary.spent.len(),
+                    summary.found.len(),
+                    wo_wallet.balance()
+                );
+                if !summary.unchecked.is_empty() {
+                    println!(
+                        "Failed to check {} CashNotes on the Network, they are kept as unspent. Please try again later.",
+                        summary.unchecked.len()
+                    );
+                }
+            }
+            Ok(())
+        }
         WatchOnlyWalletCmds::Broadcast { signed_tx, force } => {
             broadcast_signed_spends(signed_tx, client, verify_store, force).await
         }
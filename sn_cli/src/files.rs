@@ -10,17 +10,45 @@ mod chunk_manager;
 mod download;
 mod estimate;
 mod files_uploader;
+mod manifest;
+mod share;
 mod upload;
 
 pub use chunk_manager::ChunkManager;
 pub use download::{download_file, download_files};
 pub use estimate::Estimator;
 pub use files_uploader::{FilesUploadStatusNotifier, FilesUploadSummary, FilesUploader};
+pub use manifest::{read_manifest, upload_manifest, verify_manifest, MANIFESTS_DIR};
+pub use share::{read_share_token, share_file};
 pub use upload::{UploadedFile, UPLOADED_FILES};
 
 use color_eyre::Result;
 use indicatif::{ProgressBar, ProgressStyle};
+use sn_client::TransferProgress;
 use std::time::Duration;
+use tokio::{sync::mpsc::Receiver, task::JoinHandle};
+
+/// How the progress of an upload or a download is reported.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ProgressMode {
+    /// Display progress bars.
+    #[default]
+    Bar,
+    /// Print a `TransferProgress` JSON object per line to stdout, without any progress bars. Other
+    /// messages are printed to stderr.
+    Json,
+}
+
+impl ProgressMode {
+    /// Print a message for the user. In JSON mode it goes to stderr, so that stdout only has the
+    /// progress objects.
+    pub fn println(self, msg: impl std::fmt::Display) {
+        match self {
+            ProgressMode::Bar => println!("{msg}"),
+            ProgressMode::Json => eprintln!("{msg}"),
+        }
+    }
+}
 
 pub fn get_progress_bar(length: u64) -> Result<ProgressBar> {
     let progress_bar = ProgressBar::new(length);
@@ -32,3 +60,15 @@ pub fn get_progress_bar(length: u64) -> Result<ProgressBar> {
     progress_bar.enable_steady_tick(Duration::from_millis(100));
     Ok(progress_bar)
 }
+
+/// Print each `TransferProgress` as a line of JSON to stdout until the channel is closed.
+pub fn spawn_json_progress_printer(mut progress_rx: Receiver<TransferProgress>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(progress) = progress_rx.recv().await {
+            match serde_json::to_string(&progress) {
+                Ok(line) => println!("{line}"),
+                Err(err) => tracing::error!("Failed to serialize the progress: {err:?}"),
+            }
+        }
+    })
+}
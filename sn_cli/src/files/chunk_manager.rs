@@ -16,7 +16,7 @@ use color_eyre::{
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
 use sn_client::{
     protocol::storage::{Chunk, ChunkAddress},
-    FilesApi,
+    ErasureCodingCfg, FilesApi,
 };
 use std::{
     collections::{BTreeMap, BTreeSet},
@@ -32,6 +32,8 @@ use xor_name::XorName;
 
 const CHUNK_ARTIFACTS_DIR: &str = "chunk_artifacts";
 const METADATA_FILE: &str = "metadata";
+// Records the erasure coding settings that the file was chunked with, if any.
+const ERASURE_CODING_FILE: &str = "erasure_coding";
 
 // The unique hex encoded hash(path)
 // This allows us to uniquely identify if a file has been chunked or not.
@@ -72,6 +74,12 @@ pub struct ChunkManager {
     completed_files: Vec<(PathBuf, OsString, ChunkAddress)>,
     resumed_chunk_count: usize,
     resumed_files_count: usize,
+    // The files whose chunks were read back from the artifacts dir, some of which may have been
+    // uploaded and removed already.
+    resumed_files: BTreeSet<PathXorName>,
+    erasure_coding: Option<ErasureCodingCfg>,
+    // The path of each file relative to the parent of the path that is being chunked.
+    relative_paths: BTreeMap<PathXorName, PathBuf>,
 }
 
 impl ChunkManager {
@@ -86,9 +94,19 @@ impl ChunkManager {
             completed_files: Default::default(),
             resumed_files_count: 0,
             resumed_chunk_count: 0,
+            resumed_files: Default::default(),
+            erasure_coding: None,
+            relative_paths: Default::default(),
         }
     }
 
+    /// Generate the Reed-Solomon parity chunks for every file that gets chunked.
+    /// Files that have been chunked previously with different settings are chunked again, along with
+    /// the chunks that have been uploaded already.
+    pub fn set_erasure_coding(&mut self, erasure_coding: Option<ErasureCodingCfg>) {
+        self.erasure_coding = erasure_coding;
+    }
+
     /// Chunk all the files in the provided `files_path`
     /// These are stored to the CHUNK_ARTIFACTS_DIR
     /// if read_cache is true, will take cache from previous runs into account
@@ -137,6 +155,8 @@ impl ChunkManager {
         self.completed_files = Default::default();
         self.resumed_chunk_count = 0;
         self.resumed_files_count = 0;
+        self.resumed_files = Default::default();
+        self.relative_paths = Default::default();
 
         // collect the files to chunk
         entries_iter.for_each(|entry| {
@@ -146,6 +166,8 @@ impl ChunkManager {
                     "Added file {:?} with path_xor: {path_xor:?} to be chunked/resumed",
                     entry.path()
                 );
+                self.relative_paths
+                    .insert(path_xor.clone(), Self::relative_path(&entry));
                 self.files_to_chunk.push((
                     entry.file_name().to_owned(),
                     path_xor,
@@ -171,7 +193,8 @@ impl ChunkManager {
             .flat_map(|chunked_file| &chunked_file.chunks)
             .count();
         // note the number of files that we've resumed
-        self.resumed_files_count = self.chunks.keys().collect::<BTreeSet<_>>().len();
+        self.resumed_files = self.chunks.keys().cloned().collect();
+        self.resumed_files_count = self.resumed_files.len();
 
         // Filter out files_to_chunk; Any PathXorName in chunks_to_upload is considered to be resumed.
         {
@@ -210,6 +233,7 @@ impl ChunkManager {
         progress_bar.println(format!("Chunking {total_files} files..."));
 
         let artifacts_dir = &self.artifacts_dir.clone();
+        let erasure_coding = self.erasure_coding;
         let chunked_files = self.files_to_chunk
             .par_iter()
             .map(|(original_file_name, path_xor, path)| {
@@ -222,7 +246,7 @@ impl ChunkManager {
                     file_chunks_dir
                 };
 
-                match FilesApi::chunk_file(path, &file_chunks_dir, include_data_maps) {
+                match FilesApi::chunk_file_with_erasure_coding(path, &file_chunks_dir, include_data_maps, erasure_coding) {
                     Ok((head_chunk_address, data_map, size, chunks)) => {
                         progress_bar.clone().inc(1);
                         debug!("Chunked {original_file_name:?} with {path_xor:?} into file's XorName: {head_chunk_address:?} of size {size}, and chunks len: {}", chunks.len());
@@ -237,7 +261,7 @@ impl ChunkManager {
                         Ok((path_xor.clone(), chunked_file))
                     }
                     Err(err) => {
-                        println!("Failed to chunk file {path:?}/{path_xor:?} with err: {err:?}");
+                        eprintln!("Failed to chunk file {path:?}/{path_xor:?} with err: {err:?}");
                         error!("Failed to chunk file {path:?}/{path_xor:?} with err: {err:?}");
                         Err(eyre!("Failed to chunk file {path:?}/{path_xor:?} with err: {err:?}"))
                     }
@@ -286,6 +310,16 @@ impl ChunkManager {
                 })?;
 
                 debug!("Wrote metadata for {path_xor:?}");
+
+                if let Some(erasure_coding) = erasure_coding {
+                    let erasure_coding_path =
+                        artifacts_dir.join(&path_xor.0).join(ERASURE_CODING_FILE);
+                    let erasure_coding = rmp_serde::to_vec(&erasure_coding)?;
+                    fs::write(&erasure_coding_path, erasure_coding).map_err(|_| {
+                        error!("Failed to write the erasure coding settings to {erasure_coding_path:?} for {path_xor:?}");
+                        eyre!("Failed to write the erasure coding settings to {erasure_coding_path:?} for {path_xor:?}")
+                    })?;
+                }
                 Ok(())
             })
             .collect::<Result<()>>()?;
@@ -300,6 +334,7 @@ impl ChunkManager {
     // Try to resume the chunks
     fn resume_path(&mut self) {
         let artifacts_dir = self.artifacts_dir.clone();
+        let erasure_coding = self.erasure_coding;
         let resumed = self
             .files_to_chunk
             .par_iter()
@@ -309,6 +344,14 @@ impl ChunkManager {
                 if !file_chunks_dir.exists() {
                     return None;
                 }
+                // The parity chunks would not match the requested settings, so start over.
+                if Self::read_erasure_coding(&file_chunks_dir) != erasure_coding {
+                    info!("{original_file_path:?} was chunked with different erasure coding settings, chunking it again");
+                    if let Err(err) = fs::remove_dir_all(&file_chunks_dir) {
+                        error!("Failed to remove {file_chunks_dir:?} with err: {err:?}");
+                    }
+                    return None;
+                }
                 Self::read_file_chunks_dir(
                     file_chunks_dir,
                     path_xor,
@@ -400,6 +443,7 @@ impl ChunkManager {
                 let uploaded_file_metadata = UploadedFile {
                     filename: chunked_file.file_name,
                     data_map: Some(chunked_file.data_map.value),
+                    relative_path: self.relative_paths.get(path_xor).cloned(),
                 };
                 // errors are logged by write()
                 let _result =
@@ -455,6 +499,14 @@ impl ChunkManager {
         self.chunks.values()
     }
 
+    /// Returns the files that have just been chunked, all of whose chunks are in the artifacts dir.
+    pub(crate) fn freshly_chunked_files(&self) -> impl Iterator<Item = &ChunkedFile> {
+        self.chunks
+            .iter()
+            .filter(|(path_xor, _)| !self.resumed_files.contains(path_xor))
+            .map(|(_, chunked_file)| chunked_file)
+    }
+
     // Try to read the chunks from `file_chunks_dir`
     // Returns the ChunkedFile if the metadata file exists
     // file_chunks_dir: artifacts_dir/path_xor
@@ -477,6 +529,9 @@ impl ChunkManager {
                 if !entry.file_type().is_file() {
                     return None;
                 }
+                if entry.file_name() == ERASURE_CODING_FILE {
+                    return None;
+                }
                 if entry.file_name() == METADATA_FILE {
                     if let Some((address, optional_data_map)) =
                         Self::try_read_metadata(entry.path())
@@ -542,6 +597,25 @@ impl ChunkManager {
         Some(metadata)
     }
 
+    // Read the erasure coding settings that the file was chunked with, if any.
+    fn read_erasure_coding(file_chunks_dir: &Path) -> Option<ErasureCodingCfg> {
+        let erasure_coding = fs::read(file_chunks_dir.join(ERASURE_CODING_FILE)).ok()?;
+        rmp_serde::from_slice(&erasure_coding)
+            .map_err(|err| {
+                error!("Failed to deserialize the erasure coding settings with err {err:?}")
+            })
+            .ok()
+    }
+
+    // The path of the entry relative to the parent of the root of the walk, i.e., the last `depth + 1` components.
+    // E.g. `photos/2024/beach.jpg` when walking `/home/me/photos`, or `beach.jpg` when walking the file itself.
+    fn relative_path(entry: &DirEntry) -> PathBuf {
+        let components = entry.path().components().collect::<Vec<_>>();
+        components[components.len().saturating_sub(entry.depth() + 1)..]
+            .iter()
+            .collect()
+    }
+
     // Decode the hex encoded xorname
     fn hex_decode_xorname(string: &str) -> Option<XorName> {
         let hex_decoded = hex::decode(string)
@@ -790,6 +864,38 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn uploaded_files_should_record_their_relative_path() -> Result<()> {
+        let _log_guards = LogBuilder::init_single_threaded_tokio_test("chunk_manager");
+        let (_tmp_dir, mut manager, root_dir, random_files_dir) = init_manager()?;
+        let nested_dir = random_files_dir.join("nested");
+        fs::create_dir_all(&nested_dir)?;
+        let _ = create_random_files(&nested_dir, 1, 1)?;
+        manager.chunk_path(&random_files_dir, true, true)?;
+        manager.mark_completed_all()?;
+
+        let uploaded_file_paths = WalkDir::new(root_dir.join(super::super::UPLOADED_FILES))
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .collect::<Vec<_>>();
+        assert_eq!(uploaded_file_paths.len(), 1);
+        let uploaded_file = UploadedFile::read(&uploaded_file_paths[0])?;
+        assert_eq!(
+            uploaded_file.relative_path,
+            Some(PathBuf::from("random_files/nested/random_file_0"))
+        );
+
+        // the format read by older versions should be kept as is
+        let (filename, data_map): (OsString, Option<bytes::Bytes>) =
+            rmp_serde::from_slice(&fs::read(&uploaded_file_paths[0])?)?;
+        assert_eq!(filename, uploaded_file.filename);
+        assert_eq!(data_map, uploaded_file.data_map);
+
+        Ok(())
+    }
+
     #[test]
     fn marking_all_chunks_as_completed_should_not_remove_the_dir() -> Result<()> {
         let _log_guards = LogBuilder::init_single_threaded_tokio_test("chunk_manager");
@@ -939,6 +1045,40 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn changing_the_erasure_coding_should_re_chunk_the_resumed_files() -> Result<()> {
+        let _log_guards = LogBuilder::init_single_threaded_tokio_test("chunk_manager");
+        let (_tmp_dir, mut manager, root_dir, random_files_dir) = init_manager()?;
+
+        let _ = create_random_files(&random_files_dir, 1, 1)?;
+        manager.chunk_path(&random_files_dir, true, true)?;
+        let chunks_count = manager.get_chunks().len();
+        manager.mark_completed_all()?;
+
+        // 1. the completed file should be chunked again along with its parity chunks
+        let mut new_manager = ChunkManager::new(&root_dir);
+        new_manager.set_erasure_coding(Some(ErasureCodingCfg::default()));
+        new_manager.chunk_path(&random_files_dir, true, true)?;
+        assert_eq!(new_manager.resumed_chunk_count, 0);
+        assert!(new_manager.completed_files.is_empty());
+        let erasure_coded_chunks_count = new_manager.get_chunks().len();
+        assert!(erasure_coded_chunks_count > chunks_count);
+
+        // 2. the same settings should resume the chunks
+        let mut new_manager = ChunkManager::new(&root_dir);
+        new_manager.set_erasure_coding(Some(ErasureCodingCfg::default()));
+        new_manager.chunk_path(&random_files_dir, true, true)?;
+        assert_eq!(new_manager.resumed_chunk_count, erasure_coded_chunks_count);
+
+        // 3. and dropping the erasure coding should chunk the file again, without the parity chunks
+        let mut new_manager = ChunkManager::new(&root_dir);
+        new_manager.chunk_path(&random_files_dir, true, true)?;
+        assert_eq!(new_manager.resumed_chunk_count, 0);
+        assert_eq!(new_manager.get_chunks().len(), chunks_count);
+
+        Ok(())
+    }
+
     #[test]
     fn absence_of_metadata_file_should_re_chunk_the_entire_file() -> Result<()> {
         let _log_guards = LogBuilder::init_single_threaded_tokio_test("chunk_manager");
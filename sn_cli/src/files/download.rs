@@ -7,13 +7,14 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use super::{
-    get_progress_bar,
+    get_progress_bar, spawn_json_progress_printer,
     upload::{UploadedFile, UPLOADED_FILES},
+    ProgressMode,
 };
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::ffi::OsString;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use color_eyre::Result;
 use indicatif::ProgressBar;
@@ -22,21 +23,29 @@ use xor_name::XorName;
 
 use sn_client::{
     protocol::storage::{Chunk, ChunkAddress, RetryStrategy},
-    FilesApi, FilesDownload, FilesDownloadEvent,
+    BatchDownloadItem, DownloadFilter, FilesApi, FilesDownload, FilesDownloadEvent,
 };
 use tracing::{debug, error, info};
 
 /// The default folder to download files to.
 const DOWNLOAD_FOLDER: &str = "safe_files";
 
+/// Download all the files uploaded by the current user, `concurrent_files` at a time.
+///
+/// The files are written under the default download folder, following the directory tree they were uploaded
+/// from. Only the files whose relative path matches the `filter` are downloaded.
+#[allow(clippy::too_many_arguments)]
 pub async fn download_files(
     files_api: &FilesApi,
     root_dir: &Path,
     show_holders: bool,
     batch_size: usize,
+    concurrent_files: usize,
     retry_strategy: RetryStrategy,
+    progress_mode: ProgressMode,
+    filter: &DownloadFilter,
 ) -> Result<()> {
-    info!("Downloading with batch size of {}", batch_size);
+    info!("Downloading with batch size of {batch_size} and {concurrent_files} concurrent files");
     let uploaded_files_path = root_dir.join(UPLOADED_FILES);
     let download_path = dirs_next::download_dir()
         .unwrap_or(root_dir.to_path_buf())
@@ -67,26 +76,111 @@ pub async fn download_files(
                 address,
                 value: bytes,
             });
-            uploaded_files.insert((xor_name, (uploaded_file_metadata.filename, datamap_chunk)));
+            // files uploaded by older versions do not have a relative path, place them at the root.
+            let relative_path = uploaded_file_metadata
+                .relative_path
+                .unwrap_or_else(|| PathBuf::from(&uploaded_file_metadata.filename));
+            uploaded_files.insert((xor_name, relative_path, datamap_chunk));
         }
     }
 
-    for (xorname, file_data) in uploaded_files.into_iter() {
-        download_file(
-            files_api.clone(),
-            xorname,
-            file_data,
-            &download_path,
-            show_holders,
-            batch_size,
-            retry_strategy,
-        )
+    // the same path might have been uploaded multiple times with different content, keep all of them.
+    let mut used_paths = BTreeMap::new();
+    let items = uploaded_files
+        .into_iter()
+        .map(|(xor_name, mut relative_path, data_map_chunk)| {
+            if used_paths.insert(relative_path.clone(), xor_name).is_some() {
+                let mut file_name = relative_path.file_name().unwrap_or_default().to_owned();
+                file_name.push(format!(".{}", &hex::encode(xor_name)[..8]));
+                relative_path.set_file_name(file_name);
+            }
+            BatchDownloadItem {
+                address: ChunkAddress::new(xor_name),
+                data_map_chunk,
+                relative_path,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut files_download = FilesDownload::new(files_api.clone())
+        .set_batch_size(batch_size)
+        .set_max_concurrent_chunks(batch_size)
+        .set_max_concurrent_files(concurrent_files)
+        .set_show_holders(show_holders)
+        .set_retry_strategy(retry_strategy);
+
+    let mut download_events_rx = files_download.get_events();
+    let json_progress_handler = match progress_mode {
+        ProgressMode::Bar => None,
+        ProgressMode::Json => Some(spawn_json_progress_printer(
+            files_download.get_progress_events(),
+        )),
+    };
+    let progress_handler = tokio::spawn(async move {
+        let mut progress_bar: Option<ProgressBar> = None;
+        // The loop is guaranteed to end, as the channel will be closed when the download completes or errors out.
+        while let Some(event) = download_events_rx.recv().await {
+            match event {
+                FilesDownloadEvent::FilesCount(count) => {
+                    progress_bar = new_progress_bar(count, progress_mode);
+                }
+                FilesDownloadEvent::FileDownloaded(_, path) => {
+                    let msg = format!("Saved {}", path.to_string_lossy());
+                    match &progress_bar {
+                        Some(progress_bar) => {
+                            progress_bar.println(msg);
+                            progress_bar.inc(1);
+                        }
+                        None => progress_mode.println(msg),
+                    }
+                }
+                FilesDownloadEvent::FileFailed(..) => {
+                    if let Some(progress_bar) = &progress_bar {
+                        progress_bar.inc(1);
+                    }
+                }
+                FilesDownloadEvent::Error => {
+                    error!("Got FilesDownloadEvent::Error");
+                }
+                FilesDownloadEvent::Downloaded(_)
+                | FilesDownloadEvent::ChunksCount(_)
+                | FilesDownloadEvent::DatamapCount(_) => {}
+            }
+        }
+        if let Some(progress_bar) = progress_bar {
+            progress_bar.finish_and_clear();
+        }
+    });
+
+    let summary = files_download
+        .download_batch(items, &download_path, filter)
         .await;
+
+    // await on the progress handler first as we want to clear the progress bar before printing things.
+    let _ = progress_handler.await;
+    if let Some(json_progress_handler) = json_progress_handler {
+        let _ = json_progress_handler.await;
     }
+    let summary = summary?;
+
+    for (_, path, error) in &summary.failed {
+        error!("Error downloading {path:?}: {error}");
+        progress_mode.println(format_args!("Error downloading {path:?}: {error}"));
+    }
+    progress_mode.println(format_args!(
+        "Downloaded {} files to {}, {} failed and {} did not match the filters.",
+        summary.downloaded.len(),
+        download_path.to_string_lossy(),
+        summary.failed.len(),
+        summary.skipped.len()
+    ));
 
     Ok(())
 }
 
+/// Download a single file to `download_path`, reporting the outcome. An error is returned if the
+/// download failed.
+#[allow(clippy::too_many_arguments)]
 pub async fn download_file(
     files_api: FilesApi,
     xor_name: XorName,
@@ -96,17 +190,26 @@ pub async fn download_file(
     show_holders: bool,
     batch_size: usize,
     retry_strategy: RetryStrategy,
-) {
+    progress_mode: ProgressMode,
+) -> Result<()> {
     let mut files_download = FilesDownload::new(files_api.clone())
         .set_batch_size(batch_size)
         .set_show_holders(show_holders)
         .set_retry_strategy(retry_strategy);
 
-    println!("Downloading {file_name:?} from {xor_name:64x} with batch-size {batch_size}");
+    progress_mode.println(format_args!(
+        "Downloading {file_name:?} from {xor_name:64x} with batch-size {batch_size}"
+    ));
     debug!("Downloading {file_name:?} from {:64x}", xor_name);
     let downloaded_file_path = download_path.join(&file_name);
 
     let mut download_events_rx = files_download.get_events();
+    let json_progress_handler = match progress_mode {
+        ProgressMode::Bar => None,
+        ProgressMode::Json => Some(spawn_json_progress_printer(
+            files_download.get_progress_events(),
+        )),
+    };
 
     let progress_handler = tokio::spawn(async move {
         let mut progress_bar: Option<ProgressBar> = None;
@@ -123,26 +226,21 @@ pub async fn download_file(
                     if let Some(progress_bar) = progress_bar {
                         progress_bar.finish_and_clear();
                     }
-                    progress_bar = get_progress_bar(count as u64).map_err(|err|{
-                        println!("Unable to initialize progress bar. The download process will continue without a progress bar.");
-                        error!("Failed to obtain progress bar with err: {err:?}");
-                        err
-                    }).ok();
+                    progress_bar = new_progress_bar(count, progress_mode);
                 }
                 FilesDownloadEvent::DatamapCount(count) => {
                     // terminate the progress bar if it was loaded here. This should not happen.
                     if let Some(progress_bar) = progress_bar {
                         progress_bar.finish_and_clear();
                     }
-                    progress_bar = get_progress_bar(count as u64).map_err(|err|{
-                        println!("Unable to initialize progress bar. The download process will continue without a progress bar.");
-                        error!("Failed to obtain progress bar with err: {err:?}");
-                        err
-                    }).ok();
+                    progress_bar = new_progress_bar(count, progress_mode);
                 }
                 FilesDownloadEvent::Error => {
                     error!("Got FilesDownloadEvent::Error");
                 }
+                FilesDownloadEvent::FilesCount(_)
+                | FilesDownloadEvent::FileDownloaded(..)
+                | FilesDownloadEvent::FileFailed(..) => {}
             }
         }
         if let Some(progress_bar) = progress_bar {
@@ -160,20 +258,36 @@ pub async fn download_file(
 
     // await on the progress handler first as we want to clear the progress bar before printing things.
     let _ = progress_handler.await;
+    if let Some(json_progress_handler) = json_progress_handler {
+        let _ = json_progress_handler.await;
+    }
     match download_result {
         Ok(_) => {
             debug!(
                 "Saved {file_name:?} at {}",
                 downloaded_file_path.to_string_lossy()
             );
-            println!(
+            progress_mode.println(format_args!(
                 "Saved {file_name:?} at {}",
                 downloaded_file_path.to_string_lossy()
-            );
+            ));
+            Ok(())
         }
         Err(error) => {
             error!("Error downloading {file_name:?}: {error}");
-            println!("Error downloading {file_name:?}: {error}")
+            progress_mode.println(format_args!("Error downloading {file_name:?}: {error}"));
+            Err(error.into())
         }
     }
 }
+
+fn new_progress_bar(count: usize, progress_mode: ProgressMode) -> Option<ProgressBar> {
+    match progress_mode {
+        ProgressMode::Bar => get_progress_bar(count as u64).map_err(|err|{
+            println!("Unable to initialize progress bar. The download process will continue without a progress bar.");
+            error!("Failed to obtain progress bar with err: {err:?}");
+            err
+        }).ok(),
+        ProgressMode::Json => None,
+    }
+}
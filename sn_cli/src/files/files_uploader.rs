@@ -6,19 +6,21 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::get_progress_bar;
+use super::{get_progress_bar, spawn_json_progress_printer, ProgressMode};
 use crate::ChunkManager;
 use bytes::Bytes;
 use color_eyre::{eyre::eyre, Report, Result};
 use futures::StreamExt;
+use indicatif::ProgressBar;
 use rand::prelude::SliceRandom;
 use rand::thread_rng;
 use sn_client::{
     transfers::{TransferError, WalletError},
-    Client, Error as ClientError, UploadCfg, UploadEvent, UploadSummary, Uploader,
+    Client, Error as ClientError, ManifestEntry, UploadCfg, UploadEvent, UploadSummary, Uploader,
 };
 use sn_protocol::storage::{Chunk, ChunkAddress};
 use std::{
+    collections::BTreeMap,
     ffi::OsString,
     path::{Path, PathBuf},
     time::{Duration, Instant},
@@ -36,12 +38,18 @@ pub struct FilesUploadSummary {
     pub completed_files: Vec<(PathBuf, OsString, ChunkAddress)>,
     /// The list of incomplete files (FilePath, FileName, HeadChunkAddress)
     pub incomplete_files: Vec<(PathBuf, OsString, ChunkAddress)>,
+    /// The manifest entries of the files that were chunked, keyed by their path, if they were
+    /// requested with `set_collect_manifest_entries`. Files resumed from a previous upload are not
+    /// included, as some of their chunks are gone.
+    pub manifest_entries: BTreeMap<PathBuf, ManifestEntry>,
 }
 
 /// A trait designed to customize the standard output behavior for file upload processes.
 pub trait FilesUploadStatusNotifier: Send {
     fn collect_entries(&mut self, entries_iter: Vec<DirEntry>);
     fn collect_paths(&mut self, path: &Path);
+    /// Called with the way the progress is reported, so that any output can be kept apart from it.
+    fn set_progress_mode(&mut self, _progress_mode: ProgressMode) {}
     fn on_verifying_uploaded_chunks_init(&self, chunks_len: usize);
     fn on_verifying_uploaded_chunks_success(
         &self,
@@ -80,12 +88,15 @@ pub struct FilesUploader {
     /// config
     make_data_public: bool,
     upload_cfg: UploadCfg,
+    progress_mode: ProgressMode,
+    collect_manifest_entries: bool,
 }
 
 impl FilesUploader {
     pub fn new(client: Client, root_dir: PathBuf) -> Self {
         let status_notifier = Box::new(StdOutPrinter {
             file_paths_to_print: Default::default(),
+            progress_mode: Default::default(),
         });
         Self {
             client,
@@ -94,6 +105,8 @@ impl FilesUploader {
             status_notifier: Some(status_notifier),
             make_data_public: false,
             upload_cfg: Default::default(),
+            progress_mode: Default::default(),
+            collect_manifest_entries: false,
         }
     }
 
@@ -107,11 +120,32 @@ impl FilesUploader {
         self
     }
 
+    /// Sets how the progress of the upload is reported.
+    ///
+    /// By default, this option is set to `ProgressMode::Bar`.
+    pub fn set_progress_mode(mut self, progress_mode: ProgressMode) -> Self {
+        self.progress_mode = progress_mode;
+        if let Some(notifier) = &mut self.status_notifier {
+            notifier.set_progress_mode(progress_mode);
+        }
+        self
+    }
+
+    /// Create a manifest entry for each file from its chunks, before they are uploaded and
+    /// removed, so that a manifest of the upload can be made without chunking the files again.
+    ///
+    /// By default, this option is set to false.
+    pub fn set_collect_manifest_entries(mut self, collect_manifest_entries: bool) -> Self {
+        self.collect_manifest_entries = collect_manifest_entries;
+        self
+    }
+
     /// Override the default status notifier. By default we print things to stdout.
     pub fn set_status_notifier(
         mut self,
-        status_notifier: Box<dyn FilesUploadStatusNotifier>,
+        mut status_notifier: Box<dyn FilesUploadStatusNotifier>,
     ) -> Self {
+        status_notifier.set_progress_mode(self.progress_mode);
         self.status_notifier = Some(status_notifier);
         self
     }
@@ -132,7 +166,11 @@ impl FilesUploader {
 
     pub async fn start_upload(mut self) -> Result<FilesUploadSummary> {
         let mut chunk_manager = ChunkManager::new(&self.root_dir);
-        let chunks_to_upload = self.get_chunks_to_upload(&mut chunk_manager).await?;
+        chunk_manager.set_erasure_coding(self.upload_cfg.erasure_coding);
+        let mut manifest_entries = BTreeMap::new();
+        let chunks_to_upload = self
+            .get_chunks_to_upload(&mut chunk_manager, &mut manifest_entries)
+            .await?;
         let chunks_to_upload_len = chunks_to_upload.len();
 
         // Notify on chunking complete
@@ -155,7 +193,14 @@ impl FilesUploader {
             chunks_to_upload_len,
             uploader.get_event_receiver(),
             self.status_notifier.take(),
+            self.progress_mode,
         )?;
+        let progress_handle = match self.progress_mode {
+            ProgressMode::Bar => None,
+            ProgressMode::Json => Some(spawn_json_progress_printer(
+                uploader.get_progress_receiver(),
+            )),
+        };
 
         let upload_sum = match uploader.start_upload().await {
             Ok(summary) => summary,
@@ -171,6 +216,9 @@ impl FilesUploader {
             Err(err) => return Err(eyre!("Failed to upload chunk batch: {err}")),
         };
         let (chunk_manager, status_notifier) = events_handle.await??;
+        if let Some(progress_handle) = progress_handle {
+            let _ = progress_handle.await;
+        }
         self.status_notifier = status_notifier;
 
         // Notify on upload complete
@@ -188,6 +236,7 @@ impl FilesUploader {
                     (path.clone(), file_name.clone(), *head_address)
                 })
                 .collect(),
+            manifest_entries,
         };
         Ok(summary)
     }
@@ -197,6 +246,7 @@ impl FilesUploader {
     async fn get_chunks_to_upload(
         &self,
         chunk_manager: &mut ChunkManager,
+        manifest_entries: &mut BTreeMap<PathBuf, ManifestEntry>,
     ) -> Result<Vec<(XorName, PathBuf)>> {
         // Initially try reading from the cache
         chunk_manager.chunk_with_iter(
@@ -204,6 +254,7 @@ impl FilesUploader {
             true,
             self.make_data_public,
         )?;
+        self.collect_manifest_entries(chunk_manager, manifest_entries)?;
         // We verify if there are no chunks left to upload.
         let mut chunks_to_upload = if !chunk_manager.is_chunks_empty() {
             chunk_manager.get_chunks()
@@ -213,6 +264,7 @@ impl FilesUploader {
                 self.entries_to_upload.iter().cloned(),
                 self.make_data_public,
             )?;
+            self.collect_manifest_entries(chunk_manager, manifest_entries)?;
 
             // Notify on verification init
             if let Some(notifier) = &self.status_notifier {
@@ -252,6 +304,27 @@ impl FilesUploader {
         Ok(chunks_to_upload)
     }
 
+    fn collect_manifest_entries(
+        &self,
+        chunk_manager: &ChunkManager,
+        manifest_entries: &mut BTreeMap<PathBuf, ManifestEntry>,
+    ) -> Result<()> {
+        if !self.collect_manifest_entries {
+            return Ok(());
+        }
+        for chunked_file in chunk_manager.freshly_chunked_files() {
+            let entry = ManifestEntry::from_chunk_paths(
+                &chunked_file.file_path,
+                chunked_file.file_name.to_string_lossy().to_string(),
+                chunked_file.head_chunk_address,
+                chunked_file.chunks.iter().map(|(_, path)| path.as_path()),
+                self.client.signer(),
+            )?;
+            let _ = manifest_entries.insert(chunked_file.file_path.clone(), entry);
+        }
+        Ok(())
+    }
+
     async fn verify_uploaded_chunks(
         &self,
         chunks_paths: &[(XorName, PathBuf)],
@@ -283,9 +356,13 @@ impl FilesUploader {
         chunks_to_upload_len: usize,
         mut upload_event_rx: Receiver<UploadEvent>,
         status_notifier: Option<Box<dyn FilesUploadStatusNotifier>>,
+        progress_mode: ProgressMode,
     ) -> Result<JoinHandle<Result<(ChunkManager, Option<Box<dyn FilesUploadStatusNotifier>>)>>>
     {
-        let progress_bar = get_progress_bar(chunks_to_upload_len as u64)?;
+        let progress_bar = match progress_mode {
+            ProgressMode::Bar => get_progress_bar(chunks_to_upload_len as u64)?,
+            ProgressMode::Json => ProgressBar::hidden(),
+        };
         let handle = tokio::spawn(async move {
             let mut upload_terminated_with_error = false;
             // The loop is guaranteed to end, as the channel will be
@@ -337,6 +414,7 @@ impl FilesUploader {
 /// The default
 struct StdOutPrinter {
     file_paths_to_print: Vec<PathBuf>,
+    progress_mode: ProgressMode,
 }
 
 impl FilesUploadStatusNotifier for StdOutPrinter {
@@ -346,8 +424,14 @@ impl FilesUploadStatusNotifier for StdOutPrinter {
         self.file_paths_to_print.push(path.to_path_buf());
     }
 
+    fn set_progress_mode(&mut self, progress_mode: ProgressMode) {
+        self.progress_mode = progress_mode;
+    }
+
     fn on_verifying_uploaded_chunks_init(&self, chunks_len: usize) {
-        println!("Files upload attempted previously, verifying {chunks_len} chunks",);
+        self.progress_mode.println(format_args!(
+            "Files upload attempted previously, verifying {chunks_len} chunks",
+        ));
     }
 
     fn on_verifying_uploaded_chunks_success(
@@ -355,17 +439,18 @@ impl FilesUploadStatusNotifier for StdOutPrinter {
         completed_files: &[(PathBuf, OsString, ChunkAddress)],
         make_data_public: bool,
     ) {
-        println!("All files were already uploaded and verified");
-        Self::print_uploaded_msg(make_data_public);
+        self.progress_mode
+            .println("All files were already uploaded and verified");
+        self.print_uploaded_msg(make_data_public);
 
         if completed_files.is_empty() {
-            println!("chunk_manager doesn't have any verified_files, nor any failed_chunks to re-upload.");
+            self.progress_mode.println("chunk_manager doesn't have any verified_files, nor any failed_chunks to re-upload.");
         }
-        Self::print_completed_file_list(completed_files);
+        self.print_completed_file_list(completed_files);
     }
 
     fn on_verifying_uploaded_chunks_failure(&self, failed_chunks_len: usize) {
-        println!("{failed_chunks_len} chunks were uploaded in the past but failed to verify. Will attempt to upload them again...");
+        self.progress_mode.println(format_args!("{failed_chunks_len} chunks were uploaded in the past but failed to verify. Will attempt to upload them again..."));
     }
 
     fn on_failed_to_upload_all_files(
@@ -376,17 +461,21 @@ impl FilesUploadStatusNotifier for StdOutPrinter {
     ) {
         for (_, file_name, _) in incomplete_files {
             if let Some(file_name) = file_name.to_str() {
-                println!("Unverified file \"{file_name}\", suggest to re-upload again.");
+                self.progress_mode.println(format_args!(
+                    "Unverified file \"{file_name}\", suggest to re-upload again."
+                ));
                 info!("Unverified {file_name}");
             } else {
-                println!("Unverified file \"{file_name:?}\", suggest to re-upload again.");
+                self.progress_mode.println(format_args!(
+                    "Unverified file \"{file_name:?}\", suggest to re-upload again."
+                ));
                 info!("Unverified file {file_name:?}");
             }
         }
 
         // log uploaded file information
-        Self::print_uploaded_msg(make_data_public);
-        Self::print_completed_file_list(completed_files);
+        self.print_uploaded_msg(make_data_public);
+        self.print_completed_file_list(completed_files);
     }
 
     fn on_chunking_complete(
@@ -402,19 +491,20 @@ impl FilesUploadStatusNotifier for StdOutPrinter {
             );
             if make_data_public {
                 info!("{path:?} will be made public and linkable");
-                println!("{path:?} will be made public and linkable");
+                self.progress_mode
+                    .println(format_args!("{path:?} will be made public and linkable"));
             }
         }
         if self.file_paths_to_print.len() == 1 {
-            println!(
+            self.progress_mode.println(format_args!(
                 "Splitting and uploading {:?} into {chunks_to_upload_len} chunks",
                 self.file_paths_to_print[0]
-            );
+            ));
         } else {
-            println!(
+            self.progress_mode.println(format_args!(
                 "Splitting and uploading {:?} into {chunks_to_upload_len} chunks",
                 self.file_paths_to_print
-            );
+            ));
         }
     }
 
@@ -432,54 +522,69 @@ impl FilesUploadStatusNotifier for StdOutPrinter {
             format!("{elapsed_seconds} seconds")
         };
 
-        println!(
+        self.progress_mode.println(format_args!(
             "Among {chunks_to_upload_len} chunks, found {} already existed in network, uploaded \
             the leftover {} chunks in {elapsed}",
             upload_sum.skipped_count, upload_sum.uploaded_count,
-        );
+        ));
         info!(
             "Among {chunks_to_upload_len} chunks, found {} already existed in network, uploaded \
             the leftover {} chunks in {elapsed}",
             upload_sum.skipped_count, upload_sum.uploaded_count,
         );
-        println!("**************************************");
-        println!("*          Payment Details           *");
-        println!("**************************************");
-        println!(
+        self.progress_mode
+            .println("**************************************");
+        self.progress_mode
+            .println("*          Payment Details           *");
+        self.progress_mode
+            .println("**************************************");
+        self.progress_mode.println(format_args!(
             "Made payment of {:?} for {} chunks",
             upload_sum.storage_cost, upload_sum.uploaded_count
-        );
-        println!(
+        ));
+        self.progress_mode.println(format_args!(
             "Made payment of {:?} for royalties fees",
             upload_sum.royalty_fees
-        );
-        println!("New wallet balance: {}", upload_sum.final_balance);
+        ));
+        self.progress_mode.println(format_args!(
+            "New wallet balance: {}",
+            upload_sum.final_balance
+        ));
     }
 }
 
 impl StdOutPrinter {
-    fn print_completed_file_list(completed_files: &[(PathBuf, OsString, ChunkAddress)]) {
+    fn print_completed_file_list(&self, completed_files: &[(PathBuf, OsString, ChunkAddress)]) {
         for (_, file_name, addr) in completed_files {
             let hex_addr = addr.to_hex();
             if let Some(file_name) = file_name.to_str() {
-                println!("\"{file_name}\" {hex_addr}");
+                self.progress_mode
+                    .println(format_args!("\"{file_name}\" {hex_addr}"));
                 info!("Uploaded {file_name} to {hex_addr}");
             } else {
-                println!("\"{file_name:?}\" {hex_addr}");
+                self.progress_mode
+                    .println(format_args!("\"{file_name:?}\" {hex_addr}"));
                 info!("Uploaded {file_name:?} to {hex_addr}");
             }
         }
     }
 
-    fn print_uploaded_msg(make_data_public: bool) {
-        println!("**************************************");
-        println!("*          Uploaded Files            *");
+    fn print_uploaded_msg(&self, make_data_public: bool) {
+        self.progress_mode
+            .println("**************************************");
+        self.progress_mode
+            .println("*          Uploaded Files            *");
         if !make_data_public {
-            println!("*                                    *");
-            println!("*  These are not public by default.  *");
-            println!("*     Reupload with `-p` option      *");
-            println!("*      to publish the datamaps.      *");
+            self.progress_mode
+                .println("*                                    *");
+            self.progress_mode
+                .println("*  These are not public by default.  *");
+            self.progress_mode
+                .println("*     Reupload with `-p` option      *");
+            self.progress_mode
+                .println("*      to publish the datamaps.      *");
         }
-        println!("**************************************");
+        self.progress_mode
+            .println("**************************************");
     }
 }
@@ -0,0 +1,140 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use color_eyre::{eyre::eyre, Result};
+use sn_client::{
+    protocol::storage::{ChunkAddress, RetryStrategy},
+    Client, DatasetManifest, FilesApi, ManifestEntry, ManifestVerification, SignedDatasetManifest,
+    UploadCfg, Uploader,
+};
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+use xor_name::XorName;
+
+/// Subdir for storing a local copy of the manifests that have been created
+pub const MANIFESTS_DIR: &str = "manifests";
+
+/// Create a signed manifest for the provided uploaded files and store it on the network as a chunk.
+/// A copy of the manifest is kept locally under the `MANIFESTS_DIR` of the `root_dir`.
+///
+/// The entries are taken from `manifest_entries`, which are made from the chunks of the upload.
+/// Files that aren't in it, because they were uploaded by an earlier run, are chunked again.
+///
+/// The name recorded for each file is its path relative to the parent of `upload_path`.
+/// Returns the address of the root chunk of the manifest.
+pub async fn upload_manifest(
+    client: &Client,
+    root_dir: &Path,
+    upload_path: &Path,
+    uploaded_files: &[(PathBuf, OsString, ChunkAddress)],
+    manifest_entries: &BTreeMap<PathBuf, ManifestEntry>,
+    make_data_public: bool,
+    upload_cfg: UploadCfg,
+) -> Result<ChunkAddress> {
+    let base_dir = upload_path.parent().unwrap_or(upload_path);
+
+    let mut manifest = DatasetManifest::new();
+    for (file_path, file_name, _) in uploaded_files {
+        let name = file_path
+            .strip_prefix(base_dir)
+            .map(|relative| relative.to_string_lossy().to_string())
+            .unwrap_or_else(|_| file_name.to_string_lossy().to_string());
+        let entry = match manifest_entries.get(file_path) {
+            Some(entry) => ManifestEntry {
+                name,
+                ..entry.clone()
+            },
+            None => {
+                FilesApi::create_manifest_entry(file_path, name, make_data_public, client.signer())?
+            }
+        };
+        manifest.push(entry);
+    }
+    let signed_manifest = manifest.sign(client.signer())?;
+
+    let mut uploader = Uploader::new(client.clone(), root_dir.to_path_buf());
+    uploader.set_upload_cfg(upload_cfg);
+    let address = uploader.insert_manifest(&signed_manifest)?;
+    let _summary = uploader.start_upload().await?;
+
+    let manifests_dir = root_dir.join(MANIFESTS_DIR);
+    std::fs::create_dir_all(&manifests_dir)?;
+    std::fs::write(
+        manifests_dir.join(address.to_hex()),
+        signed_manifest.to_bytes()?,
+    )?;
+    info!(
+        "Stored manifest with {} files at {address:?}",
+        signed_manifest.manifest.entries.len()
+    );
+
+    Ok(address)
+}
+
+/// Read a signed manifest from a local file, or fetch it from the network if a hex address is provided.
+pub async fn read_manifest(
+    files_api: &FilesApi,
+    manifest: &str,
+    retry_strategy: RetryStrategy,
+) -> Result<SignedDatasetManifest> {
+    let path = Path::new(manifest);
+    if path.is_file() {
+        let bytes = std::fs::read(path)?;
+        return Ok(SignedDatasetManifest::from_bytes(&bytes)?);
+    }
+
+    let bytes = hex::decode(manifest)
+        .map_err(|_| eyre!("The manifest must be a file path or a hex address"))?;
+    let xorname = XorName(
+        bytes
+            .try_into()
+            .map_err(|_| eyre!("Failed to parse XorName from hex string"))?,
+    );
+    Ok(files_api
+        .get_manifest(ChunkAddress::new(xorname), Some(retry_strategy))
+        .await?)
+}
+
+/// Check that every chunk listed by the manifest can be proven to exist on the network.
+pub async fn verify_manifest(
+    files_api: &FilesApi,
+    manifest: &SignedDatasetManifest,
+    batch_size: usize,
+) -> Result<ManifestVerification> {
+    let chunks_count = manifest.manifest.chunks_count();
+    println!(
+        "Verifying {chunks_count} chunks of {} files...",
+        manifest.manifest.entries.len()
+    );
+
+    let verification = files_api.verify_manifest(manifest, batch_size).await?;
+
+    if verification.is_complete() {
+        println!("All {chunks_count} chunks are retrievable from the network.");
+    } else {
+        for (name, missing) in &verification.missing_chunks {
+            println!(
+                "\"{name}\" has {} chunks that could not be verified:",
+                missing.len()
+            );
+            for address in missing {
+                println!("    {}", address.to_hex());
+            }
+        }
+        println!(
+            "Verified {} out of {chunks_count} chunks.",
+            verification.verified_chunks
+        );
+    }
+
+    Ok(verification)
+}
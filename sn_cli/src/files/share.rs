@@ -0,0 +1,129 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::upload::{UploadedFile, UPLOADED_FILES};
+use color_eyre::{
+    eyre::{bail, eyre},
+    Result,
+};
+use sn_client::{
+    protocol::storage::{Chunk, ChunkAddress, RetryStrategy},
+    Client, FilesApi, ShareToken, SharedFile, UploadCfg, Uploader,
+};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::info;
+use walkdir::WalkDir;
+use xor_name::XorName;
+
+/// Create a `ShareToken` for a file uploaded from this machine, encrypted to the `recipient` key.
+///
+/// The `file` is either the hex address of the uploaded file, or its name (or relative path) as recorded
+/// under `UPLOADED_FILES`. If `upload_cfg` is provided, the token is stored on the network and the address of
+/// its chunk is returned, else the hex of the token itself is returned.
+pub async fn share_file(
+    files_api: &FilesApi,
+    root_dir: &Path,
+    file: &str,
+    recipient: bls::PublicKey,
+    label: Option<String>,
+    expires_in: Option<Duration>,
+    upload_cfg: Option<UploadCfg>,
+) -> Result<String> {
+    let (head_address, uploaded_file) = find_uploaded_file(root_dir, file)?;
+    let data_map = uploaded_file
+        .data_map
+        .ok_or_else(|| eyre!("The DataMap of {file:?} is not available locally"))?;
+
+    let expires_at = expires_in
+        .map(|expires_in| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|now| (now + expires_in).as_secs())
+        })
+        .transpose()?;
+    let shared_file = SharedFile {
+        head_address,
+        data_map_chunk: Chunk {
+            address: head_address,
+            value: data_map,
+        },
+        file_name: Some(uploaded_file.filename.to_string_lossy().to_string()),
+        label,
+        expires_at,
+    };
+    let token = files_api.create_share_token(&shared_file, recipient)?;
+    info!("Created share token of {head_address:?} for {recipient:?}");
+
+    match upload_cfg {
+        Some(upload_cfg) => {
+            let chunk = token.to_chunk()?;
+            let address = *chunk.address();
+            let mut uploader = Uploader::new(files_api.client().clone(), root_dir.to_path_buf());
+            uploader.set_upload_cfg(upload_cfg);
+            uploader.insert_chunks(vec![chunk]);
+            let _summary = uploader.start_upload().await?;
+            Ok(address.to_hex())
+        }
+        None => Ok(token.to_hex()?),
+    }
+}
+
+/// Read a `ShareToken` from its hex representation, or fetch it from the network if the hex address of a
+/// token chunk is provided.
+pub async fn read_share_token(
+    client: &Client,
+    token: &str,
+    retry_strategy: RetryStrategy,
+) -> Result<ShareToken> {
+    let bytes = hex::decode(token.trim())
+        .map_err(|_| eyre!("The token must be a hex encoded token or chunk address"))?;
+    match <[u8; 32]>::try_from(bytes.as_slice()) {
+        Ok(xorname) => {
+            let chunk = client
+                .get_chunk(
+                    ChunkAddress::new(XorName(xorname)),
+                    false,
+                    Some(retry_strategy),
+                )
+                .await?;
+            Ok(ShareToken::from_chunk(&chunk)?)
+        }
+        Err(_) => Ok(ShareToken::from_bytes(&bytes)?),
+    }
+}
+
+// Look up an uploaded file by its hex address, name or relative path.
+fn find_uploaded_file(root_dir: &Path, file: &str) -> Result<(ChunkAddress, UploadedFile)> {
+    let uploaded_files_path = root_dir.join(UPLOADED_FILES);
+    for entry in WalkDir::new(&uploaded_files_path).into_iter().flatten() {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Some(hex_address) = entry.file_name().to_str() else {
+            continue;
+        };
+        let Some(xorname) = hex::decode(hex_address)
+            .ok()
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        else {
+            continue;
+        };
+        let address = ChunkAddress::new(XorName(xorname));
+        let uploaded_file = UploadedFile::read(entry.path())?;
+        let matches = hex_address == file
+            || uploaded_file.filename == *file
+            || uploaded_file.relative_path.as_deref() == Some(PathBuf::from(file).as_path());
+        if matches {
+            return Ok((address, uploaded_file));
+        }
+    }
+    bail!("{file:?} has not been uploaded from this machine")
+}
@@ -10,11 +10,17 @@ use bytes::Bytes;
 use color_eyre::Result;
 use serde::Deserialize;
 use sn_client::protocol::storage::ChunkAddress;
-use std::{ffi::OsString, path::Path};
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
 use tracing::{error, warn};
 
 /// Subdir for storing uploaded file into
 pub const UPLOADED_FILES: &str = "uploaded_files";
+/// Subdir for storing the relative path of the uploaded files. They are kept apart from the
+/// `UPLOADED_FILES` so that the format of those stays readable by older versions.
+const UPLOADED_FILE_PATHS: &str = "uploaded_file_paths";
 
 /// The metadata related to file that has been uploaded.
 /// This is written during upload and read during downloads.
@@ -22,6 +28,11 @@ pub const UPLOADED_FILES: &str = "uploaded_files";
 pub struct UploadedFile {
     pub filename: OsString,
     pub data_map: Option<Bytes>,
+    /// The path of the file relative to the parent of the uploaded directory, used to recreate the
+    /// directory tree when downloading. Not available for the files uploaded by older versions.
+    /// Stored under `UPLOADED_FILE_PATHS`.
+    #[serde(default)]
+    pub relative_path: Option<PathBuf>,
 }
 
 impl UploadedFile {
@@ -57,6 +68,21 @@ impl UploadedFile {
             err
         })?;
 
+        if let Some(relative_path) = &self.relative_path {
+            let uploaded_file_paths = root_dir.join(UPLOADED_FILE_PATHS);
+            std::fs::create_dir_all(&uploaded_file_paths)?;
+            let serialized = rmp_serde::to_vec(relative_path).inspect_err(|_| {
+                error!(
+                    "Failed to serialize the relative path of {:?}",
+                    self.filename
+                );
+            })?;
+            std::fs::write(
+                uploaded_file_paths.join(head_chunk_address.to_hex()),
+                serialized,
+            )?;
+        }
+
         Ok(())
     }
 
@@ -65,10 +91,32 @@ impl UploadedFile {
             error!("Error while reading the UploadedFile from {path:?}");
             err
         })?;
-        let metadata = rmp_serde::from_slice(&bytes).map_err(|err| {
+        let mut metadata: Self = rmp_serde::from_slice(&bytes).map_err(|err| {
             error!("Error while deserializing UploadedFile for {path:?}");
             err
         })?;
+
+        if metadata.relative_path.is_none() {
+            metadata.relative_path = Self::read_relative_path(path)?;
+        }
         Ok(metadata)
     }
+
+    // Read the relative path stored next to the `UPLOADED_FILES` dir that holds `path`, if any.
+    fn read_relative_path(path: &Path) -> Result<Option<PathBuf>> {
+        let (Some(root_dir), Some(file_name)) =
+            (path.parent().and_then(Path::parent), path.file_name())
+        else {
+            return Ok(None);
+        };
+        let relative_path_file = root_dir.join(UPLOADED_FILE_PATHS).join(file_name);
+        if !relative_path_file.exists() {
+            return Ok(None);
+        }
+        let bytes = std::fs::read(&relative_path_file)?;
+        let relative_path = rmp_serde::from_slice(&bytes).inspect_err(|_| {
+            error!("Error while deserializing the relative path from {relative_path_file:?}");
+        })?;
+        Ok(Some(relative_path))
+    }
 }
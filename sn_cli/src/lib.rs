@@ -11,6 +11,7 @@ mod files;
 
 pub use acc_packet::AccountPacket;
 pub use files::{
-    download_file, download_files, ChunkManager, Estimator, FilesUploadStatusNotifier,
-    FilesUploadSummary, FilesUploader, UploadedFile, UPLOADED_FILES,
+    download_file, download_files, read_manifest, read_share_token, share_file, upload_manifest,
+    verify_manifest, ChunkManager, Estimator, FilesUploadStatusNotifier, FilesUploadSummary,
+    FilesUploader, ProgressMode, UploadedFile, MANIFESTS_DIR, UPLOADED_FILES,
 };
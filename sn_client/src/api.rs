@@ -20,6 +20,8 @@ use libp2p::{
 #[cfg(feature = "open-metrics")]
 use prometheus_client::registry::Registry;
 use rand::{thread_rng, Rng};
+#[cfg(feature = "sim")]
+use sn_networking::sim::SimNetwork;
 use sn_networking::{
     get_signed_spend_from_record, multiaddr_is_global,
     target_arch::{interval, spawn, timeout, Instant},
@@ -28,7 +30,7 @@ use sn_networking::{
 };
 use sn_protocol::{
     error::Error as ProtocolError,
-    messages::ChunkProof,
+    messages::{ChunkProof, Nonce},
     storage::{
         try_deserialize_record, try_serialize_record, Chunk, ChunkAddress, RecordHeader,
         RecordKind, RegisterAddress, RetryStrategy, SpendAddress,
@@ -115,6 +117,38 @@ impl Client {
         #[cfg(feature = "open-metrics")]
         network_builder.metrics_registry(Registry::default());
 
+        Self::start(
+            signer,
+            peers,
+            connection_timeout,
+            client_event_broadcaster,
+            network_builder,
+        )
+        .await
+    }
+
+    /// Instantiate a new client that connects to the given peers through the simulated network
+    /// rather than real sockets.
+    #[cfg(feature = "sim")]
+    pub async fn new_in_sim(
+        signer: SecretKey,
+        peers: Vec<Multiaddr>,
+        sim: SimNetwork,
+    ) -> Result<Self> {
+        let mut network_builder =
+            NetworkBuilder::new(Keypair::generate_ed25519(), true, std::env::temp_dir());
+        network_builder.sim(sim);
+        Self::start(signer, Some(peers), None, None, network_builder).await
+    }
+
+    /// Start the network of the client and wait until it's connected to the peers.
+    async fn start(
+        signer: SecretKey,
+        peers: Option<Vec<Multiaddr>>,
+        connection_timeout: Option<Duration>,
+        client_event_broadcaster: Option<ClientEventsBroadcaster>,
+        network_builder: NetworkBuilder,
+    ) -> Result<Self> {
         let (network, mut network_event_receiver, swarm_driver) = network_builder.build_client()?;
         info!("Client constructed network and swarm_driver");
 
@@ -394,6 +428,27 @@ impl Client {
         self.signer.public_key()
     }
 
+    /// Return the peer ID the client connects to the network with.
+    ///
+    /// Return Type:
+    ///
+    /// [PeerId]
+    ///
+    /// # Example
+    /// ```no_run
+    /// use sn_client::{Client, Error};
+    /// use bls::SecretKey;
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(),Error>{
+    /// let client = Client::new(SecretKey::random(), None, None, None).await?;
+    /// let peer_id = client.peer_id();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn peer_id(&self) -> PeerId {
+        *self.network.peer_id
+    }
+
     /// Set the signing key for this client.
     ///
     /// # Arguments
@@ -757,17 +812,35 @@ impl Client {
     /// Verify if a `Chunk` is stored by expected nodes on the network.
     /// Single local use. Marked Private.
     pub async fn verify_chunk_stored(&self, chunk: &Chunk) -> Result<()> {
-        let address = chunk.network_address();
-        info!("Verifying chunk: {address:?}");
         let random_nonce = thread_rng().gen::<u64>();
         let record_value = try_serialize_record(&chunk, RecordKind::Chunk)?;
         let expected_proof = ChunkProof::new(record_value.as_ref(), random_nonce);
 
+        self.verify_chunk_existence_proof(*chunk.address(), random_nonce, expected_proof)
+            .await
+    }
+
+    /// Verify if a `Chunk` is stored by expected nodes on the network, using a pre-computed `ChunkProof`.
+    /// This allows to verify the existence of a chunk without holding its content.
+    ///
+    /// # Arguments
+    /// * 'address' - [ChunkAddress]
+    /// * 'nonce' - [Nonce] used to compute the expected proof
+    /// * 'expected_proof' - [ChunkProof]
+    pub async fn verify_chunk_existence_proof(
+        &self,
+        address: ChunkAddress,
+        nonce: Nonce,
+        expected_proof: ChunkProof,
+    ) -> Result<()> {
+        let address = NetworkAddress::from_chunk_address(address);
+        info!("Verifying chunk: {address:?}");
+
         if let Err(err) = self
             .network
             .verify_chunk_existence(
                 address.clone(),
-                random_nonce,
+                nonce,
                 expected_proof,
                 Quorum::N(NonZeroUsize::new(2).ok_or(Error::NonZeroUsizeWasInitialisedAsZero)?),
                 None,
@@ -10,9 +10,11 @@ mod dag_error;
 mod spend_check;
 mod spend_dag;
 mod spend_dag_building;
+mod spend_dag_crawler;
 
 #[cfg(test)]
 mod tests;
 
 pub use dag_error::{DagError, SpendFault};
-pub use spend_dag::{SpendDag, SpendDagGet};
+pub use spend_dag::{SpendDag, SpendDagGet, SupplyReport};
+pub use spend_dag_crawler::{CrawlProgress, CrawlerCfg, SpendDagCrawler, DEFAULT_CRAWLER_WORKERS};
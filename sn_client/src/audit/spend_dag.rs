@@ -11,11 +11,13 @@ use petgraph::graph::{DiGraph, NodeIndex};
 use petgraph::visit::EdgeRef;
 use serde::{Deserialize, Serialize};
 use sn_transfers::{
-    is_genesis_spend, CashNoteRedemption, NanoTokens, SignedSpend, SpendAddress,
-    CASHNOTE_PURPOSE_OF_GENESIS, CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES,
+    is_genesis_spend, is_network_royalties_purpose, CashNoteRedemption, Hash, NanoTokens,
+    SignedSpend, SpendAddress, Transaction, CASHNOTE_PURPOSE_OF_CHANGE,
+    CASHNOTE_PURPOSE_OF_GENESIS, CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES, TOTAL_SUPPLY,
 };
 use std::{
     collections::{BTreeMap, BTreeSet},
+    convert::Infallible,
     fmt,
     path::Path,
 };
@@ -108,6 +110,145 @@ pub enum SpendDagGet {
     Spend(Box<SignedSpend>),
 }
 
+/// Where the tokens of the DAG are, computed from its UTXOs
+/// All the amounts are in nanos
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SupplyReport {
+    /// The total supply of tokens that will eventually exist on the network
+    pub total_supply: u64,
+    /// The amount of the source of the DAG (aka Genesis)
+    pub genesis_amount: u64,
+    /// The amount held by the UTXOs that have no fault
+    pub circulating: u64,
+    /// The part of `circulating` held by network royalties outputs
+    pub royalties: u64,
+    /// The part of `circulating` held by the faucet: the outputs of the source spend and the
+    /// change outputs of the spends made from them
+    pub faucet: u64,
+    /// The amount held by the UTXOs descending from a double spend
+    pub double_spent: u64,
+    /// The amount held by the UTXOs with any other fault (missing ancestry, invalid transaction, ...)
+    pub unverifiable: u64,
+    /// The amount spent by transactions but not sent to any output
+    pub burned: u64,
+    /// The number of UTXOs, faulty or not
+    pub utxos: usize,
+    /// The number of UTXOs for which the amount is not known
+    pub utxos_with_unknown_amount: usize,
+    /// `genesis_amount` minus all the amounts accounted for: `circulating`, `double_spent`,
+    /// `unverifiable` and `burned`. Negative if tokens were created out of thin air, which double
+    /// spends do as both branches are accounted for.
+    pub discrepancy: i128,
+}
+
+impl SupplyReport {
+    /// True if all the tokens from the source are accounted for, and none were created
+    pub fn is_reconciled(&self) -> bool {
+        self.discrepancy == 0 && self.utxos_with_unknown_amount == 0
+    }
+
+    /// Sum up the amounts of the UTXOs of a DAG by category and compare them to the amount of its
+    /// source, wherever the DAG is kept.
+    /// `utxos` are the UTXOs with the purpose and amount they were created with, if known, and
+    /// their faults. `faucet` is the result of `SupplyReport::faucet_outputs` and `burned` the
+    /// amount burned by all the transactions of the DAG, see `SupplyReport::burned_by`.
+    pub fn from_utxos<'a>(
+        genesis_amount: u64,
+        utxos: impl IntoIterator<
+            Item = (
+                SpendAddress,
+                Option<(&'a str, u64)>,
+                Option<&'a BTreeSet<SpendFault>>,
+            ),
+        >,
+        faucet: &BTreeSet<SpendAddress>,
+        burned: u64,
+    ) -> Self {
+        let mut report = SupplyReport {
+            total_supply: TOTAL_SUPPLY,
+            genesis_amount,
+            burned,
+            ..Default::default()
+        };
+
+        for (utxo, creation, faults) in utxos {
+            report.utxos += 1;
+            let Some((purpose, amount)) = creation else {
+                report.utxos_with_unknown_amount += 1;
+                continue;
+            };
+            let bucket = match faults {
+                Some(faults)
+                    if faults.iter().any(|f| {
+                        matches!(
+                            f,
+                            SpendFault::DoubleSpend(_) | SpendFault::DoubleSpentAncestor { .. }
+                        )
+                    }) =>
+                {
+                    &mut report.double_spent
+                }
+                Some(faults) if !faults.is_empty() => &mut report.unverifiable,
+                _ => {
+                    if is_network_royalties_purpose(purpose) {
+                        report.royalties = report.royalties.saturating_add(amount);
+                    } else if faucet.contains(&utxo) {
+                        report.faucet = report.faucet.saturating_add(amount);
+                    }
+                    &mut report.circulating
+                }
+            };
+            *bucket = bucket.saturating_add(amount);
+        }
+
+        let accounted = i128::from(report.circulating)
+            + i128::from(report.double_spent)
+            + i128::from(report.unverifiable)
+            + i128::from(report.burned);
+        report.discrepancy = i128::from(genesis_amount) - accounted;
+        report
+    }
+
+    /// The outputs held by the faucet: the outputs of the source spend (the faucet claims Genesis)
+    /// and, recursively, the change outputs of the spends made from them.
+    /// `get_spend` gives the spend at an address, if it is known and not double spent.
+    pub fn faucet_outputs<E>(
+        source_spend: Option<&SignedSpend>,
+        mut get_spend: impl FnMut(&SpendAddress) -> Result<Option<SignedSpend>, E>,
+    ) -> Result<BTreeSet<SpendAddress>, E> {
+        let mut faucet = BTreeSet::new();
+        let mut to_visit: Vec<SpendAddress> = source_spend
+            .iter()
+            .flat_map(|s| s.spend.spent_tx.outputs.iter())
+            .map(|o| SpendAddress::from_unique_pubkey(&o.unique_pubkey))
+            .collect();
+        while let Some(addr) = to_visit.pop() {
+            if !faucet.insert(addr) {
+                continue;
+            }
+            if let Some(spend) = get_spend(&addr)? {
+                to_visit.extend(
+                    spend
+                        .spend
+                        .spent_tx
+                        .outputs
+                        .iter()
+                        .filter(|o| o.purpose == CASHNOTE_PURPOSE_OF_CHANGE)
+                        .map(|o| SpendAddress::from_unique_pubkey(&o.unique_pubkey)),
+                );
+            }
+        }
+        Ok(faucet)
+    }
+
+    /// The amount spent by the transaction but not sent to any of its outputs
+    pub fn burned_by(tx: &Transaction) -> u64 {
+        let inputs: u64 = tx.inputs.iter().map(|i| i.amount.as_nano()).sum();
+        let outputs: u64 = tx.outputs.iter().map(|o| o.amount.as_nano()).sum();
+        inputs.saturating_sub(outputs)
+    }
+}
+
 impl SpendDag {
     /// Create a new DAG with a given source
     pub fn new(source: SpendAddress) -> Self {
@@ -330,22 +471,86 @@ impl SpendDag {
     }
 
     pub fn dump_creation_reasons_statistics(&self) -> String {
-        let mut statistics: BTreeMap<String, Vec<NanoTokens>> = Default::default();
-        for (_spend_addr, (reason, amount)) in self.creation_reasons.iter() {
-            let holders = statistics.entry(reason.clone()).or_default();
-            holders.push(*amount);
-        }
         let mut content = "Purpose,Times,Amount".to_string();
-        for (purpose, payments) in statistics.iter() {
-            let total_amount: u64 = payments
-                .iter()
-                .map(|nano_tokens| nano_tokens.as_nano())
-                .sum();
-            content = format!("{content}\n{purpose},{},{total_amount}", payments.len());
+        for (purpose, (times, total_amount)) in self.creation_reasons_totals().iter() {
+            content = format!("{content}\n{purpose},{times},{}", total_amount.as_nano());
         }
         content
     }
 
+    /// Get the number of outputs and the total amount created for each creation reason (purpose)
+    pub fn creation_reasons_totals(&self) -> BTreeMap<String, (usize, NanoTokens)> {
+        let mut totals: BTreeMap<String, (usize, NanoTokens)> = Default::default();
+        for (reason, amount) in self.creation_reasons.values() {
+            let (times, total_amount) = totals
+                .entry(reason.clone())
+                .or_insert((0, NanoTokens::zero()));
+            *times += 1;
+            *total_amount =
+                NanoTokens::from(total_amount.as_nano().saturating_add(amount.as_nano()));
+        }
+        totals
+    }
+
+    /// Get the creation reason (purpose) and the amount of the output at the given address
+    pub fn get_creation_reason(&self, addr: &SpendAddress) -> Option<(String, NanoTokens)> {
+        self.creation_reasons.get(addr).cloned()
+    }
+
+    /// Get all the faults recorded in the DAG
+    pub fn all_faults(&self) -> Vec<&SpendFault> {
+        self.faults.values().flatten().collect()
+    }
+
+    /// Reconcile the supply of the DAG: sum up the amounts of its UTXOs by category and compare
+    /// them to the amount of its source. Call `record_faults` beforehand for the faulty UTXOs
+    /// to be set apart.
+    pub fn supply_report(&self) -> SupplyReport {
+        let source_spend = match self.spends.get(&self.source) {
+            Some(DagEntry::Spend(spend, _)) => Some(spend.as_ref()),
+            _ => None,
+        };
+        let faucet = match SupplyReport::faucet_outputs(source_spend, |addr| {
+            Ok::<_, Infallible>(match self.spends.get(addr) {
+                Some(DagEntry::Spend(spend, _)) => Some(spend.as_ref().clone()),
+                _ => None,
+            })
+        }) {
+            Ok(faucet) => faucet,
+            Err(never) => match never {},
+        };
+
+        // the same transaction is shared by all the spends of its inputs
+        let mut seen_txs: BTreeSet<Hash> = BTreeSet::new();
+        let mut burned: u64 = 0;
+        for entry in self.spends.values() {
+            let spend = match entry {
+                DagEntry::Spend(spend, _) => spend,
+                _ => continue,
+            };
+            let tx = &spend.spend.spent_tx;
+            if seen_txs.insert(tx.hash()) {
+                burned = burned.saturating_add(SupplyReport::burned_by(tx));
+            }
+        }
+
+        let utxos = self.get_utxos().into_iter().map(|utxo| {
+            let creation = self
+                .creation_reasons
+                .get(&utxo)
+                .map(|(purpose, amount)| (purpose.as_str(), amount.as_nano()));
+            (utxo, creation, self.faults.get(&utxo))
+        });
+        SupplyReport::from_utxos(
+            source_spend
+                .map(|s| s.spend.amount.as_nano())
+                .unwrap_or_default(),
+            utxos,
+            &faucet,
+            burned,
+        )
+    }
+
     /// Merges the given dag into ours
     pub fn merge(&mut self, sub_dag: SpendDag) -> Result<(), DagError> {
         let source = self.source();
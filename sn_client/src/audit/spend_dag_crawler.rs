@@ -0,0 +1,444 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::{Client, Error, SpendDag};
+
+use futures::StreamExt;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use sn_networking::{
+    target_arch::{sleep, Instant},
+    GetRecordError, NetworkError,
+};
+use sn_protocol::NetworkAddress;
+use sn_transfers::{SignedSpend, SpendAddress, WalletError, WalletResult};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::mpsc;
+
+/// The default number of spends fetched in parallel by the crawler
+pub const DEFAULT_CRAWLER_WORKERS: usize = crate::MAX_CONCURRENT_TASKS;
+/// The frontier is saved to disk every time this many addresses have been crawled
+const FRONTIER_SAVE_INTERVAL: usize = 100;
+/// The maximum delay before checking again an address that was not spent yet
+const MAX_RECHECK_DELAY: Duration = Duration::from_secs(3600);
+
+/// The configuration of a `SpendDagCrawler`
+#[derive(Debug, Clone)]
+pub struct CrawlerCfg {
+    /// The number of spends fetched in parallel
+    pub workers: usize,
+    /// The maximum number of spends fetched per second, overall
+    pub max_requests_per_sec: Option<u32>,
+    /// The maximum number of requests per second sent to a single peer.
+    /// The peers are the close group of each spend address, as found in the local routing table.
+    pub max_requests_per_peer_per_sec: Option<u32>,
+    /// Stop after crawling this many generations, the rest is left in the frontier for the next crawl
+    pub max_depth: Option<u32>,
+    /// Where to persist the frontier, so that a crawl can resume after a crash or a restart
+    pub frontier_path: Option<PathBuf>,
+    /// The delay before checking again an address that was not spent yet or could not be fetched.
+    /// The delay doubles on every failed attempt, up to an hour.
+    pub recheck_delay: Duration,
+}
+
+impl Default for CrawlerCfg {
+    fn default() -> Self {
+        Self {
+            workers: DEFAULT_CRAWLER_WORKERS,
+            max_requests_per_sec: None,
+            max_requests_per_peer_per_sec: None,
+            max_depth: None,
+            frontier_path: None,
+            recheck_delay: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A snapshot of the progress of a crawl
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CrawlProgress {
+    /// The number of spends gathered, counting each spend of a double spend
+    pub spends_gathered: usize,
+    pub double_spends: usize,
+    /// The number of addresses that were not spent yet
+    pub utxos: usize,
+    /// The number of addresses that could not be fetched
+    pub errors: usize,
+    /// The number of addresses left to crawl in the current generation
+    pub queued: usize,
+    /// The number of addresses in the frontier
+    pub frontier: usize,
+    pub generation: u32,
+    pub requests: usize,
+    pub elapsed_ms: u64,
+    pub requests_per_sec: f64,
+}
+
+/// An address to crawl, with its retry schedule
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+struct FrontierEntry {
+    attempts: u32,
+    /// In seconds since the UNIX epoch
+    next_attempt: u64,
+}
+
+/// The addresses left to crawl: the addresses refered to by gathered spends, that were not found spent yet.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Frontier {
+    entries: BTreeMap<SpendAddress, FrontierEntry>,
+}
+
+impl Frontier {
+    fn load(path: &PathBuf) -> Self {
+        match std::fs::read(path) {
+            Ok(bytes) => rmp_serde::from_slice(&bytes).unwrap_or_else(|err| {
+                warn!("Failed to parse the crawler frontier at {path:?}, starting afresh: {err}");
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    fn save(&self, path: &PathBuf) {
+        let result = rmp_serde::to_vec(self)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| {
+                let tmp_path = path.with_extension("tmp");
+                std::fs::write(&tmp_path, bytes)
+                    .and_then(|_| std::fs::rename(tmp_path, path))
+                    .map_err(|err| err.to_string())
+            });
+        if let Err(err) = result {
+            warn!("Failed to save the crawler frontier to {path:?}: {err}");
+        }
+    }
+
+    /// Add an address to crawl right away, if not known yet
+    fn push(&mut self, addr: SpendAddress) -> bool {
+        if self.entries.contains_key(&addr) {
+            return false;
+        }
+        let _ = self.entries.insert(addr, FrontierEntry::default());
+        true
+    }
+
+    /// The addresses that are due for a (re)check
+    fn due(&self, now: u64) -> Vec<SpendAddress> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.next_attempt <= now)
+            .map(|(addr, _)| *addr)
+            .collect()
+    }
+
+    /// Push back the next attempt of an address, doubling the delay on every attempt
+    fn postpone(&mut self, addr: SpendAddress, base_delay: Duration, now: u64) {
+        let entry = self.entries.entry(addr).or_default();
+        entry.attempts = entry.attempts.saturating_add(1);
+        let delay = base_delay
+            .saturating_mul(2u32.saturating_pow(entry.attempts - 1))
+            .min(MAX_RECHECK_DELAY);
+        entry.next_attempt = now + delay.as_secs();
+    }
+}
+
+/// A token bucket allowing `rate` requests per second, with bursts of up to `rate` requests.
+/// Tokens are reserved in advance, the caller is told how long to wait for its token.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u32) -> Self {
+        let rate = f64::from(rate.max(1));
+        Self {
+            rate,
+            tokens: rate,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Take a token, returning how long to wait before using it
+    fn take(&mut self) -> Duration {
+        let now = Instant::now();
+        let refill = now.duration_since(self.last_refill).as_secs_f64() * self.rate;
+        self.tokens = (self.tokens + refill).min(self.rate);
+        self.last_refill = now;
+
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+/// Crawls the Spend DAG from its UTXOs with a pool of workers, following the descendants generation by generation.
+///
+/// The addresses left to crawl are kept in a frontier that can be persisted to disk, so that a crawl resumes where
+/// it stopped. The addresses that are not spent yet are checked again with an exponential backoff, instead of on
+/// every crawl. The requests can be rate limited overall and per peer.
+pub struct SpendDagCrawler {
+    client: Client,
+    cfg: CrawlerCfg,
+    global_limit: Option<Mutex<TokenBucket>>,
+    peer_limits: Mutex<HashMap<PeerId, TokenBucket>>,
+    progress_sender: Option<mpsc::Sender<CrawlProgress>>,
+}
+
+impl SpendDagCrawler {
+    pub fn new(client: Client, cfg: CrawlerCfg) -> Self {
+        Self {
+            client,
+            global_limit: cfg
+                .max_requests_per_sec
+                .map(|rate| Mutex::new(TokenBucket::new(rate))),
+            peer_limits: Mutex::new(HashMap::new()),
+            cfg,
+            progress_sender: None,
+        }
+    }
+
+    /// Returns a receiver for the progress of the crawls. Replaces any previous receiver.
+    /// Snapshots are dropped if the receiver is lagging behind.
+    pub fn get_progress_receiver(&mut self) -> mpsc::Receiver<CrawlProgress> {
+        let (tx, rx) = mpsc::channel(100);
+        self.progress_sender = Some(tx);
+        rx
+    }
+
+    /// Extend the DAG from its UTXOs and the persisted frontier, then verify it and record its faults.
+    pub async fn crawl(&self, dag: &mut SpendDag) -> WalletResult<CrawlProgress> {
+        let utxos = dag.get_utxos();
+        let progress = self.crawl_from(dag, utxos).await?;
+        dag.record_faults(&dag.source())
+            .map_err(|e| WalletError::Dag(e.to_string()))?;
+        Ok(progress)
+    }
+
+    /// Insert into the DAG the spends found from the given addresses and the persisted frontier.
+    /// The DAG is not verified, so it can hold only the spends gathered by this crawl, when the
+    /// rest of the DAG is kept elsewhere.
+    pub async fn crawl_from(
+        &self,
+        dag: &mut SpendDag,
+        utxos: BTreeSet<SpendAddress>,
+    ) -> WalletResult<CrawlProgress> {
+        let start = Instant::now();
+        let mut frontier = self
+            .cfg
+            .frontier_path
+            .as_ref()
+            .map(Frontier::load)
+            .unwrap_or_default();
+        for utxo in utxos {
+            let _ = frontier.push(utxo);
+        }
+
+        let mut progress = CrawlProgress::default();
+        let mut to_crawl = frontier.due(unix_timestamp());
+        info!(
+            "Crawling spend DAG from {} addresses, {} in the frontier",
+            to_crawl.len(),
+            frontier.entries.len()
+        );
+
+        while !to_crawl.is_empty() {
+            if self
+                .cfg
+                .max_depth
+                .is_some_and(|max_depth| progress.generation >= max_depth)
+            {
+                break;
+            }
+
+            progress.queued = to_crawl.len();
+            let mut next_gen = BTreeSet::new();
+            let mut stream = futures::stream::iter(to_crawl)
+                .map(|addr| self.fetch(addr))
+                .buffer_unordered(self.cfg.workers.max(1));
+
+            while let Some((addr, result)) = stream.next().await {
+                progress.requests += 1;
+                progress.queued = progress.queued.saturating_sub(1);
+                let spends = match result {
+                    Ok(spend) => vec![spend],
+                    Err(Error::Network(NetworkError::DoubleSpendAttempt(s1, s2)))
+                    | Err(Error::DoubleSpend(_, s1, s2)) => {
+                        warn!("Double spend found while crawling at {addr:?}");
+                        progress.double_spends += 1;
+                        vec![*s1, *s2]
+                    }
+                    Err(Error::Network(NetworkError::GetRecordError(
+                        GetRecordError::RecordNotFound,
+                    ))) => {
+                        trace!("Address {addr:?} is not spent yet");
+                        progress.utxos += 1;
+                        frontier.postpone(addr, self.cfg.recheck_delay, unix_timestamp());
+                        vec![]
+                    }
+                    Err(err) => {
+                        warn!("Failed to fetch spend at {addr:?} while crawling: {err}");
+                        progress.errors += 1;
+                        frontier.postpone(addr, self.cfg.recheck_delay, unix_timestamp());
+                        vec![]
+                    }
+                };
+
+                if !spends.is_empty() {
+                    let _ = frontier.entries.remove(&addr);
+                }
+                for spend in spends {
+                    for output in spend.spend.spent_tx.outputs.iter() {
+                        let descendant = SpendAddress::from_unique_pubkey(&output.unique_pubkey);
+                        if frontier.push(descendant) {
+                            let _ = next_gen.insert(descendant);
+                        }
+                    }
+                    let _ = dag.insert(addr, spend);
+                    progress.spends_gathered += 1;
+                }
+
+                if progress.requests % FRONTIER_SAVE_INTERVAL == 0 {
+                    if let Some(path) = &self.cfg.frontier_path {
+                        frontier.save(path);
+                    }
+                }
+                progress.frontier = frontier.entries.len();
+                self.emit(&mut progress, start);
+            }
+
+            progress.generation += 1;
+            info!(
+                "Crawled generation {} - {} spends gathered so far, {} addresses in the frontier",
+                progress.generation,
+                progress.spends_gathered,
+                frontier.entries.len()
+            );
+            to_crawl = next_gen.into_iter().collect();
+        }
+
+        if let Some(path) = &self.cfg.frontier_path {
+            frontier.save(path);
+        }
+        progress.queued = 0;
+        progress.frontier = frontier.entries.len();
+        self.emit(&mut progress, start);
+
+        info!("Done crawling spend DAG: {progress:?}");
+        Ok(progress)
+    }
+
+    async fn fetch(&self, addr: SpendAddress) -> (SpendAddress, crate::Result<SignedSpend>) {
+        self.wait_for_rate_limits(addr).await;
+        (addr, self.client.crawl_spend_from_network(addr).await)
+    }
+
+    async fn wait_for_rate_limits(&self, addr: SpendAddress) {
+        let mut wait = Duration::ZERO;
+        if let Some(global_limit) = &self.global_limit {
+            if let Ok(mut bucket) = global_limit.lock() {
+                wait = bucket.take();
+            }
+        }
+        if let Some(rate) = self.cfg.max_requests_per_peer_per_sec {
+            match self
+                .client
+                .network
+                .get_close_group_local_peers(&NetworkAddress::from_spend_address(addr))
+                .await
+            {
+                Ok(peers) => {
+                    if let Ok(mut buckets) = self.peer_limits.lock() {
+                        for peer in peers {
+                            let peer_wait = buckets
+                                .entry(peer)
+                                .or_insert_with(|| TokenBucket::new(rate))
+                                .take();
+                            wait = wait.max(peer_wait);
+                        }
+                    }
+                }
+                Err(err) => warn!("Failed to get the close group of {addr:?}: {err}"),
+            }
+        }
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+    }
+
+    fn emit(&self, progress: &mut CrawlProgress, start: Instant) {
+        let elapsed = start.elapsed();
+        progress.elapsed_ms = elapsed.as_millis() as u64;
+        progress.requests_per_sec = if elapsed.as_secs_f64() > 0.0 {
+            progress.requests as f64 / elapsed.as_secs_f64()
+        } else {
+            0.0
+        };
+        if let Some(sender) = &self.progress_sender {
+            if let Err(err) = sender.try_send(progress.clone()) {
+                trace!("Dropped a crawl progress snapshot: {err:?}");
+            }
+        }
+    }
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use xor_name::XorName;
+
+    #[test]
+    fn frontier_should_postpone_unspent_addresses_with_backoff() {
+        let mut rng = rand::thread_rng();
+        let addr = SpendAddress::new(XorName::random(&mut rng));
+        let mut frontier = Frontier::default();
+        assert!(frontier.push(addr));
+        assert!(!frontier.push(addr));
+        assert_eq!(frontier.due(0), vec![addr]);
+
+        let delay = Duration::from_secs(60);
+        frontier.postpone(addr, delay, 1000);
+        assert!(frontier.due(1000).is_empty());
+        assert_eq!(frontier.due(1060), vec![addr]);
+
+        frontier.postpone(addr, delay, 1000);
+        assert!(frontier.due(1060).is_empty());
+        assert_eq!(frontier.due(1120), vec![addr]);
+
+        for _ in 0..20 {
+            frontier.postpone(addr, delay, 1000);
+        }
+        assert_eq!(frontier.due(1000 + MAX_RECHECK_DELAY.as_secs()), vec![addr]);
+    }
+
+    #[test]
+    fn token_bucket_should_delay_requests_over_the_rate() {
+        let mut bucket = TokenBucket::new(2);
+        assert_eq!(bucket.take(), Duration::ZERO);
+        assert_eq!(bucket.take(), Duration::ZERO);
+        let wait = bucket.take();
+        assert!(wait > Duration::from_millis(400) && wait <= Duration::from_millis(500));
+    }
+}
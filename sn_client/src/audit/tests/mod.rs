@@ -473,3 +473,57 @@ fn test_spend_dag_orphans() -> Result<()> {
     );
     Ok(())
 }
+
+#[test]
+fn test_spend_dag_supply_report() -> Result<()> {
+    let mut net = MockNetwork::genesis()?;
+    let genesis = net.genesis_spend;
+
+    let owner1 = net.new_pk_with_balance(100)?;
+    let owner2 = net.new_pk_with_balance(0)?;
+    let owner3 = net.new_pk_with_balance(0)?;
+    let owner_cheat = net.new_pk_with_balance(0)?;
+    net.send(&owner1, &owner2, 100)?;
+
+    let mut dag = SpendDag::new(genesis);
+    for spend in net.spends.iter() {
+        dag.insert(spend.address(), spend.clone());
+    }
+    dag.record_faults(&genesis)?;
+
+    let report = dag.supply_report();
+    let genesis_amount = report.genesis_amount;
+    assert!(genesis_amount > 0);
+    assert_eq!(report.circulating, genesis_amount);
+    assert_eq!(report.faucet, genesis_amount - 100);
+    assert_eq!(report.double_spent, 0);
+    assert_eq!(report.burned, 0);
+    assert!(report.is_reconciled(), "report: {report:?}");
+
+    // double spend owner2's cashnote
+    let cn_to_reuse_later = net
+        .wallets
+        .get(&owner2)
+        .expect("owner2 wallet to exist")
+        .cn
+        .clone();
+    net.send(&owner2, &owner3, 100)?;
+    net.wallets
+        .get_mut(&owner2)
+        .expect("owner2 wallet to still exist")
+        .cn = cn_to_reuse_later;
+    net.send(&owner2, &owner_cheat, 100)?;
+
+    let mut dag = SpendDag::new(genesis);
+    for spend in net.spends {
+        dag.insert(spend.address(), spend.clone());
+    }
+    dag.record_faults(&genesis)?;
+
+    let report = dag.supply_report();
+    assert_eq!(report.circulating, genesis_amount - 100);
+    assert_eq!(report.double_spent, 200);
+    assert_eq!(report.discrepancy, -100);
+    assert!(!report.is_reconciled());
+    Ok(())
+}
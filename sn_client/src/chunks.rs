@@ -6,8 +6,10 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod erasure;
 mod error;
 mod pac_man;
 
+pub use self::erasure::{ErasureCoding, ErasureCodingCfg, ParityStripe};
 pub(crate) use self::error::{Error, Result};
 pub(crate) use pac_man::{encrypt_large, DataMapLevel};
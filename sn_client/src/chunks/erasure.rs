@@ -0,0 +1,307 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{Error, Result};
+use bytes::Bytes;
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use self_encryption::DataMap;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use xor_name::XorName;
+
+/// The maximum number of shards (data + parity) supported by a single stripe.
+const MAX_SHARDS_PER_STRIPE: usize = 256;
+
+/// The Reed-Solomon parameters used to protect the self-encrypted chunks of a file.
+///
+/// The chunks are split into stripes of `data_shards` chunks, and `parity_shards` parity chunks are
+/// generated for each stripe. Any `parity_shards` chunks of a stripe can be lost and still be reconstructed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ErasureCodingCfg {
+    pub data_shards: usize,
+    pub parity_shards: usize,
+}
+
+impl Default for ErasureCodingCfg {
+    fn default() -> Self {
+        Self {
+            data_shards: 4,
+            parity_shards: 2,
+        }
+    }
+}
+
+impl ErasureCodingCfg {
+    fn validate(&self) -> Result<()> {
+        if self.data_shards == 0
+            || self.parity_shards == 0
+            || self.data_shards + self.parity_shards > MAX_SHARDS_PER_STRIPE
+        {
+            return Err(Error::InvalidErasureCodingCfg(*self));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ErasureCodingCfg {
+    type Err = String;
+
+    /// Parse the parameters from the `<data_shards>:<parity_shards>` format. E.g. `4:2`
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (data_shards, parity_shards) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Expected <data_shards>:<parity_shards>, got {s:?}"))?;
+        let cfg = Self {
+            data_shards: data_shards
+                .trim()
+                .parse()
+                .map_err(|err| format!("Invalid data_shards {data_shards:?}: {err}"))?,
+            parity_shards: parity_shards
+                .trim()
+                .parse()
+                .map_err(|err| format!("Invalid parity_shards {parity_shards:?}: {err}"))?,
+        };
+        cfg.validate().map_err(|err| err.to_string())?;
+        Ok(cfg)
+    }
+}
+
+impl fmt::Display for ErasureCodingCfg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.data_shards, self.parity_shards)
+    }
+}
+
+/// A set of data chunks and the parity chunks generated from them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParityStripe {
+    /// The name and the size of each data chunk covered by this stripe.
+    /// The chunks are padded with zeros up to `shard_size` before being encoded.
+    pub data: Vec<(XorName, usize)>,
+    /// The names of the parity chunks of this stripe.
+    pub parity: Vec<XorName>,
+    /// The size of every shard within this stripe.
+    pub shard_size: usize,
+}
+
+/// The erasure coding information that is recorded alongside the `DataMap` of a file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErasureCoding {
+    pub cfg: ErasureCodingCfg,
+    pub stripes: Vec<ParityStripe>,
+}
+
+impl ErasureCoding {
+    /// Generate the parity chunks for the self-encrypted chunks of the provided `DataMap`.
+    /// The data chunks are read from, and the parity chunks are written to the `chunk_dir`.
+    ///
+    /// Returns the erasure coding information along with the parity chunks.
+    pub(crate) fn encode(
+        cfg: ErasureCodingCfg,
+        data_map: &DataMap,
+        chunk_dir: &Path,
+    ) -> Result<(Self, Vec<(XorName, PathBuf)>)> {
+        cfg.validate()?;
+        let codec =
+            ReedSolomon::new(cfg.data_shards, cfg.parity_shards).map_err(Error::ErasureCoding)?;
+
+        let mut stripes = vec![];
+        let mut parity_chunks = vec![];
+        for infos in data_map.infos().chunks(cfg.data_shards) {
+            let data = infos
+                .iter()
+                .map(|info| {
+                    let content = fs::read(chunk_dir.join(hex::encode(info.dst_hash)))?;
+                    Ok((info.dst_hash, content))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let shard_size = data
+                .iter()
+                .map(|(_, content)| content.len())
+                .max()
+                .unwrap_or_default();
+
+            let mut shards = data
+                .iter()
+                .map(|(_, content)| pad(content, shard_size))
+                .collect::<Vec<_>>();
+            // the last stripe might not have enough data chunks, fill it with empty shards.
+            shards.resize(cfg.data_shards + cfg.parity_shards, vec![0; shard_size]);
+            codec.encode(&mut shards).map_err(Error::ErasureCoding)?;
+
+            let mut parity = vec![];
+            for shard in shards.into_iter().skip(cfg.data_shards) {
+                let name = XorName::from_content(&shard);
+                let path = chunk_dir.join(hex::encode(name));
+                File::create(&path)?.write_all(&shard)?;
+                parity.push(name);
+                parity_chunks.push((name, path));
+            }
+
+            stripes.push(ParityStripe {
+                data: data
+                    .iter()
+                    .map(|(name, content)| (*name, content.len()))
+                    .collect(),
+                parity,
+                shard_size,
+            });
+        }
+
+        Ok((Self { cfg, stripes }, parity_chunks))
+    }
+
+    /// Returns the stripe that covers the provided data chunk.
+    pub fn stripe_of(&self, data_chunk: &XorName) -> Option<&ParityStripe> {
+        self.stripes
+            .iter()
+            .find(|stripe| stripe.data.iter().any(|(name, _)| name == data_chunk))
+    }
+
+    /// Reconstruct a missing data chunk of a stripe.
+    ///
+    /// `shards` must contain the content of every data chunk followed by every parity chunk of the
+    /// stripe, with `None` for the ones that could not be retrieved.
+    pub fn reconstruct(
+        &self,
+        stripe: &ParityStripe,
+        shards: Vec<Option<Bytes>>,
+        missing: &XorName,
+    ) -> Result<Bytes> {
+        let (index, size) = stripe
+            .data
+            .iter()
+            .enumerate()
+            .find_map(|(index, (name, size))| (name == missing).then_some((index, *size)))
+            .ok_or(Error::ChunkMissing(*missing))?;
+
+        let data_len = stripe.data.len();
+        let mut padded_shards = Vec::with_capacity(self.cfg.data_shards + self.cfg.parity_shards);
+        padded_shards.extend(shards.iter().take(data_len).map(|shard| {
+            shard
+                .as_ref()
+                .map(|content| pad(content, stripe.shard_size))
+        }));
+        padded_shards.resize(self.cfg.data_shards, Some(vec![0; stripe.shard_size]));
+        padded_shards.extend(
+            shards
+                .into_iter()
+                .skip(data_len)
+                .map(|shard| shard.map(|content| content.to_vec())),
+        );
+
+        let codec = ReedSolomon::new(self.cfg.data_shards, self.cfg.parity_shards)
+            .map_err(Error::ErasureCoding)?;
+        codec
+            .reconstruct_data(&mut padded_shards)
+            .map_err(Error::ErasureCoding)?;
+
+        let mut content = padded_shards
+            .swap_remove(index)
+            .ok_or(Error::ChunkMissing(*missing))?;
+        content.truncate(size);
+
+        if XorName::from_content(&content) != *missing {
+            return Err(Error::ChunkMissing(*missing));
+        }
+        Ok(Bytes::from(content))
+    }
+}
+
+fn pad(content: &[u8], shard_size: usize) -> Vec<u8> {
+    let mut shard = content.to_vec();
+    shard.resize(shard_size, 0);
+    shard
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunks::encrypt_large;
+    use eyre::Result;
+    use rand::{thread_rng, RngCore};
+    use tempfile::tempdir;
+
+    #[test]
+    fn parse_erasure_coding_cfg() {
+        assert_eq!(
+            "10:3".parse::<ErasureCodingCfg>(),
+            Ok(ErasureCodingCfg {
+                data_shards: 10,
+                parity_shards: 3
+            })
+        );
+        assert!("10".parse::<ErasureCodingCfg>().is_err());
+        assert!("0:3".parse::<ErasureCodingCfg>().is_err());
+        assert!("250:10".parse::<ErasureCodingCfg>().is_err());
+    }
+
+    #[test]
+    fn missing_data_chunks_should_be_reconstructed_from_parity() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file_path = temp_dir.path().join("file");
+        let mut data = vec![0u8; 7 * 1024 * 1024 + 1234];
+        thread_rng().fill_bytes(&mut data);
+        File::create(&file_path)?.write_all(&data)?;
+        let chunk_dir = temp_dir.path().join("chunks");
+        fs::create_dir_all(&chunk_dir)?;
+
+        let (data_map_chunk, _) = encrypt_large(&file_path, &chunk_dir, None)?;
+        let data_map = match rmp_serde::from_slice(data_map_chunk.value())? {
+            super::super::DataMapLevel::First(data_map) => data_map,
+            _ => eyre::bail!("Expected a first level data map"),
+        };
+
+        let cfg = ErasureCodingCfg {
+            data_shards: 3,
+            parity_shards: 2,
+        };
+        let (erasure_coding, parity_chunks) = ErasureCoding::encode(cfg, &data_map, &chunk_dir)?;
+        assert_eq!(
+            erasure_coding.stripes.len(),
+            data_map.infos().len().div_ceil(3)
+        );
+        assert_eq!(parity_chunks.len(), erasure_coding.stripes.len() * 2);
+
+        let read = |name: &XorName| -> Option<Bytes> {
+            fs::read(chunk_dir.join(hex::encode(name)))
+                .ok()
+                .map(Bytes::from)
+        };
+
+        for stripe in &erasure_coding.stripes {
+            // lose the first data chunk and a parity chunk of every stripe
+            let missing = stripe.data[0].0;
+            let shards = stripe
+                .data
+                .iter()
+                .map(|(name, _)| name)
+                .chain(stripe.parity.iter())
+                .enumerate()
+                .map(|(index, name)| {
+                    if index == 0 || index == stripe.data.len() {
+                        None
+                    } else {
+                        read(name)
+                    }
+                })
+                .collect();
+
+            let reconstructed = erasure_coding.reconstruct(stripe, shards, &missing)?;
+            assert_eq!(Some(reconstructed), read(&missing));
+        }
+
+        Ok(())
+    }
+}
@@ -6,6 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+use super::ErasureCodingCfg;
 use self_encryption::MIN_ENCRYPTABLE_BYTES;
 use sn_protocol::PrettyPrintRecordKey;
 use std::io;
@@ -66,6 +67,12 @@ pub enum Error {
     #[error("Chunk could not be retrieved from the network: {0:?}")]
     ChunkMissing(XorName),
 
+    #[error("Invalid erasure coding parameters: {0:?}")]
+    InvalidErasureCodingCfg(ErasureCodingCfg),
+
+    #[error("Erasure coding error: {0}")]
+    ErasureCoding(reed_solomon_erasure::Error),
+
     #[error("Not all data was chunked, expected {expected}, but we have {chunked}.)")]
     NotAllDataWasChunked {
         /// Number of Chunks expected to be generated
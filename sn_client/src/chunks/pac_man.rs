@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use super::Result;
+use super::{ErasureCoding, ErasureCodingCfg, Result};
 use bytes::{BufMut, Bytes, BytesMut};
 use rayon::prelude::*;
 use self_encryption::{DataMap, StreamSelfEncryptor, MAX_CHUNK_SIZE};
@@ -27,13 +27,15 @@ pub(crate) enum DataMapLevel {
     // resulting from chunking up a previous level data map.
     // This happens when that previous level data map was too big to fit in a chunk itself.
     Additional(DataMap),
+    // Holds the data map to the source data, along with the parity chunks that protect its chunks.
+    FirstWithErasureCoding(DataMap, ErasureCoding),
 }
 
 #[allow(unused)]
 pub(crate) fn encrypt_from_path(path: &Path, output_dir: &Path) -> Result<(Chunk, Vec<XorName>)> {
     let (data_map, mut encrypted_chunks) = self_encryption::encrypt_from_file(path, output_dir)?;
 
-    let (data_map_chunk, additional_chunks) = pack_data_map(data_map)?;
+    let (data_map_chunk, additional_chunks) = pack_data_map(DataMapLevel::First(data_map))?;
 
     for chunk in additional_chunks.iter() {
         encrypted_chunks.push(*chunk.name());
@@ -49,6 +51,7 @@ pub(crate) fn encrypt_from_path(path: &Path, output_dir: &Path) -> Result<(Chunk
 pub(crate) fn encrypt_large(
     file_path: &Path,
     output_dir: &Path,
+    erasure_coding: Option<ErasureCodingCfg>,
 ) -> Result<(Chunk, Vec<(XorName, PathBuf)>)> {
     let mut encryptor = StreamSelfEncryptor::encrypt_from_file(
         Box::new(file_path.to_path_buf()),
@@ -75,8 +78,17 @@ pub(crate) fn encrypt_large(
         })
         .collect();
 
+    // Generate the parity chunks under the same output folder, if requested.
+    let data_map_level = if let Some(cfg) = erasure_coding {
+        let (erasure_coding, parity_chunks) = ErasureCoding::encode(cfg, &data_map, output_dir)?;
+        encrypted_chunks.extend(parity_chunks);
+        DataMapLevel::FirstWithErasureCoding(data_map, erasure_coding)
+    } else {
+        DataMapLevel::First(data_map)
+    };
+
     // Pack the datamap into chunks that under the same output folder as well.
-    let (data_map_chunk, additional_chunks) = pack_data_map(data_map)?;
+    let (data_map_chunk, additional_chunks) = pack_data_map(data_map_level)?;
     for chunk in additional_chunks.iter() {
         let file_path = output_dir.join(&hex::encode(chunk.name()));
         encrypted_chunks.push((*chunk.name(), file_path.to_path_buf()));
@@ -91,15 +103,15 @@ pub(crate) fn to_chunk(chunk_content: Bytes) -> Chunk {
     Chunk::new(chunk_content)
 }
 
-// Produces a chunk out of the first `DataMap`, which is validated for its size.
+// Produces a chunk out of the first level `DataMap`, which is validated for its size.
 // If the chunk is too big, it is self-encrypted and the resulting (additional level) `DataMap` is put into a chunk.
 // The above step is repeated as many times as required until the chunk size is valid.
 // In other words: If the chunk content is too big, it will be
 // self encrypted into additional chunks, and now we have a new `DataMap`
 // which points to all of those additional chunks.. and so on.
-fn pack_data_map(data_map: DataMap) -> Result<(Chunk, Vec<Chunk>)> {
+fn pack_data_map(data_map_level: DataMapLevel) -> Result<(Chunk, Vec<Chunk>)> {
     let mut chunks = vec![];
-    let mut chunk_content = wrap_data_map(&DataMapLevel::First(data_map))?;
+    let mut chunk_content = wrap_data_map(&data_map_level)?;
 
     let (data_map_chunk, additional_chunks) = loop {
         let chunk = to_chunk(chunk_content);
@@ -12,7 +12,7 @@ use super::ClientEvent;
 use sn_protocol::NetworkAddress;
 use sn_registers::{Entry, EntryHash};
 use sn_transfers::{SignedSpend, SpendAddress};
-use std::collections::BTreeSet;
+use std::{collections::BTreeSet, path::PathBuf};
 use thiserror::Error;
 use tokio::time::Duration;
 use xor_name::XorName;
@@ -154,4 +154,40 @@ pub enum Error {
 
     #[error("SecretKey could not be created from the provided bytes")]
     InvalidKeyBytes,
+
+    // ------ Manifest Errors --------
+    #[error("The signature of the manifest is not valid")]
+    InvalidManifestSignature,
+
+    #[error("The manifest is too large ({0} bytes) for its parts to be listed in a single chunk")]
+    ManifestTooLarge(usize),
+
+    #[error("Only the owner of the manifest ({0:?}) can verify it")]
+    NotManifestOwner(bls::PublicKey),
+
+    // ------ Download Errors --------
+    #[error("Invalid glob pattern {0:?}: {1}")]
+    InvalidGlobPattern(String, glob::PatternError),
+
+    #[error("The download path {0:?} must be relative and must not leave the download directory")]
+    InvalidDownloadPath(PathBuf),
+
+    // ------ Share Token Errors --------
+    #[error("The share token is malformed")]
+    InvalidShareToken,
+
+    #[error("The signature of the share token is not valid")]
+    InvalidShareTokenSignature,
+
+    #[error("The share token is meant for another key: {0:?}")]
+    ShareTokenRecipientMismatch(bls::PublicKey),
+
+    #[error("Failed to decrypt the share token")]
+    ShareTokenDecryption,
+
+    #[error("The share token has expired at {0} (seconds since the UNIX epoch)")]
+    ShareTokenExpired(u64),
+
+    #[error("The share token is too large ({0} bytes) to be stored as a single chunk")]
+    ShareTokenTooLarge(usize),
 }
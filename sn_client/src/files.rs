@@ -7,10 +7,15 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 pub(crate) mod download;
+pub(crate) mod manifest;
+pub(crate) mod share;
 
 use crate::{
-    acc_packet::load_account_wallet_or_create_with_mnemonic, chunks::Error as ChunksError,
-    error::Result, wallet::StoragePaymentResult, Client, Error, WalletClient,
+    acc_packet::load_account_wallet_or_create_with_mnemonic,
+    chunks::{ErasureCodingCfg, Error as ChunksError},
+    error::Result,
+    wallet::StoragePaymentResult,
+    Client, Error, WalletClient,
 };
 use bytes::Bytes;
 use self_encryption::{self, MIN_ENCRYPTABLE_BYTES};
@@ -77,6 +82,20 @@ impl FilesApi {
         file_path: &Path,
         chunk_dir: &Path,
         include_data_map_in_chunks: bool,
+    ) -> ChunkFileResult {
+        Self::chunk_file_with_erasure_coding(file_path, chunk_dir, include_data_map_in_chunks, None)
+    }
+
+    /// Tries to chunk the file, returning `(head_address, data_map_chunk, file_size, chunk_names)`
+    /// and writes encrypted chunks to disk.
+    ///
+    /// If `erasure_coding` is provided, the parity chunks are generated alongside the encrypted chunks and
+    /// their parameters are recorded inside the data map chunk.
+    pub fn chunk_file_with_erasure_coding(
+        file_path: &Path,
+        chunk_dir: &Path,
+        include_data_map_in_chunks: bool,
+        erasure_coding: Option<ErasureCodingCfg>,
     ) -> ChunkFileResult {
         let file = File::open(file_path)?;
         let metadata = file.metadata()?;
@@ -86,7 +105,7 @@ impl FilesApi {
             if file_size < MIN_ENCRYPTABLE_BYTES as u64 {
                 Err(ChunksError::FileTooSmall)?
             } else {
-                let (data_map_chunk, chunks) = encrypt_large(file_path, chunk_dir)?;
+                let (data_map_chunk, chunks) = encrypt_large(file_path, chunk_dir, erasure_coding)?;
                 (*data_map_chunk.name(), data_map_chunk, chunks)
             };
 
@@ -190,6 +209,14 @@ impl FilesApi {
 /// Does not store anything to the network.
 ///
 /// Returns data map as a chunk, and the resulting chunks
-fn encrypt_large(file_path: &Path, output_dir: &Path) -> Result<(Chunk, Vec<(XorName, PathBuf)>)> {
-    Ok(crate::chunks::encrypt_large(file_path, output_dir)?)
+fn encrypt_large(
+    file_path: &Path,
+    output_dir: &Path,
+    erasure_coding: Option<ErasureCodingCfg>,
+) -> Result<(Chunk, Vec<(XorName, PathBuf)>)> {
+    Ok(crate::chunks::encrypt_large(
+        file_path,
+        output_dir,
+        erasure_coding,
+    )?)
 }
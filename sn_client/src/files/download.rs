@@ -7,19 +7,29 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 use crate::{
-    chunks::{DataMapLevel, Error as ChunksError},
+    chunks::{DataMapLevel, ErasureCoding, Error as ChunksError},
     error::{Error as ClientError, Result},
-    Client, FilesApi, BATCH_SIZE,
+    progress::{ProgressTracker, TransferKind},
+    Client, FilesApi, TransferProgress, BATCH_SIZE,
 };
 use bytes::Bytes;
 use futures::StreamExt;
+use glob::Pattern;
 use itertools::Itertools;
 use self_encryption::{decrypt_full_set, DataMap, EncryptedChunk, StreamSelfDecryptor};
 use sn_networking::target_arch::Instant;
 use sn_protocol::storage::{Chunk, ChunkAddress, RetryStrategy};
 
-use std::{collections::HashMap, fs, path::PathBuf};
-use tokio::sync::mpsc::{self};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::{
+    mpsc::{self},
+    Semaphore,
+};
 use xor_name::XorName;
 
 /// The events emitted from the download process.
@@ -33,10 +43,83 @@ pub enum FilesDownloadEvent {
     /// very large.
     /// Note: This count currently is not accurate. It does not take into account how we fetch the initial head chunk.
     DatamapCount(usize),
+    /// The total number of files we are about to download as part of a batch.
+    FilesCount(usize),
+    /// A file of the batch has been downloaded and written to the provided path.
+    FileDownloaded(ChunkAddress, PathBuf),
+    /// A file of the batch could not be downloaded.
+    FileFailed(ChunkAddress, PathBuf),
     /// The download process has terminated with an error.
     Error,
 }
 
+/// The default number of files that are downloaded in parallel during a batch download.
+pub const MAX_CONCURRENT_FILE_DOWNLOADS: usize = 4;
+
+/// A file to be downloaded as part of a batch.
+#[derive(Debug, Clone)]
+pub struct BatchDownloadItem {
+    /// The address of the head chunk (DataMap) of the file.
+    pub address: ChunkAddress,
+    /// The DataMap chunk if it is available locally, else it is fetched from the network.
+    pub data_map_chunk: Option<Chunk>,
+    /// The path of the file, relative to the download directory.
+    pub relative_path: PathBuf,
+}
+
+/// The outcome of a batch download.
+#[derive(Debug, Default)]
+pub struct BatchDownloadSummary {
+    /// The files that have been downloaded, along with the path they were written to.
+    pub downloaded: Vec<(ChunkAddress, PathBuf)>,
+    /// The files that could not be downloaded.
+    pub failed: Vec<(ChunkAddress, PathBuf, ClientError)>,
+    /// The files that have been left out by the `DownloadFilter`.
+    pub skipped: Vec<(ChunkAddress, PathBuf)>,
+}
+
+/// Selects the files of a batch download through glob patterns matched against their relative paths.
+///
+/// A file is downloaded if it matches any of the `include` patterns (or if there are none), and none of the
+/// `exclude` patterns.
+#[derive(Debug, Clone, Default)]
+pub struct DownloadFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl DownloadFilter {
+    /// Create a filter from the provided glob patterns. E.g. `photos/**/*.jpg`
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+        let parse = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    Pattern::new(pattern)
+                        .map_err(|err| ClientError::InvalidGlobPattern(pattern.clone(), err))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            include: parse(include)?,
+            exclude: parse(exclude)?,
+        })
+    }
+
+    /// Returns true if the file at the provided relative path should be downloaded.
+    pub fn matches(&self, relative_path: &Path) -> bool {
+        (self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|pattern| pattern.matches_path(relative_path)))
+            && !self
+                .exclude
+                .iter()
+                .any(|pattern| pattern.matches_path(relative_path))
+    }
+}
+
 // Internally used to differentiate between the various ways that the downloaded chunks are returned.
 enum DownloadReturnType {
     EncryptedChunks(Vec<EncryptedChunk>),
@@ -52,11 +135,16 @@ pub struct FilesDownload {
     batch_size: usize,
     show_holders: bool,
     retry_strategy: RetryStrategy,
+    max_concurrent_files: usize,
+    max_concurrent_chunks: usize,
+    // Limits the number of chunks fetched in parallel across all the files of a batch.
+    chunk_permits: Option<Arc<Semaphore>>,
     // API
     api: FilesApi,
     // Events
     event_sender: Option<mpsc::Sender<FilesDownloadEvent>>,
     logged_event_sender_absence: bool,
+    progress: ProgressTracker,
 }
 
 impl FilesDownload {
@@ -67,9 +155,13 @@ impl FilesDownload {
             batch_size: BATCH_SIZE,
             show_holders: false,
             retry_strategy: RetryStrategy::Quick,
+            max_concurrent_files: MAX_CONCURRENT_FILE_DOWNLOADS,
+            max_concurrent_chunks: BATCH_SIZE,
+            chunk_permits: None,
             api: files_api,
             event_sender: None,
             logged_event_sender_absence: false,
+            progress: ProgressTracker::new(TransferKind::Download),
         }
     }
 
@@ -97,6 +189,23 @@ impl FilesDownload {
         self
     }
 
+    /// Sets the number of files that are downloaded in parallel during a batch download.
+    ///
+    /// By default, this option is set to the constant `MAX_CONCURRENT_FILE_DOWNLOADS: usize = 4`.
+    pub fn set_max_concurrent_files(mut self, max_concurrent_files: usize) -> Self {
+        self.max_concurrent_files = max_concurrent_files.max(1);
+        self
+    }
+
+    /// Sets the number of chunks that are downloaded in parallel across all the files of a batch download.
+    /// The `batch_size` still applies to each file individually.
+    ///
+    /// By default, this option is set to the constant `BATCH_SIZE: usize = 64`.
+    pub fn set_max_concurrent_chunks(mut self, max_concurrent_chunks: usize) -> Self {
+        self.max_concurrent_chunks = max_concurrent_chunks.max(1);
+        self
+    }
+
     /// Returns a receiver for file download events.
     /// This method is optional and the download process can be performed without it.
     pub fn get_events(&mut self) -> mpsc::Receiver<FilesDownloadEvent> {
@@ -107,6 +216,12 @@ impl FilesDownload {
         event_receiver
     }
 
+    /// Returns a receiver for the byte-level `TransferProgress` of the download.
+    /// The progress snapshots are dropped if the receiver falls behind.
+    pub fn get_progress_events(&mut self) -> mpsc::Receiver<TransferProgress> {
+        self.progress.get_receiver()
+    }
+
     /// Download bytes from the network. The contents are spread across
     /// multiple chunks in the network. This function invokes the self-encryptor and returns
     /// the data that was initially stored.
@@ -123,6 +238,7 @@ impl FilesDownload {
     ) -> Result<Bytes> {
         // clean up the trackers/stats
         self.logged_event_sender_absence = false;
+        self.progress.reset();
 
         let result = self.download_from_inner(address, position, length).await;
 
@@ -134,6 +250,8 @@ impl FilesDownload {
         // drop the sender to close the channel.
         let sender = self.event_sender.take();
         drop(sender);
+        self.progress.emit();
+        self.progress.close();
 
         result
     }
@@ -153,7 +271,9 @@ impl FilesDownload {
 
         // First try to deserialize a LargeFile, if it works, we go and seek it.
         // If an error occurs, we consider it to be a SmallFile.
-        if let Ok(data_map) = self.unpack_chunk(chunk.clone()).await {
+        if let Ok((data_map, erasure_coding)) =
+            self.unpack_chunk_with_erasure_coding(chunk.clone()).await
+        {
             let info = self_encryption::seek_info(data_map.file_size(), position, length);
             let range = &info.index_range;
             let all_infos = data_map.infos();
@@ -164,9 +284,11 @@ impl FilesDownload {
                 .collect_vec();
             let to_download = DataMap::new(to_download);
 
-            // not written to file and return the encrypted chunks
-            if let DownloadReturnType::EncryptedChunks(encrypted_chunks) =
-                self.read(to_download, None, true, false).await?
+            // not written to file and return the encrypted chunks. The parity stripes are looked up by the
+            // chunk address, so the missing chunks of the range can be reconstructed as well.
+            if let DownloadReturnType::EncryptedChunks(encrypted_chunks) = self
+                .read(to_download, erasure_coding, None, true, false)
+                .await?
             {
                 let bytes = self_encryption::decrypt_range(
                     &data_map,
@@ -233,6 +355,164 @@ impl FilesDownload {
         }
     }
 
+    /// Download multiple files in parallel and write them under the `download_dir`, recreating the directory
+    /// tree given by their relative paths. Only the files that match the `filter` are downloaded.
+    ///
+    /// At most `max_concurrent_files` files and `max_concurrent_chunks` chunks are downloaded at once.
+    /// A failure to download a file does not stop the batch, check the returned summary for the failed ones.
+    pub async fn download_batch(
+        &mut self,
+        items: Vec<BatchDownloadItem>,
+        download_dir: &Path,
+        filter: &DownloadFilter,
+    ) -> Result<BatchDownloadSummary> {
+        // clean up the trackers/stats
+        self.logged_event_sender_absence = false;
+        self.progress.reset();
+
+        let result = self.download_batch_inner(items, download_dir, filter).await;
+
+        // send an event indicating that the download process completed with an error
+        if result.is_err() {
+            self.send_event(FilesDownloadEvent::Error).await?;
+        }
+
+        // drop the sender to close the channel.
+        let sender = self.event_sender.take();
+        drop(sender);
+        self.progress.emit();
+        self.progress.close();
+
+        result
+    }
+
+    async fn download_batch_inner(
+        &mut self,
+        items: Vec<BatchDownloadItem>,
+        download_dir: &Path,
+        filter: &DownloadFilter,
+    ) -> Result<BatchDownloadSummary> {
+        let mut summary = BatchDownloadSummary::default();
+        let mut to_download = vec![];
+        for item in items {
+            if !is_contained_relative_path(&item.relative_path) {
+                error!(
+                    "The download path {:?} of {:?} is not a contained relative path",
+                    item.relative_path, item.address
+                );
+                return Err(ClientError::InvalidDownloadPath(item.relative_path));
+            }
+            if filter.matches(&item.relative_path) {
+                to_download.push(item);
+            } else {
+                debug!(
+                    "Skipping {:?} as it does not match the filter",
+                    item.relative_path
+                );
+                summary.skipped.push((item.address, item.relative_path));
+            }
+        }
+
+        self.send_event(FilesDownloadEvent::FilesCount(to_download.len()))
+            .await?;
+        self.progress.add_to_total(to_download.len(), 0);
+
+        let chunk_permits = Arc::new(Semaphore::new(self.max_concurrent_chunks));
+        // The byte-level progress of each file is forwarded as the (bytes_total, bytes_transferred, retries)
+        // it has made since its previous snapshot.
+        let (file_progress_sender, mut file_progress_receiver) =
+            mpsc::unbounded_channel::<(u64, u64, usize)>();
+        let api = self.api.clone();
+        let (batch_size, show_holders, retry_strategy) =
+            (self.batch_size, self.show_holders, self.retry_strategy);
+        let mut stream = futures::stream::iter(to_download)
+            .map(|item| {
+                let mut files_download = FilesDownload::new(api.clone())
+                    .set_batch_size(batch_size)
+                    .set_show_holders(show_holders)
+                    .set_retry_strategy(retry_strategy);
+                files_download.chunk_permits = Some(chunk_permits.clone());
+                let mut file_progress = files_download.get_progress_events();
+                let file_progress_sender = file_progress_sender.clone();
+                let path = download_dir.join(&item.relative_path);
+                async move {
+                    if let Some(parent) = path.parent() {
+                        if let Err(err) = fs::create_dir_all(parent) {
+                            return (item.address, path, (0, 0), Err(err.into()));
+                        }
+                    }
+                    // The channel is closed once the file download completes.
+                    let forward_progress = async {
+                        let (mut total, mut transferred, mut retries) = (0, 0, 0);
+                        while let Some(progress) = file_progress.recv().await {
+                            let _ = file_progress_sender.send((
+                                progress.bytes_total.saturating_sub(total),
+                                progress.bytes_transferred.saturating_sub(transferred),
+                                progress.retries.saturating_sub(retries),
+                            ));
+                            total = total.max(progress.bytes_total);
+                            transferred = transferred.max(progress.bytes_transferred);
+                            retries = retries.max(progress.retries);
+                        }
+                        (total, transferred)
+                    };
+                    let (result, forwarded_bytes) = futures::join!(
+                        files_download.download_file_to_path(
+                            item.address,
+                            item.data_map_chunk,
+                            path.clone()
+                        ),
+                        forward_progress
+                    );
+                    (item.address, path, forwarded_bytes, result)
+                }
+            })
+            .buffer_unordered(self.max_concurrent_files);
+
+        loop {
+            let (address, path, (forwarded_total, forwarded_bytes), result) = tokio::select! {
+                Some((bytes_total, bytes, retries)) = file_progress_receiver.recv() => {
+                    self.progress.record_file_progress(bytes_total, bytes, retries);
+                    continue;
+                }
+                next = stream.next() => match next {
+                    Some(next) => next,
+                    None => break,
+                },
+            };
+            match result {
+                Ok(()) => {
+                    info!("Downloaded {address:?} to {path:?}");
+                    let size = fs::metadata(&path)
+                        .map(|metadata| metadata.len())
+                        .unwrap_or_default();
+                    // Account for the snapshots of the file that were dropped, if any.
+                    self.progress
+                        .add_to_total(0, size.saturating_sub(forwarded_total));
+                    self.progress
+                        .record_transferred(size.saturating_sub(forwarded_bytes), None);
+                    self.send_event(FilesDownloadEvent::FileDownloaded(address, path.clone()))
+                        .await?;
+                    summary.downloaded.push((address, path));
+                }
+                Err(err) => {
+                    error!("Failed to download {address:?} to {path:?}: {err:?}");
+                    self.send_event(FilesDownloadEvent::FileFailed(address, path.clone()))
+                        .await?;
+                    summary.failed.push((address, path, err));
+                }
+            }
+        }
+        drop(stream);
+        drop(file_progress_sender);
+        while let Some((bytes_total, bytes, retries)) = file_progress_receiver.recv().await {
+            self.progress
+                .record_file_progress(bytes_total, bytes, retries);
+        }
+
+        Ok(summary)
+    }
+
     /// Download a file from the network.
     /// If you want to track the download progress, use the `get_events` method.
     async fn download_entire_file(
@@ -243,6 +523,7 @@ impl FilesDownload {
     ) -> Result<Option<Bytes>> {
         // clean up the trackers/stats
         self.logged_event_sender_absence = false;
+        self.progress.reset();
 
         let result = self
             .download_entire_file_inner(address, data_map_chunk, downloaded_file_path)
@@ -256,6 +537,8 @@ impl FilesDownload {
         // drop the sender to close the channel.
         let sender = self.event_sender.take();
         drop(sender);
+        self.progress.emit();
+        self.progress.close();
 
         result
     }
@@ -285,11 +568,14 @@ impl FilesDownload {
         };
 
         // first try to deserialize a LargeFile, if it works, we go and seek it
-        match self.unpack_chunk(head_chunk.clone()).await {
-            Ok(data_map) => {
+        match self
+            .unpack_chunk_with_erasure_coding(head_chunk.clone())
+            .await
+        {
+            Ok((data_map, erasure_coding)) => {
                 // read_all emits
                 match self
-                    .read(data_map, downloaded_file_path, false, false)
+                    .read(data_map, erasure_coding, downloaded_file_path, false, false)
                     .await?
                 {
                     DownloadReturnType::EncryptedChunks(_) => {
@@ -311,6 +597,9 @@ impl FilesDownload {
                 self.send_event(FilesDownloadEvent::ChunksCount(1)).await?;
                 self.send_event(FilesDownloadEvent::Downloaded(address))
                     .await?;
+                let size = head_chunk.value().len() as u64;
+                self.progress.add_to_total(1, size);
+                self.progress.record_transferred(size, None);
                 if let Some(path) = downloaded_file_path {
                     fs::write(path, head_chunk.value().clone())?;
                     Ok(None)
@@ -336,9 +625,13 @@ impl FilesDownload {
     /// Else we return DownloadReturnType::DecryptedBytes
     ///
     /// Set we_are_downloading_a_datamap if we want to emit the DatamapCount else we emit ChunksCount
+    ///
+    /// If the erasure_coding is provided, the chunks that cannot be fetched are reconstructed from the rest of
+    /// their stripe.
     async fn read(
         &mut self,
         data_map: DataMap,
+        erasure_coding: Option<Arc<ErasureCoding>>,
         decrypted_file_path: Option<PathBuf>,
         return_encrypted_chunks: bool,
         we_are_downloading_a_datamap: bool,
@@ -361,6 +654,12 @@ impl FilesDownload {
         };
         let chunk_infos = data_map.infos();
         let expected_count = chunk_infos.len();
+        let chunk_sizes: HashMap<usize, u64> = chunk_infos
+            .iter()
+            .map(|info| (info.index, info.src_size as u64))
+            .collect();
+        self.progress
+            .add_to_total(expected_count, chunk_sizes.values().sum());
 
         if we_are_downloading_a_datamap {
             self.send_event(FilesDownloadEvent::ChunksCount(expected_count))
@@ -376,6 +675,7 @@ impl FilesDownload {
         let client_clone = self.api.client.clone();
         let show_holders = self.show_holders;
         let retry_strategy = self.retry_strategy;
+        let chunk_permits = self.chunk_permits.clone();
         // the initial index is not always 0 as we might seek a range of bytes. So fetch the first index
         let mut current_index = chunk_infos
             .first()
@@ -383,13 +683,25 @@ impl FilesDownload {
             .index;
         let mut stream = futures::stream::iter(chunk_infos.into_iter())
             .map(|chunk_info| {
-                Self::get_chunk(
-                    client_clone.clone(),
-                    chunk_info.dst_hash,
-                    chunk_info.index,
-                    show_holders,
-                    retry_strategy,
-                )
+                let client = client_clone.clone();
+                let erasure_coding = erasure_coding.clone();
+                let chunk_permits = chunk_permits.clone();
+                async move {
+                    // hold on to the permit (if any) until the chunk is fetched
+                    let _permit = match &chunk_permits {
+                        Some(permits) => permits.acquire().await.ok(),
+                        None => None,
+                    };
+                    Self::get_or_reconstruct_chunk(
+                        client,
+                        chunk_info.dst_hash,
+                        chunk_info.index,
+                        erasure_coding,
+                        show_holders,
+                        retry_strategy,
+                    )
+                    .await
+                }
             })
             .buffer_unordered(self.batch_size);
 
@@ -400,6 +712,8 @@ impl FilesDownload {
             // notify about the download
             self.send_event(FilesDownloadEvent::Downloaded(chunk_address))
                 .await?;
+            self.progress
+                .record_transferred(chunk_sizes.get(&index).copied().unwrap_or_default(), None);
             info!("Downloaded chunk of index {index:?}. We are at current_index {current_index:?}");
 
             // check if current_index is present in the cache before comparing the fetched index.
@@ -474,15 +788,28 @@ impl FilesDownload {
     /// Extracts a file DataMapLevel from a chunk.
     /// If the DataMapLevel is not the first level mapping directly to the user's contents,
     /// the process repeats itself until it obtains the first level DataMapLevel.
-    pub async fn unpack_chunk(&mut self, mut chunk: Chunk) -> Result<DataMap> {
+    pub async fn unpack_chunk(&mut self, chunk: Chunk) -> Result<DataMap> {
+        let (data_map, _erasure_coding) = self.unpack_chunk_with_erasure_coding(chunk).await?;
+        Ok(data_map)
+    }
+
+    /// Same as `unpack_chunk`, but also returns the erasure coding information if the file was uploaded
+    /// along with parity chunks.
+    async fn unpack_chunk_with_erasure_coding(
+        &mut self,
+        mut chunk: Chunk,
+    ) -> Result<(DataMap, Option<Arc<ErasureCoding>>)> {
         loop {
             match rmp_serde::from_slice(chunk.value()).map_err(ChunksError::Deserialisation)? {
                 DataMapLevel::First(data_map) => {
-                    return Ok(data_map);
+                    return Ok((data_map, None));
+                }
+                DataMapLevel::FirstWithErasureCoding(data_map, erasure_coding) => {
+                    return Ok((data_map, Some(Arc::new(erasure_coding))));
                 }
                 DataMapLevel::Additional(data_map) => {
                     if let DownloadReturnType::DecryptedBytes(serialized_chunk) =
-                        self.read(data_map, None, false, true).await?
+                        self.read(data_map, None, None, false, true).await?
                     {
                         chunk = rmp_serde::from_slice(&serialized_chunk)
                             .map_err(ChunksError::Deserialisation)?;
@@ -508,6 +835,53 @@ impl FilesDownload {
         Ok(())
     }
 
+    async fn get_or_reconstruct_chunk(
+        client: Client,
+        address: XorName,
+        index: usize,
+        erasure_coding: Option<Arc<ErasureCoding>>,
+        show_holders: bool,
+        retry_strategy: RetryStrategy,
+    ) -> std::result::Result<(ChunkAddress, usize, EncryptedChunk), ChunksError> {
+        let result =
+            Self::get_chunk(client.clone(), address, index, show_holders, retry_strategy).await;
+        let Some(erasure_coding) = erasure_coding else {
+            return result;
+        };
+        if result.is_ok() {
+            return result;
+        }
+
+        warn!("Chunk {address:?} is missing, trying to reconstruct it from its parity stripe");
+        let stripe = erasure_coding
+            .stripe_of(&address)
+            .ok_or(ChunksError::ChunkMissing(address))?;
+        let shards = stripe
+            .data
+            .iter()
+            .map(|(name, _)| *name)
+            .chain(stripe.parity.iter().copied())
+            .map(|name| {
+                let client = client.clone();
+                async move {
+                    if name == address {
+                        return None;
+                    }
+                    client
+                        .get_chunk(ChunkAddress::new(name), show_holders, Some(retry_strategy))
+                        .await
+                        .map(|chunk| chunk.value)
+                        .ok()
+                }
+            });
+        let shards = futures::future::join_all(shards).await;
+
+        let content = erasure_coding.reconstruct(stripe, shards, &address)?;
+        info!("Reconstructed chunk {address:?} from its parity stripe");
+        let encrypted_chunk = EncryptedChunk { index, content };
+        Ok((ChunkAddress::new(address), index, encrypted_chunk))
+    }
+
     async fn get_chunk(
         client: Client,
         address: XorName,
@@ -533,3 +907,48 @@ impl FilesDownload {
         Ok((chunk.address, index, encrypted_chunk))
     }
 }
+
+// Returns true if the path is relative and does not lead outside of the directory it is joined to.
+fn is_contained_relative_path(path: &Path) -> bool {
+    path.components().next().is_some()
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+
+    #[test]
+    fn download_filter_should_apply_include_and_exclude_patterns() -> Result<()> {
+        let filter = DownloadFilter::new(
+            &["photos/**/*.jpg".to_string(), "*.txt".to_string()],
+            &["photos/private/*".to_string()],
+        )?;
+
+        assert!(filter.matches(Path::new("photos/2024/beach.jpg")));
+        assert!(filter.matches(Path::new("notes.txt")));
+        assert!(!filter.matches(Path::new("photos/2024/beach.png")));
+        assert!(!filter.matches(Path::new("photos/private/me.jpg")));
+
+        let everything = DownloadFilter::default();
+        assert!(everything.matches(Path::new("any/file")));
+
+        assert!(matches!(
+            DownloadFilter::new(&["[".to_string()], &[]),
+            Err(ClientError::InvalidGlobPattern(..))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn download_paths_should_not_leave_the_download_dir() {
+        assert!(is_contained_relative_path(Path::new("dir/file")));
+        assert!(is_contained_relative_path(Path::new("./file")));
+        assert!(!is_contained_relative_path(Path::new("")));
+        assert!(!is_contained_relative_path(Path::new("/etc/passwd")));
+        assert!(!is_contained_relative_path(Path::new("dir/../../file")));
+    }
+}
@@ -0,0 +1,555 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::FilesApi;
+use crate::{error::Result, Error};
+use bls::{PublicKey, SecretKey, Signature};
+use bytes::Bytes;
+use futures::{future::try_join_all, StreamExt};
+use rand::{thread_rng, Rng};
+use self_encryption::MAX_CHUNK_SIZE;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sn_protocol::{
+    messages::{ChunkProof, Nonce},
+    storage::{try_serialize_record, Chunk, ChunkAddress, RecordKind, RetryStrategy},
+};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufReader, Read},
+    path::Path,
+};
+use tempfile::tempdir;
+
+/// The number of challenges stored for each chunk. Each verification uses one of them at random.
+pub const CHALLENGES_PER_CHUNK: u32 = 8;
+
+/// The hash of the original (unencrypted) content of a file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ContentHash {
+    Sha256([u8; 32]),
+}
+
+impl ContentHash {
+    /// Compute the SHA-256 hash of the file at the provided path.
+    pub fn sha256_from_path(path: &Path) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut hasher = Sha256::new();
+        let mut buffer = [0u8; 64 * 1024];
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(Self::Sha256(hasher.finalize().into()))
+    }
+
+    /// Return the hex representation of the hash.
+    pub fn to_hex(&self) -> String {
+        match self {
+            Self::Sha256(hash) => hex::encode(hash),
+        }
+    }
+}
+
+/// A chunk listed inside a manifest, along with pre-computed `ChunkProof` challenges.
+///
+/// The challenges are computed from the chunk content when the manifest is created, which allows the
+/// holders of the chunk to be checked later on without having to download the data.
+///
+/// Only the proofs are stored. Their nonces are derived from the owner's key, so they are not known
+/// until the owner uses them, and a holder can't answer them from the manifest alone.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestChunk {
+    pub address: ChunkAddress,
+    /// The proof for the nonce of each challenge, in order.
+    pub expected_proofs: Vec<ChunkProof>,
+}
+
+impl ManifestChunk {
+    /// Create the `CHALLENGES_PER_CHUNK` challenges of the provided chunk for the manifest owner.
+    pub fn new(chunk: &Chunk, owner: &SecretKey) -> Result<Self> {
+        let address = *chunk.address();
+        let record_value = try_serialize_record(chunk, RecordKind::Chunk)?;
+        let expected_proofs = (0..CHALLENGES_PER_CHUNK)
+            .map(|index| {
+                ChunkProof::new(
+                    record_value.as_ref(),
+                    challenge_nonce(owner, &address, index),
+                )
+            })
+            .collect();
+        Ok(Self {
+            address,
+            expected_proofs,
+        })
+    }
+
+    /// Pick one of the challenges at random, returning its nonce and the proof expected for it.
+    pub fn random_challenge(&self, owner: &SecretKey) -> Option<(Nonce, ChunkProof)> {
+        if self.expected_proofs.is_empty() {
+            return None;
+        }
+        let index = thread_rng().gen_range(0..self.expected_proofs.len());
+        Some((
+            challenge_nonce(owner, &self.address, index as u32),
+            self.expected_proofs[index].clone(),
+        ))
+    }
+}
+
+/// The nonce of a challenge, derived from the owner's signature over the chunk address and the
+/// challenge index. BLS signatures are deterministic, so the owner can derive it again later.
+fn challenge_nonce(owner: &SecretKey, address: &ChunkAddress, index: u32) -> Nonce {
+    let mut message = address.xorname().0.to_vec();
+    message.extend_from_slice(&index.to_be_bytes());
+    let digest = Sha256::digest(owner.sign(message).to_bytes());
+    let mut nonce = [0u8; 8];
+    nonce.copy_from_slice(&digest[..8]);
+    Nonce::from_be_bytes(nonce)
+}
+
+/// A single file listed inside a manifest.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// The name (or the relative path) of the file
+    pub name: String,
+    /// The address of the head chunk (DataMap) of the file
+    pub head_address: ChunkAddress,
+    /// The size of the original file in bytes
+    pub size: u64,
+    /// The hash of the original file
+    pub hash: ContentHash,
+    /// All the chunks that are required to retrieve the file
+    pub chunks: Vec<ManifestChunk>,
+}
+
+impl ManifestEntry {
+    /// Create a manifest entry for the file at `file_path` from the chunks it was split into, as
+    /// they were written to disk by the chunking process.
+    pub fn from_chunk_paths<'a>(
+        file_path: &Path,
+        name: String,
+        head_address: ChunkAddress,
+        chunk_paths: impl IntoIterator<Item = &'a Path>,
+        owner: &SecretKey,
+    ) -> Result<Self> {
+        let chunks = chunk_paths
+            .into_iter()
+            .map(|chunk_path| {
+                let chunk = Chunk::new(Bytes::from(fs::read(chunk_path)?));
+                ManifestChunk::new(&chunk, owner)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            name,
+            head_address,
+            size: fs::metadata(file_path)?.len(),
+            hash: ContentHash::sha256_from_path(file_path)?,
+            chunks,
+        })
+    }
+}
+
+/// A list of files that have been uploaded together as a dataset.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DatasetManifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl DatasetManifest {
+    /// Create an empty manifest.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file entry to the manifest.
+    pub fn push(&mut self, entry: ManifestEntry) {
+        self.entries.push(entry);
+    }
+
+    /// The total number of chunks listed by the manifest.
+    pub fn chunks_count(&self) -> usize {
+        self.entries.iter().map(|entry| entry.chunks.len()).sum()
+    }
+
+    /// The serialized representation of the manifest that is signed.
+    pub fn bytes(&self) -> Result<Vec<u8>> {
+        Ok(rmp_serde::to_vec(self)?)
+    }
+
+    /// Sign the manifest with the provided key.
+    pub fn sign(self, secret_key: &SecretKey) -> Result<SignedDatasetManifest> {
+        let signature = secret_key.sign(self.bytes()?);
+        Ok(SignedDatasetManifest {
+            manifest: self,
+            owner: secret_key.public_key(),
+            signature,
+        })
+    }
+}
+
+/// A `DatasetManifest` signed by its owner.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedDatasetManifest {
+    pub manifest: DatasetManifest,
+    pub owner: PublicKey,
+    pub signature: Signature,
+}
+
+impl SignedDatasetManifest {
+    /// Verify that the manifest has been signed by its owner.
+    pub fn verify_signature(&self) -> Result<()> {
+        if self.owner.verify(&self.signature, self.manifest.bytes()?) {
+            Ok(())
+        } else {
+            Err(Error::InvalidManifestSignature)
+        }
+    }
+
+    /// Serialize the signed manifest.
+    pub fn to_bytes(&self) -> Result<Bytes> {
+        Ok(Bytes::from(rmp_serde::to_vec(self)?))
+    }
+
+    /// Deserialize a signed manifest and verify its signature.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let manifest: Self = rmp_serde::from_slice(bytes)?;
+        manifest.verify_signature()?;
+        Ok(manifest)
+    }
+
+    /// Pack the signed manifest into chunks so that it can be stored on the network.
+    ///
+    /// Returns the root chunk, at whose address the manifest can be retrieved, along with the
+    /// chunks the manifest is split over if it doesn't fit in the root chunk. All of them have to
+    /// be stored.
+    pub fn to_chunks(&self) -> Result<(Chunk, Vec<Chunk>)> {
+        let bytes = self.to_bytes()?;
+        if bytes.len() <= MAX_CHUNK_SIZE {
+            return Ok((Chunk::new(bytes), vec![]));
+        }
+
+        let parts = (0..bytes.len())
+            .step_by(MAX_CHUNK_SIZE)
+            .map(|start| Chunk::new(bytes.slice(start..bytes.len().min(start + MAX_CHUNK_SIZE))))
+            .collect::<Vec<_>>();
+        let root = ManifestParts {
+            parts: parts.iter().map(|part| *part.address()).collect(),
+        };
+        let root = Chunk::new(Bytes::from(rmp_serde::to_vec(&root)?));
+        if root.serialised_size() > MAX_CHUNK_SIZE {
+            return Err(Error::ManifestTooLarge(bytes.len()));
+        }
+        Ok((root, parts))
+    }
+
+    /// Read a signed manifest from the chunks it was split over, in order, and verify its
+    /// signature.
+    pub fn from_parts(parts: &[Chunk]) -> Result<Self> {
+        let bytes = parts
+            .iter()
+            .flat_map(|part| part.value().iter().copied())
+            .collect::<Vec<_>>();
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// What the root chunk of a stored manifest holds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ManifestRoot {
+    /// The whole manifest, as it fits in a single chunk
+    Manifest(Box<SignedDatasetManifest>),
+    /// The addresses of the chunks the manifest is split over, in order
+    Parts(Vec<ChunkAddress>),
+}
+
+impl ManifestRoot {
+    /// Read the root chunk of a manifest, verifying the signature of the manifest if it's held
+    /// by the chunk itself.
+    pub fn from_chunk(chunk: &Chunk) -> Result<Self> {
+        match rmp_serde::from_slice::<SignedDatasetManifest>(chunk.value()) {
+            Ok(manifest) => {
+                manifest.verify_signature()?;
+                Ok(Self::Manifest(Box::new(manifest)))
+            }
+            Err(err) => match rmp_serde::from_slice::<ManifestParts>(chunk.value()) {
+                Ok(root) => Ok(Self::Parts(root.parts)),
+                Err(_) => Err(err.into()),
+            },
+        }
+    }
+}
+
+/// The content of the root chunk of a manifest that is split over several chunks.
+#[derive(Serialize, Deserialize)]
+struct ManifestParts {
+    parts: Vec<ChunkAddress>,
+}
+
+/// The result of checking all the chunks of a manifest against the network.
+#[derive(Clone, Debug, Default)]
+pub struct ManifestVerification {
+    /// The number of chunks that have been proven to exist
+    pub verified_chunks: usize,
+    /// The chunks that could not be proven to exist, grouped by file name.
+    pub missing_chunks: BTreeMap<String, Vec<ChunkAddress>>,
+}
+
+impl ManifestVerification {
+    /// Returns true if all the chunks of the manifest have been proven to exist.
+    pub fn is_complete(&self) -> bool {
+        self.missing_chunks.is_empty()
+    }
+}
+
+impl FilesApi {
+    /// Fetch the manifest stored at the address of its root chunk, along with the chunks it's
+    /// split over, and verify its signature.
+    pub async fn get_manifest(
+        &self,
+        address: ChunkAddress,
+        retry_strategy: Option<RetryStrategy>,
+    ) -> Result<SignedDatasetManifest> {
+        let root = self
+            .client
+            .get_chunk(address, false, retry_strategy)
+            .await?;
+        match ManifestRoot::from_chunk(&root)? {
+            ManifestRoot::Manifest(manifest) => Ok(*manifest),
+            ManifestRoot::Parts(addresses) => {
+                let parts = try_join_all(
+                    addresses
+                        .into_iter()
+                        .map(|address| self.client.get_chunk(address, false, retry_strategy)),
+                )
+                .await?;
+                SignedDatasetManifest::from_parts(&parts)
+            }
+        }
+    }
+
+    /// Create a manifest entry for the file at the provided path, by chunking it again inside a
+    /// temporary directory. `ManifestEntry::from_chunk_paths` avoids this if the chunks are at hand.
+    ///
+    /// The head chunk is only listed if `include_data_map` is set, i.e. if the file was made public.
+    pub fn create_manifest_entry(
+        file_path: &Path,
+        name: String,
+        include_data_map: bool,
+        owner: &SecretKey,
+    ) -> Result<ManifestEntry> {
+        let temp_dir = tempdir()?;
+        let (head_address, _data_map, _size, chunks_paths) =
+            Self::chunk_file(file_path, temp_dir.path(), include_data_map)?;
+
+        ManifestEntry::from_chunk_paths(
+            file_path,
+            name,
+            head_address,
+            chunks_paths
+                .iter()
+                .map(|(_, chunk_path)| chunk_path.as_path()),
+            owner,
+        )
+    }
+
+    /// Check the presence of every chunk listed by the manifest through their `ChunkProof` challenges.
+    /// None of the chunks are downloaded during this process.
+    ///
+    /// The nonces of the challenges are derived from the owner's key, so the client must be signing
+    /// with it. `batch_size` determines the number of chunks that are checked in parallel.
+    pub async fn verify_manifest(
+        &self,
+        manifest: &SignedDatasetManifest,
+        batch_size: usize,
+    ) -> Result<ManifestVerification> {
+        manifest.verify_signature()?;
+        let owner = self.client.signer();
+        if owner.public_key() != manifest.owner {
+            return Err(Error::NotManifestOwner(manifest.owner));
+        }
+
+        let checks = manifest.manifest.entries.iter().flat_map(|entry| {
+            entry.chunks.iter().map(move |chunk| {
+                (
+                    entry.name.clone(),
+                    chunk.address,
+                    chunk.random_challenge(owner),
+                )
+            })
+        });
+        let mut stream = futures::stream::iter(checks)
+            .map(|(name, address, challenge)| async move {
+                let res = match challenge {
+                    Some((nonce, expected_proof)) => self
+                        .client
+                        .verify_chunk_existence_proof(address, nonce, expected_proof)
+                        .await
+                        .map_err(|err| format!("{err:?}")),
+                    None => Err("the manifest has no challenges for it".to_string()),
+                };
+                (name, address, res)
+            })
+            .buffer_unordered(batch_size);
+
+        let mut verification = ManifestVerification::default();
+        while let Some((name, address, res)) = stream.next().await {
+            match res {
+                Ok(()) => verification.verified_chunks += 1,
+                Err(err) => {
+                    warn!("Could not verify the existence of {address:?} of {name:?}: {err}");
+                    verification
+                        .missing_chunks
+                        .entry(name)
+                        .or_default()
+                        .push(address);
+                }
+            }
+        }
+
+        Ok(verification)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+    use rand::RngCore;
+    use std::io::Write;
+
+    #[test]
+    fn manifest_entry_should_list_all_chunks() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file_path = temp_dir.path().join("file");
+        let mut data = vec![0u8; 5 * 1024 * 1024];
+        thread_rng().fill_bytes(&mut data);
+        File::create(&file_path)?.write_all(&data)?;
+
+        let owner = SecretKey::random();
+        let private =
+            FilesApi::create_manifest_entry(&file_path, "file".to_string(), false, &owner)?;
+        let public = FilesApi::create_manifest_entry(&file_path, "file".to_string(), true, &owner)?;
+
+        assert_eq!(private.size, data.len() as u64);
+        assert_eq!(
+            private.hash,
+            ContentHash::Sha256(Sha256::digest(&data).into())
+        );
+        assert_eq!(private.head_address, public.head_address);
+        // the public entry contains the data map chunk as well
+        assert_eq!(private.chunks.len() + 1, public.chunks.len());
+        assert!(public
+            .chunks
+            .iter()
+            .any(|chunk| chunk.address == public.head_address));
+
+        Ok(())
+    }
+
+    #[test]
+    fn signed_manifest_should_roundtrip_through_a_chunk() -> Result<()> {
+        let chunk = Chunk::new(Bytes::from_static(b"some chunk content"));
+        let mut manifest = DatasetManifest::new();
+        manifest.push(ManifestEntry {
+            name: "dir/file".to_string(),
+            head_address: *chunk.address(),
+            size: 18,
+            hash: ContentHash::Sha256([0; 32]),
+            chunks: vec![ManifestChunk::new(&chunk, &SecretKey::random())?],
+        });
+
+        let signed = manifest.sign(&SecretKey::random())?;
+        let (root, parts) = signed.to_chunks()?;
+        assert!(parts.is_empty());
+        assert_eq!(
+            ManifestRoot::from_chunk(&root)?,
+            ManifestRoot::Manifest(Box::new(signed.clone()))
+        );
+
+        let mut tampered = signed.clone();
+        tampered.manifest.entries[0].size = 19;
+        assert!(matches!(
+            tampered.verify_signature(),
+            Err(Error::InvalidManifestSignature)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn large_signed_manifest_should_be_split_over_chunks_listed_by_the_root() -> Result<()> {
+        let chunk = Chunk::new(Bytes::from_static(b"some chunk content"));
+        let manifest_chunk = ManifestChunk::new(&chunk, &SecretKey::random())?;
+        let mut manifest = DatasetManifest::new();
+        manifest.push(ManifestEntry {
+            name: "large".to_string(),
+            head_address: *chunk.address(),
+            size: 1024 * 1024 * 1024,
+            hash: ContentHash::Sha256([0; 32]),
+            chunks: vec![manifest_chunk; 5000],
+        });
+        let signed = manifest.sign(&SecretKey::random())?;
+        let size = signed.to_bytes()?.len();
+        assert!(size > 2 * MAX_CHUNK_SIZE);
+
+        let (root, parts) = signed.to_chunks()?;
+        assert_eq!(parts.len(), size.div_ceil(MAX_CHUNK_SIZE));
+        assert!(parts
+            .iter()
+            .all(|part| part.serialised_size() <= MAX_CHUNK_SIZE));
+        assert_eq!(
+            ManifestRoot::from_chunk(&root)?,
+            ManifestRoot::Parts(parts.iter().map(|part| *part.address()).collect())
+        );
+        assert_eq!(SignedDatasetManifest::from_parts(&parts)?, signed);
+
+        // the parts are only a manifest once put back together, in order
+        let mut reordered = parts.clone();
+        reordered.swap(0, 1);
+        assert!(SignedDatasetManifest::from_parts(&reordered).is_err());
+        assert!(SignedDatasetManifest::from_parts(&parts[1..]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn chunk_challenges_should_only_be_answerable_with_the_owner_key() -> Result<()> {
+        let chunk = Chunk::new(Bytes::from_static(b"some chunk content"));
+        let record_value = try_serialize_record(&chunk, RecordKind::Chunk)?;
+        let owner = SecretKey::random();
+
+        let manifest_chunk = ManifestChunk::new(&chunk, &owner)?;
+        assert_eq!(
+            manifest_chunk.expected_proofs.len(),
+            CHALLENGES_PER_CHUNK as usize
+        );
+        // every challenge has its own nonce
+        let nonces = (0..CHALLENGES_PER_CHUNK)
+            .map(|index| challenge_nonce(&owner, chunk.address(), index))
+            .collect::<std::collections::BTreeSet<_>>();
+        assert_eq!(nonces.len(), CHALLENGES_PER_CHUNK as usize);
+
+        // the owner derives the same nonces again, anyone else gets different ones
+        let (nonce, expected_proof) = manifest_chunk
+            .random_challenge(&owner)
+            .ok_or_else(|| eyre::eyre!("The chunk has no challenges"))?;
+        assert!(ChunkProof::new(record_value.as_ref(), nonce).verify(&expected_proof));
+        let (nonce, expected_proof) = manifest_chunk
+            .random_challenge(&SecretKey::random())
+            .ok_or_else(|| eyre::eyre!("The chunk has no challenges"))?;
+        assert!(!ChunkProof::new(record_value.as_ref(), nonce).verify(&expected_proof));
+
+        Ok(())
+    }
+}
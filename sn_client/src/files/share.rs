@@ -0,0 +1,227 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::FilesApi;
+use crate::{error::Result, Error};
+use bls::{Ciphertext, PublicKey, SecretKey, Signature};
+use bytes::Bytes;
+use self_encryption::MAX_CHUNK_SIZE;
+use serde::{Deserialize, Serialize};
+use sn_protocol::storage::{Chunk, ChunkAddress};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The details of a privately uploaded file that are handed over through a `ShareToken`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedFile {
+    /// The address of the head chunk (DataMap) of the file
+    pub head_address: ChunkAddress,
+    /// The DataMap chunk of the file, which is never stored on the network for private files.
+    pub data_map_chunk: Chunk,
+    /// The suggested name to save the file as
+    pub file_name: Option<String>,
+    /// A free form description of the share, e.g. who it is meant for
+    pub label: Option<String>,
+    /// The time (in seconds since the UNIX epoch) after which the token is refused.
+    ///
+    /// Note: this cannot revoke the access of a recipient that has already opened the token, as they
+    /// could have kept the DataMap.
+    pub expires_at: Option<u64>,
+}
+
+impl SharedFile {
+    /// Returns true if the expiry time of the share has passed.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= unix_timestamp())
+    }
+}
+
+/// A capability token that grants a single recipient access to a privately uploaded file.
+///
+/// The `SharedFile` is encrypted to the BLS public key of the recipient, and the token is signed by
+/// the sharer. The token can be passed around out of band or stored on the network as a chunk.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShareToken {
+    pub recipient: PublicKey,
+    pub sharer: PublicKey,
+    ciphertext: Ciphertext,
+    signature: Signature,
+}
+
+impl ShareToken {
+    /// Encrypt the `SharedFile` to the recipient and sign the token with the sharer's key.
+    pub fn new(shared_file: &SharedFile, recipient: PublicKey, sharer: &SecretKey) -> Result<Self> {
+        let ciphertext = recipient.encrypt(rmp_serde::to_vec(shared_file)?);
+        let signature = sharer.sign(Self::bytes_to_sign(&recipient, &ciphertext));
+        Ok(Self {
+            recipient,
+            sharer: sharer.public_key(),
+            ciphertext,
+            signature,
+        })
+    }
+
+    /// Verify the token and decrypt the `SharedFile` with the recipient's key.
+    /// Expired tokens are refused.
+    pub fn open(&self, secret_key: &SecretKey) -> Result<SharedFile> {
+        self.verify_signature()?;
+        if secret_key.public_key() != self.recipient {
+            return Err(Error::ShareTokenRecipientMismatch(self.recipient));
+        }
+        let bytes = secret_key
+            .decrypt(&self.ciphertext)
+            .ok_or(Error::ShareTokenDecryption)?;
+        let shared_file: SharedFile = rmp_serde::from_slice(&bytes)?;
+
+        if shared_file.head_address != *shared_file.data_map_chunk.address() {
+            return Err(Error::InvalidShareToken);
+        }
+        if let Some(expires_at) = shared_file.expires_at {
+            if shared_file.is_expired() {
+                return Err(Error::ShareTokenExpired(expires_at));
+            }
+        }
+        Ok(shared_file)
+    }
+
+    /// Verify that the token has been signed by the sharer.
+    pub fn verify_signature(&self) -> Result<()> {
+        if self.sharer.verify(
+            &self.signature,
+            Self::bytes_to_sign(&self.recipient, &self.ciphertext),
+        ) {
+            Ok(())
+        } else {
+            Err(Error::InvalidShareTokenSignature)
+        }
+    }
+
+    /// Serialize the token.
+    pub fn to_bytes(&self) -> Result<Bytes> {
+        Ok(Bytes::from(rmp_serde::to_vec(self)?))
+    }
+
+    /// Deserialize a token and verify its signature.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let token: Self = rmp_serde::from_slice(bytes)?;
+        token.verify_signature()?;
+        Ok(token)
+    }
+
+    /// The hex representation of the token, to be passed out of band.
+    pub fn to_hex(&self) -> Result<String> {
+        Ok(hex::encode(self.to_bytes()?))
+    }
+
+    /// Read a token from its hex representation and verify its signature.
+    pub fn from_hex(hex: &str) -> Result<Self> {
+        let bytes = hex::decode(hex.trim()).map_err(|_| Error::InvalidShareToken)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Pack the token into a `Chunk` so that it can be stored on the network.
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let chunk = Chunk::new(self.to_bytes()?);
+        if chunk.serialised_size() > MAX_CHUNK_SIZE {
+            return Err(Error::ShareTokenTooLarge(chunk.serialised_size()));
+        }
+        Ok(chunk)
+    }
+
+    /// Read a token from a `Chunk` and verify its signature.
+    pub fn from_chunk(chunk: &Chunk) -> Result<Self> {
+        Self::from_bytes(chunk.value())
+    }
+
+    fn bytes_to_sign(recipient: &PublicKey, ciphertext: &Ciphertext) -> Vec<u8> {
+        let mut bytes = recipient.to_bytes().to_vec();
+        bytes.extend(ciphertext.to_bytes());
+        bytes
+    }
+}
+
+impl FilesApi {
+    /// Create a token that shares a privately uploaded file with the owner of the `recipient` key.
+    /// The token is signed with the client's key.
+    pub fn create_share_token(
+        &self,
+        shared_file: &SharedFile,
+        recipient: PublicKey,
+    ) -> Result<ShareToken> {
+        ShareToken::new(shared_file, recipient, self.client.signer())
+    }
+
+    /// Open a token that has been shared with the client's key.
+    pub fn open_share_token(&self, token: &ShareToken) -> Result<SharedFile> {
+        token.open(self.client.signer())
+    }
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use eyre::Result;
+
+    fn shared_file(expires_at: Option<u64>) -> SharedFile {
+        let data_map_chunk = Chunk::new(Bytes::from_static(b"a data map"));
+        SharedFile {
+            head_address: *data_map_chunk.address(),
+            data_map_chunk,
+            file_name: Some("report.pdf".to_string()),
+            label: Some("for bob".to_string()),
+            expires_at,
+        }
+    }
+
+    #[test]
+    fn share_token_should_only_be_opened_by_the_recipient() -> Result<()> {
+        let sharer = SecretKey::random();
+        let recipient = SecretKey::random();
+        let shared_file = shared_file(Some(unix_timestamp() + 3600));
+
+        let token = ShareToken::new(&shared_file, recipient.public_key(), &sharer)?;
+        let token = ShareToken::from_hex(&token.to_hex()?)?;
+        assert_eq!(token.sharer, sharer.public_key());
+        assert_eq!(token.open(&recipient)?, shared_file);
+
+        assert!(matches!(
+            token.open(&SecretKey::random()),
+            Err(Error::ShareTokenRecipientMismatch(_))
+        ));
+
+        let mut forged = token.clone();
+        forged.sharer = SecretKey::random().public_key();
+        assert!(matches!(
+            forged.open(&recipient),
+            Err(Error::InvalidShareTokenSignature)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn expired_share_token_should_be_refused() -> Result<()> {
+        let recipient = SecretKey::random();
+        let token = ShareToken::new(
+            &shared_file(Some(unix_timestamp() - 1)),
+            recipient.public_key(),
+            &SecretKey::random(),
+        )?;
+        assert!(matches!(
+            token.open(&recipient),
+            Err(Error::ShareTokenExpired(_))
+        ));
+        Ok(())
+    }
+}
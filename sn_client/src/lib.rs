@@ -18,6 +18,7 @@ mod event;
 mod faucet;
 mod files;
 mod folders;
+mod progress;
 mod register;
 mod uploader;
 mod wallet;
@@ -37,18 +38,33 @@ pub use sn_transfers as transfers;
 const MAX_CONCURRENT_TASKS: usize = 32;
 
 pub use self::{
-    audit::{DagError, SpendDag, SpendDagGet, SpendFault},
+    audit::{
+        CrawlProgress, CrawlerCfg, DagError, SpendDag, SpendDagCrawler, SpendDagGet, SpendFault,
+        SupplyReport, DEFAULT_CRAWLER_WORKERS,
+    },
+    chunks::{ErasureCoding, ErasureCodingCfg, ParityStripe},
     error::Error,
     event::{ClientEvent, ClientEventsBroadcaster, ClientEventsReceiver},
     faucet::fund_faucet_from_genesis_wallet,
     files::{
-        download::{FilesDownload, FilesDownloadEvent},
+        download::{
+            BatchDownloadItem, BatchDownloadSummary, DownloadFilter, FilesDownload,
+            FilesDownloadEvent, MAX_CONCURRENT_FILE_DOWNLOADS,
+        },
+        manifest::{
+            ContentHash, DatasetManifest, ManifestChunk, ManifestEntry, ManifestRoot,
+            ManifestVerification, SignedDatasetManifest,
+        },
+        share::{ShareToken, SharedFile},
         FilesApi, BATCH_SIZE,
     },
     folders::{FolderEntry, FoldersApi, Metadata},
+    progress::{TransferKind, TransferProgress},
     register::ClientRegister,
     uploader::{UploadCfg, UploadEvent, UploadSummary, Uploader},
-    wallet::{broadcast_signed_spends, send, StoragePaymentResult, WalletClient},
+    wallet::{
+        broadcast_signed_spends, send, StoragePaymentResult, WalletClient, WatchOnlySyncSummary,
+    },
 };
 pub(crate) use error::Result;
 
@@ -0,0 +1,281 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use sn_networking::target_arch::Instant;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+/// The period the throughput is measured over, so it follows changes in the transfer speed.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(10);
+
+/// The direction of the data transfer that a `TransferProgress` refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferKind {
+    Upload,
+    Download,
+}
+
+/// A snapshot of the progress of an upload or a download.
+///
+/// The byte counts of a download are based on the original (unencrypted) size of each chunk.
+/// For a batch download, the items are files and `bytes_total` grows as the size of each file becomes known,
+/// i.e., once its download has started.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransferProgress {
+    pub kind: TransferKind,
+    pub bytes_transferred: u64,
+    pub bytes_total: u64,
+    pub items_transferred: usize,
+    pub items_total: usize,
+    pub elapsed_ms: u64,
+    /// The throughput over the last 10 seconds, in bytes per second.
+    pub throughput_bytes_per_sec: f64,
+    /// The estimated time left, based on the current throughput. Not available if `bytes_total` is unknown.
+    pub eta_ms: Option<u64>,
+    /// The number of failed attempts that have been retried.
+    pub retries: usize,
+    /// The average latency of the requests made to each peer, keyed by the PeerId.
+    /// Only available when the peer handling the request is known, i.e., during uploads.
+    pub peer_latencies_ms: BTreeMap<String, u64>,
+}
+
+/// Keeps track of the progress of a transfer and emits `TransferProgress` snapshots.
+pub(crate) struct ProgressTracker {
+    kind: TransferKind,
+    started_at: Instant,
+    bytes_total: u64,
+    bytes_transferred: u64,
+    items_total: usize,
+    items_transferred: usize,
+    retries: usize,
+    // total latency and the number of requests per peer
+    peer_latencies: BTreeMap<PeerId, (Duration, u32)>,
+    // the bytes transferred so far at each recent transfer, oldest first
+    samples: VecDeque<(Instant, u64)>,
+    sender: Option<mpsc::Sender<TransferProgress>>,
+}
+
+impl ProgressTracker {
+    pub(crate) fn new(kind: TransferKind) -> Self {
+        let started_at = Instant::now();
+        Self {
+            kind,
+            started_at,
+            bytes_total: 0,
+            bytes_transferred: 0,
+            items_total: 0,
+            items_transferred: 0,
+            retries: 0,
+            peer_latencies: Default::default(),
+            samples: VecDeque::from([(started_at, 0)]),
+            sender: None,
+        }
+    }
+
+    /// Returns a receiver for the progress snapshots. Replaces any previous receiver.
+    pub(crate) fn get_receiver(&mut self) -> mpsc::Receiver<TransferProgress> {
+        let (tx, rx) = mpsc::channel(100);
+        self.sender = Some(tx);
+        rx
+    }
+
+    /// Reset the counters and the clock. The sender is kept as it is.
+    pub(crate) fn reset(&mut self) {
+        let sender = self.sender.take();
+        *self = Self::new(self.kind);
+        self.sender = sender;
+    }
+
+    /// Drop the sender to close the channel.
+    pub(crate) fn close(&mut self) {
+        self.sender = None;
+    }
+
+    pub(crate) fn add_to_total(&mut self, items: usize, bytes: u64) {
+        self.items_total += items;
+        self.bytes_total += bytes;
+    }
+
+    pub(crate) fn record_transferred(
+        &mut self,
+        bytes: u64,
+        peer_latency: Option<(PeerId, Duration)>,
+    ) {
+        self.items_transferred += 1;
+        self.add_transferred_bytes(bytes);
+        if let Some((peer, latency)) = peer_latency {
+            let entry = self
+                .peer_latencies
+                .entry(peer)
+                .or_insert((Duration::ZERO, 0));
+            entry.0 += latency;
+            entry.1 += 1;
+        }
+        self.emit();
+    }
+
+    /// Add the progress made by a file of a batch download, without counting it as a transferred item.
+    pub(crate) fn record_file_progress(&mut self, bytes_total: u64, bytes: u64, retries: usize) {
+        self.bytes_total += bytes_total;
+        self.retries += retries;
+        if bytes > 0 {
+            self.add_transferred_bytes(bytes);
+        }
+        self.emit();
+    }
+
+    fn add_transferred_bytes(&mut self, bytes: u64) {
+        self.bytes_transferred += bytes;
+        let now = Instant::now();
+        self.samples.push_back((now, self.bytes_transferred));
+        // Keep one sample from before the window, to measure the throughput from.
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= THROUGHPUT_WINDOW {
+            let _ = self.samples.pop_front();
+        }
+    }
+
+    pub(crate) fn record_retry(&mut self) {
+        self.retries += 1;
+        self.emit();
+    }
+
+    pub(crate) fn snapshot(&self) -> TransferProgress {
+        let elapsed = self.started_at.elapsed();
+        let throughput_bytes_per_sec = self.throughput(Instant::now());
+        // the total is not known upfront for batch downloads, only the number of files is.
+        let eta_ms = if throughput_bytes_per_sec > 0.0 && self.bytes_total > 0 {
+            let bytes_left = self.bytes_total.saturating_sub(self.bytes_transferred);
+            Some((bytes_left as f64 / throughput_bytes_per_sec * 1000.0) as u64)
+        } else {
+            None
+        };
+
+        TransferProgress {
+            kind: self.kind,
+            bytes_transferred: self.bytes_transferred,
+            bytes_total: self.bytes_total,
+            items_transferred: self.items_transferred,
+            items_total: self.items_total,
+            elapsed_ms: elapsed.as_millis() as u64,
+            throughput_bytes_per_sec,
+            eta_ms,
+            retries: self.retries,
+            peer_latencies_ms: self
+                .peer_latencies
+                .iter()
+                .map(|(peer, (total, count))| {
+                    (
+                        peer.to_string(),
+                        (total.as_millis() / u128::from((*count).max(1))) as u64,
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// The bytes per second transferred since the most recent sample that is at least
+    /// `THROUGHPUT_WINDOW` old, or since the start if there is none.
+    fn throughput(&self, now: Instant) -> f64 {
+        let (since, bytes_then) = self
+            .samples
+            .iter()
+            .rev()
+            .find(|(time, _)| now.duration_since(*time) >= THROUGHPUT_WINDOW)
+            .or(self.samples.front())
+            .copied()
+            .unwrap_or((self.started_at, 0));
+        let secs = now.duration_since(since).as_secs_f64();
+        if secs > 0.0 {
+            self.bytes_transferred.saturating_sub(bytes_then) as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// Send the current snapshot if a receiver has been requested.
+    /// The snapshot is dropped if the receiver is lagging behind, the next one will contain the up to date values.
+    pub(crate) fn emit(&self) {
+        if let Some(sender) = &self.sender {
+            if let Err(err) = sender.try_send(self.snapshot()) {
+                trace!("Dropped a progress snapshot: {err:?}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_should_aggregate_the_peer_latencies() {
+        let mut tracker = ProgressTracker::new(TransferKind::Upload);
+        let mut receiver = tracker.get_receiver();
+        let peer = PeerId::random();
+        tracker.add_to_total(3, 300);
+
+        tracker.record_transferred(100, Some((peer, Duration::from_millis(10))));
+        tracker.record_retry();
+        tracker.record_transferred(100, Some((peer, Duration::from_millis(30))));
+
+        let mut last = None;
+        while let Ok(progress) = receiver.try_recv() {
+            last = Some(progress);
+        }
+        let last = last.expect("progress to be emitted");
+        assert_eq!(last.kind, TransferKind::Upload);
+        assert_eq!(last.bytes_transferred, 200);
+        assert_eq!(last.bytes_total, 300);
+        assert_eq!(last.items_transferred, 2);
+        assert_eq!(last.items_total, 3);
+        assert_eq!(last.retries, 1);
+        assert_eq!(last.peer_latencies_ms.get(&peer.to_string()), Some(&20));
+    }
+
+    #[test]
+    fn file_progress_should_count_the_bytes_but_not_the_items() {
+        let mut tracker = ProgressTracker::new(TransferKind::Download);
+        tracker.add_to_total(2, 0);
+
+        tracker.record_file_progress(300, 0, 0);
+        tracker.record_file_progress(200, 100, 1);
+        tracker.record_file_progress(0, 150, 0);
+        tracker.record_transferred(0, None);
+
+        let progress = tracker.snapshot();
+        assert_eq!(progress.bytes_total, 500);
+        assert_eq!(progress.bytes_transferred, 250);
+        assert_eq!(progress.items_total, 2);
+        assert_eq!(progress.items_transferred, 1);
+        assert_eq!(progress.retries, 1);
+    }
+
+    #[test]
+    fn throughput_should_only_cover_the_recent_transfers() {
+        let mut tracker = ProgressTracker::new(TransferKind::Download);
+        let now = Instant::now();
+        tracker.started_at = now - Duration::from_secs(60);
+        tracker.samples = VecDeque::from([
+            (now - Duration::from_secs(60), 0),
+            (now - Duration::from_secs(20), 100_000),
+            (now - Duration::from_secs(12), 400_000),
+            (now - Duration::from_secs(5), 900_000),
+        ]);
+        tracker.bytes_transferred = 1_000_000;
+
+        // Measured from the sample 12 seconds ago, rather than averaged over the 60 seconds.
+        let throughput = tracker.throughput(now);
+        assert!((throughput - 50_000.0).abs() < 1.0, "{throughput}");
+    }
+}
@@ -11,8 +11,12 @@ mod tests;
 mod upload;
 
 use self::upload::{start_upload, InnerUploader, MAX_REPAYMENTS_PER_FAILED_ITEM};
-use crate::{Client, ClientRegister, Error, Result, BATCH_SIZE};
+use crate::{
+    chunks::ErasureCodingCfg, Client, ClientRegister, Error, Result, SignedDatasetManifest,
+    TransferProgress, BATCH_SIZE,
+};
 use itertools::Either;
+use libp2p::PeerId;
 use sn_networking::PayeeQuote;
 use sn_protocol::{
     storage::{Chunk, ChunkAddress, RetryStrategy},
@@ -24,6 +28,7 @@ use std::{
     collections::{BTreeMap, BTreeSet},
     fmt::Debug,
     path::PathBuf,
+    time::Duration,
 };
 use tokio::sync::mpsc;
 use xor_name::XorName;
@@ -37,6 +42,9 @@ pub struct UploadCfg {
     pub retry_strategy: RetryStrategy,
     pub max_repayments_for_failed_data: usize, // we want people to specify an explicit limit here.
     pub collect_registers: bool,
+    /// Generate Reed-Solomon parity chunks for each file while chunking it.
+    /// This is applied when the files are chunked, the `Uploader` treats the parity chunks as ordinary chunks.
+    pub erasure_coding: Option<ErasureCodingCfg>,
 }
 
 impl Default for UploadCfg {
@@ -48,6 +56,7 @@ impl Default for UploadCfg {
             retry_strategy: RetryStrategy::Balanced,
             max_repayments_for_failed_data: MAX_REPAYMENTS_PER_FAILED_ITEM,
             collect_registers: false,
+            erasure_coding: None,
         }
     }
 }
@@ -241,6 +250,16 @@ impl Uploader {
             .get_event_receiver()
     }
 
+    /// Returns a receiver for the byte-level `TransferProgress` of the upload.
+    /// The progress snapshots are dropped if the receiver falls behind.
+    pub fn get_progress_receiver(&mut self) -> mpsc::Receiver<TransferProgress> {
+        self.inner
+            .as_mut()
+            .expect("Uploader::new makes sure inner is present")
+            .progress
+            .get_receiver()
+    }
+
     /// Insert a list of chunk paths to upload to upload.
     pub fn insert_chunk_paths(&mut self, chunks: impl IntoIterator<Item = (XorName, PathBuf)>) {
         self.inner
@@ -257,6 +276,17 @@ impl Uploader {
             .insert_chunks(chunks);
     }
 
+    /// Insert a signed `DatasetManifest` to be uploaded as a root chunk, along with the chunks it
+    /// is split over if it's too large for one.
+    ///
+    /// Returns the address at which the manifest can be retrieved once the upload has completed.
+    pub fn insert_manifest(&mut self, manifest: &SignedDatasetManifest) -> Result<ChunkAddress> {
+        let (root, parts) = manifest.to_chunks()?;
+        let address = *root.address();
+        self.insert_chunks(std::iter::once(root).chain(parts));
+        Ok(address)
+    }
+
     /// Insert a list of registers to upload.
     pub fn insert_register(&mut self, registers: impl IntoIterator<Item = ClientRegister>) {
         self.inner
@@ -409,6 +439,20 @@ impl UploadItem {
         }
     }
 
+    /// The number of bytes that are sent to the network for this item.
+    /// Registers are not accounted for, as their size is negligible compared to chunks.
+    fn size(&self) -> u64 {
+        match self {
+            Self::Chunk { chunk, .. } => match chunk {
+                Either::Left(chunk) => chunk.value().len() as u64,
+                Either::Right(path) => std::fs::metadata(path)
+                    .map(|metadata| metadata.len())
+                    .unwrap_or_default(),
+            },
+            Self::Register { .. } => 0,
+        }
+    }
+
     fn xorname(&self) -> XorName {
         match self {
             UploadItem::Chunk { address, .. } => *address.xorname(),
@@ -446,7 +490,11 @@ enum TaskResult {
         failed_xornames: Vec<(XorName, Box<PayeeQuote>)>,
         insufficient_balance: Option<(NanoTokens, NanoTokens)>,
     },
-    UploadOk(XorName),
+    UploadOk {
+        xorname: XorName,
+        /// The payee that stored the item and the time taken by the upload.
+        peer_latency: Option<(PeerId, Duration)>,
+    },
     UploadErr {
         xorname: XorName,
     },
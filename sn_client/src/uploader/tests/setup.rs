@@ -339,7 +339,10 @@ impl UploaderInterface for TestUploader {
             TestSteps::UploadItemOk => {
                 handle.spawn(async move {
                     task_result_sender
-                        .send(TaskResult::UploadOk(xorname))
+                        .send(TaskResult::UploadOk {
+                            xorname,
+                            peer_latency: None,
+                        })
                         .await
                         .expect("Failed to send task result");
                 });
@@ -12,12 +12,14 @@ use super::{
 };
 use crate::{
     acc_packet::load_account_wallet_or_create_with_mnemonic,
+    progress::{ProgressTracker, TransferKind},
     transfers::{TransferError, WalletError},
     Client, ClientRegister, Error as ClientError, Result, Uploader, WalletClient,
 };
 use bytes::Bytes;
 use itertools::Either;
 use libp2p::PeerId;
+use sn_networking::target_arch::Instant;
 use sn_networking::PayeeQuote;
 use sn_protocol::{
     messages::RegisterCmd,
@@ -108,6 +110,16 @@ pub(super) async fn start_upload(
         })
         .collect();
 
+    uploader.progress.reset();
+    let totals = uploader
+        .all_upload_items
+        .values()
+        .map(|item| item.size())
+        .collect::<Vec<_>>();
+    uploader
+        .progress
+        .add_to_total(totals.len(), totals.iter().sum());
+
     loop {
         // Break if we have uploaded all the items.
         // The loop also breaks if we fail to get_store_cost / make payment / upload for n consecutive times.
@@ -129,6 +141,8 @@ pub(super) async fn start_upload(
                 skipped_count: uploader.skipped_count,
                 uploaded_registers: uploader.uploaded_registers,
             };
+            uploader.progress.emit();
+            uploader.progress.close();
             return Ok(summary);
         }
 
@@ -327,6 +341,9 @@ pub(super) async fn start_upload(
                     let _ = uploader.uploaded_addresses.insert(removed_item.address());
                     trace!("{xorname:?} has store cost of 0 and it already exists on the network");
                     uploader.skipped_count += 1;
+                    uploader
+                        .progress
+                        .record_transferred(removed_item.size(), None);
 
                     // if during the first try we skip the item, then it is already present in the network.
                     match removed_item {
@@ -359,6 +376,8 @@ pub(super) async fn start_upload(
                     .push((xorname, get_store_cost_strategy.clone()));
                 trace!("GetStoreCostErr for {xorname:?} , get_store_cost_strategy: {get_store_cost_strategy:?}, max_repayments_reached: {max_repayments_reached:?}");
 
+                uploader.progress.record_retry();
+
                 // should we do something more here?
                 if max_repayments_reached {
                     error!("Max repayments reached for {xorname:?}");
@@ -422,6 +441,7 @@ pub(super) async fn start_upload(
                     uploader.pending_to_pay.push((xorname, quote));
                 }
                 uploader.make_payments_errors += 1;
+                uploader.progress.record_retry();
 
                 if uploader.make_payments_errors >= MAX_SEQUENTIAL_PAYMENT_FAILS {
                     error!("Max sequential upload failures reached during MakePaymentsErr.");
@@ -431,7 +451,10 @@ pub(super) async fn start_upload(
                     return Err(ClientError::SequentialUploadPaymentError);
                 }
             }
-            TaskResult::UploadOk(xorname) => {
+            TaskResult::UploadOk {
+                xorname,
+                peer_latency,
+            } => {
                 let _ = uploader.on_going_uploads.remove(&xorname);
                 uploader.uploaded_count += 1;
                 trace!("UploadOk for {xorname:?}");
@@ -441,6 +464,9 @@ pub(super) async fn start_upload(
                     .remove(&xorname)
                     .ok_or(ClientError::UploadableItemNotFound(xorname))?;
                 let _ = uploader.uploaded_addresses.insert(removed_item.address());
+                uploader
+                    .progress
+                    .record_transferred(removed_item.size(), peer_latency);
 
                 match removed_item {
                     UploadItem::Chunk { address, .. } => {
@@ -459,6 +485,7 @@ pub(super) async fn start_upload(
             TaskResult::UploadErr { xorname } => {
                 let _ = uploader.on_going_uploads.remove(&xorname);
                 trace!("UploadErr for {xorname:?}");
+                uploader.progress.record_retry();
 
                 // keep track of the failure
                 let n_errors = uploader.n_errors_during_uploads.entry(xorname).or_insert(0);
@@ -611,6 +638,7 @@ impl UploaderInterface for Uploader {
 
         let _handle = tokio::spawn(async move {
             let xorname = upload_item.xorname();
+            let started_at = Instant::now();
             let result = InnerUploader::upload_item(
                 client,
                 wallet_api,
@@ -622,8 +650,13 @@ impl UploaderInterface for Uploader {
 
             trace!("Upload item {xorname:?} uploaded with result {result:?}");
             match result {
-                Ok(_) => {
-                    let _ = task_result_sender.send(TaskResult::UploadOk(xorname)).await;
+                Ok(payee) => {
+                    let _ = task_result_sender
+                        .send(TaskResult::UploadOk {
+                            xorname,
+                            peer_latency: Some((payee, started_at.elapsed())),
+                        })
+                        .await;
                 }
                 Err(_) => {
                     let _ = task_result_sender
@@ -686,6 +719,8 @@ pub(super) struct InnerUploader {
     pub(super) logged_event_sender_absence: bool,
     #[debug(skip)]
     pub(super) event_sender: Option<mpsc::Sender<UploadEvent>>,
+    #[debug(skip)]
+    pub(super) progress: ProgressTracker,
 }
 
 impl InnerUploader {
@@ -725,6 +760,7 @@ impl InnerUploader {
             testing_task_channels: None,
             logged_event_sender_absence: Default::default(),
             event_sender: Default::default(),
+            progress: ProgressTracker::new(TransferKind::Upload),
         }
     }
 
@@ -945,7 +981,7 @@ impl InnerUploader {
         upload_item: UploadItem,
         verify_store: bool,
         retry_strategy: RetryStrategy,
-    ) -> Result<()> {
+    ) -> Result<PeerId> {
         let xorname = upload_item.xorname();
 
         let payment_details = wallet_api.get_recent_payment(&xorname)?;
@@ -991,7 +1027,7 @@ impl InnerUploader {
         // remove the payment if the upload is successful.
         wallet_api.remove_payment_transaction(&xorname);
 
-        Ok(())
+        Ok(payee)
     }
 
     // ====== Misc ======
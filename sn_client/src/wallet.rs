@@ -10,15 +10,15 @@ use crate::Error;
 
 use super::{error::Result, Client};
 use backoff::{backoff::Backoff, ExponentialBackoff};
-use futures::{future::join_all, TryFutureExt};
+use futures::{future::join_all, stream, StreamExt, TryFutureExt};
 use libp2p::PeerId;
 use sn_networking::target_arch::Instant;
-use sn_networking::{GetRecordError, PayeeQuote};
+use sn_networking::{GetRecordError, NetworkError, PayeeQuote};
 use sn_protocol::NetworkAddress;
 use sn_transfers::{
-    CashNote, CashNoteOutputDetails, HotWallet, MainPubkey, NanoTokens, Payment, PaymentQuote,
-    SignedSpend, SpendAddress, Transaction, Transfer, UniquePubkey, WalletError, WalletResult,
-    CASHNOTE_PURPOSE_OF_TRANSFER,
+    CashNote, CashNoteOutputDetails, Hash, HotWallet, MainPubkey, NanoTokens, Payment,
+    PaymentQuote, SignedSpend, SpendAddress, Transaction, Transfer, UniquePubkey, WalletError,
+    WalletResult, WatchOnlyWallet, CASHNOTE_PURPOSE_OF_TRANSFER,
 };
 use std::{
     collections::{BTreeMap, BTreeSet},
@@ -45,6 +45,17 @@ pub struct StoragePaymentResult {
     pub skipped_chunks: Vec<XorName>,
 }
 
+/// The changes to a watch-only wallet found on the Network by `Client::sync_watch_only_wallet`
+#[derive(Debug, Default)]
+pub struct WatchOnlySyncSummary {
+    /// The held CashNotes that were spent on the Network
+    pub spent: BTreeSet<UniquePubkey>,
+    /// The unspent CashNotes found for the wallet by following the known spends
+    pub found: Vec<CashNote>,
+    /// The CashNotes whose spend could not be fetched, they are kept as unspent
+    pub unchecked: BTreeSet<UniquePubkey>,
+}
+
 impl WalletClient {
     /// Create a new wallet client.
     ///
@@ -849,6 +860,120 @@ impl Client {
         Ok(())
     }
 
+    /// Sync a watch-only wallet with the Network, then store the updated wallet to disk.
+    ///
+    /// Each held CashNote is checked on the Network and removed from the wallet if it was spent.
+    /// The outputs of the transactions that spent or created the held CashNotes are followed to find
+    /// the CashNotes of the wallet that it doesn't know of yet. Only the outputs with a recoverable derivation
+    /// index can be found: the network royalties and the change of the transactions built by the wallet.
+    pub async fn sync_watch_only_wallet(
+        &self,
+        wallet: &mut WatchOnlyWallet,
+    ) -> WalletResult<WatchOnlySyncSummary> {
+        wallet.reload_from_disk_or_recreate()?;
+        let known_indexes = wallet.known_derivation_indexes()?;
+        let held_keys: BTreeSet<UniquePubkey> =
+            wallet.available_cash_notes().keys().copied().collect();
+
+        // the transactions to follow with their known spends, starting with the ones that created our CashNotes
+        let mut txs_to_follow: BTreeMap<Hash, BTreeSet<SignedSpend>> = BTreeMap::new();
+        for cash_note in wallet.held_cash_notes() {
+            txs_to_follow
+                .entry(cash_note.parent_tx.hash())
+                .or_default()
+                .extend(cash_note.parent_spends);
+        }
+        let mut followed_txs = BTreeSet::new();
+        let mut known_keys = held_keys.clone();
+        let mut keys_to_check = held_keys;
+        let mut summary = WatchOnlySyncSummary::default();
+
+        while !keys_to_check.is_empty() || !txs_to_follow.is_empty() {
+            // check which of our CashNotes were spent
+            let mut stream = stream::iter(std::mem::take(&mut keys_to_check))
+                .map(|key| async move {
+                    let addr = SpendAddress::from_unique_pubkey(&key);
+                    (key, self.get_spend_from_network(addr).await)
+                })
+                .buffer_unordered(crate::MAX_CONCURRENT_TASKS);
+            while let Some((key, result)) = stream.next().await {
+                let spends = match result {
+                    Ok(spend) => vec![spend],
+                    Err(Error::Network(NetworkError::GetRecordError(
+                        GetRecordError::RecordNotFound,
+                    ))) => {
+                        trace!("CashNote {key:?} is unspent");
+                        continue;
+                    }
+                    Err(Error::Network(NetworkError::DoubleSpendAttempt(s1, s2))) => {
+                        warn!("CashNote {key:?} was double spent");
+                        vec![*s1, *s2]
+                    }
+                    Err(err) => {
+                        warn!("Failed to check if CashNote {key:?} was spent: {err}");
+                        summary.unchecked.insert(key);
+                        continue;
+                    }
+                };
+                summary.spent.insert(key);
+                for spend in spends {
+                    let tx_hash = spend.spend.spent_tx.hash();
+                    if !followed_txs.contains(&tx_hash) {
+                        txs_to_follow.entry(tx_hash).or_default().insert(spend);
+                    }
+                }
+            }
+
+            // follow the transactions to find our new CashNotes
+            for (tx_hash, mut spends) in std::mem::take(&mut txs_to_follow) {
+                followed_txs.insert(tx_hash);
+                let Some(tx) = spends.first().map(|s| s.spend.spent_tx.clone()) else {
+                    continue;
+                };
+                let missing_inputs: Vec<_> = tx
+                    .inputs
+                    .iter()
+                    .filter(|input| {
+                        !spends
+                            .iter()
+                            .any(|s| s.unique_pubkey() == &input.unique_pubkey)
+                    })
+                    .map(|input| SpendAddress::from_unique_pubkey(&input.unique_pubkey))
+                    .collect();
+                let tasks = missing_inputs
+                    .iter()
+                    .map(|addr| self.get_spend_from_network(*addr));
+                match join_all(tasks)
+                    .await
+                    .into_iter()
+                    .collect::<Result<Vec<_>>>()
+                {
+                    Ok(fetched) => spends.extend(fetched),
+                    Err(err) => {
+                        warn!(
+                            "Failed to get the spends of Tx {tx_hash:?}, not following it: {err}"
+                        );
+                        continue;
+                    }
+                }
+
+                for cash_note in wallet.recover_cash_notes(&spends, &known_indexes) {
+                    if known_keys.insert(cash_note.unique_pubkey()) {
+                        debug!("Found CashNote {:?}", cash_note.unique_pubkey());
+                        keys_to_check.insert(cash_note.unique_pubkey());
+                        summary.found.push(cash_note);
+                    }
+                }
+            }
+        }
+
+        summary
+            .found
+            .retain(|cash_note| !summary.spent.contains(&cash_note.unique_pubkey()));
+        wallet.apply_sync(&summary.spent, &summary.found)?;
+        Ok(summary)
+    }
+
     /// Receive a Transfer, verify and redeem CashNotes from the Network.
     ///
     /// # Arguments
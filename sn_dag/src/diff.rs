@@ -0,0 +1,85 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use sn_client::transfers::SignedSpend;
+use sn_client::SpendDag;
+use std::collections::BTreeSet;
+
+/// The spends found in only one of two DAGs
+pub struct DagDiff<'a> {
+    pub added: Vec<&'a SignedSpend>,
+    pub removed: Vec<&'a SignedSpend>,
+}
+
+/// Compare the spends of two DAGs.
+/// A spend that was replaced by a double spend shows up as added, the original one being in both DAGs.
+pub fn diff<'a>(old: &'a SpendDag, new: &'a SpendDag) -> DagDiff<'a> {
+    let old_spends: BTreeSet<&SignedSpend> = old.all_spends().into_iter().collect();
+    let new_spends: BTreeSet<&SignedSpend> = new.all_spends().into_iter().collect();
+    DagDiff {
+        added: new_spends.difference(&old_spends).copied().collect(),
+        removed: old_spends.difference(&new_spends).copied().collect(),
+    }
+}
+
+pub fn print_diff(old: &SpendDag, new: &SpendDag) {
+    if old.source() != new.source() {
+        println!(
+            "Warning: the DAGs have different sources: {:?} and {:?}",
+            old.source(),
+            new.source()
+        );
+    }
+
+    let DagDiff { added, removed } = diff(old, new);
+    for spend in added.iter() {
+        println!("+ {} {}", spend.address().to_hex(), spend.spend.amount);
+    }
+    for spend in removed.iter() {
+        println!("- {} {}", spend.address().to_hex(), spend.spend.amount);
+    }
+    println!(
+        "{} spends added, {} spends removed",
+        added.len(),
+        removed.len()
+    );
+
+    let old_utxos = old.get_utxos();
+    let new_utxos = new.get_utxos();
+    println!(
+        "{} UTXOs added, {} UTXOs removed",
+        new_utxos.difference(&old_utxos).count(),
+        old_utxos.difference(&new_utxos).count()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{dag_of, genesis_and_child};
+    use color_eyre::eyre::Result;
+
+    #[test]
+    fn diff_should_list_the_spends_found_in_only_one_dag() -> Result<()> {
+        let (genesis, child) = genesis_and_child("payment")?;
+        let old = dag_of(&[&genesis])?;
+        let new = dag_of(&[&genesis, &child])?;
+
+        let DagDiff { added, removed } = diff(&old, &new);
+        assert_eq!(added, vec![&child]);
+        assert!(removed.is_empty());
+
+        let DagDiff { added, removed } = diff(&new, &old);
+        assert!(added.is_empty());
+        assert_eq!(removed, vec![&child]);
+
+        let DagDiff { added, removed } = diff(&new, &new);
+        assert!(added.is_empty() && removed.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use clap::ValueEnum;
+use color_eyre::eyre::Result;
+use serde::Serialize;
+use sn_client::transfers::{SignedSpend, SpendAddress};
+use sn_client::SpendDag;
+use std::{collections::BTreeSet, io::Write};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ExportFormat {
+    /// Graphviz DOT
+    Dot,
+    /// One JSON object per line for each spend and UTXO
+    Jsonl,
+    /// The edges of the DAG: `from,to,amount,purpose`
+    Csv,
+}
+
+/// A line of the JSON lines export
+#[derive(Serialize)]
+struct JsonlEntry {
+    address: String,
+    /// `spend`, `double_spend` or `utxo`
+    status: &'static str,
+    /// In nanos
+    amount: Option<u64>,
+    purpose: Option<String>,
+    /// The addresses of the spends of the parent transaction
+    parents: Vec<String>,
+    outputs: Vec<JsonlOutput>,
+    faults: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct JsonlOutput {
+    address: String,
+    /// In nanos
+    amount: u64,
+    purpose: String,
+}
+
+pub fn export(dag: &SpendDag, format: ExportFormat, writer: &mut dyn Write) -> Result<()> {
+    match format {
+        ExportFormat::Dot => writeln!(writer, "{}", dag.dump_dot_format())?,
+        ExportFormat::Jsonl => export_jsonl(dag, writer)?,
+        ExportFormat::Csv => export_csv(dag, writer)?,
+    }
+    Ok(())
+}
+
+fn export_jsonl(dag: &SpendDag, writer: &mut dyn Write) -> Result<()> {
+    let spends = dag.all_spends();
+    let mut double_spent = BTreeSet::new();
+    let mut seen = BTreeSet::new();
+    for spend in spends.iter() {
+        if !seen.insert(spend.address()) {
+            let _ = double_spent.insert(spend.address());
+        }
+    }
+
+    for spend in spends {
+        let address = spend.address();
+        let status = if double_spent.contains(&address) {
+            "double_spend"
+        } else {
+            "spend"
+        };
+        let entry = JsonlEntry {
+            address: address.to_hex(),
+            status,
+            amount: Some(spend.spend.amount.as_nano()),
+            purpose: dag
+                .get_creation_reason(&address)
+                .map(|(purpose, _)| purpose),
+            parents: spend
+                .spend
+                .parent_tx
+                .inputs
+                .iter()
+                .map(|i| SpendAddress::from_unique_pubkey(&i.unique_pubkey).to_hex())
+                .collect(),
+            outputs: outputs(spend)
+                .map(|(address, amount, purpose)| JsonlOutput {
+                    address: address.to_hex(),
+                    amount,
+                    purpose: purpose.to_string(),
+                })
+                .collect(),
+            faults: faults(dag, &address),
+        };
+        serde_json::to_writer(&mut *writer, &entry)?;
+        writeln!(writer)?;
+    }
+
+    for utxo in dag.get_utxos() {
+        let creation_reason = dag.get_creation_reason(&utxo);
+        let entry = JsonlEntry {
+            address: utxo.to_hex(),
+            status: "utxo",
+            amount: creation_reason.as_ref().map(|(_, amount)| amount.as_nano()),
+            purpose: creation_reason.map(|(purpose, _)| purpose),
+            parents: vec![],
+            outputs: vec![],
+            faults: faults(dag, &utxo),
+        };
+        serde_json::to_writer(&mut *writer, &entry)?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+fn export_csv(dag: &SpendDag, writer: &mut dyn Write) -> Result<()> {
+    writeln!(writer, "from,to,amount,purpose")?;
+    for spend in dag.all_spends() {
+        let from = spend.address().to_hex();
+        for (to, amount, purpose) in outputs(spend) {
+            writeln!(
+                writer,
+                "{from},{},{amount},{}",
+                to.to_hex(),
+                escape_csv(purpose)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// The address, amount in nanos and purpose of the outputs of a spend
+fn outputs(spend: &SignedSpend) -> impl Iterator<Item = (SpendAddress, u64, &str)> {
+    spend.spend.spent_tx.outputs.iter().map(|o| {
+        (
+            SpendAddress::from_unique_pubkey(&o.unique_pubkey),
+            o.amount.as_nano(),
+            o.purpose.as_str(),
+        )
+    })
+}
+
+fn faults(dag: &SpendDag, address: &SpendAddress) -> Vec<String> {
+    dag.get_spend_faults(address)
+        .iter()
+        .map(|f| f.to_string())
+        .collect()
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{dag_of, genesis_and_child};
+
+    fn exported(dag: &SpendDag, format: ExportFormat) -> Result<String> {
+        let mut output = Vec::new();
+        export(dag, format, &mut output)?;
+        Ok(String::from_utf8(output)?)
+    }
+
+    #[test]
+    fn jsonl_export_should_have_a_line_per_spend_and_utxo() -> Result<()> {
+        let (genesis, child) = genesis_and_child("payment")?;
+        let dag = dag_of(&[&genesis, &child])?;
+
+        let lines = exported(&dag, ExportFormat::Jsonl)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<std::result::Result<Vec<serde_json::Value>, _>>()?;
+        assert_eq!(lines.len(), 2 + dag.get_utxos().len());
+
+        let child_line = lines
+            .iter()
+            .find(|line| line["address"] == child.address().to_hex())
+            .expect("the child spend to be exported");
+        assert_eq!(child_line["status"], "spend");
+        assert_eq!(child_line["amount"], child.spend.amount.as_nano());
+        assert_eq!(
+            child_line["parents"],
+            serde_json::json!([genesis.address().to_hex()])
+        );
+        assert!(child_line["outputs"]
+            .as_array()
+            .expect("the outputs to be an array")
+            .iter()
+            .any(|output| output["amount"] == 1000 && output["purpose"] == "payment"));
+        assert_eq!(child_line["faults"], serde_json::json!([]));
+
+        let utxos = lines.iter().filter(|line| line["status"] == "utxo").count();
+        assert_eq!(utxos, dag.get_utxos().len());
+        Ok(())
+    }
+
+    #[test]
+    fn csv_export_should_have_a_row_per_output_with_escaped_purposes() -> Result<()> {
+        let (genesis, child) = genesis_and_child("rent, \"flat\"")?;
+        let dag = dag_of(&[&genesis, &child])?;
+
+        let csv = exported(&dag, ExportFormat::Csv)?;
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows[0], "from,to,amount,purpose");
+        let outputs_count =
+            genesis.spend.spent_tx.outputs.len() + child.spend.spent_tx.outputs.len();
+        assert_eq!(rows.len(), 1 + outputs_count);
+        assert!(rows
+            .iter()
+            .any(|row| row.starts_with(&child.address().to_hex())
+                && row.ends_with(",1000,\"rent, \"\"flat\"\"\"")));
+        Ok(())
+    }
+
+    #[test]
+    fn dot_export_should_be_a_graph() -> Result<()> {
+        let (genesis, child) = genesis_and_child("payment")?;
+        let dag = dag_of(&[&genesis, &child])?;
+
+        let dot = exported(&dag, ExportFormat::Dot)?;
+        assert!(dot.trim_start().starts_with("digraph"));
+        Ok(())
+    }
+}
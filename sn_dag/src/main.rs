@@ -0,0 +1,171 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+mod diff;
+mod export;
+#[cfg(test)]
+mod test_utils;
+
+use clap::{Parser, Subcommand};
+use color_eyre::eyre::{eyre, Result};
+use export::ExportFormat;
+use sn_client::transfers::{SpendAddress, GENESIS_CASHNOTE};
+use sn_client::SpendDag;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Opt {
+    #[command(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Merge several DAG dumps into one, e.g. the DAGs collected by several auditors.
+    ///
+    /// The faults of the merged DAG are recomputed from the source of the first DAG.
+    Merge {
+        /// The DAG dumps to merge
+        #[clap(required = true, num_args = 2..)]
+        dags: Vec<PathBuf>,
+        /// Where to write the merged DAG
+        #[clap(short, long)]
+        output: PathBuf,
+    },
+    /// List the spends added and removed between two DAG dumps.
+    Diff {
+        /// The older DAG dump
+        old: PathBuf,
+        /// The newer DAG dump
+        new: PathBuf,
+    },
+    /// Verify a DAG dump from its source, list its faults and reconcile its supply.
+    ///
+    /// Exits with an error code if faults are found.
+    Verify {
+        /// The DAG dump to verify
+        dag: PathBuf,
+        /// Verify from this spend address instead of the Genesis spend
+        #[clap(long, value_name = "ADDRESS")]
+        source: Option<String>,
+    },
+    /// Export a DAG dump to another format.
+    Export {
+        /// The DAG dump to export
+        dag: PathBuf,
+        #[clap(short, long, value_enum, default_value_t = ExportFormat::Dot)]
+        format: ExportFormat,
+        /// Where to write the export, defaults to stdout
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+    let opt = Opt::parse();
+
+    match opt.cmd {
+        Cmd::Merge { dags, output } => merge(&dags, &output),
+        Cmd::Diff { old, new } => {
+            let old = load_dag(&old)?;
+            let new = load_dag(&new)?;
+            diff::print_diff(&old, &new);
+            Ok(())
+        }
+        Cmd::Verify { dag, source } => {
+            let source = match source {
+                Some(hex) => SpendAddress::from_str(&hex)
+                    .map_err(|e| eyre!("Invalid source address {hex}: {e}"))?,
+                None => SpendAddress::from_unique_pubkey(&GENESIS_CASHNOTE.unique_pubkey()),
+            };
+            let faults_found = verify(load_dag(&dag)?, &source)?;
+            if faults_found > 0 {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+        Cmd::Export {
+            dag,
+            format,
+            output,
+        } => {
+            let dag = load_dag(&dag)?;
+            let mut writer: Box<dyn Write> = match output {
+                Some(path) => Box::new(std::io::BufWriter::new(std::fs::File::create(path)?)),
+                None => Box::new(std::io::stdout().lock()),
+            };
+            export::export(&dag, format, &mut writer)?;
+            writer.flush()?;
+            Ok(())
+        }
+    }
+}
+
+fn load_dag(path: &Path) -> Result<SpendDag> {
+    SpendDag::load_from_file(path).map_err(|e| eyre!("Failed to load DAG from {path:?}: {e}"))
+}
+
+fn merge(paths: &[PathBuf], output: &Path) -> Result<()> {
+    let (first, others) = paths
+        .split_first()
+        .ok_or_else(|| eyre!("No DAG to merge"))?;
+    let mut dag = load_dag(first)?;
+    for path in others {
+        let other = load_dag(path)?;
+        if other.source() != dag.source() {
+            eprintln!(
+                "Warning: {path:?} has source {:?}, merging it into a DAG with source {:?}",
+                other.source(),
+                dag.source()
+            );
+        }
+        dag.merge(other)
+            .map_err(|e| eyre!("Failed to merge {path:?}: {e}"))?;
+        println!("Merged {path:?}");
+    }
+    dag.dump_to_file(output)
+        .map_err(|e| eyre!("Failed to write the merged DAG to {output:?}: {e}"))?;
+    println!(
+        "Merged {} DAGs into {output:?}: {} spends, {} UTXOs",
+        paths.len(),
+        dag.all_spends().len(),
+        dag.get_utxos().len()
+    );
+    Ok(())
+}
+
+/// Print the faults and the supply report of the DAG, return the number of faults
+fn verify(mut dag: SpendDag, source: &SpendAddress) -> Result<usize> {
+    println!("Verifying DAG from {source:?}...");
+    dag.record_faults(source)
+        .map_err(|e| eyre!("DAG is invalid: {e}"))?;
+    let faults = dag.all_faults();
+    for fault in faults.iter() {
+        println!("{fault}");
+    }
+    println!(
+        "{} spends, {} UTXOs, {} faults",
+        dag.all_spends().len(),
+        dag.get_utxos().len(),
+        faults.len()
+    );
+
+    let report = dag.supply_report();
+    println!("Supply report (in nanos):");
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    if !report.is_reconciled() {
+        println!("The supply of the DAG does not add up to its source amount");
+    }
+    Ok(faults.len())
+}
@@ -0,0 +1,71 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use color_eyre::eyre::Result;
+use sn_client::transfers::{
+    bls::SecretKey, rand, CashNote, DerivationIndex, MainSecretKey, NanoTokens, OfflineTransfer,
+    SignedSpend, SpendReason, GENESIS_CASHNOTE, GENESIS_CASHNOTE_SK,
+};
+use sn_client::SpendDag;
+
+/// Spend all the cash notes, paying `amount` with the given `purpose` to a new key and the change back to `owner`.
+/// Returns the spends and the change.
+fn transfer(
+    owner: &MainSecretKey,
+    cash_notes: Vec<CashNote>,
+    amount: u64,
+    purpose: &str,
+) -> Result<(Vec<SignedSpend>, Vec<CashNote>)> {
+    let cash_notes_with_keys = cash_notes
+        .into_iter()
+        .map(|cn| {
+            let key = cn.derived_key(owner)?;
+            Ok((cn, Some(key)))
+        })
+        .collect::<Result<_>>()?;
+    let transfer = OfflineTransfer::new(
+        cash_notes_with_keys,
+        vec![(
+            NanoTokens::from(amount),
+            purpose.to_string(),
+            MainSecretKey::new(SecretKey::random()).main_pubkey(),
+            DerivationIndex::random(&mut rand::thread_rng()),
+        )],
+        owner.main_pubkey(),
+        SpendReason::default(),
+    )?;
+    Ok((
+        transfer.all_spend_requests,
+        transfer.change_cash_note.into_iter().collect(),
+    ))
+}
+
+/// The spend of genesis, and a spend of its change paying 1000 nanos with the given `purpose`
+pub(crate) fn genesis_and_child(purpose: &str) -> Result<(SignedSpend, SignedSpend)> {
+    let genesis_sk = MainSecretKey::new(SecretKey::from_hex(GENESIS_CASHNOTE_SK)?);
+    let everything = GENESIS_CASHNOTE.value()?.as_nano();
+    let (mut genesis_spends, change) = transfer(
+        &genesis_sk,
+        vec![GENESIS_CASHNOTE.clone()],
+        everything / 2,
+        "genesis",
+    )?;
+    let (mut child_spends, _) = transfer(&genesis_sk, change, 1000, purpose)?;
+    Ok((genesis_spends.remove(0), child_spends.remove(0)))
+}
+
+/// A DAG with the given spends, its faults recorded from the first one
+pub(crate) fn dag_of(spends: &[&SignedSpend]) -> Result<SpendDag> {
+    let source = spends[0].address();
+    let mut dag = SpendDag::new(source);
+    for spend in spends {
+        let _ = dag.insert(spend.address(), (*spend).clone());
+    }
+    dag.record_faults(&source)?;
+    Ok(dag)
+}
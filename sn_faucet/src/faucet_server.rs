@@ -8,7 +8,11 @@
 
 #[cfg(feature = "distribution")]
 use crate::token_distribution;
-use crate::{claim_genesis, send_tokens};
+use crate::{
+    claim_genesis,
+    gift_limits::{GiftDenial, GiftGrant, GiftLimiter, GiftLimitsArgs},
+    send_tokens,
+};
 use color_eyre::eyre::Result;
 use fs2::FileExt;
 use sn_client::{
@@ -16,10 +20,14 @@ use sn_client::{
     Client,
 };
 use sn_transfers::{
-    get_faucet_data_dir, wallet_lockfile_name, NanoTokens, Transfer, WALLET_DIR_NAME,
+    get_faucet_data_dir, wallet_lockfile_name, MainPubkey, NanoTokens, Transfer, WALLET_DIR_NAME,
 };
 use std::path::Path;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+};
 use tokio::sync::Semaphore;
 use tracing::{debug, error, info, warn};
 use warp::{
@@ -45,7 +53,9 @@ use warp::{
 ///
 /// # balance should be updated
 /// ```
-pub async fn run_faucet_server(client: &Client) -> Result<()> {
+///
+/// The gifts are limited per wallet address and per IP as set in `limits`, see `gift_limits`.
+pub async fn run_faucet_server(client: &Client, limits: GiftLimitsArgs) -> Result<()> {
     let root_dir = get_faucet_data_dir();
     let wallet = load_account_wallet_or_create_with_mnemonic(&root_dir, None)?;
     claim_genesis(client, wallet).await.map_err(|err| {
@@ -54,10 +64,10 @@ pub async fn run_faucet_server(client: &Client) -> Result<()> {
         error!("Faucet Server couldn't start as we failed to claim Genesis");
         err
     })?;
-    startup_server(client.clone()).await
+    startup_server(client.clone(), limits).await
 }
 
-pub async fn restart_faucet_server(client: &Client) -> Result<()> {
+pub async fn restart_faucet_server(client: &Client, limits: GiftLimitsArgs) -> Result<()> {
     let root_dir = get_faucet_data_dir();
     println!("Loading the previous wallet at {root_dir:?}");
     debug!("Loading the previous wallet at {root_dir:?}");
@@ -67,7 +77,7 @@ pub async fn restart_faucet_server(client: &Client) -> Result<()> {
     println!("Previous wallet loaded");
     debug!("Previous wallet loaded");
 
-    startup_server(client.clone()).await
+    startup_server(client.clone(), limits).await
 }
 
 #[cfg(feature = "distribution")]
@@ -211,20 +221,59 @@ async fn respond_to_donate_request(
     }
 }
 
+/// A JSON response with status 429 and a `Retry-After` header for the rate limits, 403 otherwise
+fn denial_response(denial: &GiftDenial) -> Response<String> {
+    let body = serde_json::to_string(denial).unwrap_or_else(|_| denial.message.clone());
+    let mut response = Response::new(body);
+    response.headers_mut().insert(
+        "content-type",
+        warp::http::HeaderValue::from_static("application/json"),
+    );
+    if let Some(retry_after) = denial.retry_after_secs {
+        *response.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+        response
+            .headers_mut()
+            .insert("retry-after", warp::http::HeaderValue::from(retry_after));
+    } else {
+        *response.status_mut() = StatusCode::FORBIDDEN;
+    }
+    response
+}
+
+/// The IP of the client, taken from the last `X-Forwarded-For` address if it is trusted.
+///
+/// The last address is the one appended by the trusted reverse proxy, the earlier ones are given by
+/// the client and can't be trusted.
+fn client_ip(
+    remote: Option<SocketAddr>,
+    forwarded_for: Option<String>,
+    trust_forwarded_for: bool,
+) -> Option<IpAddr> {
+    if trust_forwarded_for {
+        let forwarded_ip = forwarded_for
+            .as_deref()
+            .and_then(|header| header.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse().ok());
+        if forwarded_ip.is_some() {
+            return forwarded_ip;
+        }
+    }
+    remote.map(|addr| addr.ip())
+}
+
 async fn respond_to_gift_request(
     client: Client,
     key: String,
+    ip: Option<IpAddr>,
+    challenge_response: Option<String>,
     semaphore: Arc<Semaphore>,
+    limiter: Arc<Mutex<GiftLimiter>>,
 ) -> std::result::Result<impl Reply, std::convert::Infallible> {
-    let faucet_root = get_faucet_data_dir();
-
-    let from = match load_account_wallet_or_create_with_mnemonic(&faucet_root, None) {
-        Ok(wallet) => wallet,
-        Err(_error) => {
-            let mut response = Response::new("Could not load wallet".to_string());
-            *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
-
-            // Either opening the file or locking it failed, indicating rate limiting should occur
+    let main_pubkey = match MainPubkey::from_hex(&key) {
+        Ok(main_pubkey) => main_pubkey,
+        Err(err) => {
+            let mut response = Response::new(format!("Invalid wallet address {key}: {err}"));
+            *response.status_mut() = StatusCode::BAD_REQUEST;
             return Ok(response);
         }
     };
@@ -241,6 +290,37 @@ async fn respond_to_gift_request(
         return Ok(response);
     }
 
+    let grant = match limiter.lock() {
+        Ok(mut limiter) => limiter.grant(&main_pubkey, ip, challenge_response.as_deref()),
+        Err(err) => {
+            error!("Failed to lock the gift limiter: {err}");
+            let mut response = Response::new("Gift limits unavailable".to_string());
+            *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            return Ok(response);
+        }
+    };
+    let grant = match grant {
+        Ok(grant) => grant,
+        Err(denial) => {
+            info!("Gift request for {key} from {ip:?} refused: {denial:?}");
+            return Ok(denial_response(&denial));
+        }
+    };
+
+    let faucet_root = get_faucet_data_dir();
+
+    let from = match load_account_wallet_or_create_with_mnemonic(&faucet_root, None) {
+        Ok(wallet) => wallet,
+        Err(_error) => {
+            release_gift(&limiter, grant);
+            let mut response = Response::new("Could not load wallet".to_string());
+            *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+
+            // Either opening the file or locking it failed, indicating rate limiting should occur
+            return Ok(response);
+        }
+    };
+
     const GIFT_AMOUNT_SNT: &str = "1";
     match send_tokens(&client, from, GIFT_AMOUNT_SNT, &key).await {
         Ok(transfer) => {
@@ -249,6 +329,7 @@ async fn respond_to_gift_request(
             Ok(Response::new(transfer.to_string()))
         }
         Err(err) => {
+            release_gift(&limiter, grant);
             eprintln!("Failed to send tokens to {key}: {err}");
             error!("Failed to send tokens to {key}: {err}");
             Ok(Response::new(format!("Failed to send tokens: {err}")))
@@ -256,9 +337,45 @@ async fn respond_to_gift_request(
     }
 }
 
-async fn startup_server(client: Client) -> Result<()> {
+fn respond_to_challenge_request(limiter: &Mutex<GiftLimiter>) -> Response<String> {
+    let challenge = match limiter.lock() {
+        Ok(mut limiter) => limiter.issue_challenge(),
+        Err(err) => {
+            error!("Failed to lock the gift limiter: {err}");
+            let mut response = Response::new("Gift limits unavailable".to_string());
+            *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            return response;
+        }
+    };
+    let Some(challenge) = challenge else {
+        let mut response = Response::new("No challenge is required for gifts".to_string());
+        *response.status_mut() = StatusCode::NOT_FOUND;
+        return response;
+    };
+
+    let body = serde_json::to_string(&challenge).unwrap_or_else(|_| challenge.challenge.clone());
+    let mut response = Response::new(body);
+    response.headers_mut().insert(
+        "content-type",
+        warp::http::HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+/// Don't count a gift that could not be sent against the limits
+fn release_gift(limiter: &Mutex<GiftLimiter>, grant: GiftGrant) {
+    match limiter.lock() {
+        Ok(mut limiter) => limiter.release(grant),
+        Err(err) => error!("Failed to lock the gift limiter: {err}"),
+    }
+}
+
+async fn startup_server(client: Client, limits: GiftLimitsArgs) -> Result<()> {
     // Create a semaphore with a single permit
     let semaphore = Arc::new(Semaphore::new(1));
+    let limiter = GiftLimiter::new(limits, &get_faucet_data_dir())?;
+    let trust_forwarded_for = limiter.trust_forwarded_for();
+    let limiter = Arc::new(Mutex::new(limiter));
 
     #[allow(unused)]
     let mut balances = HashMap::<String, NanoTokens>::new();
@@ -299,18 +416,35 @@ async fn startup_server(client: Client) -> Result<()> {
             respond_to_distribution_request(client, query, balances.clone(), semaphore)
         });
 
-    // GET /key
+    // GET /challenge
+    let challenge_limiter = limiter.clone();
+    let challenge_route = warp::get().and(warp::path!("challenge")).map(move || {
+        debug!("Gift challenge request");
+        respond_to_challenge_request(&challenge_limiter)
+    });
+
+    // GET /key?challenge=response
     let gift_route = warp::get()
         .and(warp::path!(String))
-        .map(|query| {
-            debug!("Gift distribution request: {query}");
-            query
-        })
-        .and_then(move |key| {
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::addr::remote())
+        .and(warp::header::optional::<String>("x-forwarded-for"))
+        .map(
+            move |key: String,
+                  mut query: HashMap<String, String>,
+                  remote: Option<SocketAddr>,
+                  forwarded_for: Option<String>| {
+                let ip = client_ip(remote, forwarded_for, trust_forwarded_for);
+                debug!("Gift distribution request: {key} from {ip:?}");
+                (key, ip, query.remove("challenge"))
+            },
+        )
+        .and_then(move |(key, ip, challenge_response)| {
             let client = gift_client.clone();
             let semaphore = semaphore.clone();
+            let limiter = limiter.clone();
 
-            respond_to_gift_request(client, key, semaphore)
+            respond_to_gift_request(client, key, ip, challenge_response, semaphore, limiter)
         });
 
     // GET /donate
@@ -344,6 +478,7 @@ async fn startup_server(client: Client) -> Result<()> {
         distribution_route
             .or(donation_route)
             .or(donation_addr)
+            .or(challenge_route)
             .or(gift_route),
     )
     // warp::serve(gift_route)
@@ -351,9 +486,14 @@ async fn startup_server(client: Client) -> Result<()> {
     .await;
 
     #[cfg(not(feature = "distribution"))]
-    warp::serve(donation_route.or(donation_addr).or(gift_route))
-        .run(([0, 0, 0, 0], 8000))
-        .await;
+    warp::serve(
+        donation_route
+            .or(donation_addr)
+            .or(challenge_route)
+            .or(gift_route),
+    )
+    .run(([0, 0, 0, 0], 8000))
+    .await;
 
     debug!("Server closed");
     Ok(())
@@ -377,3 +517,26 @@ fn deposit(root_dir: &Path) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_client_ip_should_be_the_one_appended_by_the_proxy() {
+        let remote = Some(SocketAddr::from(([127, 0, 0, 1], 4000)));
+        let forwarded_for = Some("1.2.3.4, 10.0.0.1 , 203.0.113.7".to_string());
+        assert_eq!(
+            client_ip(remote, forwarded_for.clone(), true),
+            Some(IpAddr::from([203, 0, 113, 7]))
+        );
+        assert_eq!(
+            client_ip(remote, forwarded_for, false),
+            Some(IpAddr::from([127, 0, 0, 1]))
+        );
+        assert_eq!(
+            client_ip(remote, Some("garbage".to_string()), true),
+            Some(IpAddr::from([127, 0, 0, 1]))
+        );
+    }
+}
@@ -0,0 +1,832 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Protects the gift route of the faucet server against being drained.
+//!
+//! A gift request is checked, in order, against:
+//!  - the access list file: denied keys and IPs are refused, allowed ones skip all the other checks
+//!  - the challenge verifier, e.g. a captcha or a proof of work
+//!  - the minimum interval between two gifts to the same key or IP
+//!  - the daily quotas per key, per IP and in total
+//!
+//! The gifts are recorded in a store in the faucet data dir so that the limits survive restarts.
+//! No limit applies by default, as for the local and CI networks.
+
+use clap::Args;
+use color_eyre::eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use sn_transfers::{Hash, MainPubkey};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::IpAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::{error, info, warn};
+
+const GIFT_LIMITS_STORE_FILENAME: &str = "gift_limits.json";
+const ACCESS_LIST_FILENAME: &str = "access_list";
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+/// The most challenges kept waiting for an answer, the ones closest to expiring are dropped first
+const MAX_ISSUED_CHALLENGES: usize = 10_000;
+
+/// The limits applied to the gift requests of the faucet server
+#[derive(Args, Debug, Clone)]
+pub struct GiftLimitsArgs {
+    /// The minimum number of seconds between two gifts to the same wallet address, 0 for no limit.
+    #[clap(long, default_value_t = 0)]
+    pub key_interval: u64,
+    /// The minimum number of seconds between two gifts to the same IP address, 0 for no limit.
+    #[clap(long, default_value_t = 0)]
+    pub ip_interval: u64,
+    /// The maximum number of gifts per day to the same wallet address, 0 for no limit.
+    #[clap(long, default_value_t = 0)]
+    pub daily_quota_per_key: u32,
+    /// The maximum number of gifts per day to the same IP address, 0 for no limit.
+    #[clap(long, default_value_t = 0)]
+    pub daily_quota_per_ip: u32,
+    /// The maximum number of gifts per day in total, 0 for no limit.
+    #[clap(long, default_value_t = 0)]
+    pub daily_quota: u32,
+    /// The allow/deny list file, one `allow <entry>` or `deny <entry>` per line, an entry being a
+    /// hex-encoded wallet address or an IP address. Lines starting with `#` are ignored.
+    ///
+    /// Defaults to the `access_list` file in the faucet data dir, if it exists.
+    #[clap(long, value_name = "PATH")]
+    pub access_list: Option<PathBuf>,
+    /// Take the client IP from the `X-Forwarded-For` header, when the faucet is behind a reverse proxy.
+    ///
+    /// The last address of the header is used, which must be the one appended by the proxy.
+    #[clap(long)]
+    pub trust_forwarded_for: bool,
+    /// Require a proof of work of that many leading zero bits with the gift requests, 0 to disable it.
+    ///
+    /// The challenge is fetched from `GET /challenge` and can only be answered once. The proof of
+    /// work is a nonce, passed as `?challenge=<challenge>:<nonce>`, such that the hash of
+    /// `<hex-encoded wallet address><challenge><nonce>` has the required leading zero bits.
+    #[clap(long, default_value_t = 0)]
+    pub pow_difficulty: u8,
+    /// The number of seconds a proof of work challenge can be answered for.
+    #[clap(long, default_value_t = 300)]
+    pub pow_challenge_ttl: u64,
+}
+
+/// Verifies the response to a challenge given with a gift request, e.g. a captcha token or a proof of work
+pub trait ChallengeVerifier: Send + Sync {
+    /// Issue a challenge for a gift request to answer, if the verifier needs one from the faucet
+    fn issue(&mut self, now: u64) -> Option<IssuedChallenge>;
+
+    /// Check the `response` given along with a gift request to `key`, return the reason of the failure if any
+    fn verify(
+        &mut self,
+        key: &MainPubkey,
+        response: Option<&str>,
+        now: u64,
+    ) -> std::result::Result<(), String>;
+}
+
+/// A challenge issued by the faucet, to be answered by a single gift request before it expires
+#[derive(Debug, Clone, Serialize)]
+pub struct IssuedChallenge {
+    pub challenge: String,
+    /// The number of leading zero bits the proof of work must have
+    pub difficulty: u8,
+    /// In seconds since the UNIX epoch
+    pub expires_at: u64,
+}
+
+/// Requires a nonce such that the hash of the hex-encoded key, followed by an issued challenge and
+/// the nonce, has `difficulty` leading zero bits. Each challenge can only be answered once.
+pub struct ProofOfWork {
+    difficulty: u8,
+    ttl: u64,
+    /// The challenges waiting for an answer, with their expiry time
+    issued: BTreeMap<String, u64>,
+}
+
+impl ProofOfWork {
+    pub fn new(difficulty: u8, ttl: u64) -> Self {
+        Self {
+            difficulty,
+            ttl,
+            issued: BTreeMap::new(),
+        }
+    }
+}
+
+impl ChallengeVerifier for ProofOfWork {
+    fn issue(&mut self, now: u64) -> Option<IssuedChallenge> {
+        self.issued.retain(|_, expires_at| *expires_at > now);
+        if self.issued.len() >= MAX_ISSUED_CHALLENGES {
+            if let Some(closest) = self
+                .issued
+                .iter()
+                .min_by_key(|(_, expires_at)| **expires_at)
+                .map(|(challenge, _)| challenge.clone())
+            {
+                let _ = self.issued.remove(&closest);
+            }
+        }
+
+        let challenge = hex::encode(sn_transfers::rand::random::<[u8; 16]>());
+        let expires_at = now.saturating_add(self.ttl);
+        let _ = self.issued.insert(challenge.clone(), expires_at);
+        Some(IssuedChallenge {
+            challenge,
+            difficulty: self.difficulty,
+            expires_at,
+        })
+    }
+
+    fn verify(
+        &mut self,
+        key: &MainPubkey,
+        response: Option<&str>,
+        now: u64,
+    ) -> std::result::Result<(), String> {
+        let response = response.ok_or_else(|| {
+            format!(
+                "A proof of work of {} leading zero bits is required, for a challenge from /challenge",
+                self.difficulty
+            )
+        })?;
+        let (challenge, nonce) = response
+            .split_once(':')
+            .ok_or_else(|| "The proof of work must be given as <challenge>:<nonce>".to_string())?;
+        match self.issued.get(challenge) {
+            Some(expires_at) if *expires_at > now => {}
+            _ => return Err("The challenge is unknown, expired or already answered".to_string()),
+        }
+
+        let hash = Hash::hash(format!("{}{challenge}{nonce}", key.to_hex()).as_bytes());
+        if leading_zero_bits(hash.slice()) < self.difficulty as u32 {
+            return Err(format!(
+                "The proof of work does not have {} leading zero bits",
+                self.difficulty
+            ));
+        }
+        let _ = self.issued.remove(challenge);
+        Ok(())
+    }
+}
+
+/// The challenge verifier required by the configuration, if any.
+/// Other verifiers, e.g. for a captcha service, are to be plugged in here.
+fn challenge_verifier(cfg: &GiftLimitsArgs) -> Option<Box<dyn ChallengeVerifier>> {
+    if cfg.pow_difficulty > 0 {
+        return Some(Box::new(ProofOfWork::new(
+            cfg.pow_difficulty,
+            cfg.pow_challenge_ttl,
+        )));
+    }
+    None
+}
+
+fn leading_zero_bits(bytes: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in bytes {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// The reason a gift request was refused
+#[derive(Debug, Serialize)]
+pub struct GiftDenial {
+    /// `denied`, `challenge_failed`, `rate_limited` or `quota_exceeded`
+    pub error: &'static str,
+    pub message: String,
+    /// When the request can be retried, for the `rate_limited` and `quota_exceeded` denials
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+}
+
+impl GiftDenial {
+    fn forbidden(error: &'static str, message: String) -> Self {
+        Self {
+            error,
+            message,
+            retry_after_secs: None,
+        }
+    }
+
+    fn rate_limited(error: &'static str, message: String, retry_after_secs: u64) -> Self {
+        Self {
+            error,
+            message,
+            retry_after_secs: Some(retry_after_secs.max(1)),
+        }
+    }
+}
+
+/// The allowed and denied keys and IPs
+#[derive(Default)]
+struct AccessList {
+    allowed: BTreeSet<String>,
+    denied: BTreeSet<String>,
+}
+
+impl AccessList {
+    fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        let mut list = Self::default();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once(char::is_whitespace) {
+                Some(("allow", entry)) => {
+                    let _ = list.allowed.insert(normalise_entry(entry)?);
+                }
+                Some(("deny", entry)) => {
+                    let _ = list.denied.insert(normalise_entry(entry)?);
+                }
+                _ => {
+                    return Err(eyre!(
+                        "Invalid line {} of {path:?}, expected `allow <entry>` or `deny <entry>`",
+                        i + 1
+                    ))
+                }
+            }
+        }
+        Ok(list)
+    }
+}
+
+/// Entries are compared as IP addresses or lowercase hex-encoded keys
+fn normalise_entry(entry: &str) -> Result<String> {
+    let entry = entry.trim();
+    if let Ok(ip) = IpAddr::from_str(entry) {
+        return Ok(ip.to_string());
+    }
+    MainPubkey::from_hex(entry)
+        .map(|key| key.to_hex())
+        .map_err(|err| eyre!("Invalid access list entry {entry:?}: {err}"))
+}
+
+/// The gifts to a key or an IP
+#[derive(Default, Clone, Serialize, Deserialize)]
+struct Usage {
+    /// In seconds since the UNIX epoch
+    last_gift: u64,
+    /// The day of `count`, in days since the UNIX epoch
+    day: u64,
+    count: u32,
+}
+
+impl Usage {
+    fn count_on(&self, day: u64) -> u32 {
+        if self.day == day {
+            self.count
+        } else {
+            0
+        }
+    }
+
+    /// Count a gift, returning the time of the previous one
+    fn record(&mut self, now: u64) -> u64 {
+        let day = now / SECONDS_PER_DAY;
+        self.count = self.count_on(day) + 1;
+        self.day = day;
+        std::mem::replace(&mut self.last_gift, now)
+    }
+
+    /// Undo the recording of a gift at `at`, unless another gift was recorded since
+    fn release(&mut self, at: u64, previous_gift: u64) {
+        if self.day == at / SECONDS_PER_DAY {
+            self.count = self.count.saturating_sub(1);
+        }
+        if self.last_gift == at {
+            self.last_gift = previous_gift;
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct LimitsStore {
+    keys: BTreeMap<String, Usage>,
+    ips: BTreeMap<String, Usage>,
+    total: Usage,
+}
+
+/// Decides whether a gift request can be served, and keeps track of the gifts served
+pub struct GiftLimiter {
+    cfg: GiftLimitsArgs,
+    store_path: Option<PathBuf>,
+    store: LimitsStore,
+    access_list: AccessList,
+    challenge: Option<Box<dyn ChallengeVerifier>>,
+}
+
+impl GiftLimiter {
+    /// Load the store and the access list from the faucet data dir
+    pub fn new(cfg: GiftLimitsArgs, faucet_dir: &Path) -> Result<Self> {
+        let store_path = faucet_dir.join(GIFT_LIMITS_STORE_FILENAME);
+        let store = match std::fs::read(&store_path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|err| {
+                warn!("Failed to parse the gift limits store at {store_path:?}, starting afresh: {err}");
+                LimitsStore::default()
+            }),
+            Err(_) => LimitsStore::default(),
+        };
+
+        let default_access_list = faucet_dir.join(ACCESS_LIST_FILENAME);
+        let access_list = match &cfg.access_list {
+            Some(path) => AccessList::load(path)?,
+            None if default_access_list.is_file() => AccessList::load(&default_access_list)?,
+            None => AccessList::default(),
+        };
+        info!(
+            "Gift limits loaded with {} allowed and {} denied entries",
+            access_list.allowed.len(),
+            access_list.denied.len()
+        );
+
+        let challenge = challenge_verifier(&cfg);
+
+        Ok(Self {
+            cfg,
+            store_path: Some(store_path),
+            store,
+            access_list,
+            challenge,
+        })
+    }
+
+    pub fn trust_forwarded_for(&self) -> bool {
+        self.cfg.trust_forwarded_for
+    }
+
+    /// Issue a challenge for the next gift request to answer, if the configured verifier needs one
+    pub fn issue_challenge(&mut self) -> Option<IssuedChallenge> {
+        let now = unix_timestamp();
+        self.challenge
+            .as_mut()
+            .and_then(|challenge| challenge.issue(now))
+    }
+
+    /// Check whether a gift to `key` requested from `ip` can be served, and if so record it right
+    /// away, so concurrent requests can't all pass the limits before any of them is recorded.
+    ///
+    /// The returned grant is to be released if the gift could not be sent after all.
+    pub fn grant(
+        &mut self,
+        key: &MainPubkey,
+        ip: Option<IpAddr>,
+        challenge_response: Option<&str>,
+    ) -> std::result::Result<GiftGrant, GiftDenial> {
+        let now = unix_timestamp();
+        self.check(key, ip, challenge_response, now)?;
+        let grant = self.record(key, ip, now);
+        self.save();
+        Ok(grant)
+    }
+
+    /// Forget a gift that was granted but could not be sent
+    pub fn release(&mut self, grant: GiftGrant) {
+        if let Some(usage) = self.store.keys.get_mut(&grant.key) {
+            usage.release(grant.at, grant.previous_key_gift);
+        }
+        if let Some(ip) = &grant.ip {
+            if let Some(usage) = self.store.ips.get_mut(ip) {
+                usage.release(grant.at, grant.previous_ip_gift);
+            }
+        }
+        self.store
+            .total
+            .release(grant.at, grant.previous_total_gift);
+        self.save();
+    }
+
+    fn check(
+        &mut self,
+        key: &MainPubkey,
+        ip: Option<IpAddr>,
+        challenge_response: Option<&str>,
+        now: u64,
+    ) -> std::result::Result<(), GiftDenial> {
+        let key_hex = key.to_hex();
+        let ip = ip.map(|ip| ip.to_string());
+
+        if self.access_list.denied.contains(&key_hex)
+            || ip
+                .as_ref()
+                .is_some_and(|ip| self.access_list.denied.contains(ip))
+        {
+            return Err(GiftDenial::forbidden(
+                "denied",
+                "This address is not allowed to request gifts".to_string(),
+            ));
+        }
+        if self.access_list.allowed.contains(&key_hex)
+            || ip
+                .as_ref()
+                .is_some_and(|ip| self.access_list.allowed.contains(ip))
+        {
+            return Ok(());
+        }
+
+        if let Some(challenge) = &mut self.challenge {
+            challenge
+                .verify(key, challenge_response, now)
+                .map_err(|message| GiftDenial::forbidden("challenge_failed", message))?;
+        }
+
+        let today = now / SECONDS_PER_DAY;
+        let tomorrow = (today + 1) * SECONDS_PER_DAY;
+
+        let key_usage = self.store.keys.get(&key_hex);
+        let ip_usage = ip.as_ref().and_then(|ip| self.store.ips.get(ip));
+        for (usage, interval, what) in [
+            (key_usage, self.cfg.key_interval, "wallet address"),
+            (ip_usage, self.cfg.ip_interval, "IP address"),
+        ] {
+            if let Some(usage) = usage {
+                let next_gift = usage.last_gift.saturating_add(interval);
+                if next_gift > now {
+                    return Err(GiftDenial::rate_limited(
+                        "rate_limited",
+                        format!("A gift was sent to this {what} recently"),
+                        next_gift - now,
+                    ));
+                }
+            }
+        }
+
+        for (count, quota, what) in [
+            (
+                key_usage.map(|u| u.count_on(today)).unwrap_or_default(),
+                self.cfg.daily_quota_per_key,
+                "this wallet address",
+            ),
+            (
+                ip_usage.map(|u| u.count_on(today)).unwrap_or_default(),
+                self.cfg.daily_quota_per_ip,
+                "this IP address",
+            ),
+            (
+                self.store.total.count_on(today),
+                self.cfg.daily_quota,
+                "the faucet",
+            ),
+        ] {
+            if quota > 0 && count >= quota {
+                return Err(GiftDenial::rate_limited(
+                    "quota_exceeded",
+                    format!("The daily quota of {quota} gifts for {what} is reached"),
+                    tomorrow - now,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Record a gift to `key` requested from `ip`
+    fn record(&mut self, key: &MainPubkey, ip: Option<IpAddr>, now: u64) -> GiftGrant {
+        let key = key.to_hex();
+        let ip = ip.map(|ip| ip.to_string());
+        let previous_key_gift = self.store.keys.entry(key.clone()).or_default().record(now);
+        let previous_ip_gift = ip
+            .as_ref()
+            .map(|ip| self.store.ips.entry(ip.clone()).or_default().record(now))
+            .unwrap_or_default();
+        let previous_total_gift = self.store.total.record(now);
+
+        // forget the usages that can no longer limit a request
+        let today = now / SECONDS_PER_DAY;
+        let max_interval = self.cfg.key_interval.max(self.cfg.ip_interval);
+        let is_relevant =
+            |usage: &Usage| usage.day == today || usage.last_gift + max_interval > now;
+        self.store.keys.retain(|_, usage| is_relevant(usage));
+        self.store.ips.retain(|_, usage| is_relevant(usage));
+
+        GiftGrant {
+            key,
+            ip,
+            at: now,
+            previous_key_gift,
+            previous_ip_gift,
+            previous_total_gift,
+        }
+    }
+
+    /// Persist the store, a failure only means the limits won't survive a restart
+    fn save(&self) {
+        let path = match &self.store_path {
+            Some(path) => path,
+            None => return,
+        };
+        let result = serde_json::to_vec(&self.store)
+            .map_err(|err| eyre!(err))
+            .and_then(|bytes| {
+                let tmp_path = path.with_extension("tmp");
+                std::fs::write(&tmp_path, bytes)?;
+                std::fs::rename(tmp_path, path)?;
+                Ok(())
+            });
+        if let Err(err) = result {
+            error!("Failed to save the gift limits store to {path:?}: {err}");
+        }
+    }
+}
+
+/// A gift allowed and recorded by the `GiftLimiter`
+#[derive(Debug)]
+pub struct GiftGrant {
+    key: String,
+    ip: Option<String>,
+    /// When the gift was recorded, in seconds since the UNIX epoch
+    at: u64,
+    previous_key_gift: u64,
+    previous_ip_gift: u64,
+    previous_total_gift: u64,
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::{prelude::*, TempDir};
+    use sn_transfers::MainSecretKey;
+
+    const NOW: u64 = 1_000 * SECONDS_PER_DAY + 100;
+
+    fn limiter(cfg: GiftLimitsArgs, access_list: AccessList) -> GiftLimiter {
+        GiftLimiter {
+            challenge: challenge_verifier(&cfg),
+            cfg,
+            store_path: None,
+            store: LimitsStore::default(),
+            access_list,
+        }
+    }
+
+    fn no_limits() -> GiftLimitsArgs {
+        GiftLimitsArgs {
+            key_interval: 0,
+            ip_interval: 0,
+            daily_quota_per_key: 0,
+            daily_quota_per_ip: 0,
+            daily_quota: 0,
+            access_list: None,
+            trust_forwarded_for: false,
+            pow_difficulty: 0,
+            pow_challenge_ttl: 300,
+        }
+    }
+
+    fn key() -> MainPubkey {
+        MainSecretKey::random().main_pubkey()
+    }
+
+    fn ip(last: u8) -> Option<IpAddr> {
+        Some(IpAddr::from([10, 0, 0, last]))
+    }
+
+    fn denial(
+        limiter: &mut GiftLimiter,
+        key: &MainPubkey,
+        ip: Option<IpAddr>,
+        now: u64,
+    ) -> Option<&'static str> {
+        limiter.check(key, ip, None, now).err().map(|d| d.error)
+    }
+
+    #[test]
+    fn the_interval_should_apply_per_key_and_per_ip() {
+        let mut limiter = limiter(
+            GiftLimitsArgs {
+                key_interval: 60,
+                ip_interval: 600,
+                ..no_limits()
+            },
+            AccessList::default(),
+        );
+        let (key, other_key) = (key(), key());
+        let _ = limiter.record(&key, ip(1), NOW);
+
+        assert_eq!(
+            denial(&mut limiter, &key, ip(2), NOW + 59),
+            Some("rate_limited")
+        );
+        assert_eq!(denial(&mut limiter, &key, ip(2), NOW + 60), None);
+        assert_eq!(
+            denial(&mut limiter, &other_key, ip(1), NOW + 599),
+            Some("rate_limited")
+        );
+        assert_eq!(denial(&mut limiter, &other_key, ip(1), NOW + 600), None);
+        assert_eq!(
+            limiter
+                .check(&other_key, ip(1), None, NOW + 10)
+                .err()
+                .and_then(|d| d.retry_after_secs),
+            Some(590)
+        );
+    }
+
+    #[test]
+    fn the_daily_quotas_should_reset_the_next_day() {
+        let mut limiter = limiter(
+            GiftLimitsArgs {
+                daily_quota_per_key: 2,
+                daily_quota: 3,
+                ..no_limits()
+            },
+            AccessList::default(),
+        );
+        let key = key();
+        let _ = limiter.record(&key, ip(1), NOW);
+        assert_eq!(denial(&mut limiter, &key, ip(1), NOW), None);
+        let _ = limiter.record(&key, ip(1), NOW + 1);
+        assert_eq!(
+            denial(&mut limiter, &key, ip(1), NOW + 2),
+            Some("quota_exceeded")
+        );
+
+        let other_key = self::key();
+        assert_eq!(denial(&mut limiter, &other_key, ip(2), NOW + 2), None);
+        let _ = limiter.record(&other_key, ip(2), NOW + 2);
+        assert_eq!(
+            denial(&mut limiter, &self::key(), ip(3), NOW + 3),
+            Some("quota_exceeded")
+        );
+
+        let tomorrow = (NOW / SECONDS_PER_DAY + 1) * SECONDS_PER_DAY;
+        assert_eq!(denial(&mut limiter, &key, ip(1), tomorrow), None);
+    }
+
+    #[test]
+    fn a_released_gift_should_not_count_against_the_limits() {
+        let mut limiter = limiter(
+            GiftLimitsArgs {
+                key_interval: 60,
+                daily_quota_per_key: 1,
+                ..no_limits()
+            },
+            AccessList::default(),
+        );
+        let key = key();
+        let grant = limiter.record(&key, ip(1), NOW);
+        assert_eq!(
+            denial(&mut limiter, &key, ip(1), NOW + 1),
+            Some("rate_limited")
+        );
+        limiter.release(grant);
+        assert_eq!(denial(&mut limiter, &key, ip(1), NOW + 1), None);
+    }
+
+    #[test]
+    fn granting_should_record_the_gift() {
+        let mut limiter = limiter(
+            GiftLimitsArgs {
+                key_interval: 60,
+                ..no_limits()
+            },
+            AccessList::default(),
+        );
+        let key = key();
+        assert!(limiter.grant(&key, ip(1), None).is_ok());
+        assert_eq!(
+            limiter.grant(&key, ip(1), None).err().map(|d| d.error),
+            Some("rate_limited")
+        );
+    }
+
+    #[test]
+    fn the_access_list_should_deny_or_skip_the_limits() -> Result<()> {
+        let (allowed_key, denied_key) = (key(), key());
+        let dir = TempDir::new()?;
+        let file = dir.child(ACCESS_LIST_FILENAME);
+        file.write_str(&format!(
+            "# faucet access list\n\nallow {}\ndeny {}\nallow 10.0.0.1\ndeny  10.0.0.2\n",
+            allowed_key.to_hex().to_uppercase(),
+            denied_key.to_hex()
+        ))?;
+        let mut limiter = limiter(
+            GiftLimitsArgs {
+                daily_quota: 1,
+                ..no_limits()
+            },
+            AccessList::load(file.path())?,
+        );
+        let _ = limiter.record(&key(), ip(9), NOW);
+
+        assert_eq!(denial(&mut limiter, &allowed_key, ip(9), NOW), None);
+        assert_eq!(denial(&mut limiter, &key(), ip(1), NOW), None);
+        assert_eq!(
+            denial(&mut limiter, &key(), ip(9), NOW),
+            Some("quota_exceeded")
+        );
+        assert_eq!(
+            denial(&mut limiter, &denied_key, ip(9), NOW),
+            Some("denied")
+        );
+        // a denial wins over an allowance
+        assert_eq!(
+            denial(&mut limiter, &allowed_key, ip(2), NOW),
+            Some("denied")
+        );
+
+        file.write_str("allow 10.0.0.1\nblock 10.0.0.2\n")?;
+        assert!(AccessList::load(file.path()).is_err());
+        file.write_str("allow not-a-key\n")?;
+        assert!(AccessList::load(file.path()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn leading_zero_bits_should_be_counted_across_bytes() {
+        assert_eq!(leading_zero_bits(&[0x80, 0]), 0);
+        assert_eq!(leading_zero_bits(&[0x01, 0xff]), 7);
+        assert_eq!(leading_zero_bits(&[0, 0x10]), 11);
+        assert_eq!(leading_zero_bits(&[0, 0]), 16);
+    }
+
+    #[test]
+    fn the_proof_of_work_should_have_the_required_difficulty() {
+        let mut pow = ProofOfWork::new(8, 300);
+        let key = key();
+        let challenge = pow.issue(NOW).map(|c| c.challenge).unwrap_or_default();
+        let zero_bits = |nonce: &String| {
+            let hash = Hash::hash(format!("{}{challenge}{nonce}", key.to_hex()).as_bytes());
+            leading_zero_bits(hash.slice())
+        };
+        let nonces = (0u64..).map(|nonce| nonce.to_string());
+        let nonce = nonces
+            .clone()
+            .find(|nonce| zero_bits(nonce) >= 8)
+            .unwrap_or_default();
+        let weak_nonce = nonces
+            .clone()
+            .find(|nonce| zero_bits(nonce) < 8)
+            .unwrap_or_default();
+
+        assert!(pow.verify(&key, None, NOW).is_err());
+        assert!(pow.verify(&key, Some(&nonce), NOW).is_err());
+        assert!(pow
+            .verify(&key, Some(&format!("{challenge}:{weak_nonce}")), NOW)
+            .is_err());
+        // the proof is bound to the key
+        assert!(pow
+            .verify(&self::key(), Some(&format!("{challenge}:{nonce}")), NOW)
+            .is_err());
+        assert!(pow
+            .verify(&key, Some(&format!("{challenge}:{nonce}")), NOW)
+            .is_ok());
+
+        let mut limiter = limiter(
+            GiftLimitsArgs {
+                pow_difficulty: 8,
+                ..no_limits()
+            },
+            AccessList::default(),
+        );
+        assert_eq!(
+            limiter.check(&key, ip(1), None, NOW).err().map(|d| d.error),
+            Some("challenge_failed")
+        );
+        // only a challenge issued by the limiter can be answered
+        assert_eq!(
+            limiter
+                .check(&key, ip(1), Some(&format!("{challenge}:{nonce}")), NOW)
+                .err()
+                .map(|d| d.error),
+            Some("challenge_failed")
+        );
+    }
+
+    #[test]
+    fn a_proof_of_work_challenge_should_only_be_answered_once_before_it_expires() {
+        let mut pow = ProofOfWork::new(0, 300);
+        let key = key();
+        let challenge = pow.issue(NOW).map(|c| c.challenge).unwrap_or_default();
+        let response = format!("{challenge}:0");
+
+        assert!(pow.verify(&key, Some(&response), NOW).is_ok());
+        assert!(pow.verify(&key, Some(&response), NOW).is_err());
+
+        let expiring = pow.issue(NOW).map(|c| c.challenge).unwrap_or_default();
+        assert!(pow
+            .verify(&key, Some(&format!("{expiring}:0")), NOW + 300)
+            .is_err());
+        // expired challenges are forgotten when the next one is issued
+        let _ = pow.issue(NOW + 300);
+        assert_eq!(pow.issued.len(), 1);
+    }
+}
@@ -7,6 +7,7 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 mod faucet_server;
+mod gift_limits;
 
 #[cfg(feature = "distribution")]
 mod token_distribution;
@@ -14,6 +15,7 @@ mod token_distribution;
 use clap::{Parser, Subcommand};
 use color_eyre::eyre::{bail, eyre, Result};
 use faucet_server::{restart_faucet_server, run_faucet_server};
+use gift_limits::GiftLimitsArgs;
 use indicatif::ProgressBar;
 use sn_client::{
     acc_packet::load_account_wallet_or_create_with_mnemonic, fund_faucet_from_genesis_wallet, send,
@@ -185,7 +187,12 @@ enum SubCmd {
     },
     /// Starts an http server that will send tokens to anyone who requests them.
     /// curl http://localhost:8000/your-hex-encoded-wallet-public-address
-    Server,
+    ///
+    /// Requests over the limits get a 429 response, denied requests a 403 one.
+    Server {
+        #[command(flatten)]
+        limits: GiftLimitsArgs,
+    },
     /// Restart the faucet_server from the last breaking point.
     ///
     /// Before firing this cmd, ensure:
@@ -194,7 +201,10 @@ enum SubCmd {
     ///   3, The old `wallet` and `wallet.lock` files shall also be removed.
     /// The command will create a new wallet with the same key,
     /// then deposit all valid cash_notes into wallet and startup the faucet_server.
-    RestartServer,
+    RestartServer {
+        #[command(flatten)]
+        limits: GiftLimitsArgs,
+    },
 }
 
 async fn faucet_cmds(cmds: SubCmd, client: &Client, funded_wallet: HotWallet) -> Result<()> {
@@ -205,13 +215,13 @@ async fn faucet_cmds(cmds: SubCmd, client: &Client, funded_wallet: HotWallet) ->
         SubCmd::Send { amount, to } => {
             send_tokens(client, funded_wallet, &amount, &to).await?;
         }
-        SubCmd::Server => {
+        SubCmd::Server { limits } => {
             // shouldn't return except on error
-            run_faucet_server(client).await?;
+            run_faucet_server(client, limits).await?;
         }
-        SubCmd::RestartServer => {
+        SubCmd::RestartServer { limits } => {
             // shouldn't return except on error
-            restart_faucet_server(client).await?;
+            restart_faucet_server(client, limits).await?;
         }
     }
     Ok(())
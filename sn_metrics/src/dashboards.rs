@@ -0,0 +1,283 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Grafana dashboards for the metrics of `sn_networking` and `sn_node`.
+//!
+//! The dashboards are generated here, rather than edited in Grafana and exported, so they follow
+//! the metrics as they change. The copies under `grafana/provisioning/dashboards` are written with
+//! `cargo run --bin metrics -- --dashboards grafana/provisioning/dashboards`, from `sn_metrics`.
+
+use color_eyre::Result;
+use serde_json::{json, Value};
+use std::{fs, path::Path};
+
+const DATASOURCE_UID: &str = "prometheusdatasourceuuid";
+const NODE_FILTER: &str = "node_id=~\"$var_node_list\"";
+const PANEL_WIDTH: u64 = 12;
+const PANEL_HEIGHT: u64 = 8;
+
+/// The generated dashboards, with the file names they are provisioned from.
+pub fn dashboards() -> Vec<(&'static str, Value)> {
+    vec![
+        ("sn-networking.json", networking_dashboard()),
+        ("sn-node.json", node_dashboard()),
+    ]
+}
+
+/// Write the generated dashboards to `dir`, replacing any earlier copies.
+pub fn write_dashboards(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    for (file_name, dashboard) in dashboards() {
+        fs::write(dir.join(file_name), to_file_contents(&dashboard)?)?;
+    }
+    Ok(())
+}
+
+fn to_file_contents(dashboard: &Value) -> Result<String> {
+    Ok(format!("{}\n", serde_json::to_string_pretty(dashboard)?))
+}
+
+fn networking_dashboard() -> Value {
+    dashboard(
+        "sn_networking",
+        "Node Networking",
+        vec![
+            Panel::new("Records Stored", "short")
+                .query(metric("sn_networking_records_stored"), "{{node_id}}"),
+            Panel::new("Store Cost", "short")
+                .query(metric("sn_networking_store_cost"), "{{node_id}}"),
+            Panel::new("Currently Established Connections", "short").query(
+                format!(
+                    "{} - sum without(cause) ({})",
+                    metric("libp2p_swarm_connections_established_total"),
+                    metric("libp2p_swarm_connections_duration_seconds_count")
+                ),
+                "{{node_id}}",
+            ),
+            Panel::new("New Connections per Second", "cps").query(
+                rate("libp2p_swarm_connections_established_total"),
+                "{{node_id}}",
+            ),
+            Panel::new("Process Memory Usage", "decmbytes").query(
+                metric("sn_networking_process_memory_used_mb"),
+                "{{node_id}}",
+            ),
+            Panel::new("Process CPU Usage", "percent").query(
+                metric("sn_networking_process_cpu_usage_percentage"),
+                "{{node_id}}",
+            ),
+        ],
+    )
+}
+
+fn node_dashboard() -> Value {
+    dashboard(
+        "sn_node",
+        "Node Activity",
+        vec![
+            Panel::new("Peers in Routing Table", "short").query(
+                format!(
+                    "{} - {}",
+                    metric("sn_node_peer_added_to_routing_table_total"),
+                    metric("sn_node_peer_removed_from_routing_table_total")
+                ),
+                "{{node_id}}",
+            ),
+            Panel::new("Routing Table Churn", "ops")
+                .query(
+                    rate("sn_node_peer_added_to_routing_table_total"),
+                    "{{node_id}} added",
+                )
+                .query(
+                    rate("sn_node_peer_removed_from_routing_table_total"),
+                    "{{node_id}} removed",
+                ),
+            Panel::new("Replications Triggered per Second", "ops")
+                .query(rate("sn_node_replication_triggered_total"), "{{node_id}}"),
+            Panel::new("Replication Keys to Fetch (95th percentile)", "short").query(
+                format!(
+                    "histogram_quantile(0.95, sum by(le, node_id) ({}))",
+                    rate("sn_node_replication_keys_to_fetch_bucket")
+                ),
+                "{{node_id}}",
+            ),
+            Panel::new("Record PUTs per Second", "ops").query(
+                rate("sn_node_put_record_ok_total"),
+                "{{node_id}} {{record_type}}",
+            ),
+            Panel::new("Record PUT Errors per Second", "ops")
+                .query(rate("sn_node_put_record_err_total"), "{{node_id}}"),
+            Panel::new("Reward Balance", "short")
+                .query(metric("sn_node_reward_wallet_balance"), "{{node_id}}"),
+        ],
+    )
+}
+
+fn metric(name: &str) -> String {
+    format!("{name}{{{NODE_FILTER}}}")
+}
+
+fn rate(name: &str) -> String {
+    format!("rate({}[$__rate_interval])", metric(name))
+}
+
+/// A time series panel with one or more queries.
+struct Panel {
+    title: &'static str,
+    unit: &'static str,
+    queries: Vec<(String, &'static str)>,
+}
+
+impl Panel {
+    fn new(title: &'static str, unit: &'static str) -> Self {
+        Self {
+            title,
+            unit,
+            queries: Vec::new(),
+        }
+    }
+
+    fn query(mut self, expr: String, legend: &'static str) -> Self {
+        self.queries.push((expr, legend));
+        self
+    }
+
+    fn to_json(&self, id: u64) -> Value {
+        // Two panels to a row, filled from the top left.
+        let index = id - 1;
+        let targets = self
+            .queries
+            .iter()
+            .zip('A'..='Z')
+            .map(|((expr, legend), ref_id)| {
+                json!({
+                    "datasource": datasource(),
+                    "editorMode": "code",
+                    "expr": expr,
+                    "legendFormat": legend,
+                    "range": true,
+                    "refId": ref_id.to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "datasource": datasource(),
+            "fieldConfig": {
+                "defaults": {
+                    "color": { "mode": "palette-classic" },
+                    "custom": {
+                        "drawStyle": "line",
+                        "fillOpacity": 0,
+                        "lineWidth": 1,
+                        "showPoints": "never",
+                        "spanNulls": false,
+                    },
+                    "unit": self.unit,
+                },
+                "overrides": [],
+            },
+            "gridPos": {
+                "h": PANEL_HEIGHT,
+                "w": PANEL_WIDTH,
+                "x": (index % 2) * PANEL_WIDTH,
+                "y": (index / 2) * PANEL_HEIGHT,
+            },
+            "id": id,
+            "options": {
+                "legend": { "displayMode": "list", "placement": "bottom", "showLegend": true },
+                "tooltip": { "mode": "multi", "sort": "desc" },
+            },
+            "targets": targets,
+            "title": self.title,
+            "type": "timeseries",
+        })
+    }
+}
+
+fn datasource() -> Value {
+    json!({ "type": "prometheus", "uid": DATASOURCE_UID })
+}
+
+fn dashboard(uid: &str, title: &str, panels: Vec<Panel>) -> Value {
+    let panels = panels
+        .iter()
+        .zip(1..)
+        .map(|(panel, id)| panel.to_json(id))
+        .collect::<Vec<_>>();
+    json!({
+        "annotations": { "list": [] },
+        "editable": true,
+        "graphTooltip": 1,
+        "id": null,
+        "links": [],
+        "panels": panels,
+        "refresh": "5s",
+        "schemaVersion": 38,
+        "tags": ["safe-network"],
+        "templating": {
+            "list": [{
+                "current": { "selected": true, "text": ["All"], "value": ["$__all"] },
+                "datasource": datasource(),
+                "definition": "label_values(node_id)",
+                "description": "The list of nodes that we are tracking",
+                "includeAll": true,
+                "label": "Node List",
+                "multi": true,
+                "name": "var_node_list",
+                "query": {
+                    "query": "label_values(node_id)",
+                    "refId": "PrometheusVariableQueryEditor-VariableQuery",
+                },
+                "refresh": 1,
+                "type": "query",
+            }],
+        },
+        "time": { "from": "now-30m", "to": "now" },
+        "title": title,
+        "uid": uid,
+        "version": 1,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn provisioned_dashboards_should_match_the_generated_ones() -> Result<()> {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("grafana")
+            .join("provisioning")
+            .join("dashboards");
+        for (file_name, dashboard) in dashboards() {
+            assert_eq!(
+                fs::read_to_string(dir.join(file_name))?,
+                to_file_contents(&dashboard)?,
+                "{file_name} is out of date, regenerate it with the --dashboards option"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn every_query_should_be_filtered_by_the_selected_nodes() {
+        for (file_name, dashboard) in dashboards() {
+            for panel in dashboard["panels"].as_array().into_iter().flatten() {
+                for target in panel["targets"].as_array().into_iter().flatten() {
+                    let expr = target["expr"].as_str().unwrap_or_default();
+                    assert!(
+                        expr.contains(NODE_FILTER),
+                        "{file_name}: {} does not filter by node: {expr}",
+                        panel["title"]
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Serving the metrics servers of nodes to Prometheus through HTTP service discovery.
+//!
+//! See <https://prometheus.io/docs/prometheus/latest/http_sd/>.
+
+use crate::targets::{TargetGroup, TargetSource};
+use color_eyre::{eyre::eyre, Result};
+use std::{
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+use tiny_http::{Header, Response, Server};
+
+/// The path the targets are served on.
+pub const TARGETS_PATH: &str = "/targets";
+
+/// Serve the targets of all the sources on `address`, finding them again every `interval` so
+/// nodes that are added or removed are picked up. This runs until the process is stopped.
+///
+/// If a source can't be read, the targets it last had are served until it can be read again.
+pub async fn serve(
+    mut sources: Vec<TargetSource>,
+    address: SocketAddr,
+    interval: Duration,
+) -> Result<()> {
+    let targets_json = Arc::new(RwLock::new("[]".to_string()));
+    let server = Server::http(address)
+        .map_err(|err| eyre!("Failed to start the discovery server on {address}: {err}"))?;
+    println!("Serving Prometheus targets on http://{address}{TARGETS_PATH}");
+
+    let served_json = targets_json.clone();
+    let _handle = std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = if request.url() == TARGETS_PATH {
+                let json = match served_json.read() {
+                    Ok(json) => json.clone(),
+                    Err(_) => "[]".to_string(),
+                };
+                Response::from_string(json).with_header(json_content_type())
+            } else {
+                Response::from_string(format!("Not found, try {TARGETS_PATH}"))
+                    .with_status_code(404)
+            };
+            let _ = request
+                .respond(response)
+                .map_err(|err| eprintln!("Failed to send response: {err}"));
+        }
+    });
+
+    let mut last_targets: Vec<Vec<TargetGroup>> = vec![Vec::new(); sources.len()];
+    let mut refresh_interval = tokio::time::interval(interval);
+    loop {
+        let _ = refresh_interval.tick().await;
+        for (source, last) in sources.iter_mut().zip(last_targets.iter_mut()) {
+            match source.targets().await {
+                Ok(targets) => {
+                    if targets != *last {
+                        println!("{} now has {} nodes", source.name(), targets.len());
+                    }
+                    *last = targets;
+                }
+                Err(err) => {
+                    eprintln!("Failed to read the nodes from {}: {err}", source.name());
+                }
+            }
+        }
+
+        let json = serde_json::to_string(&last_targets.concat())?;
+        if let Ok(mut targets_json) = targets_json.write() {
+            *targets_json = json;
+        }
+    }
+}
+
+fn json_content_type() -> Header {
+    Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+        .expect("The content type header is valid")
+}
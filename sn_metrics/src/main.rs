@@ -6,20 +6,21 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use clap::{command, Arg, ArgAction};
+mod dashboards;
+mod discovery;
+mod targets;
+
+use clap::{command, value_parser, Arg, ArgAction};
 use color_eyre::{eyre::eyre, Result};
-use regex::Regex;
+use sn_node_manager::rpc_client::DaemonConnectionOptions;
 use std::{
-    collections::BTreeMap,
-    fs::{self, File},
-    io::{BufRead, BufReader},
+    fs,
+    net::SocketAddr,
     path::{Path, PathBuf},
     process::Command,
+    time::Duration,
 };
-use walkdir::WalkDir;
-
-const LOG_FILENAME_PREFIX: &str = "safenode.log";
-type NodeId = String;
+use targets::{TargetGroup, TargetSource};
 
 #[derive(serde::Serialize)]
 struct PrometheusConfig {
@@ -38,21 +39,28 @@ struct ScrapeConfigs {
     job_name: String,
     // Override the global default
     scrape_interval: String,
-    static_configs: Vec<StaticConfig>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    static_configs: Vec<TargetGroup>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    http_sd_configs: Vec<HttpSdConfig>,
 }
 
 #[derive(serde::Serialize)]
-struct StaticConfig {
-    targets: Vec<String>,
-    labels: Labels,
+struct HttpSdConfig {
+    url: String,
+    refresh_interval: String,
 }
 
-#[derive(serde::Serialize)]
-struct Labels {
-    node_id: NodeId,
+/// Where Prometheus finds the nodes to scrape.
+enum Targets {
+    /// A fixed list, written into the config.
+    Static(Vec<TargetGroup>),
+    /// Served by this binary, over HTTP service discovery on the given port.
+    Discovery { port: u16, refresh_interval: u64 },
 }
 
-fn main() -> Result<()> {
+#[tokio::main]
+async fn main() -> Result<()> {
     let default_log_dir = dirs_next::data_dir()
         .ok_or_else(|| eyre!("could not obtain data directory path".to_string()))?
         .join("safe")
@@ -70,26 +78,127 @@ fn main() -> Result<()> {
             Arg::new("log_dirs")
                 .help("Provide one or more log directories to get the metrics server from.\nAll the files inside a provided dir are scanned.")
                 .action(ArgAction::Append)
-                .value_parser(clap::value_parser!(PathBuf))
-                .default_value(default_log_dir.into_os_string())
+                .value_parser(value_parser!(PathBuf))
+        )
+        .arg(
+            Arg::new("registry")
+                .long("registry")
+                .help("Get the metrics servers from a node registry file. Can be used more than once.")
+                .action(ArgAction::Append)
+                .value_parser(value_parser!(PathBuf))
+        )
+        .arg(
+            Arg::new("daemon")
+                .long("daemon")
+                .value_name("host:port")
+                .help("Get the metrics servers from a safenodemand daemon. Can be used more than once.")
+                .action(ArgAction::Append)
+        )
+        .arg(
+            Arg::new("daemon_token")
+                .long("daemon-token")
+                .env("SAFENODEMAND_TOKEN")
+                .hide_env_values(true)
+                .help("The access token the daemons were configured with")
+        )
+        .arg(
+            Arg::new("daemon_ca")
+                .long("daemon-ca")
+                .help("Connect to the daemons over TLS, verifying their certificates with this PEM encoded CA certificate")
+                .value_parser(value_parser!(PathBuf))
+        )
+        .arg(
+            Arg::new("serve")
+                .long("serve")
+                .value_name("address")
+                .help("Keep running and serve the metrics servers to Prometheus through HTTP service discovery on this address, e.g. 0.0.0.0:9095.\nThe nodes are found again on every refresh, so nodes that are added or removed are picked up.")
+                .value_parser(value_parser!(SocketAddr))
+        )
+        .arg(
+            Arg::new("refresh_interval")
+                .long("refresh-interval")
+                .help("How often, in seconds, to find the nodes again when serving them")
+                .default_value("15")
+                .value_parser(value_parser!(u64).range(1..))
+        )
+        .arg(
+            Arg::new("dashboards")
+                .long("dashboards")
+                .value_name("dir")
+                .help("Only write the generated Grafana dashboards to this directory, then exit")
+                .value_parser(value_parser!(PathBuf))
+                .exclusive(true)
         )
         .get_matches();
 
-    let should_run_containers = matches.get_flag("run");
-    let mut metrics_server_list = BTreeMap::<NodeId, url::Url>::new();
-    for log_dir in matches.get_many::<PathBuf>("log_dirs").unwrap() {
-        metrics_server_list.extend(get_metric_servers(log_dir)?);
+    if let Some(dir) = matches.get_one::<PathBuf>("dashboards") {
+        dashboards::write_dashboards(dir)?;
+        println!(
+            "The Grafana dashboards have been written to {}",
+            dir.display()
+        );
+        return Ok(());
     }
 
-    if metrics_server_list.is_empty() {
-        return Err(eyre!("Could not find any metrics server. Aborting!"));
+    let mut sources = Vec::new();
+    for path in matches
+        .get_many::<PathBuf>("registry")
+        .into_iter()
+        .flatten()
+    {
+        sources.push(TargetSource::Registry(path.clone()));
+    }
+    let daemon_options = DaemonConnectionOptions {
+        token: matches.get_one::<String>("daemon_token").cloned(),
+        ca_cert_path: matches.get_one::<PathBuf>("daemon_ca").cloned(),
+        ..Default::default()
+    };
+    for address in matches.get_many::<String>("daemon").into_iter().flatten() {
+        sources.push(TargetSource::Daemon {
+            address: address.clone(),
+            options: daemon_options.clone(),
+            client: None,
+        });
+    }
+    let log_dirs = matches
+        .get_many::<PathBuf>("log_dirs")
+        .map(|dirs| dirs.cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    // The node logs are only scanned by default when no other source is given
+    if !log_dirs.is_empty() || sources.is_empty() {
+        let log_dirs = if log_dirs.is_empty() {
+            vec![default_log_dir]
+        } else {
+            log_dirs
+        };
+        for log_dir in log_dirs {
+            sources.push(TargetSource::LogDir(log_dir));
+        }
     }
 
-    println!(
-        "Collecting metrics from {} nodes",
-        metrics_server_list.len()
-    );
-    let prometheus_config = build_prometheus_config(metrics_server_list);
+    let serve_address = matches.get_one::<SocketAddr>("serve").copied();
+    let refresh_interval = *matches
+        .get_one::<u64>("refresh_interval")
+        .ok_or_else(|| eyre!("The refresh interval has a default value"))?;
+    let targets = match serve_address {
+        Some(address) => Targets::Discovery {
+            port: address.port(),
+            refresh_interval,
+        },
+        None => {
+            let mut targets = Vec::new();
+            for source in &mut sources {
+                targets.extend(source.targets().await?);
+            }
+            if targets.is_empty() {
+                return Err(eyre!("Could not find any metrics server. Aborting!"));
+            }
+            println!("Collecting metrics from {} nodes", targets.len());
+            Targets::Static(targets)
+        }
+    };
+
+    let prometheus_config = build_prometheus_config(targets);
     let prometheus_config = serde_yaml::to_string(&prometheus_config)?;
 
     let working_dir = get_working_dir()?;
@@ -97,117 +206,77 @@ fn main() -> Result<()> {
     let prometheus_dir = working_dir.join("prometheus");
     fs::create_dir_all(&prometheus_dir)?;
     fs::write(prometheus_dir.join("prometheus.yml"), prometheus_config)?;
+    dashboards::write_dashboards(
+        &working_dir
+            .join("grafana")
+            .join("provisioning")
+            .join("dashboards"),
+    )?;
 
+    let should_run_containers = matches.get_flag("run");
     if should_run_containers {
-        // stop the containers if running already
-        let docker_output = Command::new("docker-compose")
-            .arg("down")
-            .arg("--volumes")
-            .current_dir(&working_dir)
-            .output()?;
-        if !docker_output.status.success() {
-            return Err(eyre!(
-                "'docker-compose down' failed with {:?}",
-                String::from_utf8(docker_output.stderr)?
-            ));
-        }
-
-        // start the containers
-        let docker_output = Command::new("docker-compose")
-            .arg("up")
-            .arg("-d")
-            .current_dir(&working_dir)
-            .output()?;
-        if !docker_output.status.success() {
-            return Err(eyre!(
-                "'docker-compose up' failed with {:?}",
-                String::from_utf8(docker_output.stderr)?
-            ));
-        }
-
+        run_containers(&working_dir)?;
         println!("Grafana dashboard is running at http://localhost:3001/d/node_metrics/node-metrics?orgId=1&refresh=5s");
         println!("Connect with the following credentials\nusername:admin\npassword:pwd");
     } else {
         println!("The Prometheus config file has been updated with the metrics server URLs. The containers are not yet started\nRead the docs to start/stop the containers.");
     }
 
+    if let Some(address) = serve_address {
+        discovery::serve(sources, address, Duration::from_secs(refresh_interval)).await?;
+    }
+
     Ok(())
 }
 
-// Parse node logs files and extract the metrics server url for each node
-fn get_metric_servers(path: &Path) -> Result<BTreeMap<NodeId, url::Url>> {
-    let mut urls = BTreeMap::<NodeId, url::Url>::new();
-    let re_node_id = Regex::new(r"Node \(PID: (\d+)\) with PeerId: (.*)")?;
-    let re_metrics_server = Regex::new(r"Metrics server on (.*)")?;
-
-    let log_files = WalkDir::new(path).into_iter().filter_map(|entry| {
-        entry.ok().and_then(|f| {
-            if f.file_type().is_file() {
-                Some(f.into_path())
-            } else {
-                None
-            }
-        })
-    });
-
-    for file_path in log_files {
-        let file_name = if let Some(name) = file_path.file_name().and_then(|s| s.to_str()) {
-            name
-        } else {
-            println!("Failed to obtain filename from {}", file_path.display());
-            continue;
-        };
-
-        if file_name.starts_with(LOG_FILENAME_PREFIX) {
-            let file = File::open(&file_path)?;
-            let lines = BufReader::new(file).lines().map_while(|item| item.ok());
-
-            let mut peer_id: Option<NodeId> = None;
-            let mut metrics_server_url: Option<url::Url> = None;
-            for line in lines {
-                if peer_id.is_some() && metrics_server_url.is_some() {
-                    break;
-                }
-
-                if let Some(cap) = re_node_id.captures_iter(&line).next() {
-                    peer_id = Some(
-                        cap[2]
-                            .parse()
-                            .expect("Failed to parse NodeId from node log"),
-                    );
-                }
-
-                if let Some(cap) = re_metrics_server.captures_iter(&line).next() {
-                    let url = url::Url::parse(&cap[1])
-                        .expect("Failed to parse metrics server URL from node log");
-                    metrics_server_url = Some(url);
-                }
-            }
-
-            if let (Some(node), Some(url)) = (peer_id, metrics_server_url) {
-                urls.insert(node, url);
-            }
-        }
+fn run_containers(working_dir: &Path) -> Result<()> {
+    // stop the containers if running already
+    let docker_output = Command::new("docker-compose")
+        .arg("down")
+        .arg("--volumes")
+        .current_dir(working_dir)
+        .output()?;
+    if !docker_output.status.success() {
+        return Err(eyre!(
+            "'docker-compose down' failed with {:?}",
+            String::from_utf8(docker_output.stderr)?
+        ));
     }
 
-    Ok(urls)
+    // start the containers
+    let docker_output = Command::new("docker-compose")
+        .arg("up")
+        .arg("-d")
+        .current_dir(working_dir)
+        .output()?;
+    if !docker_output.status.success() {
+        return Err(eyre!(
+            "'docker-compose up' failed with {:?}",
+            String::from_utf8(docker_output.stderr)?
+        ));
+    }
+    Ok(())
 }
 
-// build the prometheus config given the NodeId and the metrics server url
-fn build_prometheus_config(metrics_server_list: BTreeMap<NodeId, url::Url>) -> PrometheusConfig {
-    let static_configs = metrics_server_list
-        .into_iter()
-        .map(|(node_id, url)| StaticConfig {
-            targets: vec![format!(
-                "host.docker.internal:{}",
-                url.port()
-                    .expect("Port should be present for the metrics server")
-            )],
-            labels: Labels {
-                node_id: last_n_chars(&node_id, 4),
-            },
-        })
-        .collect();
+// build the prometheus config given where the metrics servers are found
+fn build_prometheus_config(targets: Targets) -> PrometheusConfig {
+    let (static_configs, http_sd_configs) = match targets {
+        Targets::Static(targets) => (targets, Vec::new()),
+        Targets::Discovery {
+            port,
+            refresh_interval,
+        } => (
+            Vec::new(),
+            vec![HttpSdConfig {
+                // Prometheus runs in a container, so it reaches this binary through the host
+                url: format!(
+                    "http://host.docker.internal:{port}{}",
+                    discovery::TARGETS_PATH
+                ),
+                refresh_interval: format!("{refresh_interval}s"),
+            }],
+        ),
+    };
     PrometheusConfig {
         global: Global {
             scrape_interval: "15s".to_string(),
@@ -217,6 +286,7 @@ fn build_prometheus_config(metrics_server_list: BTreeMap<NodeId, url::Url>) -> P
             job_name: "safe_network_testnet".to_string(),
             scrape_interval: "5s".to_string(),
             static_configs,
+            http_sd_configs,
         }],
     }
 }
@@ -230,13 +300,3 @@ fn get_working_dir() -> Result<PathBuf> {
         Ok(working_dir.join("metrics"))
     }
 }
-
-fn last_n_chars(s: &str, n: usize) -> String {
-    s.chars()
-        .rev()
-        .take(n)
-        .collect::<String>()
-        .chars()
-        .rev()
-        .collect()
-}
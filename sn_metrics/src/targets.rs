@@ -0,0 +1,277 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Finding the metrics servers of nodes, from their logs, node registries or daemons.
+
+use color_eyre::{eyre::eyre, Result};
+use libp2p_identity::PeerId;
+use regex::Regex;
+use sn_node_manager::rpc_client::{connect_to_daemon, DaemonClient, DaemonConnectionOptions};
+use sn_service_management::{
+    safenode_manager_proto::{
+        get_status_response::ServiceStatus as RpcServiceStatus, GetStatusRequest,
+    },
+    NodeRegistry, ServiceStatus,
+};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+const LOG_FILENAME_PREFIX: &str = "safenode.log";
+/// The host Prometheus reaches this machine on from inside its container.
+const DOCKER_HOST: &str = "host.docker.internal";
+
+/// A group of scrape targets that share labels, in the form used by both the `static_configs` of
+/// the Prometheus config and by Prometheus HTTP service discovery.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct TargetGroup {
+    pub targets: Vec<String>,
+    pub labels: BTreeMap<String, String>,
+}
+
+impl TargetGroup {
+    fn new(host: &str, port: u16, peer_id: &str) -> Self {
+        let mut labels = BTreeMap::new();
+        labels.insert("node_id".to_string(), last_n_chars(peer_id, 4));
+        labels.insert("peer_id".to_string(), peer_id.to_string());
+        Self {
+            targets: vec![format!("{host}:{port}")],
+            labels,
+        }
+    }
+
+    fn with_label(mut self, name: &str, value: &str) -> Self {
+        self.labels.insert(name.to_string(), value.to_string());
+        self
+    }
+}
+
+/// Somewhere to find nodes and their metrics servers.
+pub enum TargetSource {
+    /// The logs of nodes, in a directory that is scanned recursively.
+    LogDir(PathBuf),
+    /// A node registry file, as written by `safenode-manager`.
+    Registry(PathBuf),
+    /// A `safenodemand` daemon, at an address in the form `host:port`.
+    Daemon {
+        address: String,
+        options: DaemonConnectionOptions,
+        client: Option<DaemonClient>,
+    },
+}
+
+impl TargetSource {
+    /// A name for the source, for messages and the `source` label of its targets.
+    pub fn name(&self) -> String {
+        match self {
+            TargetSource::LogDir(path) | TargetSource::Registry(path) => {
+                path.to_string_lossy().to_string()
+            }
+            TargetSource::Daemon { address, .. } => address.clone(),
+        }
+    }
+
+    /// The metrics servers of the nodes the source currently knows about. Nodes that aren't
+    /// running or don't have a metrics server are left out.
+    pub async fn targets(&mut self) -> Result<Vec<TargetGroup>> {
+        let source = self.name();
+        let targets = match self {
+            TargetSource::LogDir(path) => targets_from_logs(path)?,
+            TargetSource::Registry(path) => targets_from_registry(path)?,
+            TargetSource::Daemon {
+                address,
+                options,
+                client,
+            } => {
+                let daemon_client = match client {
+                    Some(daemon_client) => daemon_client,
+                    None => client.insert(connect_to_daemon(address, options).await?),
+                };
+                let result = targets_from_daemon(daemon_client, address).await;
+                if result.is_err() {
+                    // Connect again next time, in case the daemon was restarted.
+                    *client = None;
+                }
+                result?
+            }
+        };
+        Ok(targets
+            .into_iter()
+            .map(|group| group.with_label("source", &source))
+            .collect())
+    }
+}
+
+/// Parse node logs files and extract the metrics server url for each node
+fn targets_from_logs(path: &Path) -> Result<Vec<TargetGroup>> {
+    let mut urls = BTreeMap::<String, url::Url>::new();
+    let re_node_id = Regex::new(r"Node \(PID: (\d+)\) with PeerId: (.*)")?;
+    let re_metrics_server = Regex::new(r"Metrics server on (.*)")?;
+
+    let log_files = WalkDir::new(path).into_iter().filter_map(|entry| {
+        entry.ok().and_then(|f| {
+            if f.file_type().is_file() {
+                Some(f.into_path())
+            } else {
+                None
+            }
+        })
+    });
+
+    for file_path in log_files {
+        let file_name = if let Some(name) = file_path.file_name().and_then(|s| s.to_str()) {
+            name
+        } else {
+            println!("Failed to obtain filename from {}", file_path.display());
+            continue;
+        };
+
+        if file_name.starts_with(LOG_FILENAME_PREFIX) {
+            let file = File::open(&file_path)?;
+            let lines = BufReader::new(file).lines().map_while(|item| item.ok());
+
+            let mut peer_id: Option<String> = None;
+            let mut metrics_server_url: Option<url::Url> = None;
+            for line in lines {
+                if peer_id.is_some() && metrics_server_url.is_some() {
+                    break;
+                }
+
+                if let Some(cap) = re_node_id.captures_iter(&line).next() {
+                    peer_id = Some(cap[2].to_string());
+                }
+
+                if let Some(cap) = re_metrics_server.captures_iter(&line).next() {
+                    let url = url::Url::parse(&cap[1]).map_err(|err| {
+                        eyre!("Failed to parse metrics server URL from node log: {err}")
+                    })?;
+                    metrics_server_url = Some(url);
+                }
+            }
+
+            if let (Some(node), Some(url)) = (peer_id, metrics_server_url) {
+                urls.insert(node, url);
+            }
+        }
+    }
+
+    urls.into_iter()
+        .map(|(peer_id, url)| {
+            let port = url
+                .port()
+                .ok_or_else(|| eyre!("The metrics server URL {url} has no port"))?;
+            Ok(TargetGroup::new(DOCKER_HOST, port, &peer_id))
+        })
+        .collect()
+}
+
+fn targets_from_registry(path: &Path) -> Result<Vec<TargetGroup>> {
+    let node_registry = NodeRegistry::load(path)?;
+    Ok(node_registry
+        .nodes
+        .iter()
+        .filter(|node| node.status == ServiceStatus::Running)
+        .filter_map(|node| match (node.peer_id, node.metrics_port) {
+            (Some(peer_id), Some(port)) => Some(
+                TargetGroup::new(DOCKER_HOST, port, &peer_id.to_string())
+                    .with_label("service_name", &node.service_name),
+            ),
+            _ => None,
+        })
+        .collect())
+}
+
+async fn targets_from_daemon(client: &mut DaemonClient, address: &str) -> Result<Vec<TargetGroup>> {
+    let response = client
+        .get_status(GetStatusRequest {})
+        .await
+        .map_err(|err| eyre!("Failed to get the status of the nodes from {address}: {err}"))?
+        .into_inner();
+    let host = scrape_host(address);
+
+    let mut targets = Vec::new();
+    for node in response.nodes {
+        if node.status != RpcServiceStatus::Running as i32 {
+            continue;
+        }
+        let (peer_id, port) = match (node.peer_id, node.metrics_port) {
+            (Some(peer_id), Some(port)) => (PeerId::from_bytes(&peer_id)?, port),
+            _ => continue,
+        };
+        let port = u16::try_from(port)
+            .map_err(|_| eyre!("The daemon at {address} reported an invalid port {port}"))?;
+        targets.push(
+            TargetGroup::new(&host, port, &peer_id.to_string())
+                .with_label("service_name", &node.service_name),
+        );
+    }
+    Ok(targets)
+}
+
+/// The host to scrape the nodes of the daemon at `address` on. Prometheus runs in a container, so
+/// a daemon on this machine is reached through the Docker host rather than the loopback address.
+fn scrape_host(address: &str) -> String {
+    let host = address
+        .rsplit_once(':')
+        .map(|(host, _)| host)
+        .unwrap_or(address);
+    let is_loopback = host == "localhost"
+        || host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .map(|ip| ip.is_loopback())
+            .unwrap_or(false);
+    if is_loopback {
+        DOCKER_HOST.to_string()
+    } else {
+        host.to_string()
+    }
+}
+
+fn last_n_chars(s: &str, n: usize) -> String {
+    s.chars()
+        .rev()
+        .take(n)
+        .collect::<String>()
+        .chars()
+        .rev()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn daemons_on_this_machine_should_be_scraped_through_the_docker_host() {
+        assert_eq!(scrape_host("localhost:12500"), DOCKER_HOST);
+        assert_eq!(scrape_host("127.0.0.1:12500"), DOCKER_HOST);
+        assert_eq!(scrape_host("[::1]:12500"), DOCKER_HOST);
+        assert_eq!(scrape_host("10.0.0.5:12500"), "10.0.0.5");
+        assert_eq!(scrape_host("node-host.example:12500"), "node-host.example");
+    }
+
+    #[test]
+    fn target_groups_should_serialize_for_http_service_discovery() -> Result<()> {
+        let group = TargetGroup::new("10.0.0.5", 13001, "12D3KooWabcd").with_label("source", "a");
+        assert_eq!(
+            serde_json::to_value(vec![group])?,
+            serde_json::json!([{
+                "targets": ["10.0.0.5:13001"],
+                "labels": {"node_id": "abcd", "peer_id": "12D3KooWabcd", "source": "a"}
+            }])
+        );
+        Ok(())
+    }
+}
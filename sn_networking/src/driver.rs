@@ -10,6 +10,8 @@
 use crate::metrics::NetworkMetrics;
 #[cfg(feature = "open-metrics")]
 use crate::metrics_service::run_metrics_server;
+#[cfg(feature = "sim")]
+use crate::sim::SimNetwork;
 use crate::{
     bootstrap::{ContinuousBootstrap, BOOTSTRAP_INTERVAL},
     circular_vec::CircularVec,
@@ -212,6 +214,8 @@ pub struct NetworkBuilder {
     metrics_registry: Option<Registry>,
     #[cfg(feature = "open-metrics")]
     metrics_server_port: u16,
+    #[cfg(feature = "sim")]
+    sim: Option<SimNetwork>,
     #[cfg(feature = "upnp")]
     upnp: bool,
 }
@@ -231,6 +235,8 @@ impl NetworkBuilder {
             metrics_registry: None,
             #[cfg(feature = "open-metrics")]
             metrics_server_port: 0,
+            #[cfg(feature = "sim")]
+            sim: None,
             #[cfg(feature = "upnp")]
             upnp: false,
         }
@@ -266,6 +272,13 @@ impl NetworkBuilder {
         self.metrics_server_port = port;
     }
 
+    /// Connect through the simulated network rather than real sockets. The listen address
+    /// isn't used, because the node listens on the memory transport instead.
+    #[cfg(feature = "sim")]
+    pub fn sim(&mut self, sim: SimNetwork) {
+        self.sim = Some(sim);
+    }
+
     #[cfg(feature = "upnp")]
     pub fn upnp(&mut self, upnp: bool) {
         self.upnp = upnp;
@@ -330,6 +343,8 @@ impl NetworkBuilder {
         };
 
         let listen_addr = self.listen_addr;
+        #[cfg(feature = "sim")]
+        let sim_listen_addr = self.sim.as_ref().map(|sim| sim.next_listen_addr());
         #[cfg(feature = "upnp")]
         let upnp = self.upnp;
 
@@ -343,6 +358,12 @@ impl NetworkBuilder {
             upnp,
         )?;
 
+        #[cfg(feature = "sim")]
+        if let Some(addr) = sim_listen_addr {
+            swarm_driver.listen_on(addr)?;
+            return Ok((network, events_receiver, swarm_driver));
+        }
+
         // Listen on the provided address
         let listen_socket_addr = listen_addr.ok_or(NetworkError::ListenAddressNotProvided)?;
 
@@ -462,7 +483,8 @@ impl NetworkBuilder {
                     );
                     #[cfg(feature = "open-metrics")]
                     let node_record_store = node_record_store
-                        .set_record_count_metric(network_metrics.records_stored.clone());
+                        .set_record_count_metric(network_metrics.records_stored.clone())
+                        .set_store_cost_metric(network_metrics.store_cost.clone());
                     let store = UnifiedRecordStore::Node(node_record_store);
                     debug!("Using Kademlia with NodeRecordStore!");
                     kad::Behaviour::with_config(peer_id, store, kad_cfg)
@@ -497,6 +519,12 @@ impl NetworkBuilder {
             libp2p::identify::Behaviour::new(cfg)
         };
 
+        #[cfg(feature = "sim")]
+        let main_transport = match &self.sim {
+            Some(sim) => sim.build_transport(&self.keypair),
+            None => transport::build_transport(&self.keypair),
+        };
+        #[cfg(not(feature = "sim"))]
         let main_transport = transport::build_transport(&self.keypair);
 
         let transport = if !self.local {
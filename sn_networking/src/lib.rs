@@ -25,6 +25,8 @@ mod record_store;
 mod record_store_api;
 mod relay_manager;
 mod replication_fetcher;
+#[cfg(feature = "sim")]
+pub mod sim;
 mod spends;
 pub mod target_arch;
 mod transfers;
@@ -23,6 +23,7 @@ pub(crate) struct NetworkMetrics {
 
     // metrics from sn_networking
     pub(crate) records_stored: Gauge,
+    pub(crate) store_cost: Gauge,
 
     // system info
     process_memory_used_mb: Gauge,
@@ -41,6 +42,13 @@ impl NetworkMetrics {
             records_stored.clone(),
         );
 
+        let store_cost = Gauge::default();
+        sub_registry.register(
+            "store_cost",
+            "The cost in Nanos the node last quoted to store a record",
+            store_cost.clone(),
+        );
+
         let process_memory_used_mb = Gauge::default();
         sub_registry.register(
             "process_memory_used_mb",
@@ -58,6 +66,7 @@ impl NetworkMetrics {
         let network_metrics = Self {
             libp2p_metrics,
             records_stored,
+            store_cost,
             process_memory_used_mb,
             process_cpu_usage_percentage,
         };
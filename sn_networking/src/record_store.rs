@@ -81,6 +81,9 @@ pub struct NodeRecordStore {
     #[cfg(feature = "open-metrics")]
     /// Used to report the number of records held by the store to the metrics server.
     record_count_metric: Option<Gauge>,
+    #[cfg(feature = "open-metrics")]
+    /// Used to report the last quoted store cost to the metrics server.
+    store_cost_metric: Option<Gauge>,
     /// Counting how many times got paid
     received_payment_count: usize,
     /// Encyption cipher for the records, randomly generated at node startup
@@ -270,6 +273,8 @@ impl NodeRecordStore {
             responsible_distance_range: None,
             #[cfg(feature = "open-metrics")]
             record_count_metric: None,
+            #[cfg(feature = "open-metrics")]
+            store_cost_metric: None,
             received_payment_count,
             encryption_details,
             timestamp,
@@ -290,6 +295,13 @@ impl NodeRecordStore {
         self
     }
 
+    /// Set the store_cost_metric to report the last quoted store cost to the metrics server
+    #[cfg(feature = "open-metrics")]
+    pub fn set_store_cost_metric(mut self, metric: Gauge) -> Self {
+        self.store_cost_metric = Some(metric);
+        self
+    }
+
     /// Returns the current distance ilog2 (aka bucket) range of CLOSE_GROUP nodes.
     pub fn get_responsible_distance_range(&self) -> Option<u32> {
         self.responsible_distance_range
@@ -572,7 +584,12 @@ impl NodeRecordStore {
         let cost = if self.contains(key) {
             0
         } else {
-            calculate_cost_for_records(&quoting_metrics)
+            let cost = calculate_cost_for_records(&quoting_metrics);
+            #[cfg(feature = "open-metrics")]
+            if let Some(metric) = &self.store_cost_metric {
+                let _ = metric.set(cost as i64);
+            }
+            cost
         };
         // vdash metric (if modified please notify at https://github.com/happybeing/vdash/issues):
         info!("Cost is now {cost:?} for quoting_metrics {quoting_metrics:?}");
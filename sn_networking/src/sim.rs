@@ -0,0 +1,422 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! An in-process network for simulations, built on libp2p's memory transport.
+//!
+//! Every peer that is built with the same `SimNetwork` can reach the others without any sockets.
+//! The conditions of the link between each pair of peers can be changed while they run: latency
+//! is added when a stream is opened, so each request is delayed by it, and a lost stream fails
+//! the request that opened it. Peers in different partitions can't connect, and connections
+//! between them are closed when the partition is made.
+//!
+//! Latency is measured with the tokio clock, so a runtime with paused time runs a simulation
+//! without waiting for it. With this feature on, the crate's own timers use that clock too.
+//!
+//! The seed fixes which streams are lost, but not everything else: libp2p and the nodes draw
+//! their own randomness, so two runs with the same seed can still differ in their timing.
+
+use futures::{future, AsyncRead, AsyncWrite};
+use libp2p::{
+    core::{
+        muxing::{StreamMuxer, StreamMuxerBox, StreamMuxerEvent, StreamMuxerExt, SubstreamBox},
+        transport::{Boxed, MemoryTransport},
+        upgrade,
+    },
+    identity::Keypair,
+    multiaddr::Protocol,
+    noise, yamux, Multiaddr, PeerId, Transport,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    collections::HashMap,
+    future::Future as _,
+    io,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+use tokio::time::{Duration, Sleep};
+
+/// Memory transport ports are shared by the whole process, so simulations running side by side
+/// take them from the same counter.
+static NEXT_MEMORY_PORT: AtomicU64 = AtomicU64::new(1);
+
+/// The conditions of the link between two peers.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LinkConditions {
+    /// The delay before each stream between the peers is opened.
+    pub latency: Duration,
+    /// The probability, from 0 to 1, that a stream between the peers is lost.
+    pub loss: f64,
+}
+
+struct SimState {
+    default_link: LinkConditions,
+    /// Links that differ from the default, keyed by the pair of peers in order.
+    links: HashMap<(PeerId, PeerId), LinkConditions>,
+    /// The partition each peer is in. Peers that aren't in one can reach every peer.
+    partitions: HashMap<PeerId, usize>,
+    rng: StdRng,
+    /// Wakes each connection when a partition is made, so it can close if it crosses it.
+    connection_wakers: HashMap<u64, Waker>,
+    next_connection_id: u64,
+}
+
+/// The shared state of a simulated network. Clones refer to the same network.
+#[derive(Clone)]
+pub struct SimNetwork {
+    state: Arc<Mutex<SimState>>,
+}
+
+impl std::fmt::Debug for SimNetwork {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SimNetwork").finish_non_exhaustive()
+    }
+}
+
+fn link_key(a: PeerId, b: PeerId) -> (PeerId, PeerId) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+impl SimNetwork {
+    /// Create a network where every link has no latency or loss. The seed is used to decide which
+    /// streams are lost.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(SimState {
+                default_link: LinkConditions::default(),
+                links: HashMap::new(),
+                partitions: HashMap::new(),
+                rng: StdRng::seed_from_u64(seed),
+                connection_wakers: HashMap::new(),
+                next_connection_id: 0,
+            })),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, SimState> {
+        // The state is never left inconsistent by a panic, so a poisoned lock can still be used.
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Set the conditions for every link that hasn't been given its own.
+    pub fn set_default_link(&self, conditions: LinkConditions) {
+        self.state().default_link = conditions;
+    }
+
+    /// Set the conditions for the link between two peers, in both directions.
+    pub fn set_link(&self, a: PeerId, b: PeerId, conditions: LinkConditions) {
+        let _ = self.state().links.insert(link_key(a, b), conditions);
+    }
+
+    /// Return the link between two peers to the default conditions.
+    pub fn reset_link(&self, a: PeerId, b: PeerId) {
+        let _ = self.state().links.remove(&link_key(a, b));
+    }
+
+    pub fn link(&self, a: PeerId, b: PeerId) -> LinkConditions {
+        let state = self.state();
+        state
+            .links
+            .get(&link_key(a, b))
+            .copied()
+            .unwrap_or(state.default_link)
+    }
+
+    /// Split the peers into groups that can't reach each other. Peers that aren't in any group
+    /// can still reach every peer. This replaces any previous partition.
+    pub fn partition(&self, groups: &[Vec<PeerId>]) {
+        let mut state = self.state();
+        state.partitions = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(index, group)| group.iter().map(move |peer| (*peer, index)))
+            .collect();
+        for waker in state.connection_wakers.values() {
+            waker.wake_by_ref();
+        }
+    }
+
+    /// Remove the partition, so every peer can reach every other again.
+    pub fn heal(&self) {
+        self.state().partitions.clear();
+    }
+
+    pub fn is_reachable(&self, a: PeerId, b: PeerId) -> bool {
+        let state = self.state();
+        match (state.partitions.get(&a), state.partitions.get(&b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        }
+    }
+
+    fn is_stream_lost(&self, a: PeerId, b: PeerId) -> bool {
+        let loss = self.link(a, b).loss;
+        loss > 0.0 && self.state().rng.gen_bool(loss.min(1.0))
+    }
+
+    /// An address on the memory transport that no other peer is listening on.
+    pub fn next_listen_addr(&self) -> Multiaddr {
+        Multiaddr::empty().with(Protocol::Memory(
+            NEXT_MEMORY_PORT.fetch_add(1, Ordering::Relaxed),
+        ))
+    }
+
+    /// Build the transport for a peer in this network. Connections are secured and multiplexed
+    /// the same way they are over real sockets.
+    pub(crate) fn build_transport(&self, keypair: &Keypair) -> Boxed<(PeerId, StreamMuxerBox)> {
+        let local_peer_id = PeerId::from(keypair.public());
+        let sim = self.clone();
+        MemoryTransport::default()
+            .upgrade(upgrade::Version::V1)
+            .authenticate(
+                noise::Config::new(keypair)
+                    .expect("Signing libp2p-noise static DH keypair failed."),
+            )
+            .multiplex(yamux::Config::default())
+            .and_then(move |(peer_id, muxer), _| {
+                if sim.is_reachable(local_peer_id, peer_id) {
+                    let connection_id = {
+                        let mut state = sim.state();
+                        state.next_connection_id += 1;
+                        state.next_connection_id
+                    };
+                    let muxer = SimMuxer {
+                        connection_id,
+                        inner: StreamMuxerBox::new(muxer),
+                        local_peer_id,
+                        outbound_delay: None,
+                        peer_id,
+                        sim: sim.clone(),
+                    };
+                    future::ready(Ok((peer_id, StreamMuxerBox::new(muxer))))
+                } else {
+                    future::ready(Err(io::Error::new(
+                        io::ErrorKind::ConnectionRefused,
+                        "the peers are in different partitions",
+                    )))
+                }
+            })
+            .boxed()
+    }
+}
+
+/// Applies the conditions of the link to the streams of a connection.
+struct SimMuxer {
+    connection_id: u64,
+    inner: StreamMuxerBox,
+    local_peer_id: PeerId,
+    outbound_delay: Option<Pin<Box<Sleep>>>,
+    peer_id: PeerId,
+    sim: SimNetwork,
+}
+
+impl SimMuxer {
+    fn wrap(&self, stream: SubstreamBox) -> SimStream {
+        if !self.sim.is_reachable(self.local_peer_id, self.peer_id)
+            || self.sim.is_stream_lost(self.local_peer_id, self.peer_id)
+        {
+            SimStream::Lost
+        } else {
+            SimStream::Open(stream)
+        }
+    }
+}
+
+impl StreamMuxer for SimMuxer {
+    type Substream = SimStream;
+    type Error = io::Error;
+
+    fn poll_inbound(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        match self.inner.poll_inbound_unpin(cx) {
+            Poll::Ready(Ok(stream)) => Poll::Ready(Ok(self.wrap(stream))),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn poll_outbound(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Self::Substream, Self::Error>> {
+        if self.outbound_delay.is_none() {
+            let latency = self.sim.link(self.local_peer_id, self.peer_id).latency;
+            if !latency.is_zero() {
+                self.outbound_delay = Some(Box::pin(tokio::time::sleep(latency)));
+            }
+        }
+        if let Some(delay) = self.outbound_delay.as_mut() {
+            if delay.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+
+        match self.inner.poll_outbound_unpin(cx) {
+            Poll::Ready(Ok(stream)) => {
+                self.outbound_delay = None;
+                Poll::Ready(Ok(self.wrap(stream)))
+            }
+            Poll::Ready(Err(err)) => {
+                self.outbound_delay = None;
+                Poll::Ready(Err(err))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_close_unpin(cx)
+    }
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<StreamMuxerEvent, Self::Error>> {
+        if !self.sim.is_reachable(self.local_peer_id, self.peer_id) {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::ConnectionReset,
+                "the peers are in different partitions",
+            )));
+        }
+        let _ = self
+            .sim
+            .state()
+            .connection_wakers
+            .insert(self.connection_id, cx.waker().clone());
+        self.inner.poll_unpin(cx)
+    }
+}
+
+impl Drop for SimMuxer {
+    fn drop(&mut self) {
+        let _ = self
+            .sim
+            .state()
+            .connection_wakers
+            .remove(&self.connection_id);
+    }
+}
+
+/// A stream over a simulated link, which fails straight away if it was lost.
+enum SimStream {
+    Open(SubstreamBox),
+    Lost,
+}
+
+fn lost_stream_error() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "the stream was lost")
+}
+
+impl AsyncRead for SimStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            SimStream::Open(stream) => Pin::new(stream).poll_read(cx, buf),
+            SimStream::Lost => Poll::Ready(Err(lost_stream_error())),
+        }
+    }
+}
+
+impl AsyncWrite for SimStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            SimStream::Open(stream) => Pin::new(stream).poll_write(cx, buf),
+            SimStream::Lost => Poll::Ready(Err(lost_stream_error())),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            SimStream::Open(stream) => Pin::new(stream).poll_flush(cx),
+            SimStream::Lost => Poll::Ready(Err(lost_stream_error())),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            SimStream::Open(stream) => Pin::new(stream).poll_close(cx),
+            SimStream::Lost => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partitions_should_only_separate_peers_in_different_groups() {
+        let sim = SimNetwork::new(0);
+        let peers = (0..5).map(|_| PeerId::random()).collect::<Vec<_>>();
+
+        sim.partition(&[peers[0..2].to_vec(), peers[2..4].to_vec()]);
+        assert!(sim.is_reachable(peers[0], peers[1]));
+        assert!(!sim.is_reachable(peers[0], peers[2]));
+        assert!(!sim.is_reachable(peers[3], peers[1]));
+        // The last peer isn't in a group, so it can reach everyone.
+        assert!(sim.is_reachable(peers[4], peers[0]));
+        assert!(sim.is_reachable(peers[4], peers[3]));
+
+        sim.heal();
+        assert!(sim.is_reachable(peers[0], peers[2]));
+    }
+
+    #[test]
+    fn links_should_be_the_same_in_both_directions() {
+        let sim = SimNetwork::new(0);
+        let (a, b, c) = (PeerId::random(), PeerId::random(), PeerId::random());
+        let slow = LinkConditions {
+            latency: Duration::from_millis(200),
+            loss: 0.1,
+        };
+
+        sim.set_link(a, b, slow);
+        assert_eq!(sim.link(b, a), slow);
+        assert_eq!(sim.link(a, c), LinkConditions::default());
+
+        sim.reset_link(b, a);
+        assert_eq!(sim.link(a, b), LinkConditions::default());
+    }
+
+    #[test]
+    fn streams_should_be_lost_at_the_rate_of_the_link() {
+        let sim = SimNetwork::new(42);
+        let (a, b) = (PeerId::random(), PeerId::random());
+        assert!(!sim.is_stream_lost(a, b));
+
+        sim.set_link(
+            a,
+            b,
+            LinkConditions {
+                latency: Duration::ZERO,
+                loss: 0.25,
+            },
+        );
+        let lost = (0..10_000).filter(|_| sim.is_stream_lost(a, b)).count();
+        assert!((2_000..3_000).contains(&lost), "{lost} streams were lost");
+    }
+}
@@ -6,8 +6,11 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-#[cfg(not(target_arch = "wasm32"))]
+#[cfg(all(not(target_arch = "wasm32"), not(feature = "sim")))]
 pub use std::time::Instant;
+/// Simulations run on tokio's clock, which tests can pause and advance
+#[cfg(all(not(target_arch = "wasm32"), feature = "sim"))]
+pub use tokio::time::Instant;
 /// Wasm32 target arch does not support `time` or spawning via tokio
 /// so we shim in alternatives here when building for that architecture
 
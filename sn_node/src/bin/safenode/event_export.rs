@@ -0,0 +1,499 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Exporting the `NodeEvent`s of the node as JSON, to a rotating file and/or a webhook, so they can
+//! be fed into a logging pipeline without parsing the log text.
+
+use async_trait::async_trait;
+use eyre::{eyre, Result};
+use file_rotate::{compression::Compression, suffix::AppendCount, ContentLimit, FileRotate};
+use libp2p::PeerId;
+use serde::Serialize;
+use sn_node::{NodeEvent, NodeEventsChannel, NodeEventsReceiver};
+use std::{io::Write, path::PathBuf, sync::Arc, time::Duration};
+use strum::VariantNames;
+use tokio::{
+    sync::{
+        broadcast::error::{RecvError, TryRecvError},
+        watch,
+    },
+    task::JoinHandle,
+};
+
+/// The size the events file can grow to before it is rotated
+const EVENTS_FILE_MAX_BYTES: usize = 10 * 1024 * 1024;
+/// The number of rotated events files to keep, the oldest are deleted
+const EVENTS_FILE_MAX_FILES: usize = 10;
+/// The most events sent to a sink at once
+const BATCH_SIZE: usize = 100;
+/// How long events can wait for a batch to fill before being sent anyway
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+/// The number of attempts made to deliver a batch of events to a webhook
+const WEBHOOK_ATTEMPTS: u32 = 3;
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the node waits for the sinks to deliver their last events when it stops, which allows
+/// a webhook a few attempts
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Check the name of a `NodeEvent` variant given on the command line.
+pub fn parse_event_kind(val: &str) -> Result<String> {
+    if NodeEvent::VARIANTS.contains(&val) {
+        Ok(val.to_string())
+    } else {
+        Err(eyre!(
+            "Unknown node event {val:?}, expected one of: {}",
+            NodeEvent::VARIANTS.join(", ")
+        ))
+    }
+}
+
+/// A node event as it is exported, one per line of the events file
+#[derive(Clone, Debug, Serialize)]
+pub struct EventRecord {
+    /// When the event was received, in RFC 3339 format
+    pub timestamp: String,
+    pub peer_id: String,
+    /// The name of the `NodeEvent` variant
+    pub kind: &'static str,
+    pub event: NodeEvent,
+}
+
+impl EventRecord {
+    fn new(event: NodeEvent, peer_id: PeerId) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            peer_id: peer_id.to_string(),
+            kind: (&event).into(),
+            event,
+        }
+    }
+}
+
+/// A destination for node events
+#[async_trait]
+pub trait EventSink: Send {
+    /// A short name to identify the sink in the logs
+    fn name(&self) -> String;
+
+    /// Deliver a batch of events
+    async fn send(&mut self, records: &[EventRecord]) -> Result<()>;
+}
+
+/// Appends the events to a file as NDJSON, rotating it once it grows past
+/// `EVENTS_FILE_MAX_BYTES`
+pub struct FileSink {
+    path: PathBuf,
+    writer: FileRotate<AppendCount>,
+}
+
+impl FileSink {
+    pub fn new(path: PathBuf) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let writer = FileRotate::new(
+            &path,
+            AppendCount::new(EVENTS_FILE_MAX_FILES),
+            ContentLimit::BytesSurpassed(EVENTS_FILE_MAX_BYTES),
+            Compression::None,
+            #[cfg(unix)]
+            None,
+        );
+        Ok(Self { path, writer })
+    }
+}
+
+#[async_trait]
+impl EventSink for FileSink {
+    fn name(&self) -> String {
+        format!("events file {:?}", self.path)
+    }
+
+    async fn send(&mut self, records: &[EventRecord]) -> Result<()> {
+        for record in records {
+            // Each line is written whole, so a rotation never splits one.
+            let mut line = serde_json::to_vec(record)?;
+            line.push(b'\n');
+            self.writer.write_all(&line)?;
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// POSTs batches of events as JSON to a URL: `{"events": [...]}`
+pub struct WebhookSink {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl WebhookSink {
+    pub fn new(url: String) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(WEBHOOK_TIMEOUT)
+            .build()
+            .map_err(|e| eyre!("Failed to create webhook client: {e}"))?;
+        Ok(Self { url, client })
+    }
+}
+
+#[async_trait]
+impl EventSink for WebhookSink {
+    fn name(&self) -> String {
+        format!("webhook {}", self.url)
+    }
+
+    async fn send(&mut self, records: &[EventRecord]) -> Result<()> {
+        let payload = serde_json::json!({ "events": records });
+        let mut last_error = None;
+        for attempt in 1..=WEBHOOK_ATTEMPTS {
+            match self
+                .client
+                .post(&self.url)
+                .json(&payload)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+            {
+                Ok(_) => return Ok(()),
+                Err(e) => {
+                    warn!(
+                        "Attempt {attempt} to POST node events to {} failed: {e}",
+                        self.url
+                    );
+                    last_error = Some(e);
+                    tokio::time::sleep(Duration::from_secs(u64::from(attempt))).await;
+                }
+            }
+        }
+        Err(eyre!(
+            "Failed to POST node events to {}: {last_error:?}",
+            self.url
+        ))
+    }
+}
+
+/// Exports the node events to the configured sinks, each from its own task so a slow webhook
+/// doesn't hold up the file.
+pub struct EventExporter {
+    sinks: Vec<Box<dyn EventSink>>,
+    /// The names of the events to export, all of them if empty
+    kinds: Arc<Vec<String>>,
+}
+
+impl EventExporter {
+    pub fn new(sinks: Vec<Box<dyn EventSink>>, kinds: Vec<String>) -> Self {
+        Self {
+            sinks,
+            kinds: Arc::new(kinds),
+        }
+    }
+
+    /// Start exporting the events broadcast on the channel, until the returned handle is shut down
+    pub fn start(
+        self,
+        node_events_channel: &NodeEventsChannel,
+        peer_id: PeerId,
+    ) -> EventExportHandle {
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let mut tasks = Vec::new();
+        for sink in self.sinks {
+            info!(
+                "Exporting node events {:?} to {}",
+                self.kinds.as_slice(),
+                sink.name()
+            );
+            let node_events_rx = node_events_channel.subscribe();
+            tasks.push(tokio::spawn(export_events(
+                node_events_rx,
+                sink,
+                peer_id,
+                self.kinds.clone(),
+                shutdown_rx.clone(),
+            )));
+        }
+        EventExportHandle { shutdown_tx, tasks }
+    }
+}
+
+/// Stops the export tasks once the node is stopping or restarting
+pub struct EventExportHandle {
+    shutdown_tx: watch::Sender<bool>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl EventExportHandle {
+    /// Have each sink export the events it has received but not sent yet, and wait for them to be
+    /// delivered, for up to `SHUTDOWN_TIMEOUT`
+    pub async fn shutdown(self) {
+        if self.tasks.is_empty() {
+            return;
+        }
+        info!("Exporting the remaining node events");
+        let _ = self.shutdown_tx.send(true);
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, futures::future::join_all(self.tasks))
+            .await
+            .is_err()
+        {
+            warn!("Gave up exporting the remaining node events after {SHUTDOWN_TIMEOUT:?}");
+        }
+    }
+}
+
+fn is_exported(kinds: &[String], event: &NodeEvent) -> bool {
+    let kind: &str = event.into();
+    kinds.is_empty() || kinds.iter().any(|k| k == kind)
+}
+
+async fn export_events(
+    mut node_events_rx: NodeEventsReceiver,
+    mut sink: Box<dyn EventSink>,
+    peer_id: PeerId,
+    kinds: Arc<Vec<String>>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    let mut batch = Vec::new();
+    let mut flush_interval = tokio::time::interval(FLUSH_INTERVAL);
+    loop {
+        let flush = tokio::select! {
+            event = node_events_rx.recv() => match event {
+                Ok(event) => {
+                    // The node stops shortly after these, so don't wait for the batch to fill.
+                    let stopping = matches!(
+                        event,
+                        NodeEvent::ChannelClosed | NodeEvent::TerminateNode(_)
+                    );
+                    if is_exported(&kinds, &event) {
+                        batch.push(EventRecord::new(event, peer_id));
+                    }
+                    stopping || batch.len() >= BATCH_SIZE
+                }
+                Err(RecvError::Lagged(n)) => {
+                    warn!("Skipped {n} node events exporting to {}", sink.name());
+                    false
+                }
+                Err(RecvError::Closed) => {
+                    send_batch(sink.as_mut(), &mut batch).await;
+                    break;
+                }
+            },
+            _ = flush_interval.tick() => true,
+            _ = shutdown_rx.changed() => {
+                // Take the events that were broadcast before the node stopped.
+                loop {
+                    match node_events_rx.try_recv() {
+                        Ok(event) => {
+                            if is_exported(&kinds, &event) {
+                                batch.push(EventRecord::new(event, peer_id));
+                            }
+                        }
+                        Err(TryRecvError::Lagged(n)) => {
+                            warn!("Skipped {n} node events exporting to {}", sink.name());
+                        }
+                        Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+                    }
+                }
+                while !batch.is_empty() {
+                    let mut next: Vec<_> = batch.drain(..batch.len().min(BATCH_SIZE)).collect();
+                    send_batch(sink.as_mut(), &mut next).await;
+                }
+                break;
+            }
+        };
+        if flush {
+            send_batch(sink.as_mut(), &mut batch).await;
+        }
+    }
+}
+
+async fn send_batch(sink: &mut dyn EventSink, batch: &mut Vec<EventRecord>) {
+    if batch.is_empty() {
+        return;
+    }
+    if let Err(e) = sink.send(batch).await {
+        error!(
+            "Failed to export {} node events to {}: {e}",
+            batch.len(),
+            sink.name()
+        );
+    }
+    batch.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_protocol::storage::ChunkAddress;
+    use xor_name::XorName;
+
+    #[test]
+    fn only_known_event_kinds_should_be_accepted() {
+        assert!(parse_event_kind("ChunkStored").is_ok());
+        assert!(parse_event_kind("TerminateNode").is_ok());
+        assert!(parse_event_kind("chunkstored").is_err());
+        assert!(parse_event_kind("ChunkFetched").is_err());
+    }
+
+    #[test]
+    fn events_should_be_filtered_by_kind() {
+        let event = NodeEvent::ChunkStored(ChunkAddress::new(XorName::default()));
+        assert!(is_exported(&[], &event));
+        assert!(is_exported(&["ChunkStored".to_string()], &event));
+        assert!(!is_exported(
+            &["SpendStored".to_string(), "TerminateNode".to_string()],
+            &event
+        ));
+    }
+
+    #[tokio::test]
+    async fn file_sink_should_write_one_node_event_per_line() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("events").join("node_events.ndjson");
+        let mut sink = FileSink::new(path.clone())?;
+        let peer_id = PeerId::random();
+        let address = ChunkAddress::new(XorName::random(&mut rand::thread_rng()));
+        sink.send(&[
+            EventRecord::new(NodeEvent::ChunkStored(address), peer_id),
+            EventRecord::new(NodeEvent::TerminateNode("test".to_string()), peer_id),
+        ])
+        .await?;
+
+        let contents = std::fs::read_to_string(&path)?;
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["kind"], "ChunkStored");
+        assert_eq!(lines[0]["peer_id"], peer_id.to_string());
+        match serde_json::from_value(lines[0]["event"].clone())? {
+            NodeEvent::ChunkStored(stored) => assert_eq!(stored, address),
+            event => panic!("Unexpected event {event:?}"),
+        }
+        assert_eq!(lines[1]["kind"], "TerminateNode");
+        Ok(())
+    }
+
+    /// Keeps the batches it's sent, to check what was exported
+    struct RecordingSink(Arc<std::sync::Mutex<Vec<Vec<EventRecord>>>>);
+
+    #[async_trait]
+    impl EventSink for RecordingSink {
+        fn name(&self) -> String {
+            "recording sink".to_string()
+        }
+
+        async fn send(&mut self, records: &[EventRecord]) -> Result<()> {
+            self.0
+                .lock()
+                .map_err(|_| eyre!("Poisoned lock"))?
+                .push(records.to_vec());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_should_export_the_events_waiting_for_a_batch() -> Result<()> {
+        let (node_events_tx, node_events_rx) = tokio::sync::broadcast::channel(100);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let batches = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let handle = EventExportHandle {
+            shutdown_tx,
+            tasks: vec![tokio::spawn(export_events(
+                node_events_rx,
+                Box::new(RecordingSink(batches.clone())),
+                PeerId::random(),
+                Arc::new(vec!["ChunkStored".to_string()]),
+                shutdown_rx,
+            ))],
+        };
+
+        let address = ChunkAddress::new(XorName::random(&mut rand::thread_rng()));
+        let _ = node_events_tx.send(NodeEvent::ChunkStored(address))?;
+        let _ = node_events_tx.send(NodeEvent::ConnectedToNetwork)?;
+        let _ = node_events_tx.send(NodeEvent::ChunkStored(address))?;
+        handle.shutdown().await;
+
+        let batches = batches.lock().map_err(|_| eyre!("Poisoned lock"))?;
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].len(), 2);
+        assert!(batches[0].iter().all(|record| record.kind == "ChunkStored"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn webhook_sink_should_post_the_events_and_retry_on_errors() -> Result<()> {
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpListener,
+        };
+
+        // A webhook that fails the first request and accepts the second.
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/events", listener.local_addr()?);
+        let server = tokio::spawn(async move {
+            let mut bodies = Vec::new();
+            for status in ["500 Internal Server Error", "200 OK"] {
+                let (mut stream, _) = listener.accept().await?;
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                let body = loop {
+                    let read = stream.read(&mut buf).await?;
+                    if read == 0 {
+                        return Err(eyre!("The request ended early"));
+                    }
+                    request.extend_from_slice(&buf[..read]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    let Some((headers, body)) = text.split_once("\r\n\r\n") else {
+                        continue;
+                    };
+                    let content_length = headers
+                        .lines()
+                        .find_map(|line| {
+                            line.to_ascii_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|len| len.trim().parse::<usize>())
+                        })
+                        .ok_or_else(|| eyre!("The request has no content length"))??;
+                    if body.len() >= content_length {
+                        break body.to_string();
+                    }
+                };
+                stream
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                        )
+                        .as_bytes(),
+                    )
+                    .await?;
+                bodies.push(body);
+            }
+            Ok::<_, eyre::Report>(bodies)
+        });
+
+        let mut sink = WebhookSink::new(url)?;
+        let peer_id = PeerId::random();
+        sink.send(&[EventRecord::new(
+            NodeEvent::TerminateNode("test".to_string()),
+            peer_id,
+        )])
+        .await?;
+
+        let bodies = server.await??;
+        assert_eq!(bodies.len(), 2);
+        let payload: serde_json::Value = serde_json::from_str(&bodies[1])?;
+        let events = payload["events"]
+            .as_array()
+            .ok_or_else(|| eyre!("The payload has no events"))?;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0]["kind"], "TerminateNode");
+        assert_eq!(events[0]["peer_id"], peer_id.to_string());
+        Ok(())
+    }
+}
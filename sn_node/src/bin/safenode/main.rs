@@ -9,9 +9,11 @@
 #[macro_use]
 extern crate tracing;
 
+mod event_export;
 mod rpc_service;
 
 use clap::Parser;
+use event_export::{parse_event_kind, EventExporter, EventSink, FileSink, WebhookSink};
 use eyre::{eyre, Result};
 use libp2p::{identity::Keypair, PeerId};
 #[cfg(feature = "metrics")]
@@ -20,6 +22,7 @@ use sn_logging::{Level, LogFormat, LogOutputDest, ReloadHandle};
 use sn_node::{Marker, NodeBuilder, NodeEvent, NodeEventsReceiver};
 use sn_peers_acquisition::{get_peers_from_args, PeersArgs};
 use sn_protocol::{node::get_safenode_root_dir, node_rpc::NodeCtrl};
+use sn_transfers::RewardId;
 use std::{
     env,
     io::Write,
@@ -155,9 +158,12 @@ struct Opt {
     #[clap(long)]
     local: bool,
 
-    /// Specify the owner(readable discord user name).
+    /// Specify the owner to track the rewards with, as `<type>:<value>`.
+    ///
+    /// The type is one of `discord`, `email`, `wallet` or `id`. A value without a type is a Discord username.
+    /// The types other than `discord` need the node to be built with the `typed-reward-tracking` feature.
     #[clap(long)]
-    owner: Option<String>,
+    owner: Option<RewardId>,
 
     #[cfg(feature = "open-metrics")]
     /// Specify the port for the OpenMetrics server.
@@ -165,6 +171,22 @@ struct Opt {
     /// If not specified, a port will be selected at random.
     #[clap(long, default_value_t = 0)]
     metrics_server_port: u16,
+
+    /// Write the node's events to a file, as one JSON object per line.
+    ///
+    /// The file is rotated when it reaches 10MB, and the last 10 rotated files are kept.
+    #[clap(long, verbatim_doc_comment)]
+    events_file: Option<PathBuf>,
+
+    /// POST the node's events to a URL, in batches of the form `{"events": [...]}`.
+    #[clap(long)]
+    events_webhook: Option<String>,
+
+    /// Only export events of these types, e.g. `ChunkStored,SpendStored`.
+    ///
+    /// All events are exported if this is not provided.
+    #[clap(long, value_delimiter = ',', value_parser = parse_event_kind, verbatim_doc_comment)]
+    events_filter: Vec<String>,
 }
 
 fn main() -> Result<()> {
@@ -192,13 +214,28 @@ fn main() -> Result<()> {
 
     info!("Node started with initial_peers {bootstrap_peers:?}");
 
+    let mut event_sinks: Vec<Box<dyn EventSink>> = Vec::new();
+    if let Some(path) = opt.events_file {
+        event_sinks.push(Box::new(FileSink::new(path)?));
+    }
+    if let Some(url) = opt.events_webhook {
+        event_sinks.push(Box::new(WebhookSink::new(url)?));
+    }
+    let event_exporter = EventExporter::new(event_sinks, opt.events_filter);
+
     // Create a tokio runtime per `run_node` attempt, this ensures
     // any spawned tasks are closed before we would attempt to run
     // another process with these args.
     #[cfg(feature = "metrics")]
     rt.spawn(init_metrics(std::process::id()));
     let owner = if let Some(owner) = opt.owner {
-        owner.clone()
+        if !owner.kind.can_be_sent() {
+            return Err(eyre!(
+                "The rewards can't be tracked with {} identifiers without the typed-reward-tracking feature",
+                owner.kind
+            ));
+        }
+        owner.to_string()
     } else {
         "user".to_owned()
     };
@@ -218,8 +255,14 @@ fn main() -> Result<()> {
         let mut node_builder = node_builder;
         #[cfg(feature = "open-metrics")]
         node_builder.metrics_server_port(opt.metrics_server_port);
-        let restart_options =
-            run_node(node_builder, opt.rpc, &log_output_dest, log_reload_handle).await?;
+        let restart_options = run_node(
+            node_builder,
+            opt.rpc,
+            &log_output_dest,
+            log_reload_handle,
+            event_exporter,
+        )
+        .await?;
 
         Ok::<_, eyre::Report>(restart_options)
     })?;
@@ -244,6 +287,7 @@ async fn run_node(
     rpc: Option<SocketAddr>,
     log_output_dest: &str,
     log_reload_handle: ReloadHandle,
+    event_exporter: EventExporter,
 ) -> Result<Option<(PathBuf, u16)>> {
     let started_instant = std::time::Instant::now();
 
@@ -274,6 +318,10 @@ You can check your reward balance by running:
     let node_events_rx = running_node.node_events_channel().subscribe();
     monitor_node_events(node_events_rx, ctrl_tx.clone());
 
+    // Export `NodeEvents` to the sinks configured by the user, if any
+    let event_export =
+        event_exporter.start(running_node.node_events_channel(), running_node.peer_id());
+
     // Monitor ctrl-c
     let ctrl_tx_clone = ctrl_tx.clone();
     tokio::spawn(async move {
@@ -306,7 +354,7 @@ You can check your reward balance by running:
 
     // Keep the node and gRPC service (if enabled) running.
     // We'll monitor any NodeCtrl cmd to restart/stop/update,
-    loop {
+    let result = loop {
         match ctrl_rx.recv().await {
             Some(NodeCtrl::Restart {
                 delay,
@@ -314,8 +362,10 @@ You can check your reward balance by running:
             }) => {
                 let res = if retain_peer_id {
                     let root_dir = running_node.root_dir_path();
-                    let node_port = running_node.get_node_listening_port().await?;
-                    Some((root_dir, node_port))
+                    match running_node.get_node_listening_port().await {
+                        Ok(node_port) => Some((root_dir, node_port)),
+                        Err(err) => break Err(err.into()),
+                    }
                 } else {
                     None
                 };
@@ -331,7 +381,7 @@ You can check your reward balance by running:
                 info!("{msg}");
                 println!("{msg} Node log path: {log_output_dest}");
                 sleep(delay).await;
-                return Err(cause);
+                break Err(cause);
             }
             Some(NodeCtrl::Update(_delay)) => {
                 // TODO: implement self-update once safenode app releases are published again
@@ -342,7 +392,11 @@ You can check your reward balance by running:
                 break Err(eyre!("Internal node ctrl cmds channel has been closed"));
             }
         }
-    }
+    };
+
+    // Don't lose the events that haven't been exported yet when the node stops or restarts.
+    event_export.shutdown().await;
+    result
 }
 
 fn monitor_node_events(mut node_events_rx: NodeEventsReceiver, ctrl_tx: mpsc::Sender<NodeCtrl>) {
@@ -85,4 +85,12 @@ pub enum Error {
     /// Error occurred in an async thread
     #[error("Error occured in async thread: {0}")]
     JoinErrorInAsyncThread(String),
+    /// The simulated network didn't reach the expected state in time
+    #[cfg(feature = "sim")]
+    #[error("The simulated network did not settle within {0:?}")]
+    SimulationTimeout(std::time::Duration),
+    /// A client of the simulated network failed
+    #[cfg(feature = "sim")]
+    #[error("Client error {0}")]
+    Client(#[from] sn_client::Error),
 }
@@ -11,6 +11,7 @@ use crate::error::{Error, Result};
 use serde::{Deserialize, Serialize};
 use sn_protocol::storage::{ChunkAddress, RegisterAddress};
 use sn_transfers::UniquePubkey;
+use strum::{IntoStaticStr, VariantNames};
 use tokio::sync::broadcast;
 
 const NODE_EVENT_CHANNEL_SIZE: usize = 500;
@@ -52,7 +53,10 @@ impl NodeEventsChannel {
 }
 
 /// Type of events broadcasted by the node to the public API.
-#[derive(Clone, Serialize, custom_debug::Debug, Deserialize)]
+///
+/// The name of each variant, as given by `<&str>::from(&event)` and listed in
+/// `NodeEvent::VARIANTS`, is used to filter the events that are exported.
+#[derive(Clone, Serialize, custom_debug::Debug, Deserialize, IntoStaticStr, VariantNames)]
 pub enum NodeEvent {
     /// The node has been connected to the network
     ConnectedToNetwork,
@@ -36,6 +36,9 @@ mod node;
 mod put_validation;
 mod quote;
 mod replication;
+/// In-process networks of nodes for tests
+#[cfg(feature = "sim")]
+pub mod sim;
 
 pub use self::{
     event::{NodeEvent, NodeEventsChannel, NodeEventsReceiver},
@@ -21,6 +21,8 @@ use libp2p::{identity::Keypair, Multiaddr, PeerId};
 #[cfg(feature = "open-metrics")]
 use prometheus_client::registry::Registry;
 use rand::{rngs::StdRng, thread_rng, Rng, SeedableRng};
+#[cfg(feature = "sim")]
+use sn_networking::sim::SimNetwork;
 use sn_networking::{
     close_group_majority, Network, NetworkBuilder, NetworkError, NetworkEvent, NodeIssue,
     SwarmDriver, CLOSE_GROUP_SIZE,
@@ -75,6 +77,8 @@ pub struct NodeBuilder {
     /// Enable hole punching for nodes connecting from home networks.
     pub is_behind_home_network: bool,
     owner: String,
+    #[cfg(feature = "sim")]
+    sim: Option<SimNetwork>,
     #[cfg(feature = "upnp")]
     upnp: bool,
 }
@@ -100,6 +104,8 @@ impl NodeBuilder {
             metrics_server_port: 0,
             is_behind_home_network: false,
             owner,
+            #[cfg(feature = "sim")]
+            sim: None,
             #[cfg(feature = "upnp")]
             upnp,
         }
@@ -111,6 +117,12 @@ impl NodeBuilder {
         self.metrics_server_port = port;
     }
 
+    #[cfg(feature = "sim")]
+    /// Connect through a simulated network rather than real sockets
+    pub fn sim(&mut self, sim: SimNetwork) {
+        self.sim = Some(sim);
+    }
+
     /// Asynchronously runs a new node instance, setting up the swarm driver,
     /// creating a data storage, and handling network events. Returns the
     /// created `RunningNode` which contains a `NodeEventsChannel` for listening
@@ -157,6 +169,10 @@ impl NodeBuilder {
 
         #[cfg(feature = "upnp")]
         network_builder.upnp(self.upnp);
+        #[cfg(feature = "sim")]
+        if let Some(sim) = self.sim {
+            network_builder.sim(sim);
+        }
 
         let (network, network_event_receiver, swarm_driver) = network_builder.build_node()?;
         let node_events_channel = NodeEventsChannel::default();
@@ -0,0 +1,236 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Networks of nodes that run inside one process, for tests.
+//!
+//! The nodes connect over a [`SimNetwork`], so no sockets or `safenode` processes are needed, and
+//! the links between them can be slowed, made lossy or partitioned while the test runs. Run the
+//! test with paused time, e.g. `#[tokio::test(start_paused = true)]`, and the timers the nodes use
+//! for bootstrapping and replication fire as soon as the nodes are idle, so minutes of network
+//! activity take seconds.
+
+use crate::{error::Result, Error, NodeBuilder, RunningNode};
+use bls::SecretKey;
+use libp2p::{identity::Keypair, multiaddr::Protocol, Multiaddr, PeerId};
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use sn_client::{Client, Uploader};
+use sn_networking::CLOSE_GROUP_SIZE;
+use sn_protocol::{storage::Chunk, NetworkAddress};
+use sn_transfers::{HotWallet, MainSecretKey, GENESIS_CASHNOTE, GENESIS_CASHNOTE_SK};
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub use sn_networking::sim::{LinkConditions, SimNetwork};
+
+/// How often the nodes are checked while waiting for them to reach a state.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// The directory, under the root directory of the simulation, that clients pay for uploads from.
+const CLIENT_DIR: &str = "client";
+
+/// Nodes running in this process, connected by a simulated network.
+pub struct Simulation {
+    network: SimNetwork,
+    nodes: Vec<RunningNode>,
+    bootstrap_peer: Multiaddr,
+    root_dir: PathBuf,
+}
+
+impl Simulation {
+    /// Start `count` nodes, each with its root directory under `root_dir`. The first node is the
+    /// one the others bootstrap from.
+    ///
+    /// The seed determines the keypairs of the nodes, so the same nodes are responsible for the
+    /// same data each time, and which streams are lost on lossy links.
+    pub async fn start(count: usize, seed: u64, root_dir: &Path) -> Result<Self> {
+        let network = SimNetwork::new(seed);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut nodes: Vec<RunningNode> = Vec::with_capacity(count);
+        let mut bootstrap_peers = Vec::new();
+
+        for _ in 0..count {
+            let mut secret = [0u8; 32];
+            rng.fill_bytes(&mut secret);
+            let keypair = Keypair::ed25519_from_bytes(secret)
+                .map_err(|err| Error::InvalidRequest(err.to_string()))?;
+            let peer_id = PeerId::from(keypair.public());
+
+            let mut node_builder = NodeBuilder::new(
+                keypair,
+                SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+                bootstrap_peers.clone(),
+                true,
+                root_dir.join(peer_id.to_string()),
+                "simulation".to_string(),
+                #[cfg(feature = "upnp")]
+                false,
+            );
+            node_builder.sim(network.clone());
+            let node = node_builder.build_and_run()?;
+
+            if bootstrap_peers.is_empty() {
+                bootstrap_peers.push(Self::listen_addr(&node).await?);
+            }
+            nodes.push(node);
+        }
+
+        let bootstrap_peer = bootstrap_peers
+            .pop()
+            .ok_or_else(|| Error::InvalidRequest("A simulation needs nodes".to_string()))?;
+
+        Ok(Self {
+            network,
+            nodes,
+            bootstrap_peer,
+            root_dir: root_dir.to_path_buf(),
+        })
+    }
+
+    /// The address the node listens on, once it's listening.
+    async fn listen_addr(node: &RunningNode) -> Result<Multiaddr> {
+        loop {
+            let state = node.get_swarm_local_state().await?;
+            if let Some(addr) = state.listeners.into_iter().next() {
+                return Ok(addr.with(Protocol::P2p(node.peer_id())));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// The simulated network, for setting the conditions of links directly.
+    pub fn network(&self) -> &SimNetwork {
+        &self.network
+    }
+
+    /// The nodes, in the order they were started.
+    pub fn nodes(&self) -> &[RunningNode] {
+        &self.nodes
+    }
+
+    /// The peer IDs of the nodes with the given indexes.
+    pub fn peer_ids(&self, indexes: &[usize]) -> Vec<PeerId> {
+        indexes
+            .iter()
+            .map(|index| self.nodes[*index].peer_id())
+            .collect()
+    }
+
+    /// Split the nodes, given by index, into groups that can't reach each other. Nodes that
+    /// aren't in any group can still reach every node.
+    pub fn partition(&self, groups: &[&[usize]]) {
+        let groups = groups
+            .iter()
+            .map(|group| self.peer_ids(group))
+            .collect::<Vec<_>>();
+        self.network.partition(&groups);
+    }
+
+    /// Remove the partition, so every node can reach every other again.
+    pub fn heal(&self) {
+        self.network.heal();
+    }
+
+    /// Let the nodes run for a while. With paused time, this returns as soon as the nodes have
+    /// done everything they would have in that time.
+    pub async fn run_for(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+
+    /// Wait until every node has at least `min_peers` peers in its routing table.
+    pub async fn wait_for_routing_tables(&self, min_peers: usize, timeout: Duration) -> Result<()> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let mut ready = true;
+            for node in &self.nodes {
+                let peers = node
+                    .get_kbuckets()
+                    .await?
+                    .values()
+                    .map(|peers| peers.len())
+                    .sum::<usize>();
+                if peers < min_peers {
+                    ready = false;
+                    break;
+                }
+            }
+            if ready {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(Error::SimulationTimeout(timeout));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// The indexes of all the nodes, closest to the address first.
+    pub fn by_distance(&self, address: &NetworkAddress) -> Vec<usize> {
+        let mut indexes = (0..self.nodes.len()).collect::<Vec<_>>();
+        indexes.sort_by_key(|index| {
+            address.distance(&NetworkAddress::from_peer(self.nodes[*index].peer_id()))
+        });
+        indexes
+    }
+
+    /// The indexes of the nodes that should hold the data at the address, i.e., the close group
+    /// of the address among all the nodes.
+    pub fn close_group(&self, address: &NetworkAddress) -> Vec<usize> {
+        let mut indexes = self.by_distance(address);
+        indexes.truncate(CLOSE_GROUP_SIZE);
+        indexes
+    }
+
+    /// Connect a new client to the nodes, through the node the others bootstrapped from.
+    pub async fn client(&self) -> Result<Client> {
+        let client = Client::new_in_sim(
+            SecretKey::random(),
+            vec![self.bootstrap_peer.clone()],
+            self.network.clone(),
+        )
+        .await?;
+        Ok(client)
+    }
+
+    /// Upload the chunks through the client, paying for them from the genesis wallet. As with any
+    /// upload, the nodes that are paid store the chunks and replicate them to the peers that should
+    /// also hold them.
+    pub async fn upload_chunks(
+        &self,
+        client: &Client,
+        chunks: impl IntoIterator<Item = Chunk>,
+    ) -> Result<()> {
+        let wallet_dir = self.root_dir.join(CLIENT_DIR);
+        if !wallet_dir.exists() {
+            let genesis_key = SecretKey::from_hex(GENESIS_CASHNOTE_SK)
+                .map_err(|err| Error::InvalidRequest(err.to_string()))?;
+            let mut wallet =
+                HotWallet::create_from_key(&wallet_dir, MainSecretKey::new(genesis_key))?;
+            wallet.deposit_and_store_to_disk(&vec![GENESIS_CASHNOTE.clone()])?;
+        }
+
+        let mut uploader = Uploader::new(client.clone(), wallet_dir);
+        uploader.insert_chunks(chunks);
+        let _summary = uploader.start_upload().await?;
+        Ok(())
+    }
+
+    /// The indexes of the nodes that hold the data at the address.
+    pub async fn holders(&self, address: &NetworkAddress) -> Result<Vec<usize>> {
+        // The record store only knows the addresses by their record key
+        let address = NetworkAddress::from_record_key(&address.to_record_key());
+        let mut holders = Vec::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            if node.get_all_record_addresses().await?.contains(&address) {
+                holders.push(index);
+            }
+        }
+        Ok(holders)
+    }
+}
@@ -0,0 +1,116 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Replication tests that run the whole network in this process. Unlike the other tests here,
+//! they don't need a local network to be running:
+//!
+//! `cargo test --package sn_node --features sim --test sim_replication`
+
+#![cfg(feature = "sim")]
+
+use bytes::Bytes;
+use color_eyre::Result;
+use sn_logging::LogBuilder;
+use sn_networking::CLOSE_GROUP_SIZE;
+use sn_node::sim::Simulation;
+use sn_protocol::{storage::Chunk, NetworkAddress};
+use std::time::Duration;
+
+const NODE_COUNT: usize = 20;
+/// Long enough for every node's periodic replication to run at least once.
+const REPLICATION_TIME: Duration = Duration::from_secs(120);
+/// Long enough for the nodes on each side of a healed partition to find each other again.
+const HEALING_TIME: Duration = Duration::from_secs(300);
+
+fn chunk(content: &str) -> (Chunk, NetworkAddress) {
+    let chunk = Chunk::new(Bytes::from(content.to_string()));
+    let address = NetworkAddress::from_chunk_address(*chunk.address());
+    (chunk, address)
+}
+
+#[tokio::test(start_paused = true)]
+async fn chunk_should_be_replicated_to_its_close_group() -> Result<()> {
+    let _log_guards = LogBuilder::init_single_threaded_tokio_test("sim_replication");
+    let root_dir = tempfile::tempdir()?;
+    let sim = Simulation::start(NODE_COUNT, 1, root_dir.path()).await?;
+    sim.wait_for_routing_tables(NODE_COUNT / 2, Duration::from_secs(300))
+        .await?;
+
+    let (chunk, address) = chunk("replicated to the close group");
+    let close_group = sim.close_group(&address);
+    let client = sim.client().await?;
+    sim.upload_chunks(&client, [chunk]).await?;
+    sim.run_for(REPLICATION_TIME).await;
+
+    let holders = sim.holders(&address).await?;
+    for index in &close_group {
+        assert!(
+            holders.contains(index),
+            "node {index} in the close group {close_group:?} does not hold the chunk; holders: {holders:?}"
+        );
+    }
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn chunk_should_not_be_replicated_across_a_partition() -> Result<()> {
+    let _log_guards = LogBuilder::init_single_threaded_tokio_test("sim_replication");
+    let root_dir = tempfile::tempdir()?;
+    let sim = Simulation::start(NODE_COUNT, 2, root_dir.path()).await?;
+    sim.wait_for_routing_tables(NODE_COUNT / 2, Duration::from_secs(300))
+        .await?;
+
+    // The client uploads the chunk from the side of the partition where only one node of the
+    // chunk's close group is. The node farthest from the chunk stays in neither group, so the two
+    // sides can find each other again once the partition heals.
+    let client = sim.client().await?;
+    let (chunk, address) = chunk("held on one side of a partition");
+    let mut by_distance = sim.by_distance(&address);
+    let _bridge = by_distance.pop();
+    let close_group = sim.close_group(&address);
+    // Half of the nodes outside the close group stay with it, so it still has peers to talk to.
+    let cut_off = by_distance
+        .iter()
+        .enumerate()
+        .filter(|(position, _)| {
+            *position == 0 || (*position >= CLOSE_GROUP_SIZE && position % 2 == 1)
+        })
+        .map(|(_, index)| *index)
+        .collect::<Vec<_>>();
+    let others = by_distance
+        .into_iter()
+        .filter(|index| !cut_off.contains(index))
+        .collect::<Vec<_>>();
+    let mut cut_off_peers = sim.peer_ids(&cut_off);
+    cut_off_peers.push(client.peer_id());
+    sim.network()
+        .partition(&[cut_off_peers, sim.peer_ids(&others)]);
+    sim.upload_chunks(&client, [chunk]).await?;
+    sim.run_for(REPLICATION_TIME).await;
+    let holders = sim.holders(&address).await?;
+    assert!(holders.contains(&close_group[0]));
+    for index in &others {
+        assert!(
+            !holders.contains(index),
+            "node {index} got the chunk across the partition; holders: {holders:?}"
+        );
+    }
+
+    // Once the partition heals and the nodes have found each other again, the chunk reaches the
+    // rest of the close group.
+    sim.heal();
+    sim.run_for(HEALING_TIME).await;
+    let holders = sim.holders(&address).await?;
+    for index in &close_group {
+        assert!(
+            holders.contains(index),
+            "node {index} in the close group {close_group:?} does not hold the chunk; holders: {holders:?}"
+        );
+    }
+    Ok(())
+}
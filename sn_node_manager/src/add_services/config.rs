@@ -22,6 +22,15 @@ pub enum PortRange {
     Range(u16, u16),
 }
 
+impl std::fmt::Display for PortRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PortRange::Single(port) => write!(f, "{port}"),
+            PortRange::Range(start, end) => write!(f, "{start}-{end}"),
+        }
+    }
+}
+
 pub fn parse_port_range(s: &str) -> Result<PortRange> {
     if let Ok(port) = u16::from_str(s) {
         Ok(PortRange::Single(port))
@@ -194,12 +194,17 @@ pub async fn add_node(
                 node_registry.nodes.push(NodeServiceData {
                     connected_peers: None,
                     data_dir_path: service_data_dir_path.clone(),
+                    fleet_group: None,
                     genesis: options.genesis,
                     home_network: options.home_network,
+                    last_restart: None,
                     listen_addr: None,
                     local: options.local,
                     log_dir_path: service_log_dir_path.clone(),
+                    metrics_port,
+                    node_port,
                     number: node_number,
+                    restart_count: 0,
                     reward_balance: None,
                     rpc_socket_addr,
                     peer_id: None,
@@ -23,7 +23,7 @@ use libp2p::Multiaddr;
 use mockall::{mock, predicate::*, Sequence};
 use predicates::prelude::*;
 use service_manager::ServiceInstallCtx;
-use sn_service_management::control::ServiceControl;
+use sn_service_management::control::{ServiceBackend, ServiceControl};
 use sn_service_management::error::Result as ServiceControlResult;
 use sn_service_management::{
     DaemonServiceData, FaucetServiceData, NodeRegistry, NodeServiceData, ServiceStatus,
@@ -90,7 +90,9 @@ async fn add_genesis_node_should_use_latest_version_and_add_one_service() -> Res
 
     let mut node_registry = NodeRegistry {
         faucet: None,
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
         nodes: vec![],
         bootstrap_peers: vec![],
         environment_variables: None,
@@ -197,18 +199,25 @@ async fn add_genesis_node_should_return_an_error_if_there_is_already_a_genesis_n
     let latest_version = "0.96.4";
     let mut node_registry = NodeRegistry {
         faucet: None,
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
         nodes: vec![NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: true,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             pid: None,
             peer_id: None,
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             status: ServiceStatus::Added,
@@ -277,7 +286,9 @@ async fn add_genesis_node_should_return_an_error_if_count_is_greater_than_1() ->
 
     let mut node_registry = NodeRegistry {
         faucet: None,
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
         nodes: vec![],
         bootstrap_peers: vec![],
         environment_variables: None,
@@ -337,7 +348,9 @@ async fn add_node_should_use_latest_version_and_add_three_services() -> Result<(
 
     let mut node_registry = NodeRegistry {
         faucet: None,
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
         nodes: vec![],
         bootstrap_peers: vec![],
         environment_variables: None,
@@ -550,7 +563,9 @@ async fn add_node_should_update_the_bootstrap_peers_inside_node_registry() -> Re
 
     let mut node_registry = NodeRegistry {
         faucet: None,
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
         nodes: vec![],
         bootstrap_peers: old_peers.clone(),
         environment_variables: None,
@@ -669,7 +684,9 @@ async fn add_node_should_update_the_environment_variables_inside_node_registry()
 
     let mut node_registry = NodeRegistry {
         faucet: None,
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
         nodes: vec![],
         bootstrap_peers: vec![],
         environment_variables: None,
@@ -782,18 +799,25 @@ async fn add_new_node_should_add_another_service() -> Result<()> {
     let latest_version = "0.96.4";
     let mut node_registry = NodeRegistry {
         faucet: None,
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
         nodes: vec![NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: true,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             pid: None,
             peer_id: None,
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
@@ -906,7 +930,9 @@ async fn add_node_should_use_custom_ports_for_one_service() -> Result<()> {
 
     let mut node_registry = NodeRegistry {
         faucet: None,
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
         nodes: vec![],
         bootstrap_peers: vec![],
         environment_variables: None,
@@ -1019,7 +1045,9 @@ async fn add_node_should_use_a_custom_port_range() -> Result<()> {
 
     let mut node_registry = NodeRegistry {
         faucet: None,
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
         nodes: vec![],
         bootstrap_peers: vec![],
         environment_variables: None,
@@ -1211,6 +1239,9 @@ async fn add_node_should_use_a_custom_port_range() -> Result<()> {
     node_data_dir.assert(predicate::path::is_dir());
     node_logs_dir.assert(predicate::path::is_dir());
     assert_eq!(node_registry.nodes.len(), 3);
+    assert_eq!(node_registry.nodes[0].node_port, Some(12000));
+    assert_eq!(node_registry.nodes[1].node_port, Some(12001));
+    assert_eq!(node_registry.nodes[2].node_port, Some(12002));
 
     Ok(())
 }
@@ -1222,7 +1253,9 @@ async fn add_node_should_return_an_error_if_port_and_node_count_do_not_match() -
 
     let mut node_registry = NodeRegistry {
         faucet: None,
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
         nodes: vec![],
         bootstrap_peers: vec![],
         environment_variables: None,
@@ -1285,7 +1318,9 @@ async fn add_node_should_return_an_error_if_multiple_services_are_specified_with
 
     let mut node_registry = NodeRegistry {
         faucet: None,
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
         nodes: vec![],
         bootstrap_peers: vec![],
         environment_variables: None,
@@ -1349,7 +1384,9 @@ async fn add_node_should_use_a_custom_port_range_for_metrics_server() -> Result<
 
     let mut node_registry = NodeRegistry {
         faucet: None,
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
         nodes: vec![],
         bootstrap_peers: vec![],
         environment_variables: None,
@@ -1541,6 +1578,9 @@ async fn add_node_should_use_a_custom_port_range_for_metrics_server() -> Result<
     node_data_dir.assert(predicate::path::is_dir());
     node_logs_dir.assert(predicate::path::is_dir());
     assert_eq!(node_registry.nodes.len(), 3);
+    assert_eq!(node_registry.nodes[0].metrics_port, Some(12000));
+    assert_eq!(node_registry.nodes[1].metrics_port, Some(12001));
+    assert_eq!(node_registry.nodes[2].metrics_port, Some(12002));
 
     Ok(())
 }
@@ -1554,7 +1594,9 @@ async fn add_node_should_use_a_custom_port_range_for_the_rpc_server() -> Result<
 
     let mut node_registry = NodeRegistry {
         faucet: None,
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
         nodes: vec![],
         bootstrap_peers: vec![],
         environment_variables: None,
@@ -1763,7 +1805,9 @@ async fn add_faucet_should_add_a_faucet_service() -> Result<()> {
         faucet: None,
         environment_variables: None,
         nodes: vec![],
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
     };
 
     let mut mock_service_control = MockServiceControl::new();
@@ -1857,7 +1901,9 @@ async fn add_faucet_should_return_an_error_if_a_faucet_service_was_already_creat
         }),
         environment_variables: None,
         nodes: vec![],
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
     };
 
     let result = add_faucet(
@@ -1909,7 +1955,9 @@ async fn add_daemon_should_add_a_daemon_service() -> Result<()> {
         faucet: None,
         environment_variables: None,
         nodes: vec![],
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
     };
 
     let mut mock_service_control = MockServiceControl::new();
@@ -1994,7 +2042,9 @@ async fn add_daemon_should_return_an_error_if_a_daemon_service_was_already_creat
         faucet: None,
         environment_variables: None,
         nodes: vec![],
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
     };
 
     let result = add_daemon(
@@ -2033,7 +2083,9 @@ async fn add_node_should_not_delete_the_source_binary_if_path_arg_is_used() -> R
 
     let mut node_registry = NodeRegistry {
         faucet: None,
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
         nodes: vec![],
         bootstrap_peers: vec![],
         environment_variables: None,
@@ -2126,7 +2178,9 @@ async fn add_node_should_apply_the_home_network_flag_if_it_is_used() -> Result<(
 
     let mut node_registry = NodeRegistry {
         faucet: None,
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
         nodes: vec![],
         bootstrap_peers: vec![],
         environment_variables: None,
@@ -2219,7 +2273,9 @@ async fn add_node_should_add_the_node_in_user_mode() -> Result<()> {
 
     let mut node_registry = NodeRegistry {
         faucet: None,
+        rolling_upgrade: None,
         save_path: node_reg_path.to_path_buf(),
+        service_backend: ServiceBackend::Native,
         nodes: vec![],
         bootstrap_peers: vec![],
         environment_variables: None,
@@ -6,14 +6,20 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use color_eyre::{eyre::eyre, Result};
 use sn_node_manager::{
     add_services::config::{parse_port_range, PortRange},
-    cmd, VerbosityLevel,
+    cmd, config,
+    preflight::{DEFAULT_MAX_RECORDS, DEFAULT_NODE_MEMORY_MB},
+    rolling_upgrade::{HealthGate, RollingUpgradeOptions},
+    rpc_client::{connect_to_daemon, DaemonConnectionOptions},
+    supervisor::SupervisorPolicy,
+    VerbosityLevel,
 };
 use sn_peers_acquisition::PeersArgs;
-use std::{net::Ipv4Addr, path::PathBuf};
+use sn_service_management::control::{ContainerRuntime, ServiceBackend};
+use std::{net::Ipv4Addr, path::PathBuf, time::Duration};
 
 const DEFAULT_NODE_COUNT: u16 = 25;
 
@@ -26,6 +32,34 @@ pub(crate) struct Cmd {
 
     #[clap(short, long, action = clap::ArgAction::Count, default_value_t = 2)]
     verbose: u8,
+
+    /// Send the command to a safenodemand daemon rather than running it locally.
+    ///
+    /// The address is in the form host:port. When TLS is used, the host is the name the daemon's
+    /// certificate is verified against.
+    ///
+    /// The add, balance, events, logs, remove, reset, start, status, stop and upgrade commands can
+    /// be sent to a daemon. Any paths they are given refer to the machine the daemon runs on.
+    #[clap(long, global = true, value_name = "host:port")]
+    remote: Option<String>,
+    /// The access token the remote daemon was configured with.
+    #[clap(
+        long,
+        global = true,
+        env = "SAFENODEMAND_TOKEN",
+        hide_env_values = true
+    )]
+    remote_token: Option<String>,
+    /// Connect to the remote daemon over TLS, verifying its certificate with this PEM encoded CA
+    /// certificate.
+    #[clap(long, global = true, requires = "remote")]
+    remote_ca: Option<PathBuf>,
+    /// A PEM encoded certificate to present to the remote daemon, for mutual TLS.
+    #[clap(long, global = true, requires_all = ["remote_ca", "remote_key"])]
+    remote_cert: Option<PathBuf>,
+    /// The PEM encoded private key for the certificate presented to the remote daemon.
+    #[clap(long, global = true, requires = "remote_cert")]
+    remote_key: Option<PathBuf>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -135,6 +169,10 @@ pub enum SubCmd {
         /// services, which in this case would be 5. The range must also go from lower to higher.
         #[clap(long, value_parser = parse_port_range)]
         rpc_port: Option<PortRange>,
+        /// Set this flag to add the services without checking the host has the disk space, memory,
+        /// file handles and ports for them.
+        #[clap(long)]
+        skip_preflight: bool,
         /// Provide a safenode binary using a URL.
         ///
         /// The binary must be inside a zip or gzipped tar archive.
@@ -159,6 +197,51 @@ pub enum SubCmd {
         #[clap(long)]
         version: Option<String>,
     },
+    /// Make the safenode services match a fleet file.
+    ///
+    /// The file describes groups of nodes, their options and their versions. Services are added,
+    /// removed, reconfigured or upgraded until they match it. Use the 'export' command to write
+    /// out a file for the services as they are.
+    ///
+    /// On Windows, this command must run as the administrative user. On Linux/macOS, run using
+    /// sudo if you defined system-wide services; otherwise, do not run the command elevated.
+    #[clap(name = "apply")]
+    Apply {
+        /// The path of the fleet file.
+        #[clap(name = "path")]
+        path: PathBuf,
+        /// Set this flag to print the changes without making them.
+        #[clap(long)]
+        dry_run: bool,
+        /// Set this flag to keep the data and log directories of the services that are removed.
+        #[clap(long)]
+        keep_directories: bool,
+        /// Set this flag to add services without checking the host has the resources for them.
+        #[clap(long)]
+        skip_preflight: bool,
+    },
+    /// Print or choose how the services are defined and run.
+    ///
+    /// By default, services are defined with the platform's service manager. On hosts without one,
+    /// such as containers, 'process' runs them as detached processes with PID files, while
+    /// 'docker' and 'podman' run each of them in a container.
+    ///
+    /// The choice applies to all the services in the registry, so it can only be changed when
+    /// there are none.
+    #[clap(name = "backend")]
+    Backend {
+        /// The backend to use.
+        ///
+        /// If not provided, the current backend is printed.
+        #[clap(name = "kind", value_enum)]
+        kind: Option<BackendKind>,
+        /// The image the containers are created from, for the 'docker' and 'podman' backends.
+        ///
+        /// The binary for each service is mounted into its container, so the image only needs to
+        /// provide an environment it can run in.
+        #[clap(long, default_value = "debian:bookworm-slim")]
+        image: String,
+    },
     /// Get node reward balances.
     #[clap(name = "balance")]
     Balance {
@@ -175,10 +258,88 @@ pub enum SubCmd {
     },
     #[clap(subcommand)]
     Daemon(DaemonSubCmd),
+    /// Write the safenode services out as a fleet file that the 'apply' command accepts.
+    #[clap(name = "export")]
+    Export {
+        /// The path to write the file to.
+        ///
+        /// If not set, the file is written to stdout.
+        #[clap(name = "path")]
+        path: Option<PathBuf>,
+    },
+    /// Stream the events emitted by running safenode services.
+    ///
+    /// If no peer ID(s) or service name(s) are supplied, the events of all running services will
+    /// be streamed.
+    #[clap(name = "events")]
+    Events {
+        /// The peer ID of the service to stream events from.
+        ///
+        /// The argument can be used multiple times to stream events from many services.
+        #[clap(long)]
+        peer_id: Vec<String>,
+        /// The name of the service to stream events from.
+        ///
+        /// The argument can be used multiple times to stream events from many services.
+        #[clap(long, conflicts_with = "peer_id")]
+        service_name: Vec<String>,
+    },
     #[clap(subcommand)]
     Faucet(FaucetSubCmd),
     #[clap(subcommand)]
     Local(LocalSubCmd),
+    /// Print the log output of a safenode service.
+    #[clap(name = "logs")]
+    Logs {
+        /// The name of the service.
+        #[clap(long)]
+        service_name: String,
+        /// Set this flag to keep printing lines as they are written.
+        #[clap(long, short)]
+        follow: bool,
+        /// The number of existing lines to print.
+        #[clap(long, short = 'n', default_value_t = 20)]
+        lines: usize,
+    },
+    /// Check the host has the disk space, memory, file handles and ports for more nodes.
+    ///
+    /// The same checks run before the 'add' command adds services. The disk space and memory the
+    /// services in the registry are yet to use are treated as taken. The largest number of nodes
+    /// the host could take is also reported.
+    #[clap(name = "preflight")]
+    Preflight {
+        /// The number of nodes to check for.
+        #[clap(long, default_value_t = 1)]
+        count: u16,
+        /// The path the data directories of the nodes would be created under.
+        ///
+        /// If not provided, the default location for the 'add' command is used.
+        #[clap(long)]
+        data_dir_path: Option<PathBuf>,
+        /// The path the log directories of the nodes would be created under.
+        ///
+        /// If not provided, the default location for the 'add' command is used.
+        #[clap(long)]
+        log_dir_path: Option<PathBuf>,
+        /// The number of records each node is expected to store, which determines its disk usage.
+        #[clap(long, default_value_t = DEFAULT_MAX_RECORDS, value_parser = clap::value_parser!(u64).range(1..))]
+        max_records: u64,
+        /// The port or range of ports the open metrics servers would use.
+        #[clap(long, value_parser = parse_port_range)]
+        metrics_port: Option<PortRange>,
+        /// The memory each node is expected to use, in MB.
+        #[clap(long, default_value_t = DEFAULT_NODE_MEMORY_MB, value_parser = clap::value_parser!(u64).range(1..))]
+        node_memory: u64,
+        /// The port or range of ports the nodes would use.
+        #[clap(long, value_parser = parse_port_range)]
+        node_port: Option<PortRange>,
+        /// The address the RPC servers would listen on.
+        #[clap(long)]
+        rpc_address: Option<Ipv4Addr>,
+        /// The port or range of ports the RPC servers would use.
+        #[clap(long, value_parser = parse_port_range)]
+        rpc_port: Option<PortRange>,
+    },
     /// Remove safenode service(s).
     ///
     /// If no peer ID(s) or service name(s) are supplied, all services will be removed.
@@ -271,6 +432,68 @@ pub enum SubCmd {
         #[clap(long, conflicts_with = "peer_id")]
         service_name: Vec<String>,
     },
+    /// Watch running safenode services and restart any that become unhealthy.
+    ///
+    /// Each service is checked on an interval: its process has to be running, its RPC service has
+    /// to respond and it has to be connected to enough peers. Optionally, it also has to have
+    /// stored or dropped records recently. Unhealthy services are restarted, with a backoff
+    /// between restarts of the same service. Restarts are recorded in the node registry.
+    ///
+    /// Services that are not running, e.g., because they were stopped with the 'stop' command, are
+    /// not supervised. The command runs until it's interrupted.
+    ///
+    /// On Windows, this command must run as the administrative user. On Linux/macOS, run using
+    /// sudo if you defined system-wide services; otherwise, do not run the command elevated.
+    #[clap(name = "supervise")]
+    Supervise {
+        /// The number of failed checks in a row after which a service is restarted.
+        #[clap(long, default_value_t = 3)]
+        failure_threshold: u32,
+        /// The time to wait after restarting a service before it can be restarted again.
+        ///
+        /// It doubles with each restart that does not make the service healthy, up to
+        /// --max-backoff.
+        ///
+        /// Units are seconds.
+        #[clap(long, default_value_t = 60)]
+        initial_backoff: u64,
+        /// The interval between checks.
+        ///
+        /// Units are seconds.
+        #[clap(long, default_value_t = 30)]
+        interval: u64,
+        /// The longest time to wait between restarts of a service.
+        ///
+        /// Units are seconds.
+        #[clap(long, default_value_t = 3600)]
+        max_backoff: u64,
+        /// Stop restarting a service after it has been restarted this many times in a row without
+        /// becoming healthy.
+        ///
+        /// If not set, there is no limit.
+        #[clap(long)]
+        max_restarts: Option<u32>,
+        /// The number of peers a service has to be connected to.
+        #[clap(long, default_value_t = 1)]
+        min_connected_peers: usize,
+        /// The peer ID of the service to supervise.
+        ///
+        /// The argument can be used multiple times to supervise many services.
+        #[clap(long)]
+        peer_id: Vec<String>,
+        /// Restart a service whose record count has not changed for this long.
+        ///
+        /// Record activity is not checked if this is not set.
+        ///
+        /// Units are seconds.
+        #[clap(long)]
+        record_activity_timeout: Option<u64>,
+        /// The name of the service to supervise.
+        ///
+        /// The argument can be used multiple times to supervise many services.
+        #[clap(long, conflicts_with = "peer_id")]
+        service_name: Vec<String>,
+    },
     /// Upgrade safenode services.
     ///
     /// The running node will be stopped, its binary will be replaced, then it will be started
@@ -282,6 +505,14 @@ pub enum SubCmd {
     /// sudo if you defined system-wide services; otherwise, do not run the command elevated.
     #[clap(name = "upgrade")]
     Upgrade {
+        /// Upgrade this many services at a time, waiting for each batch to become healthy before
+        /// upgrading the next.
+        ///
+        /// A batch is healthy when each of its nodes reports the new version over RPC, is connected
+        /// to enough peers and holds enough records. If a batch does not become healthy, it is
+        /// rolled back to the previous binary and the upgrade stops.
+        #[clap(long, conflicts_with = "do_not_start")]
+        batch_size: Option<usize>,
         /// Set this flag to upgrade the nodes without automatically starting them.
         ///
         /// Can be useful for testing scenarios.
@@ -303,11 +534,26 @@ pub enum SubCmd {
         /// Required if we want to downgrade, or for testing purposes.
         #[clap(long)]
         force: bool,
+        /// How long a batch has to become healthy when --batch-size is used.
+        ///
+        /// Units are seconds.
+        #[clap(long, default_value_t = 300)]
+        health_timeout: u64,
         /// An interval applied between upgrading each service.
         ///
         /// Units are milliseconds.
         #[clap(long, default_value_t = 200)]
         interval: u64,
+        /// The number of peers each node must be connected to for its batch to be healthy when
+        /// --batch-size is used.
+        #[clap(long, default_value_t = 5)]
+        min_connected_peers: usize,
+        /// The number of records each node must hold for its batch to be healthy when
+        /// --batch-size is used.
+        ///
+        /// Use 0 to skip this check, e.g., on a new network that does not hold any data yet.
+        #[clap(long, default_value_t = 1)]
+        min_records: usize,
         /// Provide a path for the safenode binary to be used by the service.
         ///
         /// Useful for upgrading the service using a custom built binary.
@@ -503,6 +749,22 @@ pub enum FaucetSubCmd {
 #[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 pub enum LocalSubCmd {
+    /// Run a chaos scenario against the running local network.
+    ///
+    /// The scenario file describes events that disrupt random nodes at intervals: killing,
+    /// restarting, pausing, adding and removing them, and filling their disks. A client uploads
+    /// and downloads files throughout, and every file is checked at the end, to report on data
+    /// loss and latency.
+    #[cfg(feature = "chaos")]
+    #[clap(name = "chaos")]
+    Chaos {
+        /// The path of the TOML scenario file.
+        #[clap(name = "scenario")]
+        scenario_path: PathBuf,
+        /// Specify the owner(readable discord user name) for nodes that are added.
+        #[clap(long)]
+        owner: Option<String>,
+    },
     /// Kill the running local network.
     #[clap(name = "kill")]
     Kill {
@@ -622,12 +884,30 @@ pub enum LocalSubCmd {
     },
 }
 
+#[derive(Clone, Debug, ValueEnum)]
+pub enum BackendKind {
+    Docker,
+    Native,
+    Podman,
+    Process,
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     color_eyre::install()?;
     let args = Cmd::parse();
     let verbosity = VerbosityLevel::from(args.verbose);
 
+    if let Some(address) = args.remote {
+        let options = DaemonConnectionOptions {
+            token: args.remote_token,
+            ca_cert_path: args.remote_ca,
+            client_cert_path: args.remote_cert,
+            client_key_path: args.remote_key,
+        };
+        return run_remote(args.cmd, &address, &options, verbosity).await;
+    }
+
     match args.cmd {
         SubCmd::Add {
             count,
@@ -642,6 +922,7 @@ async fn main() -> Result<()> {
             peers,
             rpc_address,
             rpc_port,
+            skip_preflight,
             url,
             user,
             version,
@@ -658,6 +939,7 @@ async fn main() -> Result<()> {
                 peers,
                 rpc_address,
                 rpc_port,
+                skip_preflight,
                 path,
                 url,
                 user,
@@ -666,6 +948,32 @@ async fn main() -> Result<()> {
             )
             .await
         }
+        SubCmd::Apply {
+            path,
+            dry_run,
+            keep_directories,
+            skip_preflight,
+        } => cmd::fleet::apply(path, dry_run, keep_directories, skip_preflight, verbosity).await,
+        SubCmd::Backend { kind, image } => {
+            let backend = match kind {
+                Some(BackendKind::Docker) => Some(ServiceBackend::Container {
+                    dir: config::get_service_definitions_dir_path()?,
+                    image,
+                    runtime: ContainerRuntime::Docker,
+                }),
+                Some(BackendKind::Native) => Some(ServiceBackend::Native),
+                Some(BackendKind::Podman) => Some(ServiceBackend::Container {
+                    dir: config::get_service_definitions_dir_path()?,
+                    image,
+                    runtime: ContainerRuntime::Podman,
+                }),
+                Some(BackendKind::Process) => Some(ServiceBackend::Process {
+                    dir: config::get_service_definitions_dir_path()?,
+                }),
+                None => None,
+            };
+            cmd::node::backend(backend, verbosity)
+        }
         SubCmd::Balance {
             peer_id: peer_ids,
             service_name: service_names,
@@ -680,6 +988,11 @@ async fn main() -> Result<()> {
         }) => cmd::daemon::add(address, env_variables, port, path, url, version, verbosity).await,
         SubCmd::Daemon(DaemonSubCmd::Start {}) => cmd::daemon::start(verbosity).await,
         SubCmd::Daemon(DaemonSubCmd::Stop {}) => cmd::daemon::stop(verbosity).await,
+        SubCmd::Events {
+            peer_id: peer_ids,
+            service_name: service_names,
+        } => cmd::node::events(peer_ids, service_names).await,
+        SubCmd::Export { path } => cmd::fleet::export(path).await,
         SubCmd::Faucet(faucet_command) => match faucet_command {
             FaucetSubCmd::Add {
                 env_variables,
@@ -721,6 +1034,14 @@ async fn main() -> Result<()> {
             }
         },
         SubCmd::Local(local_command) => match local_command {
+            #[cfg(feature = "chaos")]
+            LocalSubCmd::Chaos {
+                scenario_path,
+                owner,
+            } => {
+                let owner = owner.unwrap_or("maidsafe_test".to_string());
+                cmd::local::chaos(scenario_path, owner, verbosity).await
+            }
             LocalSubCmd::Join {
                 build,
                 count,
@@ -779,6 +1100,33 @@ async fn main() -> Result<()> {
                 .await
             }
         },
+        SubCmd::Logs {
+            service_name,
+            follow,
+            lines,
+        } => cmd::node::logs(service_name, lines, follow).await,
+        SubCmd::Preflight {
+            count,
+            data_dir_path,
+            log_dir_path,
+            max_records,
+            metrics_port,
+            node_memory,
+            node_port,
+            rpc_address,
+            rpc_port,
+        } => cmd::node::preflight(
+            count,
+            data_dir_path,
+            log_dir_path,
+            max_records,
+            metrics_port,
+            node_memory,
+            node_port,
+            rpc_address,
+            rpc_port,
+            verbosity,
+        ),
         SubCmd::Remove {
             keep_directories,
             peer_id: peer_ids,
@@ -799,10 +1147,42 @@ async fn main() -> Result<()> {
             peer_id: peer_ids,
             service_name: service_names,
         } => cmd::node::stop(peer_ids, service_names, verbosity).await,
+        SubCmd::Supervise {
+            failure_threshold,
+            initial_backoff,
+            interval,
+            max_backoff,
+            max_restarts,
+            min_connected_peers,
+            peer_id: peer_ids,
+            record_activity_timeout,
+            service_name: service_names,
+        } => {
+            let policy = SupervisorPolicy {
+                min_connected_peers,
+                record_activity_timeout: record_activity_timeout.map(Duration::from_secs),
+                failure_threshold,
+                initial_backoff: Duration::from_secs(initial_backoff),
+                max_backoff: Duration::from_secs(max_backoff),
+                max_restarts,
+            };
+            cmd::node::supervise(
+                Duration::from_secs(interval),
+                peer_ids,
+                policy,
+                service_names,
+                verbosity,
+            )
+            .await
+        }
         SubCmd::Upgrade {
+            batch_size,
             do_not_start,
             force,
+            health_timeout,
             interval,
+            min_connected_peers,
+            min_records,
             path,
             peer_id: peer_ids,
             service_name: service_names,
@@ -810,6 +1190,14 @@ async fn main() -> Result<()> {
             url,
             version,
         } => {
+            let rolling_options = batch_size.map(|batch_size| RollingUpgradeOptions {
+                batch_size,
+                health_gate: HealthGate::new(
+                    min_connected_peers,
+                    min_records,
+                    Duration::from_secs(health_timeout),
+                ),
+            });
             cmd::node::upgrade(
                 do_not_start,
                 path,
@@ -817,6 +1205,155 @@ async fn main() -> Result<()> {
                 interval,
                 peer_ids,
                 provided_env_variable,
+                rolling_options,
+                service_names,
+                url,
+                version,
+                verbosity,
+            )
+            .await
+        }
+    }
+}
+
+async fn run_remote(
+    cmd: SubCmd,
+    address: &str,
+    options: &DaemonConnectionOptions,
+    verbosity: VerbosityLevel,
+) -> Result<()> {
+    let unsupported = match &cmd {
+        SubCmd::Apply { .. } => Some("apply"),
+        SubCmd::Backend { .. } => Some("backend"),
+        SubCmd::Daemon(_) => Some("daemon"),
+        SubCmd::Export { .. } => Some("export"),
+        SubCmd::Faucet(_) => Some("faucet"),
+        SubCmd::Local(_) => Some("local"),
+        SubCmd::Preflight { .. } => Some("preflight"),
+        SubCmd::Supervise { .. } => Some("supervise"),
+        SubCmd::Status { details: true, .. } | SubCmd::Status { json: true, .. } => {
+            Some("status --details/--json")
+        }
+        _ => None,
+    };
+    if let Some(unsupported) = unsupported {
+        return Err(eyre!(
+            "The '{unsupported}' command cannot be sent to a daemon with --remote"
+        ));
+    }
+
+    let mut client = connect_to_daemon(address, options).await?;
+    match cmd {
+        SubCmd::Add {
+            count,
+            data_dir_path,
+            env_variables,
+            home_network,
+            local,
+            log_dir_path,
+            metrics_port,
+            node_port,
+            path,
+            peers,
+            rpc_address,
+            rpc_port,
+            skip_preflight,
+            url,
+            user,
+            version,
+        } => {
+            cmd::remote::add(
+                &mut client,
+                count,
+                data_dir_path,
+                env_variables,
+                home_network,
+                local,
+                log_dir_path,
+                metrics_port,
+                node_port,
+                peers,
+                rpc_address,
+                rpc_port,
+                skip_preflight,
+                path,
+                url,
+                user,
+                version,
+                verbosity,
+            )
+            .await
+        }
+        SubCmd::Balance {
+            peer_id: peer_ids,
+            service_name: service_names,
+        } => cmd::remote::balance(&mut client, peer_ids, service_names, verbosity).await,
+        SubCmd::Events {
+            peer_id: peer_ids,
+            service_name: service_names,
+        } => cmd::remote::events(&mut client, peer_ids, service_names).await,
+        SubCmd::Logs {
+            service_name,
+            follow,
+            lines,
+        } => cmd::remote::logs(&mut client, service_name, lines, follow).await,
+        SubCmd::Remove {
+            keep_directories,
+            peer_id: peer_ids,
+            service_name: service_names,
+        } => {
+            cmd::remote::remove(
+                &mut client,
+                keep_directories,
+                peer_ids,
+                service_names,
+                verbosity,
+            )
+            .await
+        }
+        SubCmd::Reset { force } => cmd::remote::reset(&mut client, force, verbosity).await,
+        SubCmd::Start {
+            interval,
+            peer_id: peer_ids,
+            service_name: service_names,
+        } => cmd::remote::start(&mut client, interval, peer_ids, service_names, verbosity).await,
+        SubCmd::Status { fail, .. } => cmd::remote::status(&mut client, fail).await,
+        SubCmd::Stop {
+            peer_id: peer_ids,
+            service_name: service_names,
+        } => cmd::remote::stop(&mut client, peer_ids, service_names, verbosity).await,
+        SubCmd::Upgrade {
+            batch_size,
+            do_not_start,
+            force,
+            health_timeout,
+            interval,
+            min_connected_peers,
+            min_records,
+            path,
+            peer_id: peer_ids,
+            service_name: service_names,
+            env_variables: provided_env_variable,
+            url,
+            version,
+        } => {
+            let rolling_options = batch_size.map(|batch_size| RollingUpgradeOptions {
+                batch_size,
+                health_gate: HealthGate::new(
+                    min_connected_peers,
+                    min_records,
+                    Duration::from_secs(health_timeout),
+                ),
+            });
+            cmd::remote::upgrade(
+                &mut client,
+                do_not_start,
+                path,
+                force,
+                interval,
+                peer_ids,
+                provided_env_variable,
+                rolling_options,
                 service_names,
                 url,
                 version,
@@ -824,6 +1361,16 @@ async fn main() -> Result<()> {
             )
             .await
         }
+        SubCmd::Apply { .. }
+        | SubCmd::Backend { .. }
+        | SubCmd::Daemon(_)
+        | SubCmd::Export { .. }
+        | SubCmd::Faucet(_)
+        | SubCmd::Local(_)
+        | SubCmd::Preflight { .. }
+        | SubCmd::Supervise { .. } => {
+            unreachable!()
+        }
     }
 }
 
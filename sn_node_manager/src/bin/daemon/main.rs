@@ -11,18 +11,45 @@ extern crate tracing;
 
 use clap::Parser;
 use color_eyre::eyre::{eyre, Result};
+use libp2p::Multiaddr;
 use libp2p_identity::PeerId;
-use sn_node_manager::{config::get_node_registry_path, rpc, DAEMON_DEFAULT_PORT};
+use sn_node_manager::{
+    add_services::config::parse_port_range,
+    cmd,
+    config::get_node_registry_path,
+    helpers::tail_log_file,
+    rolling_upgrade::{HealthGate, RollingUpgradeOptions},
+    rpc,
+    rpc_client::DAEMON_AUTH_METADATA_KEY,
+    VerbosityLevel, DAEMON_DEFAULT_PORT,
+};
+use sn_peers_acquisition::PeersArgs;
 use sn_service_management::{
     safenode_manager_proto::{
+        get_balances_response::Balance,
         get_status_response::Node,
         safe_node_manager_server::{SafeNodeManager, SafeNodeManagerServer},
-        GetStatusRequest, GetStatusResponse, NodeServiceRestartRequest, NodeServiceRestartResponse,
+        AddNodesRequest, AddNodesResponse, EnvironmentVariable, GetBalancesRequest,
+        GetBalancesResponse, GetStatusRequest, GetStatusResponse, LogLine, NodeEventMessage,
+        NodeServiceRestartRequest, NodeServiceRestartResponse, RemoveNodesRequest, ResetRequest,
+        StartNodesRequest, StopNodesRequest, StreamLogsRequest, StreamNodeEventsRequest,
+        UpgradeNodesRequest,
     },
     NodeRegistry,
 };
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use tonic::{transport::Server, Code, Request, Response, Status};
+use std::{
+    future::Future,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+use tokio::sync::{mpsc, Mutex};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{
+    transport::{Certificate, Identity, Server, ServerTlsConfig},
+    Code, Request, Response, Status,
+};
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -35,9 +62,27 @@ struct Args {
     /// If not set, the daemon listens locally for commands.
     #[clap(long, default_value_t = Ipv4Addr::new(127, 0, 0, 1))]
     address: Ipv4Addr,
+    /// Require every request to carry this access token.
+    ///
+    /// Prefer the environment variable, which keeps the token out of the process list.
+    #[clap(long, env = "SAFENODEMAND_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+    /// Serve over TLS using this PEM encoded certificate.
+    #[clap(long, env = "SAFENODEMAND_TLS_CERT", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// The PEM encoded private key for the TLS certificate.
+    #[clap(long, env = "SAFENODEMAND_TLS_KEY", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// Require clients to present a certificate signed by this PEM encoded CA certificate.
+    #[clap(long, env = "SAFENODEMAND_TLS_CLIENT_CA", requires = "tls_cert")]
+    tls_client_ca: Option<PathBuf>,
 }
 
-struct SafeNodeManagerDaemon {}
+struct SafeNodeManagerDaemon {
+    // Operations that modify the node registry are run one at a time, since each of them loads,
+    // changes and saves the registry.
+    op_lock: Mutex<()>,
+}
 
 // Implementing RPC interface for service defined in .proto
 #[tonic::async_trait]
@@ -48,6 +93,7 @@ impl SafeNodeManager for SafeNodeManagerDaemon {
     ) -> Result<Response<NodeServiceRestartResponse>, Status> {
         println!("RPC request received {:?}", request.get_ref());
         info!("RPC request received {:?}", request.get_ref());
+        let _guard = self.op_lock.lock().await;
         let node_registry = Self::load_node_registry().map_err(|err| {
             Status::new(
                 Code::Internal,
@@ -73,6 +119,309 @@ impl SafeNodeManager for SafeNodeManagerDaemon {
     ) -> Result<Response<GetStatusResponse>, Status> {
         println!("RPC request received {:?}", request.get_ref());
         info!("RPC request received {:?}", request.get_ref());
+        Ok(Response::new(Self::status_response()?))
+    }
+
+    async fn add_nodes(
+        &self,
+        request: Request<AddNodesRequest>,
+    ) -> Result<Response<AddNodesResponse>, Status> {
+        println!("RPC request received {:?}", request.get_ref());
+        info!("RPC request received {:?}", request.get_ref());
+        let request = request.into_inner();
+
+        let peers = request
+            .peers
+            .iter()
+            .map(|peer| Multiaddr::from_str(peer))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| invalid_argument("peer", err))?;
+        // The `network_contacts_url` field only exists when the `network-contacts` feature is
+        // enabled on `sn_peers_acquisition`.
+        #[allow(clippy::needless_update)]
+        let peers = PeersArgs {
+            first: request.first,
+            peers,
+            ..Default::default()
+        };
+        let metrics_port = request
+            .metrics_port
+            .map(|port| parse_port_range(&port))
+            .transpose()
+            .map_err(|err| invalid_argument("metrics port", err))?;
+        let node_port = request
+            .node_port
+            .map(|port| parse_port_range(&port))
+            .transpose()
+            .map_err(|err| invalid_argument("node port", err))?;
+        let rpc_port = request
+            .rpc_port
+            .map(|port| parse_port_range(&port))
+            .transpose()
+            .map_err(|err| invalid_argument("RPC port", err))?;
+        let rpc_address = request
+            .rpc_address
+            .map(|address| Ipv4Addr::from_str(&address))
+            .transpose()
+            .map_err(|err| invalid_argument("RPC address", err))?;
+        let count = request
+            .count
+            .map(u16::try_from)
+            .transpose()
+            .map_err(|err| invalid_argument("count", err))?;
+
+        let _guard = self.op_lock.lock().await;
+        let existing_services = Self::load_node_registry()
+            .map_err(internal_error)?
+            .nodes
+            .into_iter()
+            .map(|node| node.service_name)
+            .collect::<Vec<_>>();
+
+        run_on_blocking_thread(move || {
+            cmd::node::add(
+                count,
+                request.data_dir_path.map(PathBuf::from),
+                from_rpc_env_variables(request.env_variables),
+                request.home_network,
+                request.local,
+                request.log_dir_path.map(PathBuf::from),
+                metrics_port,
+                node_port,
+                peers,
+                rpc_address,
+                rpc_port,
+                request.skip_preflight,
+                request.path.map(PathBuf::from),
+                request.url,
+                request.user,
+                request.version,
+                VerbosityLevel::Minimal,
+            )
+        })
+        .await
+        .map_err(|err| Status::new(Code::Internal, format!("Failed to add services: {err}")))?;
+
+        let service_names = Self::load_node_registry()
+            .map_err(internal_error)?
+            .nodes
+            .into_iter()
+            .map(|node| node.service_name)
+            .filter(|service_name| !existing_services.contains(service_name))
+            .collect();
+        Ok(Response::new(AddNodesResponse { service_names }))
+    }
+
+    async fn start_nodes(
+        &self,
+        request: Request<StartNodesRequest>,
+    ) -> Result<Response<GetStatusResponse>, Status> {
+        println!("RPC request received {:?}", request.get_ref());
+        info!("RPC request received {:?}", request.get_ref());
+        let request = request.into_inner();
+        let _guard = self.op_lock.lock().await;
+        cmd::node::start(
+            request.interval_millis,
+            request.peer_ids,
+            request.service_names,
+            VerbosityLevel::Minimal,
+        )
+        .await
+        .map_err(internal_error)?;
+        Ok(Response::new(Self::status_response()?))
+    }
+
+    async fn stop_nodes(
+        &self,
+        request: Request<StopNodesRequest>,
+    ) -> Result<Response<GetStatusResponse>, Status> {
+        println!("RPC request received {:?}", request.get_ref());
+        info!("RPC request received {:?}", request.get_ref());
+        let request = request.into_inner();
+        let _guard = self.op_lock.lock().await;
+        cmd::node::stop(
+            request.peer_ids,
+            request.service_names,
+            VerbosityLevel::Minimal,
+        )
+        .await
+        .map_err(internal_error)?;
+        Ok(Response::new(Self::status_response()?))
+    }
+
+    async fn remove_nodes(
+        &self,
+        request: Request<RemoveNodesRequest>,
+    ) -> Result<Response<GetStatusResponse>, Status> {
+        println!("RPC request received {:?}", request.get_ref());
+        info!("RPC request received {:?}", request.get_ref());
+        let request = request.into_inner();
+        let _guard = self.op_lock.lock().await;
+        cmd::node::remove(
+            request.keep_directories,
+            request.peer_ids,
+            request.service_names,
+            VerbosityLevel::Minimal,
+        )
+        .await
+        .map_err(internal_error)?;
+        Ok(Response::new(Self::status_response()?))
+    }
+
+    async fn reset(
+        &self,
+        request: Request<ResetRequest>,
+    ) -> Result<Response<GetStatusResponse>, Status> {
+        println!("RPC request received {:?}", request.get_ref());
+        info!("RPC request received {:?}", request.get_ref());
+        let _guard = self.op_lock.lock().await;
+        Self::reset_handler().await.map_err(internal_error)?;
+        Ok(Response::new(Self::status_response()?))
+    }
+
+    async fn upgrade_nodes(
+        &self,
+        request: Request<UpgradeNodesRequest>,
+    ) -> Result<Response<GetStatusResponse>, Status> {
+        println!("RPC request received {:?}", request.get_ref());
+        info!("RPC request received {:?}", request.get_ref());
+        let request = request.into_inner();
+        let rolling_options = request.batch_size.map(|batch_size| RollingUpgradeOptions {
+            batch_size: batch_size as usize,
+            health_gate: HealthGate::new(
+                request.min_connected_peers as usize,
+                request.min_records as usize,
+                Duration::from_secs(request.health_timeout_secs),
+            ),
+        });
+        let _guard = self.op_lock.lock().await;
+        run_on_blocking_thread(move || {
+            cmd::node::upgrade(
+                request.do_not_start,
+                request.path.map(PathBuf::from),
+                request.force,
+                request.interval_millis,
+                request.peer_ids,
+                from_rpc_env_variables(request.env_variables),
+                rolling_options,
+                request.service_names,
+                request.url,
+                request.version,
+                VerbosityLevel::Minimal,
+            )
+        })
+        .await
+        .map_err(internal_error)?;
+        Ok(Response::new(Self::status_response()?))
+    }
+
+    async fn get_balances(
+        &self,
+        request: Request<GetBalancesRequest>,
+    ) -> Result<Response<GetBalancesResponse>, Status> {
+        println!("RPC request received {:?}", request.get_ref());
+        info!("RPC request received {:?}", request.get_ref());
+        let request = request.into_inner();
+        let balances = cmd::node::get_balances(
+            request.peer_ids,
+            request.service_names,
+            VerbosityLevel::Minimal,
+        )
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .map(|(service_name, balance)| Balance {
+            service_name,
+            nanos: balance.as_nano(),
+        })
+        .collect();
+        Ok(Response::new(GetBalancesResponse { balances }))
+    }
+
+    type StreamLogsStream = ReceiverStream<Result<LogLine, Status>>;
+
+    async fn stream_logs(
+        &self,
+        request: Request<StreamLogsRequest>,
+    ) -> Result<Response<Self::StreamLogsStream>, Status> {
+        println!("RPC request received {:?}", request.get_ref());
+        info!("RPC request received {:?}", request.get_ref());
+        let request = request.into_inner();
+        let log_file_path = cmd::node::get_log_file_path(&request.service_name)
+            .map_err(|err| Status::new(Code::NotFound, err.to_string()))?;
+
+        let (sender, receiver) = mpsc::channel(100);
+        tokio::spawn(async move {
+            let (line_sender, mut line_receiver) = mpsc::channel(100);
+            let tail = tokio::spawn(tail_log_file(
+                log_file_path,
+                request.lines as usize,
+                request.follow,
+                line_sender,
+            ));
+            while let Some(line) = line_receiver.recv().await {
+                if sender.send(Ok(LogLine { line })).await.is_err() {
+                    break;
+                }
+            }
+            drop(line_receiver);
+            if let Ok(Err(err)) = tail.await {
+                let _ = sender.send(Err(internal_error(err))).await;
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    type StreamNodeEventsStream = ReceiverStream<Result<NodeEventMessage, Status>>;
+
+    async fn stream_node_events(
+        &self,
+        request: Request<StreamNodeEventsRequest>,
+    ) -> Result<Response<Self::StreamNodeEventsStream>, Status> {
+        println!("RPC request received {:?}", request.get_ref());
+        info!("RPC request received {:?}", request.get_ref());
+        let request = request.into_inner();
+        let nodes = cmd::node::get_node_event_sources(request.peer_ids, request.service_names)
+            .await
+            .map_err(|err| Status::new(Code::FailedPrecondition, err.to_string()))?;
+
+        let (sender, receiver) = mpsc::channel(100);
+        tokio::spawn(async move {
+            let (event_sender, mut event_receiver) = mpsc::channel(100);
+            let events = tokio::spawn(rpc::stream_node_events(nodes, event_sender));
+            while let Some((service_name, event)) = event_receiver.recv().await {
+                if sender
+                    .send(Ok(NodeEventMessage {
+                        service_name,
+                        event,
+                    }))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            drop(event_receiver);
+            if let Ok(Err(err)) = events.await {
+                let _ = sender
+                    .send(Err(Status::new(Code::Unavailable, err.to_string())))
+                    .await;
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
+
+impl SafeNodeManagerDaemon {
+    fn load_node_registry() -> Result<NodeRegistry> {
+        let node_registry_path = get_node_registry_path()
+            .map_err(|err| eyre!("Could not obtain node registry path: {err:?}"))?;
+        let node_registry = NodeRegistry::load(&node_registry_path)
+            .map_err(|err| eyre!("Could not load node registry: {err:?}"))?;
+        Ok(node_registry)
+    }
+
+    fn status_response() -> Result<GetStatusResponse, Status> {
         let node_registry = Self::load_node_registry().map_err(|err| {
             Status::new(
                 Code::Internal,
@@ -87,19 +436,12 @@ impl SafeNodeManager for SafeNodeManagerDaemon {
                 peer_id: node.peer_id.map(|id| id.to_bytes()),
                 status: node.status.clone() as i32,
                 number: node.number as u32,
+                service_name: node.service_name.clone(),
+                version: node.version.clone(),
+                metrics_port: node.metrics_port.map(u32::from),
             })
             .collect::<Vec<_>>();
-        Ok(Response::new(GetStatusResponse { nodes: nodes_info }))
-    }
-}
-
-impl SafeNodeManagerDaemon {
-    fn load_node_registry() -> Result<NodeRegistry> {
-        let node_registry_path = get_node_registry_path()
-            .map_err(|err| eyre!("Could not obtain node registry path: {err:?}"))?;
-        let node_registry = NodeRegistry::load(&node_registry_path)
-            .map_err(|err| eyre!("Could not load node registry: {err:?}"))?;
-        Ok(node_registry)
+        Ok(GetStatusResponse { nodes: nodes_info })
     }
 
     async fn restart_handler(
@@ -114,21 +456,144 @@ impl SafeNodeManagerDaemon {
 
         res
     }
+
+    async fn reset_handler() -> Result<()> {
+        // Resetting deletes the registry, but the daemon itself, and the faucet, should remain
+        // registered.
+        let node_registry = Self::load_node_registry()?;
+        cmd::node::reset(true, VerbosityLevel::Minimal).await?;
+
+        if node_registry.daemon.is_some() || node_registry.faucet.is_some() {
+            let mut new_registry = Self::load_node_registry()?;
+            new_registry.daemon = node_registry.daemon;
+            new_registry.faucet = node_registry.faucet;
+            new_registry.save()?;
+        }
+        Ok(())
+    }
+}
+
+/// Downloading releases holds a non-`Send` release repository across await points, so commands
+/// that do it are driven on a blocking thread rather than within the RPC handler.
+async fn run_on_blocking_thread<F, Fut>(command: F) -> Result<()>
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<()>>,
+{
+    let handle = tokio::runtime::Handle::current();
+    tokio::task::spawn_blocking(move || handle.block_on(command())).await?
+}
+
+fn from_rpc_env_variables(
+    env_variables: Vec<EnvironmentVariable>,
+) -> Option<Vec<(String, String)>> {
+    if env_variables.is_empty() {
+        return None;
+    }
+    Some(
+        env_variables
+            .into_iter()
+            .map(|variable| (variable.name, variable.value))
+            .collect(),
+    )
+}
+
+fn internal_error(err: color_eyre::Report) -> Status {
+    Status::new(Code::Internal, err.to_string())
+}
+
+fn invalid_argument(name: &str, err: impl std::fmt::Display) -> Status {
+    Status::new(Code::InvalidArgument, format!("Invalid {name}: {err}"))
+}
+
+/// Rejects any request that doesn't carry the configured access token.
+fn check_token(request: Request<()>, token: &Option<String>) -> Result<Request<()>, Status> {
+    let Some(token) = token else {
+        return Ok(request);
+    };
+    let provided = request
+        .metadata()
+        .get(DAEMON_AUTH_METADATA_KEY)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+
+    // Compare in constant time so the token can't be discovered through response timings.
+    let matches = provided.len() == token.len()
+        && provided
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0;
+    if matches {
+        Ok(request)
+    } else {
+        Err(Status::new(
+            Code::Unauthenticated,
+            "Missing or invalid access token",
+        ))
+    }
 }
 
-// The SafeNodeManager trait returns `Status` as its error. So the actual logic is here and we can easily map the errors
-// into Status inside the trait fns.
-impl SafeNodeManagerDaemon {}
+/// The daemon can add services that run any binary, as any user, so it must not be reachable from
+/// other machines without authentication, and the access token must not cross the network in
+/// plaintext.
+fn check_exposure(args: &Args) -> Result<()> {
+    if args.address.is_loopback() {
+        return Ok(());
+    }
+    if args.token.is_none() && args.tls_client_ca.is_none() {
+        return Err(eyre!(
+            "Refusing to listen on {} without authentication. Anyone who can reach the daemon \
+            could run any program on this machine. Use --token or --tls-client-ca to require \
+            authentication.",
+            args.address
+        ));
+    }
+    if args.token.is_some() && args.tls_cert.is_none() {
+        return Err(eyre!(
+            "Refusing to accept an access token on {} without TLS, as it would be sent in \
+            plaintext. Use --tls-cert and --tls-key to serve over TLS.",
+            args.address
+        ));
+    }
+    Ok(())
+}
 
-#[tokio::main(flavor = "current_thread")]
+// `start` and `upgrade` block while applying their interval between services, so the daemon needs
+// more than one thread to keep serving log and event streams in the meantime.
+#[tokio::main]
 async fn main() -> Result<()> {
     println!("Starting safenodemand");
     let args = Args::parse();
-    let service = SafeNodeManagerDaemon {};
+    let service = SafeNodeManagerDaemon {
+        op_lock: Mutex::new(()),
+    };
+
+    let mut server = Server::builder();
+    if let (Some(cert_path), Some(key_path)) = (&args.tls_cert, &args.tls_key) {
+        let mut tls_config = ServerTlsConfig::new().identity(Identity::from_pem(
+            std::fs::read(cert_path)?,
+            std::fs::read(key_path)?,
+        ));
+        if let Some(client_ca_path) = &args.tls_client_ca {
+            tls_config =
+                tls_config.client_ca_root(Certificate::from_pem(std::fs::read(client_ca_path)?));
+        }
+        server = server.tls_config(tls_config)?;
+    }
+    if let Err(err) = check_exposure(&args) {
+        error!("Safenode Manager Daemon refused to start: {err}");
+        return Err(err);
+    }
 
+    let token = args.token;
     // adding our service to our server.
-    if let Err(err) = Server::builder()
-        .add_service(SafeNodeManagerServer::new(service))
+    if let Err(err) = server
+        .add_service(SafeNodeManagerServer::with_interceptor(
+            service,
+            move |request| check_token(request, &token),
+        ))
         .serve(SocketAddr::new(IpAddr::V4(args.address), args.port))
         .await
     {
@@ -139,3 +604,32 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(address: Ipv4Addr, token: bool, tls_cert: bool, tls_client_ca: bool) -> Args {
+        Args {
+            port: DAEMON_DEFAULT_PORT,
+            address,
+            token: token.then(|| "token".to_string()),
+            tls_cert: tls_cert.then(|| PathBuf::from("cert.pem")),
+            tls_key: tls_cert.then(|| PathBuf::from("key.pem")),
+            tls_client_ca: tls_client_ca.then(|| PathBuf::from("ca.pem")),
+        }
+    }
+
+    #[test]
+    fn the_daemon_should_only_be_exposed_with_authentication_over_tls() {
+        let public = Ipv4Addr::new(0, 0, 0, 0);
+        assert!(check_exposure(&args(Ipv4Addr::LOCALHOST, false, false, false)).is_ok());
+        assert!(check_exposure(&args(Ipv4Addr::LOCALHOST, true, false, false)).is_ok());
+        assert!(check_exposure(&args(public, false, false, false)).is_err());
+        assert!(check_exposure(&args(public, false, true, false)).is_err());
+        assert!(check_exposure(&args(public, true, false, false)).is_err());
+        assert!(check_exposure(&args(public, true, true, false)).is_ok());
+        assert!(check_exposure(&args(public, false, true, true)).is_ok());
+        assert!(check_exposure(&args(public, true, true, true)).is_ok());
+    }
+}
@@ -0,0 +1,980 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Runs churn scenarios against a local network while a client keeps uploading and downloading.
+//!
+//! A scenario is a TOML file that describes how long to run for, the client workload, and events
+//! that disrupt random nodes at intervals. At the end, every file that was uploaded is downloaded
+//! again, to find out whether any data was lost.
+
+use crate::local::{run_node, LocalSafeLauncher, RunNodeOptions};
+use color_eyre::{eyre::eyre, Result};
+use colored::Colorize;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, RngCore, SeedableRng};
+use serde::Deserialize;
+use sn_client::{test_utils::get_funded_wallet, Client, FilesApi, FilesDownload, Uploader};
+use sn_protocol::storage::ChunkAddress;
+use sn_service_management::{
+    control::{ServiceControl, ServiceController},
+    rpc::{RpcActions, RpcClient},
+    NodeRegistry, NodeServiceData, ServiceStatus,
+};
+use sn_transfers::bls::SecretKey;
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    hash::{Hash, Hasher},
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use sysinfo::{Pid, Signal, System};
+use tokio::{
+    sync::watch,
+    time::{sleep, sleep_until, Instant},
+};
+
+/// The name of the file written to a node's data directory by the `fill-disk` action.
+const FILLER_FILE_NAME: &str = "chaos-filler";
+/// The time allowed for a node that was added to start its RPC service.
+///
+/// Units are milliseconds.
+const NODE_LAUNCH_INTERVAL: u64 = 1000;
+/// The time a restarted node is given before it's asked for the PID of its new process.
+const RESTART_SETTLE_TIME: Duration = Duration::from_secs(5);
+/// The number of times a file is downloaded in the final check before it's considered lost.
+const VERIFY_ATTEMPTS: usize = 3;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChaosAction {
+    /// Launch new nodes that join the network.
+    Add,
+    /// Write a file of `size` bytes to the data directory of a node.
+    FillDisk,
+    /// Kill node processes without giving them a chance to shut down. They're left dead.
+    Kill,
+    /// Suspend node processes with SIGSTOP, then resume them after `duration`.
+    Pause,
+    /// Kill node processes and delete their record stores, so the data they held is gone.
+    Remove,
+    /// Ask nodes to restart through their RPC service.
+    Restart,
+}
+
+impl std::fmt::Display for ChaosAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChaosAction::Add => write!(f, "add"),
+            ChaosAction::FillDisk => write!(f, "fill-disk"),
+            ChaosAction::Kill => write!(f, "kill"),
+            ChaosAction::Pause => write!(f, "pause"),
+            ChaosAction::Remove => write!(f, "remove"),
+            ChaosAction::Restart => write!(f, "restart"),
+        }
+    }
+}
+
+/// Something that happens to the network repeatedly while the scenario runs.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ChaosEvent {
+    pub action: ChaosAction,
+    /// The number of nodes affected each time the event happens.
+    #[serde(default = "default_event_count")]
+    pub count: usize,
+    /// For `pause` and `fill-disk`, how long before the action is undone. Disks that are filled
+    /// without a duration stay full until the scenario ends.
+    ///
+    /// Units are seconds.
+    pub duration: Option<u64>,
+    /// The time between each occurrence of the event.
+    ///
+    /// Units are seconds.
+    pub interval: u64,
+    /// For `restart`, whether the nodes keep their peer IDs.
+    #[serde(default)]
+    pub retain_peer_id: bool,
+    /// For `fill-disk`, the number of bytes to write.
+    pub size: Option<u64>,
+    /// The time of the first occurrence, if it should be different from `interval`.
+    ///
+    /// Units are seconds.
+    pub start: Option<u64>,
+}
+
+fn default_event_count() -> usize {
+    1
+}
+
+/// The uploads and downloads the client keeps doing while the scenario runs.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Workload {
+    /// The time between downloads of a random file that was uploaded.
+    ///
+    /// Units are seconds.
+    pub download_interval: u64,
+    /// The size of each file that is uploaded.
+    ///
+    /// Units are bytes.
+    pub file_size: u64,
+    /// The time the network is given to recover after the scenario, before the final check.
+    ///
+    /// Units are seconds.
+    pub settle_time: u64,
+    /// The time between uploads of a new file.
+    ///
+    /// Units are seconds.
+    pub upload_interval: u64,
+}
+
+impl Default for Workload {
+    fn default() -> Self {
+        Self {
+            download_interval: 5,
+            file_size: 1024 * 1024,
+            settle_time: 30,
+            upload_interval: 10,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// How long the events run for.
+    ///
+    /// Units are seconds.
+    pub duration: u64,
+    #[serde(default)]
+    pub events: Vec<ChaosEvent>,
+    /// Seeds the choice of nodes for each event, so a run can be repeated. A random seed is used
+    /// if this isn't set.
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub workload: Workload,
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let scenario: Scenario = toml::from_str(&contents)
+            .map_err(|err| eyre!("Could not parse {}: {err}", path.to_string_lossy()))?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.duration == 0 {
+            return Err(eyre!("The scenario duration must be greater than zero"));
+        }
+        if self.workload.upload_interval == 0 || self.workload.download_interval == 0 {
+            return Err(eyre!("The workload intervals must be greater than zero"));
+        }
+        if self.workload.file_size < 1024 {
+            return Err(eyre!("The workload file size must be at least 1024 bytes"));
+        }
+
+        for (index, event) in self.events.iter().enumerate() {
+            let event_name = format!("Event {} ({})", index + 1, event.action);
+            if event.interval == 0 {
+                return Err(eyre!(
+                    "{event_name}: the interval must be greater than zero"
+                ));
+            }
+            if event.count == 0 {
+                return Err(eyre!("{event_name}: the count must be greater than zero"));
+            }
+            match event.action {
+                ChaosAction::Pause if event.duration.is_none() => {
+                    return Err(eyre!("{event_name}: a duration is required"));
+                }
+                ChaosAction::FillDisk if event.size.is_none() => {
+                    return Err(eyre!("{event_name}: a size is required"));
+                }
+                ChaosAction::Pause | ChaosAction::FillDisk => {}
+                _ if event.duration.is_some() => {
+                    return Err(eyre!(
+                        "{event_name}: a duration only applies to pause and fill-disk"
+                    ));
+                }
+                _ => {}
+            }
+            if event.size.is_some() && event.action != ChaosAction::FillDisk {
+                return Err(eyre!("{event_name}: a size only applies to fill-disk"));
+            }
+            if event.retain_peer_id && event.action != ChaosAction::Restart {
+                return Err(eyre!(
+                    "{event_name}: retain_peer_id only applies to restart"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every occurrence of every event within the duration of the scenario, as the time from the
+    /// start and the index of the event, in the order they happen.
+    pub fn timeline(&self) -> Vec<(Duration, usize)> {
+        let mut timeline = Vec::new();
+        for (index, event) in self.events.iter().enumerate() {
+            let mut at = event.start.unwrap_or(event.interval);
+            while at < self.duration {
+                timeline.push((Duration::from_secs(at), index));
+                at += event.interval;
+            }
+        }
+        timeline.sort();
+        timeline
+    }
+}
+
+/// The outcome of one kind of operation performed by the client.
+#[derive(Clone, Debug, Default)]
+pub struct OperationStats {
+    pub failed: usize,
+    /// The time each successful operation took.
+    pub latencies: Vec<Duration>,
+}
+
+impl OperationStats {
+    pub fn succeeded(&self) -> usize {
+        self.latencies.len()
+    }
+
+    /// The latency below which `percent` of the successful operations completed, using the
+    /// nearest-rank method.
+    pub fn percentile(&self, percent: f64) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let mut latencies = self.latencies.clone();
+        latencies.sort();
+        let rank = ((percent / 100.0) * latencies.len() as f64).ceil() as usize;
+        Some(latencies[rank.clamp(1, latencies.len()) - 1])
+    }
+
+    pub fn mean(&self) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        Some(self.latencies.iter().sum::<Duration>() / self.latencies.len() as u32)
+    }
+
+    fn summary(&self) -> String {
+        let mut summary = format!("{} succeeded, {} failed", self.succeeded(), self.failed);
+        if let (Some(min), Some(mean), Some(p50), Some(p95), Some(max)) = (
+            self.percentile(0.0),
+            self.mean(),
+            self.percentile(50.0),
+            self.percentile(95.0),
+            self.percentile(100.0),
+        ) {
+            summary.push_str(&format!(
+                "; latency min {min:.2?}, mean {mean:.2?}, p50 {p50:.2?}, p95 {p95:.2?}, max {max:.2?}"
+            ));
+        }
+        summary
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ChaosReport {
+    pub downloads: OperationStats,
+    /// What was done to the network, with the time from the start of the scenario.
+    pub events: Vec<(Duration, String)>,
+    /// Files that couldn't be downloaded intact in the final check.
+    pub files_lost: Vec<ChunkAddress>,
+    pub files_verified: usize,
+    /// Set if the scenario was stopped with Ctrl-C before it finished.
+    pub interrupted: bool,
+    pub seed: u64,
+    pub uploads: OperationStats,
+}
+
+impl ChaosReport {
+    pub fn print(&self) {
+        println!("Seed: {}", self.seed);
+        if self.interrupted {
+            println!("{}", "The scenario was interrupted".yellow());
+        }
+        println!("Events:");
+        if self.events.is_empty() {
+            println!("  None");
+        }
+        for (at, description) in &self.events {
+            println!("  [{:>6}s] {description}", at.as_secs());
+        }
+        println!("Uploads: {}", self.uploads.summary());
+        println!("Downloads: {}", self.downloads.summary());
+        let lost = format!(
+            "{} of {} files could not be retrieved",
+            self.files_lost.len(),
+            self.files_verified
+        );
+        if self.files_lost.is_empty() {
+            println!("Data loss: {lost}");
+        } else {
+            println!("Data loss: {}", lost.red());
+            for address in &self.files_lost {
+                println!("  {address:?}");
+            }
+        }
+    }
+}
+
+/// A file that was uploaded by the workload.
+#[derive(Clone, Debug)]
+struct UploadedFile {
+    address: ChunkAddress,
+    content_hash: u64,
+}
+
+/// Something to undo once a `pause` or `fill-disk` action has run for its duration.
+enum Undo {
+    RemoveFiller(PathBuf),
+    Resume { service_name: String, pid: u32 },
+}
+
+pub struct ChaosOptions {
+    pub owner: String,
+    pub scenario: Scenario,
+    /// Where the client keeps its wallet and the files it uploads.
+    pub work_dir: PathBuf,
+}
+
+/// Run the scenario against the local network in the registry, which is updated as nodes are
+/// added, killed and removed.
+pub async fn run(options: ChaosOptions, node_registry: &mut NodeRegistry) -> Result<ChaosReport> {
+    let scenario = options.scenario;
+    let peers = node_registry
+        .nodes
+        .iter()
+        .filter(|node| node.status == ServiceStatus::Running)
+        .filter_map(|node| node.listen_addr.clone())
+        .flatten()
+        .collect::<Vec<_>>();
+    if peers.is_empty() {
+        return Err(eyre!("There are no running nodes in the local network"));
+    }
+
+    let wallet_dir = options.work_dir.join("wallet");
+    std::fs::create_dir_all(&wallet_dir)?;
+    println!("Connecting a client to the network...");
+    let client = Client::new(SecretKey::random(), Some(peers), None, None).await?;
+    get_funded_wallet(&client, &wallet_dir)
+        .await
+        .map_err(|err| eyre!("Could not fund the client wallet: {err}"))?;
+
+    let seed = scenario.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    println!(
+        "Running the scenario for {}s with seed {seed}",
+        scenario.duration
+    );
+    let uploaded_files = Arc::new(Mutex::new(Vec::new()));
+    let (stop_sender, stop_receiver) = watch::channel(false);
+    let upload_task = tokio::spawn(upload_files(
+        client.clone(),
+        options.work_dir.clone(),
+        scenario.workload.clone(),
+        uploaded_files.clone(),
+        stop_receiver.clone(),
+    ));
+    let download_task = tokio::spawn(download_files(
+        client.clone(),
+        wallet_dir.clone(),
+        scenario.workload.download_interval,
+        uploaded_files.clone(),
+        stop_receiver,
+    ));
+
+    let mut runner = ChaosRunner {
+        events: Vec::new(),
+        fillers: Vec::new(),
+        node_registry,
+        owner: options.owner,
+        paused: HashSet::new(),
+        rng: StdRng::seed_from_u64(seed),
+        start: Instant::now(),
+    };
+    let interrupted = runner.run_events(&scenario).await?;
+
+    let _ = stop_sender.send(true);
+    let uploads = upload_task.await?;
+    let downloads = download_task.await?;
+
+    let settle_time = Duration::from_secs(scenario.workload.settle_time);
+    if !interrupted && !settle_time.is_zero() {
+        println!("Giving the network {settle_time:?} to recover...");
+        sleep(settle_time).await;
+    }
+
+    let uploaded_files = uploaded_files
+        .lock()
+        .map_err(|_| eyre!("The list of uploaded files was poisoned"))?
+        .clone();
+    println!("Verifying {} uploaded files...", uploaded_files.len());
+    let mut files_lost = Vec::new();
+    for file in &uploaded_files {
+        let mut retrieved = false;
+        for _ in 0..VERIFY_ATTEMPTS {
+            if download_file(&client, &wallet_dir, file).await.is_ok() {
+                retrieved = true;
+                break;
+            }
+        }
+        if !retrieved {
+            files_lost.push(file.address);
+        }
+    }
+
+    Ok(ChaosReport {
+        downloads,
+        events: runner.events,
+        files_lost,
+        files_verified: uploaded_files.len(),
+        interrupted,
+        seed,
+        uploads,
+    })
+}
+
+struct ChaosRunner<'a> {
+    events: Vec<(Duration, String)>,
+    fillers: Vec<PathBuf>,
+    node_registry: &'a mut NodeRegistry,
+    owner: String,
+    /// The service names of the nodes that are currently suspended.
+    paused: HashSet<String>,
+    rng: StdRng,
+    start: Instant,
+}
+
+impl ChaosRunner<'_> {
+    /// Apply the events on the timeline as they become due. Returns whether the scenario was
+    /// interrupted.
+    async fn run_events(&mut self, scenario: &Scenario) -> Result<bool> {
+        let end = self.start + Duration::from_secs(scenario.duration);
+        let mut timeline = scenario.timeline().into_iter().peekable();
+        let mut pending: Vec<(Instant, Undo)> = Vec::new();
+        let mut interrupted = false;
+
+        loop {
+            let next_event = timeline.peek().map(|(at, _)| self.start + *at);
+            let next_undo = pending.iter().map(|(at, _)| *at).min();
+            let next = [next_event, next_undo]
+                .into_iter()
+                .flatten()
+                .fold(end, |next, at| next.min(at));
+            tokio::select! {
+                _ = sleep_until(next) => {}
+                _ = tokio::signal::ctrl_c() => {
+                    println!("Stopping the scenario...");
+                    interrupted = true;
+                    break;
+                }
+            }
+
+            let now = Instant::now();
+            let (due, not_due): (Vec<_>, Vec<_>) =
+                pending.into_iter().partition(|(at, _)| *at <= now);
+            pending = not_due;
+            for (_, undo) in due {
+                self.undo(undo).await;
+            }
+            while let Some((_, index)) = timeline.next_if(|(at, _)| self.start + *at <= now) {
+                let event = &scenario.events[index];
+                for _ in 0..event.count {
+                    match self.apply(event).await {
+                        Ok(Some(undo)) => {
+                            if let Some(duration) = event.duration {
+                                pending
+                                    .push((Instant::now() + Duration::from_secs(duration), undo));
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(err) => self.record(format!("Failed to {}: {err}", event.action)),
+                    }
+                }
+            }
+            if now >= end {
+                break;
+            }
+        }
+
+        for (_, undo) in pending {
+            self.undo(undo).await;
+        }
+        for filler in std::mem::take(&mut self.fillers) {
+            let _ = std::fs::remove_file(filler);
+        }
+        self.refresh_nodes().await;
+        self.node_registry.save()?;
+        Ok(interrupted)
+    }
+
+    fn record(&mut self, description: String) {
+        let at = self.start.elapsed();
+        println!("[{:>6}s] {description}", at.as_secs());
+        self.events.push((at, description));
+    }
+
+    /// Choose a random node that is running and not paused. The genesis node is never chosen,
+    /// because it's the contact for the faucet and for nodes that are added.
+    fn choose_node(&mut self) -> Result<usize> {
+        let candidates = self
+            .node_registry
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| {
+                node.status == ServiceStatus::Running
+                    && !node.genesis
+                    && !self.paused.contains(&node.service_name)
+            })
+            .map(|(index, _)| index)
+            .collect::<Vec<_>>();
+        candidates
+            .choose(&mut self.rng)
+            .copied()
+            .ok_or_else(|| eyre!("no running nodes are available"))
+    }
+
+    async fn apply(&mut self, event: &ChaosEvent) -> Result<Option<Undo>> {
+        if event.action == ChaosAction::Add {
+            self.add_node().await?;
+            return Ok(None);
+        }
+
+        let index = self.choose_node()?;
+        let node = &self.node_registry.nodes[index];
+        let service_name = node.service_name.clone();
+        let pid = get_pid(node).await?;
+        match event.action {
+            ChaosAction::FillDisk => {
+                let size = event.size.unwrap_or_default();
+                let path = node
+                    .data_dir_path
+                    .join(format!("{FILLER_FILE_NAME}-{}", self.fillers.len() + 1));
+                let filler_path = path.clone();
+                tokio::task::spawn_blocking(move || write_filler(&filler_path, size)).await??;
+                self.fillers.push(path.clone());
+                self.record(format!(
+                    "Wrote {size} bytes to the data directory of {service_name}"
+                ));
+                Ok(Some(Undo::RemoveFiller(path)))
+            }
+            ChaosAction::Kill => {
+                kill_process(pid)?;
+                let node = &mut self.node_registry.nodes[index];
+                node.status = ServiceStatus::Stopped;
+                node.pid = None;
+                self.node_registry.save()?;
+                self.record(format!("Killed {service_name}"));
+                Ok(None)
+            }
+            ChaosAction::Pause => {
+                signal_process(pid, Signal::Stop)?;
+                self.paused.insert(service_name.clone());
+                self.record(format!("Paused {service_name}"));
+                Ok(Some(Undo::Resume { service_name, pid }))
+            }
+            ChaosAction::Remove => {
+                kill_process(pid)?;
+                let record_store_path = node.data_dir_path.join("record_store");
+                if record_store_path.is_dir() {
+                    std::fs::remove_dir_all(record_store_path)?;
+                }
+                let node = &mut self.node_registry.nodes[index];
+                node.status = ServiceStatus::Removed;
+                node.pid = None;
+                self.node_registry.save()?;
+                self.record(format!("Removed {service_name}"));
+                Ok(None)
+            }
+            ChaosAction::Restart => {
+                let rpc_client = RpcClient::from_socket_addr(node.rpc_socket_addr);
+                rpc_client.node_restart(0, event.retain_peer_id).await?;
+                self.record(format!("Restarted {service_name}"));
+                // The node runs in a new process after it restarts, which `kill` needs to know.
+                sleep(RESTART_SETTLE_TIME).await;
+                if let Ok(node_info) = rpc_client.node_info().await {
+                    let node = &mut self.node_registry.nodes[index];
+                    node.pid = Some(node_info.pid);
+                    node.peer_id = Some(node_info.peer_id);
+                    node.data_dir_path = node_info.data_path;
+                    node.log_dir_path = node_info.log_path;
+                    self.node_registry.save()?;
+                }
+                Ok(None)
+            }
+            ChaosAction::Add => unreachable!("nodes are added above"),
+        }
+    }
+
+    async fn add_node(&mut self) -> Result<()> {
+        let contact = self
+            .node_registry
+            .nodes
+            .iter()
+            .find(|node| node.genesis)
+            .or_else(|| self.node_registry.nodes.first())
+            .ok_or_else(|| eyre!("the local network has no nodes"))?;
+        let bootstrap_peers = contact.listen_addr.clone().unwrap_or_default();
+        let launcher = LocalSafeLauncher {
+            safenode_bin_path: contact.safenode_path.clone(),
+            ..Default::default()
+        };
+        let version = contact.version.clone();
+
+        let rpc_port = ServiceController {}.get_available_port()?;
+        let rpc_socket_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), rpc_port);
+        let rpc_client = RpcClient::from_socket_addr(rpc_socket_addr);
+        let number = (self.node_registry.nodes.len() as u16) + 1;
+        let node = run_node(
+            RunNodeOptions {
+                version,
+                owner: self.owner.clone(),
+                number,
+                genesis: false,
+                interval: NODE_LAUNCH_INTERVAL,
+                rpc_socket_addr,
+                bootstrap_peers,
+            },
+            &launcher,
+            &rpc_client,
+        )
+        .await?;
+        let service_name = node.service_name.clone();
+        self.node_registry.nodes.push(node);
+        self.node_registry.save()?;
+        self.record(format!("Added {service_name}"));
+        Ok(())
+    }
+
+    async fn undo(&mut self, undo: Undo) {
+        match undo {
+            Undo::RemoveFiller(path) => {
+                if let Err(err) = std::fs::remove_file(&path) {
+                    self.record(format!(
+                        "Failed to remove {}: {err}",
+                        path.to_string_lossy()
+                    ));
+                }
+                self.fillers.retain(|filler| *filler != path);
+            }
+            Undo::Resume { service_name, pid } => {
+                match signal_process(pid, Signal::Continue) {
+                    Ok(()) => self.record(format!("Resumed {service_name}")),
+                    Err(err) => self.record(format!("Failed to resume {service_name}: {err}")),
+                }
+                self.paused.remove(&service_name);
+            }
+        }
+    }
+
+    /// Mark nodes whose processes are no longer running as stopped, so the registry agrees with
+    /// what's left after the scenario.
+    async fn refresh_nodes(&mut self) {
+        let mut system = System::new();
+        for node in self.node_registry.nodes.iter_mut() {
+            if node.status != ServiceStatus::Running {
+                continue;
+            }
+            let running = match node.pid {
+                Some(pid) => system.refresh_process(Pid::from(pid as usize)),
+                None => false,
+            };
+            if !running {
+                node.status = ServiceStatus::Stopped;
+                node.pid = None;
+            }
+        }
+    }
+}
+
+/// The PID of the node's process, which is obtained from the node itself if possible, because
+/// it changes when a node restarts.
+async fn get_pid(node: &NodeServiceData) -> Result<u32> {
+    let rpc_client = RpcClient::from_socket_addr(node.rpc_socket_addr);
+    match rpc_client.node_info().await {
+        Ok(node_info) => Ok(node_info.pid),
+        Err(_) => node
+            .pid
+            .ok_or_else(|| eyre!("the PID of {} is not known", node.service_name)),
+    }
+}
+
+fn kill_process(pid: u32) -> Result<()> {
+    let pid = Pid::from(pid as usize);
+    let mut system = System::new();
+    system.refresh_process(pid);
+    match system.process(pid) {
+        Some(process) => {
+            process.kill();
+            Ok(())
+        }
+        None => Err(eyre!("process {pid} is not running")),
+    }
+}
+
+fn signal_process(pid: u32, signal: Signal) -> Result<()> {
+    let pid = Pid::from(pid as usize);
+    let mut system = System::new();
+    system.refresh_process(pid);
+    match system.process(pid).map(|process| process.kill_with(signal)) {
+        Some(Some(true)) => Ok(()),
+        Some(Some(false)) => Err(eyre!("could not send {signal:?} to process {pid}")),
+        Some(None) => Err(eyre!("{signal:?} is not supported on this platform")),
+        None => Err(eyre!("process {pid} is not running")),
+    }
+}
+
+fn write_filler(path: &Path, size: u64) -> Result<()> {
+    use std::io::Write;
+    let mut file = std::fs::File::create(path)?;
+    // The data is random so the filesystem can't compress it or store it sparsely.
+    let mut block = vec![0u8; 1024 * 1024];
+    rand::thread_rng().fill_bytes(&mut block);
+    let mut remaining = size;
+    while remaining > 0 {
+        let length = remaining.min(block.len() as u64) as usize;
+        file.write_all(&block[..length])?;
+        remaining -= length as u64;
+    }
+    file.sync_all()?;
+    Ok(())
+}
+
+fn content_hash(content: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Upload a new file of random data at each interval, until told to stop.
+async fn upload_files(
+    client: Client,
+    work_dir: PathBuf,
+    workload: Workload,
+    uploaded_files: Arc<Mutex<Vec<UploadedFile>>>,
+    mut stop: watch::Receiver<bool>,
+) -> OperationStats {
+    let mut stats = OperationStats::default();
+    let mut interval = tokio::time::interval(Duration::from_secs(workload.upload_interval));
+    let mut file_number = 0;
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = stop.changed() => break,
+        }
+        file_number += 1;
+        let started = std::time::Instant::now();
+        match upload_file(&client, &work_dir, file_number, workload.file_size).await {
+            Ok(file) => {
+                stats.latencies.push(started.elapsed());
+                if let Ok(mut uploaded_files) = uploaded_files.lock() {
+                    uploaded_files.push(file);
+                }
+            }
+            Err(err) => {
+                println!("{} Upload failed: {err}", "✕".red());
+                stats.failed += 1;
+            }
+        }
+    }
+    stats
+}
+
+async fn upload_file(
+    client: &Client,
+    work_dir: &Path,
+    file_number: usize,
+    file_size: u64,
+) -> Result<UploadedFile> {
+    let mut content = vec![0u8; file_size as usize];
+    rand::thread_rng().fill_bytes(&mut content);
+    let file_path = work_dir.join(format!("file-{file_number}"));
+    let chunks_dir = work_dir.join(format!("chunks-{file_number}"));
+    std::fs::write(&file_path, &content)?;
+    std::fs::create_dir_all(&chunks_dir)?;
+
+    let (address, _, _, chunks) = FilesApi::chunk_file(&file_path, &chunks_dir, true)?;
+    let mut uploader = Uploader::new(client.clone(), work_dir.join("wallet"));
+    uploader.insert_chunk_paths(chunks);
+    let result = uploader.start_upload().await;
+
+    let _ = std::fs::remove_file(&file_path);
+    let _ = std::fs::remove_dir_all(&chunks_dir);
+    result?;
+    Ok(UploadedFile {
+        address,
+        content_hash: content_hash(&content),
+    })
+}
+
+/// Download a random file that was uploaded at each interval, until told to stop.
+async fn download_files(
+    client: Client,
+    wallet_dir: PathBuf,
+    download_interval: u64,
+    uploaded_files: Arc<Mutex<Vec<UploadedFile>>>,
+    mut stop: watch::Receiver<bool>,
+) -> OperationStats {
+    let mut stats = OperationStats::default();
+    let mut interval = tokio::time::interval(Duration::from_secs(download_interval));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = stop.changed() => break,
+        }
+        let file = match uploaded_files.lock() {
+            Ok(uploaded_files) => uploaded_files.choose(&mut rand::thread_rng()).cloned(),
+            Err(_) => None,
+        };
+        let file = match file {
+            Some(file) => file,
+            None => continue,
+        };
+        let started = std::time::Instant::now();
+        match download_file(&client, &wallet_dir, &file).await {
+            Ok(()) => stats.latencies.push(started.elapsed()),
+            Err(err) => {
+                println!("{} Download of {:?} failed: {err}", "✕".red(), file.address);
+                stats.failed += 1;
+            }
+        }
+    }
+    stats
+}
+
+async fn download_file(client: &Client, wallet_dir: &Path, file: &UploadedFile) -> Result<()> {
+    let mut files_download =
+        FilesDownload::new(FilesApi::new(client.clone(), wallet_dir.to_path_buf()));
+    let content = files_download.download_file(file.address, None).await?;
+    if content_hash(&content) != file.content_hash {
+        return Err(eyre!("the content does not match what was uploaded"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENARIO: &str = r#"
+        duration = 100
+        seed = 7
+
+        [workload]
+        upload_interval = 20
+
+        [[events]]
+        action = "restart"
+        interval = 30
+        count = 2
+
+        [[events]]
+        action = "pause"
+        interval = 40
+        start = 10
+        duration = 5
+
+        [[events]]
+        action = "fill-disk"
+        interval = 200
+        size = 1048576
+    "#;
+
+    #[test]
+    fn scenario_should_be_parsed_with_defaults() -> Result<()> {
+        let scenario: Scenario = toml::from_str(SCENARIO)?;
+        scenario.validate()?;
+
+        assert_eq!(scenario.duration, 100);
+        assert_eq!(scenario.seed, Some(7));
+        assert_eq!(scenario.workload.upload_interval, 20);
+        assert_eq!(scenario.workload.download_interval, 5);
+        assert_eq!(scenario.events.len(), 3);
+        assert_eq!(scenario.events[0].action, ChaosAction::Restart);
+        assert_eq!(scenario.events[0].count, 2);
+        assert_eq!(scenario.events[1].action, ChaosAction::Pause);
+        assert_eq!(scenario.events[1].count, 1);
+        assert_eq!(scenario.events[2].action, ChaosAction::FillDisk);
+        assert_eq!(scenario.events[2].size, Some(1048576));
+        Ok(())
+    }
+
+    #[test]
+    fn scenario_should_be_rejected_when_an_option_does_not_fit_the_action() -> Result<()> {
+        let scenario: Scenario = toml::from_str(
+            r#"
+            duration = 60
+            [[events]]
+            action = "pause"
+            interval = 10
+            "#,
+        )?;
+        assert!(scenario.validate().is_err());
+
+        let scenario: Scenario = toml::from_str(
+            r#"
+            duration = 60
+            [[events]]
+            action = "kill"
+            interval = 10
+            duration = 5
+            "#,
+        )?;
+        assert!(scenario.validate().is_err());
+
+        let result: std::result::Result<Scenario, _> = toml::from_str(
+            r#"
+            duration = 60
+            [[events]]
+            action = "explode"
+            interval = 10
+            "#,
+        );
+        assert!(result.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn timeline_should_order_occurrences_within_the_duration() -> Result<()> {
+        let scenario: Scenario = toml::from_str(SCENARIO)?;
+        let timeline = scenario
+            .timeline()
+            .into_iter()
+            .map(|(at, index)| (at.as_secs(), index))
+            .collect::<Vec<_>>();
+
+        // The fill-disk event's interval is longer than the scenario, so it never happens.
+        assert_eq!(
+            timeline,
+            vec![(10, 1), (30, 0), (50, 1), (60, 0), (90, 0), (90, 1)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn operation_stats_should_report_percentiles_and_mean() {
+        let stats = OperationStats {
+            failed: 1,
+            latencies: (1..=20).rev().map(Duration::from_secs).collect(),
+        };
+
+        assert_eq!(stats.succeeded(), 20);
+        assert_eq!(stats.percentile(0.0), Some(Duration::from_secs(1)));
+        assert_eq!(stats.percentile(50.0), Some(Duration::from_secs(10)));
+        assert_eq!(stats.percentile(95.0), Some(Duration::from_secs(19)));
+        assert_eq!(stats.percentile(100.0), Some(Duration::from_secs(20)));
+        assert_eq!(stats.mean(), Some(Duration::from_millis(10500)));
+        assert_eq!(OperationStats::default().percentile(50.0), None);
+    }
+}
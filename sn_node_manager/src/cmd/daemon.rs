@@ -15,10 +15,7 @@ use crate::{
 };
 use color_eyre::{eyre::eyre, Result};
 use sn_releases::{ReleaseType, SafeReleaseRepoActions};
-use sn_service_management::{
-    control::{ServiceControl, ServiceController},
-    DaemonService, NodeRegistry,
-};
+use sn_service_management::{DaemonService, NodeRegistry};
 use std::{net::Ipv4Addr, path::PathBuf};
 
 pub async fn add(
@@ -40,11 +37,11 @@ pub async fn add(
         println!("=================================================");
     }
 
+    let mut node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
     let service_user = "safe";
-    let service_manager = ServiceController {};
+    let service_manager = node_registry.service_backend.controller();
     service_manager.create_service_user(service_user)?;
 
-    let mut node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
     let release_repo = <dyn SafeReleaseRepoActions>::default_config();
 
     let (daemon_src_bin_path, version) = if let Some(path) = src_path {
@@ -77,7 +74,7 @@ pub async fn add(
             version,
         },
         &mut node_registry,
-        &ServiceController {},
+        &*service_manager,
     )?;
     Ok(())
 }
@@ -95,9 +92,12 @@ pub async fn start(verbosity: VerbosityLevel) -> Result<()> {
             println!("=================================================");
         }
 
-        let service = DaemonService::new(daemon, Box::new(ServiceController {}));
-        let mut service_manager =
-            ServiceManager::new(service, Box::new(ServiceController {}), verbosity);
+        let service = DaemonService::new(daemon, node_registry.service_backend.controller());
+        let mut service_manager = ServiceManager::new(
+            service,
+            node_registry.service_backend.controller(),
+            verbosity,
+        );
         service_manager.start().await?;
 
         println!(
@@ -129,9 +129,12 @@ pub async fn stop(verbosity: VerbosityLevel) -> Result<()> {
             println!("=================================================");
         }
 
-        let service = DaemonService::new(daemon, Box::new(ServiceController {}));
-        let mut service_manager =
-            ServiceManager::new(service, Box::new(ServiceController {}), verbosity);
+        let service = DaemonService::new(daemon, node_registry.service_backend.controller());
+        let mut service_manager = ServiceManager::new(
+            service,
+            node_registry.service_backend.controller(),
+            verbosity,
+        );
         service_manager.stop().await?;
 
         node_registry.save()?;
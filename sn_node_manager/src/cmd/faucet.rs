@@ -18,10 +18,7 @@ use colored::Colorize;
 use semver::Version;
 use sn_peers_acquisition::{get_peers_from_args, PeersArgs};
 use sn_releases::{ReleaseType, SafeReleaseRepoActions};
-use sn_service_management::{
-    control::{ServiceControl, ServiceController},
-    FaucetService, NodeRegistry, UpgradeOptions,
-};
+use sn_service_management::{FaucetService, NodeRegistry, UpgradeOptions};
 use sn_transfers::get_faucet_data_dir;
 use std::path::PathBuf;
 
@@ -44,8 +41,9 @@ pub async fn add(
         println!("=================================================");
     }
 
+    let mut node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
     let service_user = "safe";
-    let service_manager = ServiceController {};
+    let service_manager = node_registry.service_backend.controller();
     service_manager.create_service_user(service_user)?;
 
     let service_log_dir_path = config::get_service_log_dir_path(
@@ -54,7 +52,6 @@ pub async fn add(
         Some(service_user.to_string()),
     )?;
 
-    let mut node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
     let release_repo = <dyn SafeReleaseRepoActions>::default_config();
 
     let (faucet_src_bin_path, version) = if let Some(path) = src_path {
@@ -85,7 +82,7 @@ pub async fn add(
             version,
         },
         &mut node_registry,
-        &service_manager,
+        &*service_manager,
         verbosity,
     )?;
 
@@ -105,10 +102,10 @@ pub async fn start(verbosity: VerbosityLevel) -> Result<()> {
             println!("=================================================");
         }
 
-        let service = FaucetService::new(faucet, Box::new(ServiceController {}));
+        let service = FaucetService::new(faucet, node_registry.service_backend.controller());
         let mut service_manager = ServiceManager::new(
             service,
-            Box::new(ServiceController {}),
+            node_registry.service_backend.controller(),
             VerbosityLevel::Normal,
         );
         service_manager.start().await?;
@@ -133,9 +130,12 @@ pub async fn stop(verbosity: VerbosityLevel) -> Result<()> {
             println!("=================================================");
         }
 
-        let service = FaucetService::new(faucet, Box::new(ServiceController {}));
-        let mut service_manager =
-            ServiceManager::new(service, Box::new(ServiceController {}), verbosity);
+        let service = FaucetService::new(faucet, node_registry.service_backend.controller());
+        let mut service_manager = ServiceManager::new(
+            service,
+            node_registry.service_backend.controller(),
+            verbosity,
+        );
         service_manager.stop().await?;
 
         node_registry.save()?;
@@ -199,9 +199,12 @@ pub async fn upgrade(
         target_bin_path: upgrade_bin_path.clone(),
         target_version: target_version.clone(),
     };
-    let service = FaucetService::new(faucet, Box::new(ServiceController {}));
-    let mut service_manager =
-        ServiceManager::new(service, Box::new(ServiceController {}), verbosity);
+    let service = FaucetService::new(faucet, node_registry.service_backend.controller());
+    let mut service_manager = ServiceManager::new(
+        service,
+        node_registry.service_backend.controller(),
+        verbosity,
+    );
 
     match service_manager.upgrade(options).await {
         Ok(upgrade_result) => {
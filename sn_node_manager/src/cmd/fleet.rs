@@ -0,0 +1,332 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{download_and_get_upgrade_bin_path, node::summarise_any_failed_ops};
+use crate::{
+    add_services::config::PortRange,
+    cmd, config,
+    fleet::{self, FleetChange, FleetConfig},
+    helpers::get_bin_version,
+    print_banner, refresh_node_registry, ServiceManager, VerbosityLevel,
+};
+use color_eyre::{eyre::eyre, Result};
+use semver::Version;
+use sn_peers_acquisition::PeersArgs;
+use sn_releases::ReleaseType;
+use sn_service_management::{
+    rpc::RpcClient, NodeRegistry, NodeService, ServiceStateActions, ServiceStatus, UpgradeOptions,
+    UpgradeResult,
+};
+use std::{collections::HashMap, path::PathBuf};
+
+/// Add, remove, reconfigure and upgrade services until they match the fleet file.
+pub async fn apply(
+    fleet_path: PathBuf,
+    dry_run: bool,
+    keep_directories: bool,
+    skip_preflight: bool,
+    verbosity: VerbosityLevel,
+) -> Result<()> {
+    if verbosity != VerbosityLevel::Minimal {
+        print_banner("Apply Fleet Configuration");
+    }
+
+    let fleet = FleetConfig::load(&fleet_path)?;
+    let mut node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
+    let service_control = node_registry.service_backend.controller();
+    refresh_node_registry(
+        &mut node_registry,
+        &*service_control,
+        verbosity != VerbosityLevel::Minimal,
+    )
+    .await?;
+
+    // A binary path takes precedence over a version, so its version has to be obtained from it.
+    let group_versions = fleet
+        .node_groups
+        .iter()
+        .map(|group| match &group.path {
+            Some(path) => get_bin_version(path).map(Some),
+            None => Ok(group.version.clone()),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let changes = fleet::plan(&fleet, &node_registry, &group_versions)?;
+    if changes.is_empty() {
+        if verbosity != VerbosityLevel::Minimal {
+            println!(
+                "The services already match {}",
+                fleet_path.to_string_lossy()
+            );
+        }
+        return Ok(());
+    }
+    if verbosity != VerbosityLevel::Minimal || dry_run {
+        println!("Changes:");
+        for change in &changes {
+            println!("  {change}");
+        }
+    }
+    if dry_run {
+        return Ok(());
+    }
+
+    for change in &changes {
+        match change {
+            FleetChange::SetBootstrapPeers(peers) => {
+                node_registry.bootstrap_peers.clone_from(peers);
+            }
+            FleetChange::SetEnvVariables(env_variables) => {
+                node_registry
+                    .environment_variables
+                    .clone_from(env_variables);
+            }
+            _ => {}
+        }
+    }
+    node_registry.save()?;
+
+    let specs = fleet.node_specs()?;
+    let mut upgrade_bins: HashMap<usize, (PathBuf, Version)> = HashMap::new();
+    let mut failed_services = Vec::new();
+    for change in &changes {
+        match change {
+            FleetChange::Update {
+                service_name,
+                spec,
+                upgrade,
+                ..
+            } => {
+                let spec = &specs[*spec];
+                // Each group's binary is only obtained once.
+                let target = match (upgrade, upgrade_bins.get(&spec.group)) {
+                    (Some(_), Some(bin)) => Some(bin.clone()),
+                    (Some(_), None) => {
+                        let bin = download_and_get_upgrade_bin_path(
+                            fleet.node_groups[spec.group].path.clone(),
+                            ReleaseType::Safenode,
+                            None,
+                            group_versions[spec.group].clone(),
+                            verbosity,
+                        )
+                        .await?;
+                        upgrade_bins.insert(spec.group, bin.clone());
+                        Some(bin)
+                    }
+                    (None, _) => None,
+                };
+
+                let bootstrap_peers = node_registry.bootstrap_peers.clone();
+                let env_variables = node_registry.environment_variables.clone();
+                let service_backend = node_registry.service_backend.clone();
+                let node = node_registry
+                    .nodes
+                    .iter_mut()
+                    .find(|node| node.service_name == *service_name)
+                    .ok_or_else(|| eyre!("No service named '{service_name}'"))?;
+                // The service is reinstalled from a copy with the new options, which only
+                // replaces the one in the registry if the reinstall succeeds.
+                let mut updated_node = node.clone();
+                spec.apply_to(&mut updated_node);
+
+                let start_service = updated_node.status == ServiceStatus::Running;
+                let rpc_client = RpcClient::from_socket_addr(updated_node.rpc_socket_addr);
+                let service = NodeService::new(&mut updated_node, Box::new(rpc_client));
+                let mut service_manager =
+                    ServiceManager::new(service, service_backend.controller(), verbosity);
+                // The error is only set if the service was reinstalled but couldn't be started.
+                let result: Result<Option<String>> = match target {
+                    Some((target_bin_path, target_version)) => service_manager
+                        .upgrade(UpgradeOptions {
+                            bootstrap_peers,
+                            env_variables,
+                            force: true,
+                            start_service,
+                            target_bin_path,
+                            target_version,
+                        })
+                        .await
+                        .and_then(|result| match result {
+                            UpgradeResult::UpgradedButNotStarted(_, _, err) => Ok(Some(err)),
+                            UpgradeResult::Error(err) => Err(eyre!(err)),
+                            _ => Ok(None),
+                        }),
+                    None => reconfigure(&mut service_manager, bootstrap_peers, env_variables).await,
+                };
+                match result {
+                    Ok(start_error) => {
+                        *node = updated_node;
+                        node.fleet_group = Some(fleet.node_groups[spec.group].name.clone());
+                        node_registry.save()?;
+                        if let Some(err) = start_error {
+                            failed_services.push((service_name.clone(), err));
+                        }
+                    }
+                    Err(err) => {
+                        // The service may have been stopped before the error.
+                        node.status = updated_node.status;
+                        node.pid = updated_node.pid;
+                        node_registry.save()?;
+                        failed_services.push((service_name.clone(), err.to_string()));
+                    }
+                }
+            }
+            FleetChange::Assign {
+                service_name,
+                group,
+            } => {
+                let node = node_registry
+                    .nodes
+                    .iter_mut()
+                    .find(|node| node.service_name == *service_name)
+                    .ok_or_else(|| eyre!("No service named '{service_name}'"))?;
+                node.fleet_group = Some(group.clone());
+                node_registry.save()?;
+            }
+            FleetChange::Remove { service_name } => {
+                let node = node_registry
+                    .nodes
+                    .iter_mut()
+                    .find(|node| node.service_name == *service_name)
+                    .ok_or_else(|| eyre!("No service named '{service_name}'"))?;
+                let rpc_client = RpcClient::from_socket_addr(node.rpc_socket_addr);
+                let service = NodeService::new(node, Box::new(rpc_client));
+                let mut service_manager = ServiceManager::new(
+                    service,
+                    node_registry.service_backend.controller(),
+                    verbosity,
+                );
+                match service_manager.remove(keep_directories).await {
+                    Ok(()) => node_registry.save()?,
+                    Err(err) => failed_services.push((service_name.clone(), err.to_string())),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Adding services loads and saves the registry itself.
+    for change in &changes {
+        if let FleetChange::Add {
+            group,
+            first_spec,
+            count,
+        } = change
+        {
+            let group = &fleet.node_groups[*group];
+            let spec = &specs[*first_spec];
+            let existing_services: Vec<String> =
+                NodeRegistry::load(&config::get_node_registry_path()?)?
+                    .nodes
+                    .into_iter()
+                    .map(|node| node.service_name)
+                    .collect();
+            let port_range = |start: Option<u16>| {
+                start.map(|start| match count {
+                    1 => PortRange::Single(start),
+                    _ => PortRange::Range(start, start + count - 1),
+                })
+            };
+            // The `network_contacts_url` field only exists when the `network-contacts` feature is
+            // enabled on `sn_peers_acquisition`.
+            #[allow(clippy::needless_update)]
+            let peers = PeersArgs {
+                peers: fleet.bootstrap_peers()?,
+                ..Default::default()
+            };
+            let result = cmd::node::add(
+                Some(*count),
+                spec.data_dir.clone(),
+                fleet.env_variables(),
+                spec.home_network,
+                spec.local,
+                spec.log_dir.clone(),
+                port_range(spec.metrics_port),
+                port_range(spec.node_port),
+                peers,
+                spec.rpc_address,
+                port_range(spec.rpc_port),
+                skip_preflight,
+                group.path.clone(),
+                None,
+                spec.user.clone(),
+                group.version.clone(),
+                verbosity,
+            )
+            .await;
+
+            // Any services that were added belong to the group, even if adding others failed.
+            let mut node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
+            for node in node_registry
+                .nodes
+                .iter_mut()
+                .filter(|node| !existing_services.contains(&node.service_name))
+            {
+                node.fleet_group = Some(group.name.clone());
+            }
+            node_registry.save()?;
+            result?;
+        }
+    }
+
+    summarise_any_failed_ops(failed_services, "update", verbosity)
+}
+
+/// Write the services in the registry out as a fleet file, or to stdout if no path is given.
+pub async fn export(fleet_path: Option<PathBuf>) -> Result<()> {
+    let node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
+    let fleet = fleet::export(&node_registry);
+    match fleet_path {
+        Some(fleet_path) => {
+            fleet.save(&fleet_path)?;
+            println!(
+                "Fleet configuration written to {}",
+                fleet_path.to_string_lossy()
+            );
+        }
+        None => print!("{}", fleet.to_toml()?),
+    }
+    Ok(())
+}
+
+/// Reinstall the service with its current binary, so changes to its options take effect.
+///
+/// If the service was reinstalled but couldn't be started, the error is returned in the result.
+async fn reconfigure(
+    service_manager: &mut ServiceManager<NodeService<'_>>,
+    bootstrap_peers: Vec<libp2p::Multiaddr>,
+    env_variables: Option<Vec<(String, String)>>,
+) -> Result<Option<String>> {
+    let start_service = service_manager.service.status() == ServiceStatus::Running;
+    let options = UpgradeOptions {
+        bootstrap_peers,
+        env_variables,
+        force: false,
+        start_service,
+        target_bin_path: service_manager.service.bin_path(),
+        target_version: Version::parse(&service_manager.service.version())?,
+    };
+
+    service_manager.stop().await?;
+    let user_mode = service_manager.service.is_user_mode();
+    service_manager
+        .service_control
+        .uninstall(&service_manager.service.name(), user_mode)?;
+    service_manager.service_control.install(
+        service_manager
+            .service
+            .build_upgrade_install_context(options)?,
+        user_mode,
+    )?;
+    if start_service {
+        if let Err(err) = service_manager.start().await {
+            return Ok(Some(err.to_string()));
+        }
+    }
+    Ok(None)
+}
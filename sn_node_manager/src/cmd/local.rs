@@ -21,6 +21,55 @@ use sn_service_management::{
 };
 use std::path::PathBuf;
 
+/// Run a chaos scenario against the running local network and report what happened to the data.
+#[cfg(feature = "chaos")]
+pub async fn chaos(
+    scenario_path: PathBuf,
+    owner: String,
+    verbosity: VerbosityLevel,
+) -> Result<(), Report> {
+    use crate::chaos::{self, ChaosOptions, Scenario};
+
+    let scenario = Scenario::load(&scenario_path)?;
+    let local_node_reg_path = &get_local_node_registry_path()?;
+    let mut local_node_registry = NodeRegistry::load(local_node_reg_path)?;
+    if local_node_registry.nodes.is_empty() {
+        return Err(eyre!("No local network is currently running")
+            .suggestion("Use the run command to launch a local network then try again"));
+    }
+
+    if verbosity != VerbosityLevel::Minimal {
+        println!("=================================================");
+        println!("             Running Chaos Scenario              ");
+        println!("=================================================");
+    }
+
+    let work_dir = dirs_next::data_dir()
+        .ok_or_else(|| eyre!("Could not obtain user's data directory"))?
+        .join("safe")
+        .join("chaos")
+        .join(uuid::Uuid::new_v4().to_string());
+    std::fs::create_dir_all(&work_dir)?;
+    let options = ChaosOptions {
+        owner,
+        scenario,
+        work_dir: work_dir.clone(),
+    };
+    let result = chaos::run(options, &mut local_node_registry).await;
+    std::fs::remove_dir_all(&work_dir)?;
+
+    let report = result?;
+    report.print();
+    if !report.files_lost.is_empty() {
+        return Err(eyre!(
+            "{} of {} files were lost",
+            report.files_lost.len(),
+            report.files_verified
+        ));
+    }
+    Ok(())
+}
+
 pub async fn join(
     build: bool,
     count: u16,
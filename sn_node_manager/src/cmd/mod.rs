@@ -8,8 +8,10 @@
 
 pub mod daemon;
 pub mod faucet;
+pub mod fleet;
 pub mod local;
 pub mod node;
+pub mod remote;
 
 use crate::{
     helpers::{download_and_extract_release, get_bin_version},
@@ -162,11 +164,6 @@ fn build_binary(bin_type: &ReleaseType) -> Result<()> {
     args.push(&bin_name);
 
     // Keep features consistent to avoid recompiling.
-    if cfg!(feature = "chaos") {
-        println!("*** Building testnet with CHAOS enabled. Watch out. ***");
-        args.push("--features");
-        args.push("chaos");
-    }
     if cfg!(feature = "statemap") {
         args.extend(["--features", "statemap"]);
     }
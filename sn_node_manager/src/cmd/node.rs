@@ -15,8 +15,14 @@ use crate::{
         config::{AddNodeServiceOptions, PortRange},
     },
     config,
-    helpers::{download_and_extract_release, get_bin_version},
-    print_banner, refresh_node_registry, status_report, ServiceManager, VerbosityLevel,
+    helpers::{download_and_extract_release, get_bin_version, tail_log_file},
+    preflight::{self, NodeResourceProfile, PreflightOptions},
+    print_banner, refresh_node_registry,
+    rolling_upgrade::{rolling_upgrade, RollingUpgradeOptions},
+    rpc::stream_node_events,
+    status_report,
+    supervisor::{Supervisor, SupervisorPolicy},
+    ServiceManager, VerbosityLevel,
 };
 use color_eyre::{eyre::eyre, Help, Result};
 use colored::Colorize;
@@ -25,15 +31,23 @@ use semver::Version;
 use sn_peers_acquisition::{get_peers_from_args, PeersArgs};
 use sn_releases::{ReleaseType, SafeReleaseRepoActions};
 use sn_service_management::{
-    control::{ServiceControl, ServiceController},
-    get_local_node_registry_path,
-    rpc::RpcClient,
-    NodeRegistry, NodeService, ServiceStateActions, ServiceStatus, UpgradeOptions, UpgradeResult,
+    control::ServiceBackend, get_local_node_registry_path, rpc::RpcClient, NodeRegistry,
+    NodeService, ServiceStateActions, ServiceStatus, UpgradeOptions, UpgradeResult,
 };
-use sn_transfers::HotWallet;
-use std::{io::Write, net::Ipv4Addr, path::PathBuf, str::FromStr};
+use sn_transfers::{HotWallet, NanoTokens};
+use std::{
+    io::Write,
+    net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+use tokio::sync::mpsc;
 use tracing::debug;
 
+/// The name of the file, within its log directory, that a node is currently logging to.
+pub const NODE_LOG_FILE_NAME: &str = "safenode.log";
+
 pub async fn add(
     count: Option<u16>,
     data_dir_path: Option<PathBuf>,
@@ -46,6 +60,7 @@ pub async fn add(
     peers: PeersArgs,
     rpc_address: Option<Ipv4Addr>,
     rpc_port: Option<PortRange>,
+    skip_preflight: bool,
     src_path: Option<PathBuf>,
     url: Option<String>,
     user: Option<String>,
@@ -59,7 +74,8 @@ pub async fn add(
         println!("{} service(s) to be added", count.unwrap_or(1));
     }
 
-    let service_manager = ServiceController {};
+    let mut node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
+    let service_manager = node_registry.service_backend.controller();
     let service_user = if user_mode {
         None
     } else {
@@ -76,7 +92,25 @@ pub async fn add(
         service_user.clone(),
     )?;
 
-    let mut node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
+    if !skip_preflight {
+        let report = preflight::run(
+            &PreflightOptions {
+                count: PreflightOptions::service_count(count, node_port.as_ref()),
+                data_dir_path: service_data_dir_path.clone(),
+                log_dir_path: service_log_dir_path.clone(),
+                metrics_port: metrics_port.clone(),
+                node_port: node_port.clone(),
+                profile: NodeResourceProfile::default(),
+                rpc_address,
+                rpc_port: rpc_port.clone(),
+            },
+            &node_registry,
+        );
+        if verbosity == VerbosityLevel::Full {
+            report.print();
+        }
+        report.into_result()?;
+    }
     let release_repo = <dyn SafeReleaseRepoActions>::default_config();
 
     let (safenode_src_path, version) = if let Some(path) = src_path.clone() {
@@ -136,7 +170,7 @@ pub async fn add(
         version,
     };
 
-    add_node(options, &mut node_registry, &service_manager, verbosity).await?;
+    add_node(options, &mut node_registry, &*service_manager, verbosity).await?;
 
     node_registry.save()?;
     tracing::debug!("Node registry saved");
@@ -144,6 +178,61 @@ pub async fn add(
     Ok(())
 }
 
+/// Print the backend the services are run with, or change it.
+///
+/// It can only be changed while there are no services, because they would be left defined with
+/// the previous backend.
+pub fn backend(backend: Option<ServiceBackend>, verbosity: VerbosityLevel) -> Result<()> {
+    let mut node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
+    let backend = match backend {
+        Some(backend) => backend,
+        None => {
+            println!(
+                "Services are run with the {}",
+                node_registry.service_backend
+            );
+            return Ok(());
+        }
+    };
+    if backend == node_registry.service_backend {
+        if verbosity != VerbosityLevel::Minimal {
+            println!("Services are already run with the {backend}");
+        }
+        return Ok(());
+    }
+
+    let has_services = node_registry
+        .nodes
+        .iter()
+        .any(|node| node.status != ServiceStatus::Removed)
+        || node_registry
+            .daemon
+            .as_ref()
+            .is_some_and(|daemon| daemon.status != ServiceStatus::Removed)
+        || node_registry
+            .faucet
+            .as_ref()
+            .is_some_and(|faucet| faucet.status != ServiceStatus::Removed);
+    if has_services {
+        return Err(eyre!(
+            "The backend cannot be changed while there are services defined with the {}",
+            node_registry.service_backend
+        )
+        .suggestion("Remove the services first, or use the 'reset' command"));
+    }
+
+    node_registry.service_backend = backend;
+    node_registry.save()?;
+    if verbosity != VerbosityLevel::Minimal {
+        println!(
+            "{} Services will be run with the {}",
+            "✓".green(),
+            node_registry.service_backend
+        );
+    }
+    Ok(())
+}
+
 pub async fn balance(
     peer_ids: Vec<String>,
     service_names: Vec<String>,
@@ -153,31 +242,148 @@ pub async fn balance(
         print_banner("Reward Balances");
     }
 
+    let balances = get_balances(peer_ids, service_names, verbosity).await?;
+    if balances.is_empty() {
+        // This could be the case if all services are at `Removed` status.
+        println!("No balances to display");
+        return Ok(());
+    }
+
+    for (service_name, balance) in balances {
+        println!("{service_name}: {balance}");
+    }
+    Ok(())
+}
+
+/// Get the reward balance of each of the selected services, keyed by service name.
+pub async fn get_balances(
+    peer_ids: Vec<String>,
+    service_names: Vec<String>,
+    verbosity: VerbosityLevel,
+) -> Result<Vec<(String, NanoTokens)>> {
     let mut node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
+    let service_control = node_registry.service_backend.controller();
     refresh_node_registry(
         &mut node_registry,
-        &ServiceController {},
+        &*service_control,
         verbosity != VerbosityLevel::Minimal,
     )
     .await?;
 
     let service_indices = get_services_for_ops(&node_registry, peer_ids, service_names)?;
-    if service_indices.is_empty() {
-        // This could be the case if all services are at `Removed` status.
-        println!("No balances to display");
-        return Ok(());
+    let mut balances = Vec::new();
+    for &index in &service_indices {
+        let node = &node_registry.nodes[index];
+        let wallet = HotWallet::load_from(&node.data_dir_path)?;
+        balances.push((node.service_name.clone(), wallet.balance()));
     }
+    Ok(balances)
+}
 
-    for &index in &service_indices {
-        let node = &mut node_registry.nodes[index];
-        let rpc_client = RpcClient::from_socket_addr(node.rpc_socket_addr);
-        let service = NodeService::new(node, Box::new(rpc_client));
-        let wallet = HotWallet::load_from(&service.service_data.data_dir_path)?;
-        println!(
-            "{}: {}",
-            service.service_data.service_name,
-            wallet.balance()
-        );
+/// Print the last `lines` lines of a service's log file, then, if `follow` is set, keep printing
+/// lines as they are written.
+pub async fn logs(service_name: String, lines: usize, follow: bool) -> Result<()> {
+    let log_file_path = get_log_file_path(&service_name)?;
+    let (sender, mut receiver) = mpsc::channel(100);
+    let tail = tokio::spawn(tail_log_file(log_file_path, lines, follow, sender));
+    while let Some(line) = receiver.recv().await {
+        println!("{line}");
+    }
+    tail.await?
+}
+
+/// Get the path of the file a service is currently logging to.
+pub fn get_log_file_path(service_name: &str) -> Result<PathBuf> {
+    let node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
+    let node = node_registry
+        .nodes
+        .iter()
+        .find(|node| node.service_name == service_name)
+        .ok_or_else(|| eyre!("No service named '{service_name}'"))?;
+    Ok(node.log_dir_path.join(NODE_LOG_FILE_NAME))
+}
+
+/// Print the events emitted by running services as they occur.
+///
+/// If no peer ID(s) or service name(s) are supplied, the events of all running services are
+/// printed.
+pub async fn events(peer_ids: Vec<String>, service_names: Vec<String>) -> Result<()> {
+    let nodes = get_node_event_sources(peer_ids, service_names).await?;
+    let (sender, mut receiver) = mpsc::channel(100);
+    let events = tokio::spawn(stream_node_events(nodes, sender));
+    while let Some((service_name, event)) = receiver.recv().await {
+        println!("{service_name}: {event}");
+    }
+    events.await?
+}
+
+/// Get the service name and RPC address of each of the selected services that is running.
+pub async fn get_node_event_sources(
+    peer_ids: Vec<String>,
+    service_names: Vec<String>,
+) -> Result<Vec<(String, SocketAddr)>> {
+    let mut node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
+    let service_control = node_registry.service_backend.controller();
+    refresh_node_registry(&mut node_registry, &*service_control, false).await?;
+
+    let service_indices = get_services_for_ops(&node_registry, peer_ids, service_names)?;
+    let nodes = service_indices
+        .into_iter()
+        .map(|index| &node_registry.nodes[index])
+        .filter(|node| node.status == ServiceStatus::Running)
+        .map(|node| (node.service_name.clone(), node.rpc_socket_addr))
+        .collect::<Vec<_>>();
+    if nodes.is_empty() {
+        return Err(eyre!("None of the selected services are running"));
+    }
+    Ok(nodes)
+}
+
+/// Check whether the host has the disk space, memory, file handles and ports for more nodes.
+pub fn preflight(
+    count: u16,
+    data_dir_path: Option<PathBuf>,
+    log_dir_path: Option<PathBuf>,
+    max_records: u64,
+    metrics_port: Option<PortRange>,
+    node_memory: u64,
+    node_port: Option<PortRange>,
+    rpc_address: Option<Ipv4Addr>,
+    rpc_port: Option<PortRange>,
+    verbosity: VerbosityLevel,
+) -> Result<()> {
+    if verbosity != VerbosityLevel::Minimal {
+        print_banner("Preflight Checks");
+    }
+
+    let system_wide = is_running_as_root();
+    let data_dir_path = match data_dir_path {
+        Some(path) => path,
+        None => config::get_default_service_data_dir_path(system_wide)?,
+    };
+    let log_dir_path = match log_dir_path {
+        Some(path) => path,
+        None => config::get_default_service_log_dir_path(ReleaseType::Safenode, system_wide)?,
+    };
+    let node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
+    let report = preflight::run(
+        &PreflightOptions {
+            count,
+            data_dir_path,
+            log_dir_path,
+            metrics_port,
+            node_port,
+            profile: NodeResourceProfile::new(max_records, node_memory),
+            rpc_address,
+            rpc_port,
+        },
+        &node_registry,
+    );
+    report.print();
+    if !report.passed() {
+        return Err(eyre!(
+            "The host does not have the resources for {count} more node(s)"
+        ));
     }
     Ok(())
 }
@@ -193,9 +399,10 @@ pub async fn remove(
     }
 
     let mut node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
+    let service_control = node_registry.service_backend.controller();
     refresh_node_registry(
         &mut node_registry,
-        &ServiceController {},
+        &*service_control,
         verbosity != VerbosityLevel::Minimal,
     )
     .await?;
@@ -214,8 +421,11 @@ pub async fn remove(
         let node = &mut node_registry.nodes[index];
         let rpc_client = RpcClient::from_socket_addr(node.rpc_socket_addr);
         let service = NodeService::new(node, Box::new(rpc_client));
-        let mut service_manager =
-            ServiceManager::new(service, Box::new(ServiceController {}), verbosity);
+        let mut service_manager = ServiceManager::new(
+            service,
+            node_registry.service_backend.controller(),
+            verbosity,
+        );
         match service_manager.remove(keep_directories).await {
             Ok(()) => {
                 node_registry.save()?;
@@ -262,9 +472,10 @@ pub async fn start(
     }
 
     let mut node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
+    let service_control = node_registry.service_backend.controller();
     refresh_node_registry(
         &mut node_registry,
-        &ServiceController {},
+        &*service_control,
         verbosity != VerbosityLevel::Minimal,
     )
     .await?;
@@ -283,8 +494,11 @@ pub async fn start(
         let node = &mut node_registry.nodes[index];
         let rpc_client = RpcClient::from_socket_addr(node.rpc_socket_addr);
         let service = NodeService::new(node, Box::new(rpc_client));
-        let mut service_manager =
-            ServiceManager::new(service, Box::new(ServiceController {}), verbosity);
+        let mut service_manager = ServiceManager::new(
+            service,
+            node_registry.service_backend.controller(),
+            verbosity,
+        );
         if service_manager.service.status() != ServiceStatus::Running {
             // It would be possible here to check if the service *is* running and then just
             // continue without applying the delay. The reason for not doing so is because when
@@ -310,9 +524,10 @@ pub async fn status(details: bool, fail: bool, json: bool) -> Result<()> {
         if !json {
             print_banner("Local Network");
         }
+        let service_control = local_node_registry.service_backend.controller();
         status_report(
             &mut local_node_registry,
-            &ServiceController {},
+            &*service_control,
             details,
             json,
             fail,
@@ -327,14 +542,8 @@ pub async fn status(details: bool, fail: bool, json: bool) -> Result<()> {
         if !json && !details {
             print_banner("Safenode Services");
         }
-        status_report(
-            &mut node_registry,
-            &ServiceController {},
-            details,
-            json,
-            fail,
-        )
-        .await?;
+        let service_control = node_registry.service_backend.controller();
+        status_report(&mut node_registry, &*service_control, details, json, fail).await?;
         node_registry.save()?;
     }
     Ok(())
@@ -350,9 +559,10 @@ pub async fn stop(
     }
 
     let mut node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
+    let service_control = node_registry.service_backend.controller();
     refresh_node_registry(
         &mut node_registry,
-        &ServiceController {},
+        &*service_control,
         verbosity != VerbosityLevel::Minimal,
     )
     .await?;
@@ -371,8 +581,11 @@ pub async fn stop(
         let node = &mut node_registry.nodes[index];
         let rpc_client = RpcClient::from_socket_addr(node.rpc_socket_addr);
         let service = NodeService::new(node, Box::new(rpc_client));
-        let mut service_manager =
-            ServiceManager::new(service, Box::new(ServiceController {}), verbosity);
+        let mut service_manager = ServiceManager::new(
+            service,
+            node_registry.service_backend.controller(),
+            verbosity,
+        );
         match service_manager.stop().await {
             Ok(()) => {
                 node_registry.save()?;
@@ -384,6 +597,49 @@ pub async fn stop(
     summarise_any_failed_ops(failed_services, "stop", verbosity)
 }
 
+/// Supervise the services until interrupted, checking them every `interval`.
+pub async fn supervise(
+    interval: Duration,
+    peer_ids: Vec<String>,
+    policy: SupervisorPolicy,
+    service_names: Vec<String>,
+    verbosity: VerbosityLevel,
+) -> Result<()> {
+    if verbosity != VerbosityLevel::Minimal {
+        print_banner("Supervise Safenode Services");
+        println!("Checking services every {} seconds", interval.as_secs());
+    }
+
+    let registry_path = config::get_node_registry_path()?;
+    let mut supervisor = Supervisor::new(policy);
+    loop {
+        // Other commands can change the registry while this runs, so it's loaded for each pass.
+        let mut node_registry = NodeRegistry::load(&registry_path)?;
+        let service_backend = node_registry.service_backend.clone();
+        let service_indices =
+            get_services_for_ops(&node_registry, peer_ids.clone(), service_names.clone())?;
+        supervisor
+            .supervise(
+                &mut node_registry,
+                &service_indices,
+                &|socket_addr| Box::new(RpcClient::from_socket_addr(socket_addr)),
+                &|| service_backend.controller(),
+                verbosity,
+            )
+            .await?;
+
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = tokio::signal::ctrl_c() => {
+                if verbosity != VerbosityLevel::Minimal {
+                    println!("Supervision stopped");
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
 pub async fn upgrade(
     do_not_start: bool,
     custom_bin_path: Option<PathBuf>,
@@ -391,11 +647,18 @@ pub async fn upgrade(
     interval: u64,
     peer_ids: Vec<String>,
     provided_env_variables: Option<Vec<(String, String)>>,
+    rolling_options: Option<RollingUpgradeOptions>,
     service_names: Vec<String>,
     url: Option<String>,
     version: Option<String>,
     verbosity: VerbosityLevel,
 ) -> Result<()> {
+    if rolling_options.is_some() && do_not_start {
+        return Err(eyre!(
+            "A rolling upgrade needs to start services to check they are healthy"
+        ));
+    }
+
     // In the case of a custom binary, we want to force the use of it. Regardless of its version
     // number, the user has probably built it for some special case. They may have not used the
     // `--force` flag; if they didn't, we can just do that for them here.
@@ -415,9 +678,10 @@ pub async fn upgrade(
     .await?;
 
     let mut node_registry = NodeRegistry::load(&config::get_node_registry_path()?)?;
+    let service_control = node_registry.service_backend.controller();
     refresh_node_registry(
         &mut node_registry,
-        &ServiceController {},
+        &*service_control,
         verbosity != VerbosityLevel::Minimal,
     )
     .await?;
@@ -440,6 +704,34 @@ pub async fn upgrade(
     }
 
     let service_indices = get_services_for_ops(&node_registry, peer_ids, service_names)?;
+
+    if let Some(rolling_options) = rolling_options {
+        let service_backend = node_registry.service_backend.clone();
+        let options = UpgradeOptions {
+            bootstrap_peers: node_registry.bootstrap_peers.clone(),
+            env_variables: provided_env_variables
+                .or_else(|| node_registry.environment_variables.clone()),
+            force: use_force,
+            start_service: true,
+            target_bin_path: upgrade_bin_path,
+            target_version,
+        };
+        return rolling_upgrade(
+            &mut node_registry,
+            &service_indices,
+            &rolling_options,
+            options,
+            &|rpc_socket_addr| Box::new(RpcClient::from_socket_addr(rpc_socket_addr)),
+            &|| service_backend.controller(),
+            verbosity,
+        )
+        .await
+        .suggestion(
+            "Services in earlier batches remain upgraded. Use the 'status --details' command to \
+            see the outcome of each batch.",
+        );
+    }
+
     let mut upgrade_summary = Vec::new();
 
     for &index in &service_indices {
@@ -460,8 +752,11 @@ pub async fn upgrade(
 
         let rpc_client = RpcClient::from_socket_addr(node.rpc_socket_addr);
         let service = NodeService::new(node, Box::new(rpc_client));
-        let mut service_manager =
-            ServiceManager::new(service, Box::new(ServiceController {}), verbosity);
+        let mut service_manager = ServiceManager::new(
+            service,
+            node_registry.service_backend.controller(),
+            verbosity,
+        );
 
         match service_manager.upgrade(options).await {
             Ok(upgrade_result) => {
@@ -548,7 +843,7 @@ fn get_services_for_ops(
     Ok(service_indices)
 }
 
-fn summarise_any_failed_ops(
+pub(super) fn summarise_any_failed_ops(
     failed_services: Vec<(String, String)>,
     verb: &str,
     verbosity: VerbosityLevel,
@@ -559,9 +854,17 @@ fn summarise_any_failed_ops(
             for failed in failed_services.iter() {
                 println!("{} {}: {}", "✕".red(), failed.0, failed.1);
             }
+            return Err(eyre!("Failed to {verb} one or more services"));
         }
 
-        return Err(eyre!("Failed to {verb} one or more services"));
+        // Nothing was printed, e.g., because the daemon is running the command on behalf of a
+        // remote client, so the reasons need to be carried by the error.
+        let failures = failed_services
+            .iter()
+            .map(|(service_name, err)| format!("{service_name}: {err}"))
+            .collect::<Vec<_>>()
+            .join("; ");
+        return Err(eyre!("Failed to {verb} one or more services: {failures}"));
     }
     Ok(())
 }
@@ -0,0 +1,376 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! The node commands, sent to a `safenodemand` daemon rather than run locally.
+//!
+//! Paths and other values are interpreted on the machine the daemon runs on.
+
+#![allow(clippy::too_many_arguments)]
+
+use crate::{
+    add_services::config::PortRange, format_status, print_banner,
+    rolling_upgrade::RollingUpgradeOptions, rpc_client::DaemonClient, VerbosityLevel,
+};
+use color_eyre::{eyre::eyre, Result};
+use sn_peers_acquisition::PeersArgs;
+use sn_service_management::{
+    safenode_manager_proto::{
+        get_status_response::{Node, ServiceStatus as RpcServiceStatus},
+        AddNodesRequest, EnvironmentVariable, GetBalancesRequest, GetStatusRequest,
+        GetStatusResponse, RemoveNodesRequest, ResetRequest, StartNodesRequest, StopNodesRequest,
+        StreamLogsRequest, StreamNodeEventsRequest, UpgradeNodesRequest,
+    },
+    ServiceStatus,
+};
+use sn_transfers::NanoTokens;
+use std::{io::Write, net::Ipv4Addr, path::PathBuf};
+use tonic::{Request, Status};
+
+pub async fn add(
+    client: &mut DaemonClient,
+    count: Option<u16>,
+    data_dir_path: Option<PathBuf>,
+    env_variables: Option<Vec<(String, String)>>,
+    home_network: bool,
+    local: bool,
+    log_dir_path: Option<PathBuf>,
+    metrics_port: Option<PortRange>,
+    node_port: Option<PortRange>,
+    peers: PeersArgs,
+    rpc_address: Option<Ipv4Addr>,
+    rpc_port: Option<PortRange>,
+    skip_preflight: bool,
+    src_path: Option<PathBuf>,
+    url: Option<String>,
+    user: Option<String>,
+    version: Option<String>,
+    verbosity: VerbosityLevel,
+) -> Result<()> {
+    if verbosity != VerbosityLevel::Minimal {
+        print_banner("Add Safenode Services");
+        println!("{} service(s) to be added", count.unwrap_or(1));
+    }
+
+    let response = client
+        .add_nodes(Request::new(AddNodesRequest {
+            count: count.map(u32::from),
+            data_dir_path: data_dir_path.map(|path| path.to_string_lossy().to_string()),
+            env_variables: to_rpc_env_variables(env_variables),
+            home_network,
+            local,
+            log_dir_path: log_dir_path.map(|path| path.to_string_lossy().to_string()),
+            metrics_port: metrics_port.map(|port| port.to_string()),
+            node_port: node_port.map(|port| port.to_string()),
+            path: src_path.map(|path| path.to_string_lossy().to_string()),
+            first: peers.first,
+            peers: peers.peers.iter().map(|peer| peer.to_string()).collect(),
+            rpc_address: rpc_address.map(|address| address.to_string()),
+            rpc_port: rpc_port.map(|port| port.to_string()),
+            skip_preflight,
+            url,
+            user,
+            version,
+        }))
+        .await
+        .map_err(status_to_error)?;
+
+    if verbosity != VerbosityLevel::Minimal {
+        for service_name in &response.get_ref().service_names {
+            println!("Added {service_name}");
+        }
+    }
+    Ok(())
+}
+
+pub async fn balance(
+    client: &mut DaemonClient,
+    peer_ids: Vec<String>,
+    service_names: Vec<String>,
+    verbosity: VerbosityLevel,
+) -> Result<()> {
+    if verbosity != VerbosityLevel::Minimal {
+        print_banner("Reward Balances");
+    }
+
+    let response = client
+        .get_balances(Request::new(GetBalancesRequest {
+            peer_ids,
+            service_names,
+        }))
+        .await
+        .map_err(status_to_error)?;
+    let balances = &response.get_ref().balances;
+    if balances.is_empty() {
+        println!("No balances to display");
+        return Ok(());
+    }
+
+    for balance in balances {
+        println!(
+            "{}: {}",
+            balance.service_name,
+            NanoTokens::from(balance.nanos)
+        );
+    }
+    Ok(())
+}
+
+pub async fn events(
+    client: &mut DaemonClient,
+    peer_ids: Vec<String>,
+    service_names: Vec<String>,
+) -> Result<()> {
+    let mut stream = client
+        .stream_node_events(Request::new(StreamNodeEventsRequest {
+            peer_ids,
+            service_names,
+        }))
+        .await
+        .map_err(status_to_error)?
+        .into_inner();
+    while let Some(event) = stream.message().await.map_err(status_to_error)? {
+        println!("{}: {}", event.service_name, event.event);
+    }
+    Ok(())
+}
+
+pub async fn logs(
+    client: &mut DaemonClient,
+    service_name: String,
+    lines: usize,
+    follow: bool,
+) -> Result<()> {
+    let mut stream = client
+        .stream_logs(Request::new(StreamLogsRequest {
+            service_name,
+            lines: lines as u32,
+            follow,
+        }))
+        .await
+        .map_err(status_to_error)?
+        .into_inner();
+    while let Some(log_line) = stream.message().await.map_err(status_to_error)? {
+        println!("{}", log_line.line);
+    }
+    Ok(())
+}
+
+pub async fn remove(
+    client: &mut DaemonClient,
+    keep_directories: bool,
+    peer_ids: Vec<String>,
+    service_names: Vec<String>,
+    verbosity: VerbosityLevel,
+) -> Result<()> {
+    if verbosity != VerbosityLevel::Minimal {
+        print_banner("Remove Safenode Services");
+    }
+
+    let response = client
+        .remove_nodes(Request::new(RemoveNodesRequest {
+            peer_ids,
+            service_names,
+            keep_directories,
+        }))
+        .await
+        .map_err(status_to_error)?;
+    print_nodes(response.get_ref(), verbosity);
+    Ok(())
+}
+
+pub async fn reset(
+    client: &mut DaemonClient,
+    force: bool,
+    verbosity: VerbosityLevel,
+) -> Result<()> {
+    print_banner("Reset Safenode Services");
+
+    if !force {
+        println!(
+            "WARNING: all safenode services, data, and logs on the remote host will be removed."
+        );
+        println!("Do you wish to proceed? [y/n]");
+        std::io::stdout().flush()?;
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        if input.trim().to_lowercase() != "y" {
+            println!("Reset aborted");
+            return Ok(());
+        }
+    }
+
+    let response = client
+        .reset(Request::new(ResetRequest {}))
+        .await
+        .map_err(status_to_error)?;
+    print_nodes(response.get_ref(), verbosity);
+    Ok(())
+}
+
+pub async fn start(
+    client: &mut DaemonClient,
+    interval: u64,
+    peer_ids: Vec<String>,
+    service_names: Vec<String>,
+    verbosity: VerbosityLevel,
+) -> Result<()> {
+    if verbosity != VerbosityLevel::Minimal {
+        print_banner("Start Safenode Services");
+    }
+
+    let response = client
+        .start_nodes(Request::new(StartNodesRequest {
+            peer_ids,
+            service_names,
+            interval_millis: interval,
+        }))
+        .await
+        .map_err(status_to_error)?;
+    print_nodes(response.get_ref(), verbosity);
+    Ok(())
+}
+
+pub async fn status(client: &mut DaemonClient, fail: bool) -> Result<()> {
+    print_banner("Safenode Services");
+
+    let response = client
+        .get_status(Request::new(GetStatusRequest {}))
+        .await
+        .map_err(status_to_error)?;
+    print_nodes(response.get_ref(), VerbosityLevel::Normal);
+
+    if fail
+        && response
+            .get_ref()
+            .nodes
+            .iter()
+            .any(|node| to_service_status(node) != ServiceStatus::Running)
+    {
+        return Err(eyre!("One or more nodes are not in a running state"));
+    }
+    Ok(())
+}
+
+pub async fn stop(
+    client: &mut DaemonClient,
+    peer_ids: Vec<String>,
+    service_names: Vec<String>,
+    verbosity: VerbosityLevel,
+) -> Result<()> {
+    if verbosity != VerbosityLevel::Minimal {
+        print_banner("Stop Safenode Services");
+    }
+
+    let response = client
+        .stop_nodes(Request::new(StopNodesRequest {
+            peer_ids,
+            service_names,
+        }))
+        .await
+        .map_err(status_to_error)?;
+    print_nodes(response.get_ref(), verbosity);
+    Ok(())
+}
+
+pub async fn upgrade(
+    client: &mut DaemonClient,
+    do_not_start: bool,
+    custom_bin_path: Option<PathBuf>,
+    force: bool,
+    interval: u64,
+    peer_ids: Vec<String>,
+    provided_env_variables: Option<Vec<(String, String)>>,
+    rolling_options: Option<RollingUpgradeOptions>,
+    service_names: Vec<String>,
+    url: Option<String>,
+    version: Option<String>,
+    verbosity: VerbosityLevel,
+) -> Result<()> {
+    if verbosity != VerbosityLevel::Minimal {
+        print_banner("Upgrade Safenode Services");
+    }
+
+    let response = client
+        .upgrade_nodes(Request::new(UpgradeNodesRequest {
+            peer_ids,
+            service_names,
+            interval_millis: interval,
+            do_not_start,
+            force,
+            env_variables: to_rpc_env_variables(provided_env_variables),
+            path: custom_bin_path.map(|path| path.to_string_lossy().to_string()),
+            url,
+            version,
+            batch_size: rolling_options
+                .as_ref()
+                .map(|options| options.batch_size as u32),
+            min_connected_peers: rolling_options
+                .as_ref()
+                .map_or(0, |options| options.health_gate.min_connected_peers as u32),
+            min_records: rolling_options
+                .as_ref()
+                .map_or(0, |options| options.health_gate.min_records as u32),
+            health_timeout_secs: rolling_options
+                .as_ref()
+                .map_or(0, |options| options.health_gate.timeout.as_secs()),
+        }))
+        .await
+        .map_err(status_to_error)?;
+    print_nodes(response.get_ref(), verbosity);
+    Ok(())
+}
+
+fn print_nodes(response: &GetStatusResponse, verbosity: VerbosityLevel) {
+    if verbosity == VerbosityLevel::Minimal {
+        return;
+    }
+
+    println!(
+        "{:<18} {:<52} {:<7} {:>10}",
+        "Service Name", "Peer ID", "Status", "Version"
+    );
+    for node in &response.nodes {
+        let status = to_service_status(node);
+        if status == ServiceStatus::Removed {
+            continue;
+        }
+        let peer_id = node
+            .peer_id
+            .as_ref()
+            .and_then(|bytes| libp2p_identity::PeerId::from_bytes(bytes).ok())
+            .map_or("-".to_string(), |peer_id| peer_id.to_string());
+        println!(
+            "{:<18} {:<52} {:<7} {:>10}",
+            node.service_name,
+            peer_id,
+            format_status(&status),
+            node.version
+        );
+    }
+}
+
+fn to_service_status(node: &Node) -> ServiceStatus {
+    match RpcServiceStatus::from_i32(node.status) {
+        Some(RpcServiceStatus::Added) => ServiceStatus::Added,
+        Some(RpcServiceStatus::Running) => ServiceStatus::Running,
+        Some(RpcServiceStatus::Stopped) => ServiceStatus::Stopped,
+        Some(RpcServiceStatus::Removed) | None => ServiceStatus::Removed,
+    }
+}
+
+fn to_rpc_env_variables(env_variables: Option<Vec<(String, String)>>) -> Vec<EnvironmentVariable> {
+    env_variables
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, value)| EnvironmentVariable { name, value })
+        .collect()
+}
+
+fn status_to_error(status: Status) -> color_eyre::Report {
+    eyre!("The daemon returned an error: {}", status.message())
+}
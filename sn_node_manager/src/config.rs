@@ -95,6 +95,11 @@ pub fn get_node_registry_path() -> Result<PathBuf> {
     Ok(path.join("node_registry.json"))
 }
 
+/// Get the directory the process and container backends keep the service definitions in.
+pub fn get_service_definitions_dir_path() -> Result<PathBuf> {
+    Ok(get_node_manager_path()?.join("service-definitions"))
+}
+
 /// Get the data directory for the service.
 ///
 /// It's a little counter-intuitive, but the owner will be `None` in the case of a user-mode
@@ -107,10 +112,7 @@ pub fn get_service_data_dir_path(
 ) -> Result<PathBuf> {
     let path = match custom_path {
         Some(p) => p,
-        None => match owner.is_some() {
-            true => PathBuf::from("/var/safenode-manager/services"),
-            false => get_user_safenode_data_dir()?,
-        },
+        None => get_default_service_data_dir_path(owner.is_some())?,
     };
     if let Some(owner) = owner {
         create_owned_dir(path.clone(), &owner)?;
@@ -125,7 +127,7 @@ pub fn get_service_data_dir_path(
 ) -> Result<PathBuf> {
     let path = match custom_path {
         Some(p) => p,
-        None => PathBuf::from("C:\\ProgramData\\safenode\\data"),
+        None => get_default_service_data_dir_path(true)?,
     };
     std::fs::create_dir_all(&path)?;
     Ok(path)
@@ -144,10 +146,7 @@ pub fn get_service_log_dir_path(
 ) -> Result<PathBuf> {
     let path = match custom_path {
         Some(p) => p,
-        None => match owner.is_some() {
-            true => PathBuf::from("/var/log").join(bin_type.to_string()),
-            false => get_user_safenode_data_dir()?,
-        },
+        None => get_default_service_log_dir_path(bin_type, owner.is_some())?,
     };
     if let Some(owner) = owner {
         create_owned_dir(path.clone(), &owner)?;
@@ -163,14 +162,52 @@ pub fn get_service_log_dir_path(
 ) -> Result<PathBuf> {
     let path = match custom_path {
         Some(p) => p,
-        None => PathBuf::from("C:\\ProgramData")
-            .join(bin_type.to_string())
-            .join("logs"),
+        None => get_default_service_log_dir_path(bin_type, true)?,
     };
     std::fs::create_dir_all(&path)?;
     Ok(path)
 }
 
+/// Get the directory service data directories are created under when no path is provided.
+///
+/// Unlike `get_service_data_dir_path`, the directory is not created.
+#[cfg(unix)]
+pub fn get_default_service_data_dir_path(system_wide: bool) -> Result<PathBuf> {
+    match system_wide {
+        true => Ok(PathBuf::from("/var/safenode-manager/services")),
+        false => get_user_safenode_data_dir(),
+    }
+}
+
+#[cfg(windows)]
+pub fn get_default_service_data_dir_path(_system_wide: bool) -> Result<PathBuf> {
+    Ok(PathBuf::from("C:\\ProgramData\\safenode\\data"))
+}
+
+/// Get the directory service log directories are created under when no path is provided.
+///
+/// Unlike `get_service_log_dir_path`, the directory is not created.
+#[cfg(unix)]
+pub fn get_default_service_log_dir_path(
+    bin_type: ReleaseType,
+    system_wide: bool,
+) -> Result<PathBuf> {
+    match system_wide {
+        true => Ok(PathBuf::from("/var/log").join(bin_type.to_string())),
+        false => get_user_safenode_data_dir(),
+    }
+}
+
+#[cfg(windows)]
+pub fn get_default_service_log_dir_path(
+    bin_type: ReleaseType,
+    _system_wide: bool,
+) -> Result<PathBuf> {
+    Ok(PathBuf::from("C:\\ProgramData")
+        .join(bin_type.to_string())
+        .join("logs"))
+}
+
 #[cfg(unix)]
 pub fn create_owned_dir(path: PathBuf, owner: &str) -> Result<()> {
     use nix::unistd::{chown, Gid, Uid};
@@ -0,0 +1,905 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! A declarative description of the node services on a host, and the changes needed to make the
+//! node registry match one.
+//!
+//! Each service `apply` adds or adopts records the name of its group, and is matched to the nodes
+//! of that group from then on, in the order the services were added. Services that don't belong to
+//! a group yet, like those added with the `add` command, fill the groups' remaining nodes in order.
+//! Options that aren't set for a group are left as they are on existing services.
+
+use color_eyre::{eyre::eyre, Result};
+use libp2p::Multiaddr;
+use serde::{Deserialize, Serialize};
+use sn_service_management::{node::NodeServiceData, NodeRegistry, ServiceStatus};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// The node services a host should have.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FleetConfig {
+    /// Peers every node is given to bootstrap from.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub peers: Vec<String>,
+    /// Environment variables every node runs with.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(default, rename = "nodes")]
+    pub node_groups: Vec<NodeGroup>,
+}
+
+/// A number of nodes that share the same options.
+///
+/// Ports are assigned to the nodes in a group consecutively, starting from the one given.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NodeGroup {
+    /// Identifies the group's services, so it mustn't change once they've been added.
+    pub name: String,
+    #[serde(default = "default_count")]
+    pub count: u16,
+    /// The version of safenode the nodes run.
+    ///
+    /// If it's not set, new nodes get the latest version and existing ones keep theirs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// A safenode binary to use rather than downloading one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub home_network: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub local: bool,
+    /// The directory each node's data directory is created in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_dir: Option<PathBuf>,
+    /// The directory each node's log directory is created in.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_dir: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rpc_address: Option<Ipv4Addr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rpc_port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+}
+
+fn default_count() -> u16 {
+    1
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl FleetConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        toml::from_str(&contents)
+            .map_err(|err| eyre!("Could not parse {}: {err}", path.to_string_lossy()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_toml()?)?;
+        Ok(())
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    pub fn bootstrap_peers(&self) -> Result<Vec<Multiaddr>> {
+        self.peers
+            .iter()
+            .map(|peer| {
+                Multiaddr::from_str(peer).map_err(|err| eyre!("Invalid peer '{peer}': {err}"))
+            })
+            .collect()
+    }
+
+    pub fn env_variables(&self) -> Option<Vec<(String, String)>> {
+        if self.env.is_empty() {
+            return None;
+        }
+        Some(
+            self.env
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+        )
+    }
+
+    fn group_index(&self, name: &str) -> Option<usize> {
+        self.node_groups.iter().position(|group| group.name == name)
+    }
+
+    /// The options for each node, in order.
+    pub fn node_specs(&self) -> Result<Vec<NodeSpec>> {
+        let mut specs = Vec::new();
+        for (group_index, group) in self.node_groups.iter().enumerate() {
+            if group.name.is_empty() {
+                return Err(eyre!("Group {} has no name", group_index + 1));
+            }
+            if self.group_index(&group.name) != Some(group_index) {
+                return Err(eyre!("There is more than one group named '{}'", group.name));
+            }
+            for offset in 0..group.count {
+                let port = |start: Option<u16>| match start {
+                    Some(start) => start.checked_add(offset).map(Some).ok_or_else(|| {
+                        eyre!("The ports for group '{}' go beyond 65535", group.name)
+                    }),
+                    None => Ok(None),
+                };
+                specs.push(NodeSpec {
+                    group: group_index,
+                    data_dir: group.data_dir.clone(),
+                    home_network: group.home_network,
+                    local: group.local,
+                    log_dir: group.log_dir.clone(),
+                    metrics_port: port(group.metrics_port)?,
+                    node_port: port(group.node_port)?,
+                    rpc_address: group.rpc_address,
+                    rpc_port: port(group.rpc_port)?,
+                    user: group.user.clone(),
+                });
+            }
+        }
+        Ok(specs)
+    }
+}
+
+/// The options for a single node, as given by its group.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeSpec {
+    /// The index of the group the node belongs to.
+    pub group: usize,
+    pub data_dir: Option<PathBuf>,
+    pub home_network: bool,
+    pub local: bool,
+    pub log_dir: Option<PathBuf>,
+    pub metrics_port: Option<u16>,
+    pub node_port: Option<u16>,
+    pub rpc_address: Option<Ipv4Addr>,
+    pub rpc_port: Option<u16>,
+    pub user: Option<String>,
+}
+
+impl NodeSpec {
+    /// Applies the options that can be changed on an existing service.
+    pub fn apply_to(&self, node: &mut NodeServiceData) {
+        node.home_network = self.home_network;
+        node.local = self.local;
+        if self.metrics_port.is_some() {
+            node.metrics_port = self.metrics_port;
+        }
+        if self.node_port.is_some() {
+            node.node_port = self.node_port;
+        }
+        node.rpc_socket_addr = self.rpc_socket_addr(node);
+    }
+
+    fn rpc_socket_addr(&self, node: &NodeServiceData) -> SocketAddr {
+        SocketAddr::new(
+            self.rpc_address
+                .map(IpAddr::V4)
+                .unwrap_or(node.rpc_socket_addr.ip()),
+            self.rpc_port.unwrap_or(node.rpc_socket_addr.port()),
+        )
+    }
+}
+
+/// A change to make to the services on the host.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FleetChange {
+    SetBootstrapPeers(Vec<Multiaddr>),
+    SetEnvVariables(Option<Vec<(String, String)>>),
+    /// Add `count` services for the node specs starting at `first_spec`, all in the same group.
+    Add {
+        group: usize,
+        first_spec: usize,
+        count: u16,
+    },
+    /// Record the group of a service that already matches its node spec.
+    Assign {
+        service_name: String,
+        group: String,
+    },
+    Remove {
+        service_name: String,
+    },
+    /// Reinstall an existing service with the options of its node spec, upgrading it if
+    /// `upgrade` is set. The service's group is recorded once it has been reinstalled.
+    Update {
+        service_name: String,
+        spec: usize,
+        upgrade: Option<(String, String)>,
+        reconfigure: Vec<String>,
+    },
+}
+
+impl fmt::Display for FleetChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FleetChange::SetBootstrapPeers(peers) => {
+                let peers: Vec<String> = peers.iter().map(|peer| peer.to_string()).collect();
+                write!(f, "~ bootstrap peers: [{}]", peers.join(", "))
+            }
+            FleetChange::SetEnvVariables(env_variables) => {
+                let env_variables: Vec<String> = env_variables
+                    .iter()
+                    .flatten()
+                    .map(|(name, value)| format!("{name}={value}"))
+                    .collect();
+                write!(f, "~ environment variables: [{}]", env_variables.join(", "))
+            }
+            FleetChange::Add { group, count, .. } => {
+                write!(f, "+ add {count} service(s) from group {}", group + 1)
+            }
+            FleetChange::Assign {
+                service_name,
+                group,
+            } => write!(f, "~ {service_name}: group: - -> {group}"),
+            FleetChange::Remove { service_name } => write!(f, "- remove {service_name}"),
+            FleetChange::Update {
+                service_name,
+                upgrade,
+                reconfigure,
+                ..
+            } => {
+                let mut changes = Vec::new();
+                if let Some((from, to)) = upgrade {
+                    changes.push(format!("version: {from} -> {to}"));
+                }
+                changes.extend(reconfigure.iter().cloned());
+                write!(f, "~ {service_name}: {}", changes.join("; "))
+            }
+        }
+    }
+}
+
+/// Works out the changes that make the registry match the fleet.
+///
+/// `group_versions` has the version each group should run, if it specifies one. It's resolved
+/// beforehand because a group given as a binary path only has a version once the binary is run.
+///
+/// Options that can only be set when a service is added, like its data directory, are not changed
+/// on existing services; an error is returned if they differ.
+pub fn plan(
+    fleet: &FleetConfig,
+    node_registry: &NodeRegistry,
+    group_versions: &[Option<String>],
+) -> Result<Vec<FleetChange>> {
+    let mut changes = Vec::new();
+
+    let bootstrap_peers = fleet.bootstrap_peers()?;
+    let peers_changed = bootstrap_peers != node_registry.bootstrap_peers;
+    if peers_changed {
+        changes.push(FleetChange::SetBootstrapPeers(bootstrap_peers));
+    }
+    let env_variables = fleet.env_variables();
+    let registry_env_variables = match &node_registry.environment_variables {
+        Some(env_variables) if env_variables.is_empty() => None,
+        env_variables => env_variables.clone(),
+    };
+    let env_changed = env_variables != registry_env_variables;
+    if env_changed {
+        changes.push(FleetChange::SetEnvVariables(env_variables));
+    }
+
+    let specs = fleet.node_specs()?;
+    let (matched, unmatched) = match_nodes(fleet, &specs, node_registry);
+
+    let mut errors = Vec::new();
+    for (spec_index, (spec, node)) in specs.iter().zip(matched.iter()).enumerate() {
+        let Some(node) = node else {
+            continue;
+        };
+        if let Some(data_dir) = &spec.data_dir {
+            if !node.data_dir_path.starts_with(data_dir) {
+                errors.push(format!(
+                    "{}: the data directory is {}, not in {}",
+                    node.service_name,
+                    node.data_dir_path.to_string_lossy(),
+                    data_dir.to_string_lossy()
+                ));
+            }
+        }
+        if let Some(log_dir) = &spec.log_dir {
+            if !node.log_dir_path.starts_with(log_dir) {
+                errors.push(format!(
+                    "{}: the log directory is {}, not in {}",
+                    node.service_name,
+                    node.log_dir_path.to_string_lossy(),
+                    log_dir.to_string_lossy()
+                ));
+            }
+        }
+        if spec.user.is_some() && spec.user != node.user {
+            errors.push(format!(
+                "{}: the service user is {}, not {}",
+                node.service_name,
+                format_option(&node.user),
+                format_option(&spec.user)
+            ));
+        }
+
+        let upgrade = match &group_versions[spec.group] {
+            Some(version) if *version != node.version => {
+                Some((node.version.clone(), version.clone()))
+            }
+            _ => None,
+        };
+
+        let mut reconfigure = Vec::new();
+        if spec.home_network != node.home_network {
+            reconfigure.push(format!(
+                "home network: {} -> {}",
+                node.home_network, spec.home_network
+            ));
+        }
+        if spec.local != node.local {
+            reconfigure.push(format!("local: {} -> {}", node.local, spec.local));
+        }
+        if spec.metrics_port.is_some() && spec.metrics_port != node.metrics_port {
+            reconfigure.push(format!(
+                "metrics port: {} -> {}",
+                format_option(&node.metrics_port),
+                format_option(&spec.metrics_port)
+            ));
+        }
+        if spec.node_port.is_some() && spec.node_port != node.node_port {
+            reconfigure.push(format!(
+                "node port: {} -> {}",
+                format_option(&node.node_port),
+                format_option(&spec.node_port)
+            ));
+        }
+        let rpc_socket_addr = spec.rpc_socket_addr(node);
+        if rpc_socket_addr != node.rpc_socket_addr {
+            reconfigure.push(format!(
+                "RPC address: {} -> {rpc_socket_addr}",
+                node.rpc_socket_addr
+            ));
+        }
+        if peers_changed {
+            reconfigure.push("bootstrap peers".to_string());
+        }
+        if env_changed {
+            reconfigure.push("environment variables".to_string());
+        }
+
+        let group = &fleet.node_groups[spec.group].name;
+        if upgrade.is_some() || !reconfigure.is_empty() {
+            changes.push(FleetChange::Update {
+                service_name: node.service_name.clone(),
+                spec: spec_index,
+                upgrade,
+                reconfigure,
+            });
+        } else if node.fleet_group.as_ref() != Some(group) {
+            changes.push(FleetChange::Assign {
+                service_name: node.service_name.clone(),
+                group: group.clone(),
+            });
+        }
+    }
+    if !errors.is_empty() {
+        return Err(eyre!(
+            "Some services differ in ways that can't be changed in place:\n{}",
+            errors.join("\n")
+        ));
+    }
+
+    for node in unmatched {
+        changes.push(FleetChange::Remove {
+            service_name: node.service_name.clone(),
+        });
+    }
+
+    // A group's nodes are matched from its first, so the nodes left to add follow one another.
+    let mut spec_index = 0;
+    while spec_index < specs.len() {
+        if matched[spec_index].is_some() {
+            spec_index += 1;
+            continue;
+        }
+        let group = specs[spec_index].group;
+        let count = specs[spec_index..]
+            .iter()
+            .zip(&matched[spec_index..])
+            .take_while(|(spec, node)| spec.group == group && node.is_none())
+            .count();
+        changes.push(FleetChange::Add {
+            group,
+            first_spec: spec_index,
+            count: count as u16,
+        });
+        spec_index += count;
+    }
+
+    Ok(changes)
+}
+
+/// Matches the services in the registry that haven't been removed to the node specs.
+///
+/// Returns the service matched to each spec, and the services that weren't matched to any.
+/// Services whose group is no longer in the fleet, or has fewer nodes than services, are treated
+/// like those that don't belong to a group, so they're only removed if no other group needs them.
+fn match_nodes<'a>(
+    fleet: &FleetConfig,
+    specs: &[NodeSpec],
+    node_registry: &'a NodeRegistry,
+) -> (Vec<Option<&'a NodeServiceData>>, Vec<&'a NodeServiceData>) {
+    let mut matched: Vec<Option<&NodeServiceData>> = vec![None; specs.len()];
+    let mut ungrouped = Vec::new();
+    for node in node_registry
+        .nodes
+        .iter()
+        .filter(|node| node.status != ServiceStatus::Removed)
+    {
+        let Some(name) = &node.fleet_group else {
+            ungrouped.push(node);
+            continue;
+        };
+        let free_spec = fleet.group_index(name).and_then(|group| {
+            specs
+                .iter()
+                .zip(&matched)
+                .position(|(spec, matched)| spec.group == group && matched.is_none())
+        });
+        match free_spec {
+            Some(spec_index) => matched[spec_index] = Some(node),
+            None => ungrouped.push(node),
+        }
+    }
+
+    let mut ungrouped = ungrouped.into_iter();
+    for slot in matched.iter_mut().filter(|slot| slot.is_none()) {
+        match ungrouped.next() {
+            Some(node) => *slot = Some(node),
+            None => break,
+        }
+    }
+
+    (matched, ungrouped.collect())
+}
+
+/// Describes the services in the registry as a fleet.
+///
+/// Consecutive services with the same group, options and consecutive ports are put in the same
+/// group. Groups are named after the group their services belong to, if it hasn't been used yet.
+pub fn export(node_registry: &NodeRegistry) -> FleetConfig {
+    let mut node_groups: Vec<NodeGroup> = Vec::new();
+    let mut group_names: Vec<Option<&String>> = Vec::new();
+    for node in node_registry
+        .nodes
+        .iter()
+        .filter(|node| node.status != ServiceStatus::Removed)
+    {
+        let rpc_address = match node.rpc_socket_addr.ip() {
+            IpAddr::V4(address) => Some(address),
+            IpAddr::V6(_) => None,
+        };
+        let group = NodeGroup {
+            name: String::new(),
+            count: 1,
+            version: Some(node.version.clone()),
+            path: None,
+            home_network: node.home_network,
+            local: node.local,
+            data_dir: node.data_dir_path.parent().map(Path::to_path_buf),
+            log_dir: get_log_dir_parent(node),
+            metrics_port: node.metrics_port,
+            node_port: node.node_port,
+            rpc_address,
+            rpc_port: Some(node.rpc_socket_addr.port()),
+            user: node.user.clone(),
+        };
+
+        match node_groups.last_mut() {
+            Some(last)
+                if group_names.last() == Some(&node.fleet_group.as_ref())
+                    && continues_group(last, &group) =>
+            {
+                last.count += 1
+            }
+            _ => {
+                node_groups.push(group);
+                group_names.push(node.fleet_group.as_ref());
+            }
+        }
+    }
+
+    for index in 0..node_groups.len() {
+        let is_used = |name: &str| node_groups[..index].iter().any(|group| group.name == name);
+        let name = match group_names[index] {
+            Some(name) if !is_used(name) => name.clone(),
+            _ => (index + 1..)
+                .map(|number| format!("group{number}"))
+                .find(|name| !is_used(name) && !group_names.contains(&Some(name)))
+                .unwrap_or_default(),
+        };
+        node_groups[index].name = name;
+    }
+
+    FleetConfig {
+        peers: node_registry
+            .bootstrap_peers
+            .iter()
+            .map(|peer| peer.to_string())
+            .collect(),
+        env: node_registry
+            .environment_variables
+            .iter()
+            .flatten()
+            .cloned()
+            .collect(),
+        node_groups,
+    }
+}
+
+/// Whether `next` is the node that would follow the nodes already in `group`.
+fn continues_group(group: &NodeGroup, next: &NodeGroup) -> bool {
+    let next_port = |start: Option<u16>| start.and_then(|start| start.checked_add(group.count));
+    group.version == next.version
+        && group.home_network == next.home_network
+        && group.local == next.local
+        && group.data_dir == next.data_dir
+        && group.log_dir == next.log_dir
+        && next_port(group.metrics_port) == next.metrics_port
+        && next_port(group.node_port) == next.node_port
+        && group.rpc_address == next.rpc_address
+        && next_port(group.rpc_port) == next.rpc_port
+        && group.user == next.user
+}
+
+/// The directory the node's log directory was created in.
+fn get_log_dir_parent(node: &NodeServiceData) -> Option<PathBuf> {
+    // User mode services that use the default location have an extra 'logs' directory.
+    let service_dir = if node.log_dir_path.ends_with("logs") {
+        node.log_dir_path
+            .parent()
+            .filter(|parent| parent.ends_with(&node.service_name))
+            .unwrap_or(&node.log_dir_path)
+    } else {
+        &node.log_dir_path
+    };
+    service_dir.parent().map(Path::to_path_buf)
+}
+
+fn format_option<T: fmt::Display>(value: &Option<T>) -> String {
+    value
+        .as_ref()
+        .map_or("-".to_string(), |value| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use sn_service_management::control::ServiceBackend;
+
+    fn create_node(number: u16) -> NodeServiceData {
+        let service_name = format!("safenode{number}");
+        NodeServiceData {
+            connected_peers: None,
+            data_dir_path: PathBuf::from("/var/safenode-manager/services").join(&service_name),
+            fleet_group: Some("main".to_string()),
+            genesis: false,
+            home_network: false,
+            last_restart: None,
+            listen_addr: None,
+            local: false,
+            log_dir_path: PathBuf::from("/var/log/safenode").join(&service_name),
+            metrics_port: None,
+            node_port: Some(12000 + number - 1),
+            number,
+            peer_id: None,
+            pid: None,
+            restart_count: 0,
+            reward_balance: None,
+            rpc_socket_addr: SocketAddr::new(
+                IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                13000 + number - 1,
+            ),
+            safenode_path: PathBuf::from("/var/safenode-manager/services")
+                .join(&service_name)
+                .join("safenode"),
+            service_name,
+            status: ServiceStatus::Running,
+            user: Some("safe".to_string()),
+            user_mode: false,
+            version: "0.1.0".to_string(),
+        }
+    }
+
+    fn create_node_registry(node_count: u16) -> NodeRegistry {
+        NodeRegistry {
+            bootstrap_peers: Vec::new(),
+            daemon: None,
+            environment_variables: None,
+            faucet: None,
+            nodes: (1..=node_count).map(create_node).collect(),
+            rolling_upgrade: None,
+            save_path: PathBuf::from("/tmp/node_registry.json"),
+            service_backend: ServiceBackend::Native,
+        }
+    }
+
+    const FLEET: &str = r#"
+[env]
+SN_LOG = "all"
+
+[[nodes]]
+name = "main"
+count = 3
+version = "0.1.0"
+data_dir = "/var/safenode-manager/services"
+node_port = 12000
+rpc_port = 13000
+user = "safe"
+"#;
+
+    #[test]
+    fn node_specs_should_assign_consecutive_ports_within_a_group() -> Result<()> {
+        let fleet: FleetConfig = toml::from_str(FLEET)?;
+
+        let specs = fleet.node_specs()?;
+
+        assert_eq!(specs.len(), 3);
+        for (offset, spec) in specs.iter().enumerate() {
+            assert_eq!(spec.group, 0);
+            assert_eq!(spec.node_port, Some(12000 + offset as u16));
+            assert_eq!(spec.rpc_port, Some(13000 + offset as u16));
+            assert_eq!(spec.metrics_port, None);
+            assert_eq!(spec.user.as_deref(), Some("safe"));
+        }
+        assert_eq!(
+            fleet.env_variables(),
+            Some(vec![("SN_LOG".to_string(), "all".to_string())])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn export_should_group_consecutive_nodes_and_plan_no_reinstalls_for_itself() -> Result<()> {
+        let mut node_registry = create_node_registry(4);
+        node_registry.nodes[2].version = "0.2.0".to_string();
+        node_registry.nodes[3].version = "0.2.0".to_string();
+        node_registry.nodes[3].fleet_group = Some("beta".to_string());
+
+        let fleet = export(&node_registry);
+
+        assert_eq!(fleet.node_groups.len(), 3);
+        assert_eq!(fleet.node_groups[0].name, "main");
+        assert_eq!(fleet.node_groups[0].count, 2);
+        assert_eq!(fleet.node_groups[0].node_port, Some(12000));
+        assert_eq!(fleet.node_groups[0].rpc_port, Some(13000));
+        assert_eq!(
+            fleet.node_groups[0].data_dir,
+            Some(PathBuf::from("/var/safenode-manager/services"))
+        );
+        assert_eq!(
+            fleet.node_groups[0].log_dir,
+            Some(PathBuf::from("/var/log/safenode"))
+        );
+        // The name of a group that's been split can only be used once.
+        assert_eq!(fleet.node_groups[1].name, "group2");
+        assert_eq!(fleet.node_groups[1].count, 1);
+        assert_eq!(fleet.node_groups[1].version.as_deref(), Some("0.2.0"));
+        assert_eq!(fleet.node_groups[1].node_port, Some(12002));
+        assert_eq!(fleet.node_groups[2].name, "beta");
+        assert_eq!(fleet.node_groups[2].count, 1);
+
+        let parsed: FleetConfig = toml::from_str(&fleet.to_toml()?)?;
+        assert_eq!(parsed, fleet);
+
+        let group_versions: Vec<_> = fleet
+            .node_groups
+            .iter()
+            .map(|group| group.version.clone())
+            .collect();
+        assert_eq!(
+            plan(&fleet, &node_registry, &group_versions)?,
+            vec![FleetChange::Assign {
+                service_name: "safenode3".to_string(),
+                group: "group2".to_string(),
+            }]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn plan_should_match_services_to_the_group_they_belong_to() -> Result<()> {
+        let mut fleet: FleetConfig = toml::from_str(
+            r#"
+[[nodes]]
+name = "a"
+count = 2
+
+[[nodes]]
+name = "b"
+count = 2
+"#,
+        )?;
+        let mut node_registry = create_node_registry(4);
+        node_registry.nodes[0].fleet_group = Some("b".to_string());
+        node_registry.nodes[1].fleet_group = Some("a".to_string());
+        node_registry.nodes[2].fleet_group = Some("a".to_string());
+        node_registry.nodes[3].fleet_group = None;
+
+        // The service without a group fills the node left in the second group.
+        let changes = plan(&fleet, &node_registry, &[None, None])?;
+        assert_eq!(
+            changes,
+            vec![FleetChange::Assign {
+                service_name: "safenode4".to_string(),
+                group: "b".to_string(),
+            }]
+        );
+
+        // Shrinking the first group doesn't move the services in the second one.
+        fleet.node_groups[0].count = 1;
+        let changes = plan(&fleet, &node_registry, &[None, None])?;
+        assert_eq!(
+            changes,
+            vec![
+                FleetChange::Assign {
+                    service_name: "safenode3".to_string(),
+                    group: "b".to_string(),
+                },
+                FleetChange::Remove {
+                    service_name: "safenode4".to_string()
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn node_specs_should_return_an_error_if_group_names_are_repeated() -> Result<()> {
+        let mut fleet: FleetConfig = toml::from_str(FLEET)?;
+        fleet.node_groups.push(fleet.node_groups[0].clone());
+
+        match fleet.node_specs() {
+            Ok(_) => panic!("This test should result in an error"),
+            Err(e) => assert_eq!(e.to_string(), "There is more than one group named 'main'"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn plan_should_add_and_remove_services_to_match_the_node_count() -> Result<()> {
+        let fleet: FleetConfig = toml::from_str(FLEET)?;
+
+        let changes = plan(&fleet, &create_node_registry(1), &[None])?;
+        assert_eq!(
+            changes,
+            vec![
+                FleetChange::SetEnvVariables(Some(vec![("SN_LOG".to_string(), "all".to_string())])),
+                FleetChange::Update {
+                    service_name: "safenode1".to_string(),
+                    spec: 0,
+                    upgrade: None,
+                    reconfigure: vec!["environment variables".to_string()],
+                },
+                FleetChange::Add {
+                    group: 0,
+                    first_spec: 1,
+                    count: 2,
+                },
+            ]
+        );
+
+        let mut node_registry = create_node_registry(5);
+        node_registry.environment_variables = fleet.env_variables();
+        let changes = plan(&fleet, &node_registry, &[None])?;
+        assert_eq!(
+            changes,
+            vec![
+                FleetChange::Remove {
+                    service_name: "safenode4".to_string()
+                },
+                FleetChange::Remove {
+                    service_name: "safenode5".to_string()
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn plan_should_skip_removed_services_when_matching_nodes() -> Result<()> {
+        let fleet: FleetConfig = toml::from_str(FLEET)?;
+        let mut node_registry = create_node_registry(4);
+        node_registry.environment_variables = fleet.env_variables();
+        node_registry.nodes[0].status = ServiceStatus::Removed;
+
+        let changes = plan(&fleet, &node_registry, &[None])?;
+
+        // The remaining services are matched to the nodes in order, so their ports shift.
+        assert_eq!(changes.len(), 3);
+        assert_matches!(
+            &changes[0],
+            FleetChange::Update { service_name, spec: 0, upgrade: None, reconfigure }
+                if service_name == "safenode2"
+                    && reconfigure == &vec![
+                        "node port: 12001 -> 12000".to_string(),
+                        "RPC address: 127.0.0.1:13001 -> 127.0.0.1:13000".to_string(),
+                    ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn plan_should_upgrade_and_reconfigure_services_that_differ() -> Result<()> {
+        let mut fleet: FleetConfig = toml::from_str(FLEET)?;
+        fleet.node_groups[0].count = 1;
+        fleet.node_groups[0].home_network = true;
+        fleet.node_groups[0].metrics_port = Some(14000);
+        let mut node_registry = create_node_registry(1);
+        node_registry.environment_variables = fleet.env_variables();
+
+        let changes = plan(&fleet, &node_registry, &[Some("0.2.0".to_string())])?;
+
+        assert_eq!(
+            changes,
+            vec![FleetChange::Update {
+                service_name: "safenode1".to_string(),
+                spec: 0,
+                upgrade: Some(("0.1.0".to_string(), "0.2.0".to_string())),
+                reconfigure: vec![
+                    "home network: false -> true".to_string(),
+                    "metrics port: - -> 14000".to_string(),
+                ],
+            }]
+        );
+
+        let spec = &fleet.node_specs()?[0];
+        spec.apply_to(&mut node_registry.nodes[0]);
+        assert!(node_registry.nodes[0].home_network);
+        assert_eq!(node_registry.nodes[0].metrics_port, Some(14000));
+        assert_eq!(node_registry.nodes[0].node_port, Some(12000));
+
+        Ok(())
+    }
+
+    #[test]
+    fn plan_should_return_an_error_if_a_service_has_a_different_data_dir() -> Result<()> {
+        let mut fleet: FleetConfig = toml::from_str(FLEET)?;
+        fleet.node_groups[0].data_dir = Some(PathBuf::from("/mnt/data"));
+
+        let result = plan(&fleet, &create_node_registry(3), &[None]);
+
+        match result {
+            Ok(_) => panic!("This test should result in an error"),
+            Err(e) => assert!(e.to_string().contains(
+                "safenode1: the data directory is /var/safenode-manager/services/safenode1, not in /mnt/data"
+            )),
+        }
+
+        Ok(())
+    }
+}
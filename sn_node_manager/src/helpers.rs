@@ -14,15 +14,21 @@ use indicatif::{ProgressBar, ProgressStyle};
 use semver::Version;
 use sn_releases::{get_running_platform, ArchiveType, ReleaseType, SafeReleaseRepoActions};
 use std::{
-    io::Read,
+    io::{Read, SeekFrom},
     path::PathBuf,
     process::{Command, Stdio},
     sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncSeekExt},
+    sync::mpsc,
 };
 
 use crate::{config, VerbosityLevel};
 
 const MAX_DOWNLOAD_RETRIES: u8 = 3;
+const LOG_FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
 
 /// Downloads and extracts a release binary to a temporary location.
 ///
@@ -192,6 +198,68 @@ pub fn get_username() -> Result<String> {
     Ok(std::env::var("USER")?)
 }
 
+/// Sends the last `lines` lines of a log file to `sender`, then, if `follow` is set, any lines
+/// appended to it afterwards.
+///
+/// If the file shrinks while being followed, it is assumed to have been rotated and is read again
+/// from the start. Following ends when the receiving side is dropped.
+pub async fn tail_log_file(
+    path: PathBuf,
+    lines: usize,
+    follow: bool,
+    sender: mpsc::Sender<String>,
+) -> Result<()> {
+    let content = tokio::fs::read(&path)
+        .await
+        .map_err(|err| eyre!("Could not read log file at {path:?}: {err}"))?;
+    let text = String::from_utf8_lossy(&content);
+    let existing_lines = text.lines().collect::<Vec<_>>();
+    for line in &existing_lines[existing_lines.len().saturating_sub(lines)..] {
+        if sender.send(line.to_string()).await.is_err() {
+            return Ok(());
+        }
+    }
+    if !follow {
+        return Ok(());
+    }
+
+    let mut position = content.len() as u64;
+    let mut partial_line = String::new();
+    loop {
+        tokio::time::sleep(LOG_FOLLOW_INTERVAL).await;
+        if sender.is_closed() {
+            return Ok(());
+        }
+
+        // The file can briefly be missing while it's being rotated.
+        let metadata = match tokio::fs::metadata(&path).await {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        if metadata.len() < position {
+            position = 0;
+            partial_line.clear();
+        }
+        if metadata.len() == position {
+            continue;
+        }
+
+        let mut file = tokio::fs::File::open(&path).await?;
+        file.seek(SeekFrom::Start(position)).await?;
+        let mut appended = Vec::new();
+        file.read_to_end(&mut appended).await?;
+        position += appended.len() as u64;
+
+        partial_line.push_str(&String::from_utf8_lossy(&appended));
+        while let Some(index) = partial_line.find('\n') {
+            let line = partial_line.drain(..=index).collect::<String>();
+            if sender.send(line.trim_end().to_string()).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
 /// There is a `tempdir` crate that provides the same kind of functionality, but it was flagged for
 /// a security vulnerability.
 pub fn create_temp_dir() -> Result<PathBuf> {
@@ -201,3 +269,37 @@ pub fn create_temp_dir() -> Result<PathBuf> {
     std::fs::create_dir_all(&new_temp_dir)?;
     Ok(new_temp_dir)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+
+    #[tokio::test]
+    async fn tail_log_file_should_send_the_last_lines_and_follow_appends_and_rotation() -> Result<()>
+    {
+        let tmp_dir = assert_fs::TempDir::new()?;
+        let log_file = tmp_dir.child("safenode.log");
+        log_file.write_str("one\ntwo\nthree\n")?;
+
+        let (sender, mut receiver) = mpsc::channel(10);
+        let handle = tokio::spawn(tail_log_file(log_file.to_path_buf(), 2, true, sender));
+        assert_eq!(receiver.recv().await, Some("two".to_string()));
+        assert_eq!(receiver.recv().await, Some("three".to_string()));
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(log_file.path())?;
+        std::io::Write::write_all(&mut file, b"four\nfi")?;
+        assert_eq!(receiver.recv().await, Some("four".to_string()));
+        std::io::Write::write_all(&mut file, b"ve\n")?;
+        assert_eq!(receiver.recv().await, Some("five".to_string()));
+
+        log_file.write_str("rotated\n")?;
+        assert_eq!(receiver.recv().await, Some("rotated".to_string()));
+
+        drop(receiver);
+        handle.await??;
+        Ok(())
+    }
+}
@@ -7,12 +7,18 @@
 // permissions and limitations relating to use of the SAFE Network Software.
 
 pub mod add_services;
+#[cfg(feature = "chaos")]
+pub mod chaos;
 pub mod cmd;
 pub mod config;
+pub mod fleet;
 pub mod helpers;
 pub mod local;
+pub mod preflight;
+pub mod rolling_upgrade;
 pub mod rpc;
 pub mod rpc_client;
+pub mod supervisor;
 
 #[derive(Clone, Copy, PartialEq)]
 pub enum VerbosityLevel {
@@ -42,8 +48,8 @@ use sn_service_management::{
     control::ServiceControl,
     error::Error as ServiceError,
     rpc::{RpcActions, RpcClient},
-    NodeRegistry, NodeServiceData, ServiceStateActions, ServiceStatus, UpgradeOptions,
-    UpgradeResult,
+    NodeRegistry, NodeServiceData, ServiceStateActions, ServiceStatus, UpgradeBatchStatus,
+    UpgradeOptions, UpgradeResult,
 };
 use sn_transfers::HotWallet;
 use tracing::debug;
@@ -359,6 +365,10 @@ pub async fn status_report(
                 node.reward_balance
                     .map_or("-".to_string(), |b| b.to_string())
             );
+            println!("Restarts: {}", node.restart_count);
+            if let Some(last_restart) = &node.last_restart {
+                println!("Last restart reason: {}", last_restart.reason);
+            }
             println!();
         }
 
@@ -382,6 +392,36 @@ pub async fn status_report(
             println!("Bin path: {}", faucet.faucet_path.to_string_lossy());
             println!("Log path: {}", faucet.log_dir_path.to_string_lossy());
         }
+
+        if let Some(rolling_upgrade) = &node_registry.rolling_upgrade {
+            print_banner(&format!(
+                "Rolling upgrade to {} - {}",
+                rolling_upgrade.target_version,
+                if rolling_upgrade.finished_at.is_some() {
+                    "FINISHED"
+                } else {
+                    "IN PROGRESS"
+                }
+            ));
+            println!("Batch size: {}", rolling_upgrade.batch_size);
+            for (i, batch) in rolling_upgrade.batches.iter().enumerate() {
+                let service_names = batch
+                    .nodes
+                    .iter()
+                    .map(|node| node.service_name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let status = match &batch.status {
+                    UpgradeBatchStatus::InProgress => "in progress".to_string(),
+                    UpgradeBatchStatus::Healthy => "healthy".to_string(),
+                    UpgradeBatchStatus::RolledBack(reason) => format!("rolled back: {reason}"),
+                    UpgradeBatchStatus::RollbackFailed(reason, err) => {
+                        format!("rollback failed: {reason}; {err}")
+                    }
+                };
+                println!("Batch {}: {service_names} ({status})", i + 1);
+            }
+        }
     } else {
         println!(
             "{:<18} {:<52} {:<7} {:>15}",
@@ -628,14 +668,19 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: None,
             pid: None,
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
@@ -714,16 +759,21 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: Some(PeerId::from_str(
                 "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
             )?),
             pid: None,
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
@@ -771,16 +821,21 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: Some(PeerId::from_str(
                 "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
             )?),
             pid: Some(1000),
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
@@ -864,16 +919,21 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: Some(PeerId::from_str(
                 "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
             )?),
             pid: Some(1000),
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
@@ -936,14 +996,19 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: None,
             pid: None,
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
@@ -1014,14 +1079,19 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: None,
             pid: None,
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
@@ -1061,16 +1131,21 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: Some(PeerId::from_str(
                 "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
             )?),
             pid: Some(1000),
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
@@ -1103,14 +1178,19 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: None,
             pid: None,
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
@@ -1143,16 +1223,21 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: Some(PeerId::from_str(
                 "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
             )?),
             pid: None,
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
@@ -1186,14 +1271,19 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: None,
             pid: None,
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
@@ -1240,16 +1330,21 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: Some(PeerId::from_str(
                 "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
             )?),
             pid: Some(1000),
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
@@ -1357,16 +1452,21 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: Some(PeerId::from_str(
                 "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
             )?),
             pid: Some(1000),
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: current_node_bin.to_path_buf(),
@@ -1437,16 +1537,21 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: Some(PeerId::from_str(
                 "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
             )?),
             pid: Some(1000),
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: current_node_bin.to_path_buf(),
@@ -1559,16 +1664,21 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: Some(PeerId::from_str(
                 "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
             )?),
             pid: Some(1000),
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: current_node_bin.to_path_buf(),
@@ -1694,16 +1804,21 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: Some(PeerId::from_str(
                 "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
             )?),
             pid: Some(1000),
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: current_node_bin.to_path_buf(),
@@ -1825,16 +1940,21 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: Some(PeerId::from_str(
                 "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
             )?),
             pid: Some(1000),
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: current_node_bin.to_path_buf(),
@@ -1956,16 +2076,21 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             peer_id: Some(PeerId::from_str(
                 "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
             )?),
             pid: Some(1000),
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: current_node_bin.to_path_buf(),
@@ -2036,14 +2161,19 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: data_dir.to_path_buf(),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: log_dir.to_path_buf(),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             pid: None,
             peer_id: None,
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: safenode_bin.to_path_buf(),
@@ -2084,16 +2214,21 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             pid: Some(1000),
             peer_id: Some(PeerId::from_str(
                 "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
             )?),
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
@@ -2140,16 +2275,21 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             pid: Some(1000),
             peer_id: Some(PeerId::from_str(
                 "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR",
             )?),
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
@@ -2198,14 +2338,19 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: data_dir.to_path_buf(),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: log_dir.to_path_buf(),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             pid: None,
             peer_id: None,
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: safenode_bin.to_path_buf(),
@@ -2254,14 +2399,19 @@ mod tests {
         let mut service_data = NodeServiceData {
             connected_peers: None,
             data_dir_path: data_dir.to_path_buf(),
+            fleet_group: None,
             genesis: false,
             home_network: false,
+            last_restart: None,
             listen_addr: None,
             local: false,
             log_dir_path: log_dir.to_path_buf(),
+            metrics_port: None,
+            node_port: None,
             number: 1,
             pid: None,
             peer_id: None,
+            restart_count: 0,
             reward_balance: Some(NanoTokens::zero()),
             rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
             safenode_path: safenode_bin.to_path_buf(),
@@ -339,14 +339,19 @@ pub async fn run_node(
     Ok(NodeServiceData {
         connected_peers,
         data_dir_path: node_info.data_path,
+        fleet_group: None,
         genesis: run_options.genesis,
         home_network: false,
+        last_restart: None,
         listen_addr: Some(listen_addrs),
         local: true,
         log_dir_path: node_info.log_path,
+        metrics_port: None,
+        node_port: None,
         number: run_options.number,
         peer_id: Some(peer_id),
         pid: Some(node_info.pid),
+        restart_count: 0,
         reward_balance: None,
         rpc_socket_addr: run_options.rpc_socket_addr,
         safenode_path: launcher.get_safenode_path(),
@@ -0,0 +1,706 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use crate::add_services::config::PortRange;
+use color_eyre::{eyre::eyre, Result};
+use indicatif::HumanBytes;
+use sn_service_management::{NodeRegistry, ServiceStatus};
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, UdpSocket},
+    path::{Path, PathBuf},
+};
+use sysinfo::{Disks, System};
+
+/// The number of records a node stores before it stops accepting more.
+pub const DEFAULT_MAX_RECORDS: u64 = 2048;
+/// The memory a node is expected to use, in MiB.
+pub const DEFAULT_NODE_MEMORY_MB: u64 = 512;
+
+const MB: u64 = 1024 * 1024;
+/// Chunks are at most 1MB, which also bounds the size of the other record types.
+const MAX_RECORD_SIZE: u64 = MB;
+/// Logs are rotated at 20MB and 10 files are kept uncompressed. The remainder allows for the
+/// compressed files.
+const LOG_SPACE: u64 = 256 * MB;
+const FILE_DESCRIPTORS_PER_NODE: u64 = 512;
+
+/// The resources a single node is expected to need.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeResourceProfile {
+    pub file_descriptors: u64,
+    pub log_space: u64,
+    pub memory: u64,
+    pub storage: u64,
+}
+
+impl NodeResourceProfile {
+    pub fn new(max_records: u64, memory_mb: u64) -> Self {
+        Self {
+            file_descriptors: FILE_DESCRIPTORS_PER_NODE,
+            log_space: LOG_SPACE,
+            memory: memory_mb * MB,
+            storage: max_records * MAX_RECORD_SIZE,
+        }
+    }
+}
+
+impl Default for NodeResourceProfile {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_RECORDS, DEFAULT_NODE_MEMORY_MB)
+    }
+}
+
+/// The services that are about to be added.
+pub struct PreflightOptions {
+    pub count: u16,
+    pub data_dir_path: PathBuf,
+    pub log_dir_path: PathBuf,
+    pub metrics_port: Option<PortRange>,
+    pub node_port: Option<PortRange>,
+    pub profile: NodeResourceProfile,
+    pub rpc_address: Option<Ipv4Addr>,
+    pub rpc_port: Option<PortRange>,
+}
+
+impl PreflightOptions {
+    /// The number of services the 'add' command would add: the count if it was given, otherwise
+    /// one for each port in the node port range.
+    pub fn service_count(count: Option<u16>, node_port: Option<&PortRange>) -> u16 {
+        match (count, node_port) {
+            (Some(count), _) => count,
+            (None, Some(PortRange::Range(start, end))) => end - start + 1,
+            (None, _) => 1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiskSpace {
+    pub available: u64,
+    pub mount_point: PathBuf,
+}
+
+/// A node that is already in the registry, which will keep using resources on the host.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExistingNode {
+    pub data_disk: Option<PathBuf>,
+    pub data_used: u64,
+    pub status: ServiceStatus,
+}
+
+/// What the host has available at the time of the check.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HostResources {
+    pub available_memory: u64,
+    pub data_disk: Option<DiskSpace>,
+    pub existing_nodes: Vec<ExistingNode>,
+    /// The limit on open files for a process, which a node will inherit.
+    pub file_descriptor_limit: Option<u64>,
+    pub log_disk: Option<DiskSpace>,
+    /// The number of file handles that can still be allocated across the whole system.
+    pub system_file_descriptors: Option<u64>,
+}
+
+impl HostResources {
+    /// Read the available resources from the host.
+    pub fn probe(data_dir_path: &Path, log_dir_path: &Path, node_registry: &NodeRegistry) -> Self {
+        let disks = Disks::new_with_refreshed_list();
+        let disk_for = |path: &Path| -> Option<DiskSpace> {
+            let path = existing_ancestor(path)?;
+            disks
+                .list()
+                .iter()
+                .filter(|disk| path.starts_with(disk.mount_point()))
+                .max_by_key(|disk| disk.mount_point().as_os_str().len())
+                .map(|disk| DiskSpace {
+                    available: disk.available_space(),
+                    mount_point: disk.mount_point().to_path_buf(),
+                })
+        };
+
+        let mut system = System::new();
+        system.refresh_memory();
+
+        let existing_nodes = node_registry
+            .nodes
+            .iter()
+            .filter(|node| node.status != ServiceStatus::Removed)
+            .map(|node| ExistingNode {
+                data_disk: disk_for(&node.data_dir_path).map(|disk| disk.mount_point),
+                data_used: dir_size(&node.data_dir_path),
+                status: node.status.clone(),
+            })
+            .collect();
+
+        Self {
+            available_memory: system.available_memory(),
+            data_disk: disk_for(data_dir_path),
+            existing_nodes,
+            file_descriptor_limit: get_file_descriptor_limit(),
+            log_disk: disk_for(log_dir_path),
+            system_file_descriptors: get_system_file_descriptors(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreflightCheck {
+    pub available: String,
+    pub passed: bool,
+    pub required: String,
+    pub resource: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreflightReport {
+    pub checks: Vec<PreflightCheck>,
+    pub count: u16,
+    /// The largest number of nodes the host can take, if it could be determined.
+    pub max_safe_count: Option<u64>,
+}
+
+impl PreflightReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.passed)
+    }
+
+    pub fn print(&self) {
+        for check in &self.checks {
+            println!(
+                "{} {}: {} required, {} available",
+                if check.passed { "✓" } else { "✕" },
+                check.resource,
+                check.required,
+                check.available
+            );
+        }
+        if let Some(max_safe_count) = self.max_safe_count {
+            println!("At most {max_safe_count} more node(s) can be added");
+        }
+    }
+
+    /// Return an error describing the failed checks, if there are any.
+    pub fn into_result(self) -> Result<()> {
+        if self.passed() {
+            return Ok(());
+        }
+        let mut message = format!(
+            "The host does not have the resources for {} more node(s):",
+            self.count
+        );
+        for check in self.checks.iter().filter(|check| !check.passed) {
+            message.push_str(&format!(
+                "\n  {}: {} required, {} available",
+                check.resource, check.required, check.available
+            ));
+        }
+        if let Some(max_safe_count) = self.max_safe_count {
+            message.push_str(&format!(
+                "\nAt most {max_safe_count} more node(s) can be added"
+            ));
+        }
+        message.push_str("\nUse --skip-preflight to add the services anyway");
+        Err(eyre!(message))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+/// Check whether the host can take on the nodes described by the options.
+///
+/// The disk space nodes in the registry haven't used yet, and the memory of those that haven't
+/// been started yet, are treated as taken. The `is_port_free` function reports whether a port can
+/// be bound on the host.
+pub fn evaluate(
+    options: &PreflightOptions,
+    host: &HostResources,
+    node_registry: &NodeRegistry,
+    is_port_free: &dyn Fn(SocketAddr, Protocol) -> bool,
+) -> PreflightReport {
+    let count = options.count as u64;
+    let profile = &options.profile;
+    let mut checks = Vec::new();
+    let mut max_safe_count: Option<u64> = None;
+    let mut limit = |max: u64| {
+        max_safe_count = Some(match max_safe_count {
+            Some(current) => current.min(max),
+            None => max,
+        });
+    };
+
+    // The data and log directories may be on the same disk, in which case each node needs the
+    // space for both from it.
+    let mut disk_needs: Vec<(&DiskSpace, u64, &str)> = Vec::new();
+    match (&host.data_disk, &host.log_disk) {
+        (Some(data_disk), Some(log_disk)) if data_disk.mount_point == log_disk.mount_point => {
+            disk_needs.push((data_disk, profile.storage + profile.log_space, "Disk space"));
+        }
+        (data_disk, log_disk) => {
+            if let Some(data_disk) = data_disk {
+                disk_needs.push((data_disk, profile.storage, "Data disk space"));
+            }
+            if let Some(log_disk) = log_disk {
+                disk_needs.push((log_disk, profile.log_space, "Log disk space"));
+            }
+        }
+    }
+    for (disk, per_node, name) in disk_needs {
+        let reserved = host
+            .existing_nodes
+            .iter()
+            .filter(|node| node.data_disk.as_ref() == Some(&disk.mount_point))
+            .map(|node| profile.storage.saturating_sub(node.data_used))
+            .sum::<u64>();
+        let available = disk.available.saturating_sub(reserved);
+        checks.push(PreflightCheck {
+            available: format!(
+                "{} on {}",
+                HumanBytes(available),
+                disk.mount_point.to_string_lossy()
+            ),
+            passed: available >= per_node * count,
+            required: HumanBytes(per_node * count).to_string(),
+            resource: name.to_string(),
+        });
+        if let Some(max) = available.checked_div(per_node) {
+            limit(max);
+        }
+    }
+
+    let pending = host
+        .existing_nodes
+        .iter()
+        .filter(|node| node.status == ServiceStatus::Added)
+        .count() as u64
+        * profile.memory;
+    let available_memory = host.available_memory.saturating_sub(pending);
+    checks.push(PreflightCheck {
+        available: HumanBytes(available_memory).to_string(),
+        passed: available_memory >= profile.memory * count,
+        required: HumanBytes(profile.memory * count).to_string(),
+        resource: "Memory".to_string(),
+    });
+    if let Some(max) = available_memory.checked_div(profile.memory) {
+        limit(max);
+    }
+
+    if let Some(file_descriptor_limit) = host.file_descriptor_limit {
+        checks.push(PreflightCheck {
+            available: file_descriptor_limit.to_string(),
+            passed: file_descriptor_limit >= profile.file_descriptors,
+            required: profile.file_descriptors.to_string(),
+            resource: "Open file limit per node".to_string(),
+        });
+    }
+    if let Some(system_file_descriptors) = host.system_file_descriptors {
+        checks.push(PreflightCheck {
+            available: system_file_descriptors.to_string(),
+            passed: system_file_descriptors >= profile.file_descriptors * count,
+            required: (profile.file_descriptors * count).to_string(),
+            resource: "System file handles".to_string(),
+        });
+        if let Some(max) = system_file_descriptors.checked_div(profile.file_descriptors) {
+            limit(max);
+        }
+    }
+
+    let mut registry_ports: HashMap<u16, String> = HashMap::new();
+    for node in node_registry
+        .nodes
+        .iter()
+        .filter(|node| node.status != ServiceStatus::Removed)
+    {
+        let ports = [
+            node.node_port,
+            node.metrics_port,
+            Some(node.rpc_socket_addr.port()),
+        ];
+        for port in ports.into_iter().flatten() {
+            registry_ports.insert(port, node.service_name.clone());
+        }
+    }
+    let rpc_address = IpAddr::V4(options.rpc_address.unwrap_or(Ipv4Addr::LOCALHOST));
+    let requested = [
+        (
+            &options.node_port,
+            "Node port",
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            Protocol::Udp,
+        ),
+        (
+            &options.metrics_port,
+            "Metrics port",
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            Protocol::Tcp,
+        ),
+        (&options.rpc_port, "RPC port", rpc_address, Protocol::Tcp),
+    ];
+    let mut seen = HashSet::new();
+    for (range, name, address, protocol) in requested {
+        let ports = match range {
+            Some(PortRange::Single(port)) => *port..=*port,
+            Some(PortRange::Range(start, end)) => *start..=*end,
+            None => continue,
+        };
+        for port in ports {
+            let available = if let Some(service_name) = registry_ports.get(&port) {
+                format!("used by {service_name}")
+            } else if !seen.insert(port) {
+                "requested more than once".to_string()
+            } else if !is_port_free(SocketAddr::new(address, port), protocol) {
+                "in use".to_string()
+            } else {
+                continue;
+            };
+            checks.push(PreflightCheck {
+                available,
+                passed: false,
+                required: "free".to_string(),
+                resource: format!("{name} {port}"),
+            });
+        }
+    }
+
+    PreflightReport {
+        checks,
+        count: options.count,
+        max_safe_count,
+    }
+}
+
+/// Probe the host and check whether it can take on the nodes described by the options.
+pub fn run(options: &PreflightOptions, node_registry: &NodeRegistry) -> PreflightReport {
+    let host = HostResources::probe(&options.data_dir_path, &options.log_dir_path, node_registry);
+    evaluate(options, &host, node_registry, &is_port_free)
+}
+
+pub fn is_port_free(address: SocketAddr, protocol: Protocol) -> bool {
+    match protocol {
+        Protocol::Tcp => TcpListener::bind(address).is_ok(),
+        Protocol::Udp => UdpSocket::bind(address).is_ok(),
+    }
+}
+
+/// The directories for new services usually don't exist yet, so the disk they'll be on is
+/// determined by the nearest directory above them that does.
+fn existing_ancestor(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|ancestor| ancestor.exists())
+        .and_then(|ancestor| ancestor.canonicalize().ok())
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let entries = match std::fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => dir_size(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+#[cfg(unix)]
+fn get_file_descriptor_limit() -> Option<u64> {
+    use nix::sys::resource::{getrlimit, Resource};
+    getrlimit(Resource::RLIMIT_NOFILE)
+        .ok()
+        .map(|(soft, _)| soft)
+}
+
+#[cfg(windows)]
+fn get_file_descriptor_limit() -> Option<u64> {
+    None
+}
+
+/// On Linux, the allocated and maximum number of file handles are in `/proc/sys/fs/file-nr`.
+#[cfg(target_os = "linux")]
+fn get_system_file_descriptors() -> Option<u64> {
+    let contents = std::fs::read_to_string("/proc/sys/fs/file-nr").ok()?;
+    let fields = contents
+        .split_whitespace()
+        .map(|field| field.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    match fields.as_slice() {
+        [allocated, _, max] => Some(max.saturating_sub(*allocated)),
+        _ => None,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn get_system_file_descriptors() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_service_management::{control::ServiceBackend, NodeServiceData};
+
+    fn create_profile() -> NodeResourceProfile {
+        NodeResourceProfile {
+            file_descriptors: 10,
+            log_space: 100,
+            memory: 1000,
+            storage: 900,
+        }
+    }
+
+    fn create_options(count: u16) -> PreflightOptions {
+        PreflightOptions {
+            count,
+            data_dir_path: PathBuf::from("/var/safenode-manager/services"),
+            log_dir_path: PathBuf::from("/var/log/safenode"),
+            metrics_port: None,
+            node_port: None,
+            profile: create_profile(),
+            rpc_address: None,
+            rpc_port: None,
+        }
+    }
+
+    fn create_host(disk_available: u64, memory_available: u64) -> HostResources {
+        let disk = DiskSpace {
+            available: disk_available,
+            mount_point: PathBuf::from("/"),
+        };
+        HostResources {
+            available_memory: memory_available,
+            data_disk: Some(disk.clone()),
+            existing_nodes: Vec::new(),
+            file_descriptor_limit: Some(1024),
+            log_disk: Some(disk),
+            system_file_descriptors: Some(100_000),
+        }
+    }
+
+    fn create_node_registry(nodes: Vec<(u16, ServiceStatus)>) -> NodeRegistry {
+        NodeRegistry {
+            bootstrap_peers: Vec::new(),
+            daemon: None,
+            environment_variables: None,
+            faucet: None,
+            nodes: nodes
+                .into_iter()
+                .map(|(number, status)| NodeServiceData {
+                    connected_peers: None,
+                    data_dir_path: PathBuf::from(format!(
+                        "/var/safenode-manager/services/safenode{number}"
+                    )),
+                    fleet_group: None,
+                    genesis: false,
+                    home_network: false,
+                    last_restart: None,
+                    listen_addr: None,
+                    local: false,
+                    log_dir_path: PathBuf::from(format!("/var/log/safenode/safenode{number}")),
+                    metrics_port: Some(13000 + number),
+                    node_port: Some(12000 + number),
+                    number,
+                    peer_id: None,
+                    pid: None,
+                    restart_count: 0,
+                    reward_balance: None,
+                    rpc_socket_addr: SocketAddr::new(
+                        IpAddr::V4(Ipv4Addr::LOCALHOST),
+                        14000 + number,
+                    ),
+                    safenode_path: PathBuf::from(format!(
+                        "/var/safenode-manager/services/safenode{number}/safenode"
+                    )),
+                    service_name: format!("safenode{number}"),
+                    status,
+                    user: Some("safe".to_string()),
+                    user_mode: false,
+                    version: "0.1.0".to_string(),
+                })
+                .collect(),
+            rolling_upgrade: None,
+            save_path: PathBuf::from("/var/safenode-manager/node_registry.json"),
+            service_backend: ServiceBackend::Native,
+        }
+    }
+
+    #[test]
+    fn evaluate_should_pass_when_the_host_has_the_resources_for_the_nodes() {
+        let report = evaluate(
+            &create_options(3),
+            &create_host(5000, 5000),
+            &create_node_registry(Vec::new()),
+            &|_, _| true,
+        );
+
+        assert!(report.passed());
+        assert_eq!(report.max_safe_count, Some(5));
+        assert!(report.into_result().is_ok());
+    }
+
+    #[test]
+    fn evaluate_should_reserve_the_disk_space_existing_nodes_have_yet_to_use() {
+        let mut host = create_host(3400, 10_000);
+        host.existing_nodes = vec![
+            ExistingNode {
+                data_disk: Some(PathBuf::from("/")),
+                data_used: 400,
+                status: ServiceStatus::Running,
+            },
+            ExistingNode {
+                data_disk: Some(PathBuf::from("/mnt/other")),
+                data_used: 0,
+                status: ServiceStatus::Running,
+            },
+        ];
+
+        let report = evaluate(
+            &create_options(3),
+            &host,
+            &create_node_registry(Vec::new()),
+            &|_, _| true,
+        );
+
+        assert!(!report.passed());
+        assert_eq!(report.max_safe_count, Some(2));
+        let disk_check = report
+            .checks
+            .iter()
+            .find(|check| check.resource == "Disk space")
+            .unwrap();
+        assert!(!disk_check.passed);
+        assert!(report.into_result().is_err());
+    }
+
+    #[test]
+    fn evaluate_should_check_the_data_and_log_disks_separately() {
+        let mut host = create_host(2000, 10_000);
+        host.log_disk = Some(DiskSpace {
+            available: 150,
+            mount_point: PathBuf::from("/var/log"),
+        });
+
+        let report = evaluate(
+            &create_options(2),
+            &host,
+            &create_node_registry(Vec::new()),
+            &|_, _| true,
+        );
+
+        assert!(!report.passed());
+        assert_eq!(report.max_safe_count, Some(1));
+        let failed = report
+            .checks
+            .iter()
+            .filter(|check| !check.passed)
+            .map(|check| check.resource.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(failed, vec!["Log disk space"]);
+    }
+
+    #[test]
+    fn evaluate_should_reserve_the_memory_of_nodes_that_have_not_been_started() {
+        let mut host = create_host(100_000, 3000);
+        host.existing_nodes = vec![
+            ExistingNode {
+                data_disk: Some(PathBuf::from("/")),
+                data_used: 0,
+                status: ServiceStatus::Added,
+            },
+            ExistingNode {
+                data_disk: Some(PathBuf::from("/")),
+                data_used: 0,
+                status: ServiceStatus::Running,
+            },
+        ];
+
+        let report = evaluate(
+            &create_options(3),
+            &host,
+            &create_node_registry(Vec::new()),
+            &|_, _| true,
+        );
+
+        assert!(!report.passed());
+        assert_eq!(report.max_safe_count, Some(2));
+        let memory_check = report
+            .checks
+            .iter()
+            .find(|check| check.resource == "Memory")
+            .unwrap();
+        assert!(!memory_check.passed);
+    }
+
+    #[test]
+    fn evaluate_should_fail_for_ports_that_are_taken() {
+        let mut options = create_options(3);
+        options.node_port = Some(PortRange::Range(12001, 12003));
+        options.rpc_port = Some(PortRange::Range(15001, 15003));
+        let node_registry = create_node_registry(vec![
+            (1, ServiceStatus::Running),
+            (2, ServiceStatus::Removed),
+        ]);
+
+        let report = evaluate(
+            &options,
+            &create_host(100_000, 100_000),
+            &node_registry,
+            &|address, protocol| !(address.port() == 15002 && protocol == Protocol::Tcp),
+        );
+
+        assert!(!report.passed());
+        let failed = report
+            .checks
+            .iter()
+            .filter(|check| !check.passed)
+            .map(|check| (check.resource.as_str(), check.available.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            failed,
+            vec![
+                ("Node port 12001", "used by safenode1"),
+                ("RPC port 15002", "in use"),
+            ]
+        );
+    }
+
+    #[test]
+    fn service_count_should_use_the_node_port_range_without_a_count() {
+        let range = PortRange::Range(12001, 12005);
+        assert_eq!(PreflightOptions::service_count(None, Some(&range)), 5);
+        assert_eq!(PreflightOptions::service_count(Some(2), Some(&range)), 2);
+        assert_eq!(
+            PreflightOptions::service_count(None, Some(&PortRange::Single(12001))),
+            1
+        );
+        assert_eq!(PreflightOptions::service_count(None, None), 1);
+    }
+
+    #[test]
+    fn evaluate_should_not_limit_the_count_by_resources_nodes_do_not_need() {
+        let mut options = create_options(1);
+        options.profile.memory = 0;
+        options.profile.storage = 0;
+
+        let report = evaluate(
+            &options,
+            &create_host(5000, 5000),
+            &create_node_registry(Vec::new()),
+            &|_, _| true,
+        );
+
+        assert!(report.passed());
+        assert_eq!(report.max_safe_count, Some(50));
+    }
+}
@@ -0,0 +1,650 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Upgrades services a batch at a time. Before the next batch is upgraded, every node in the
+//! current one has to become healthy again; if they don't, the batch is rolled back to the binary
+//! it was running before and the upgrade stops.
+
+use crate::{ServiceManager, VerbosityLevel};
+use color_eyre::{eyre::eyre, Result};
+use colored::Colorize;
+use semver::Version;
+use sn_service_management::{
+    control::ServiceControl, rpc::RpcActions, NodeRegistry, NodeService, RollingUpgrade,
+    UpgradeBatch, UpgradeBatchNode, UpgradeBatchStatus, UpgradeOptions, UpgradeResult,
+};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// The conditions a node has to meet after being upgraded.
+#[derive(Clone, Debug)]
+pub struct HealthGate {
+    pub min_connected_peers: usize,
+    pub min_records: usize,
+    /// How long each batch has to become healthy.
+    pub timeout: Duration,
+    pub poll_interval: Duration,
+}
+
+impl HealthGate {
+    const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+    pub fn new(min_connected_peers: usize, min_records: usize, timeout: Duration) -> Self {
+        Self {
+            min_connected_peers,
+            min_records,
+            timeout,
+            poll_interval: Self::DEFAULT_POLL_INTERVAL,
+        }
+    }
+
+    /// Checks the node once, returning the reason it isn't healthy, if it isn't.
+    pub async fn check(
+        &self,
+        rpc_client: &dyn RpcActions,
+        target_version: &Version,
+    ) -> std::result::Result<(), String> {
+        let node_info = rpc_client
+            .node_info()
+            .await
+            .map_err(|err| format!("node info unavailable: {err}"))?;
+        if node_info.version != target_version.to_string() {
+            return Err(format!(
+                "the node reports version {} rather than {target_version}",
+                node_info.version
+            ));
+        }
+
+        let network_info = rpc_client
+            .network_info()
+            .await
+            .map_err(|err| format!("network info unavailable: {err}"))?;
+        if network_info.connected_peers.len() < self.min_connected_peers {
+            return Err(format!(
+                "{} connected peers, but at least {} are required",
+                network_info.connected_peers.len(),
+                self.min_connected_peers
+            ));
+        }
+
+        if self.min_records > 0 {
+            let records = rpc_client
+                .record_addresses()
+                .await
+                .map_err(|err| format!("record addresses unavailable: {err}"))?;
+            if records.len() < self.min_records {
+                return Err(format!(
+                    "{} records held, but at least {} are required",
+                    records.len(),
+                    self.min_records
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the node repeatedly until it's healthy or the deadline passes.
+    async fn wait_until_healthy(
+        &self,
+        rpc_client: &dyn RpcActions,
+        target_version: &Version,
+        deadline: Instant,
+    ) -> std::result::Result<(), String> {
+        loop {
+            let result = self.check(rpc_client, target_version).await;
+            if result.is_ok() || Instant::now() >= deadline {
+                return result;
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RollingUpgradeOptions {
+    /// The number of services upgraded together.
+    pub batch_size: usize,
+    pub health_gate: HealthGate,
+}
+
+/// Upgrades the services at `service_indices`, a batch at a time.
+///
+/// The registry is saved as each batch progresses, and the record of the upgrade is kept in its
+/// `rolling_upgrade` field. An error is returned if a batch fails its health checks, whether or
+/// not it could be rolled back.
+pub async fn rolling_upgrade(
+    node_registry: &mut NodeRegistry,
+    service_indices: &[usize],
+    rolling_options: &RollingUpgradeOptions,
+    options: UpgradeOptions,
+    create_rpc_client: &dyn Fn(SocketAddr) -> Box<dyn RpcActions + Send>,
+    create_service_control: &dyn Fn() -> Box<dyn ServiceControl + Send>,
+    verbosity: VerbosityLevel,
+) -> Result<()> {
+    let batch_size = rolling_options.batch_size;
+    if batch_size == 0 {
+        return Err(eyre!("The batch size must be at least 1"));
+    }
+
+    node_registry.rolling_upgrade = Some(RollingUpgrade {
+        target_version: options.target_version.to_string(),
+        batch_size,
+        started_at: now(),
+        finished_at: None,
+        batches: Vec::new(),
+    });
+    node_registry.save()?;
+
+    let batch_count = service_indices.len().div_ceil(batch_size);
+    for (batch_number, batch) in service_indices.chunks(batch_size).enumerate() {
+        if verbosity != VerbosityLevel::Minimal {
+            println!("Upgrading batch {}/{batch_count}...", batch_number + 1);
+        }
+
+        let result = upgrade_batch(
+            node_registry,
+            batch,
+            &rolling_options.health_gate,
+            &options,
+            create_rpc_client,
+            create_service_control,
+            verbosity,
+        )
+        .await;
+        if let Some(record) = node_registry
+            .rolling_upgrade
+            .as_mut()
+            .and_then(|rolling_upgrade| rolling_upgrade.batches.last_mut())
+        {
+            record.status = result.clone();
+        }
+
+        match result {
+            // A batch is always settled one way or the other by the time it's returned.
+            UpgradeBatchStatus::Healthy | UpgradeBatchStatus::InProgress => {
+                node_registry.save()?;
+                if verbosity != VerbosityLevel::Minimal {
+                    println!(
+                        "{} Batch {}/{batch_count} is healthy",
+                        "✓".green(),
+                        batch_number + 1
+                    );
+                }
+            }
+            UpgradeBatchStatus::RolledBack(reason) => {
+                finish(node_registry)?;
+                return Err(eyre!(
+                    "Batch {}/{batch_count} failed its health checks and was rolled back: {reason}",
+                    batch_number + 1
+                ));
+            }
+            UpgradeBatchStatus::RollbackFailed(reason, rollback_err) => {
+                finish(node_registry)?;
+                return Err(eyre!(
+                    "Batch {}/{batch_count} failed its health checks ({reason}) and could not be \
+                    rolled back: {rollback_err}",
+                    batch_number + 1
+                ));
+            }
+        }
+    }
+
+    finish(node_registry)
+}
+
+async fn upgrade_batch(
+    node_registry: &mut NodeRegistry,
+    batch: &[usize],
+    health_gate: &HealthGate,
+    options: &UpgradeOptions,
+    create_rpc_client: &dyn Fn(SocketAddr) -> Box<dyn RpcActions + Send>,
+    create_service_control: &dyn Fn() -> Box<dyn ServiceControl + Send>,
+    verbosity: VerbosityLevel,
+) -> UpgradeBatchStatus {
+    if let Some(rolling_upgrade) = node_registry.rolling_upgrade.as_mut() {
+        rolling_upgrade.batches.push(UpgradeBatch {
+            nodes: Vec::new(),
+            status: UpgradeBatchStatus::InProgress,
+        });
+    }
+
+    // Keep the current binaries so the batch can be rolled back.
+    let mut previous_nodes = Vec::new();
+    for &index in batch {
+        let node = &node_registry.nodes[index];
+        let previous_bin_path = get_previous_bin_path(&node.safenode_path);
+        if let Err(err) = std::fs::copy(&node.safenode_path, &previous_bin_path) {
+            // Nothing has been changed yet, so there's nothing to roll back.
+            return UpgradeBatchStatus::RolledBack(format!(
+                "could not keep the binary of {}: {err}",
+                node.service_name
+            ));
+        }
+        previous_nodes.push(UpgradeBatchNode {
+            service_name: node.service_name.clone(),
+            previous_version: node.version.clone(),
+            previous_bin_path,
+        });
+    }
+    if let Some(record) = node_registry
+        .rolling_upgrade
+        .as_mut()
+        .and_then(|rolling_upgrade| rolling_upgrade.batches.last_mut())
+    {
+        record.nodes = previous_nodes.clone();
+    }
+    if let Err(err) = node_registry.save() {
+        return UpgradeBatchStatus::RolledBack(format!("could not save the node registry: {err}"));
+    }
+
+    let mut failure = None;
+    let mut upgraded = Vec::new();
+    for &index in batch {
+        let node = &mut node_registry.nodes[index];
+        let service = NodeService::new(node, create_rpc_client(node.rpc_socket_addr));
+        let mut service_manager = ServiceManager::new(service, create_service_control(), verbosity);
+        match service_manager.upgrade(options.clone()).await {
+            Ok(UpgradeResult::NotRequired) => {}
+            Ok(UpgradeResult::Upgraded(_, _)) | Ok(UpgradeResult::Forced(_, _)) => {
+                upgraded.push(index);
+            }
+            Ok(UpgradeResult::UpgradedButNotStarted(_, _, err)) | Ok(UpgradeResult::Error(err)) => {
+                upgraded.push(index);
+                failure = Some(format!("{} did not start: {err}", node.service_name));
+                break;
+            }
+            Err(err) => {
+                // The service could have been stopped or had its binary replaced before the error.
+                upgraded.push(index);
+                failure = Some(format!(
+                    "{} could not be upgraded: {err}",
+                    node.service_name
+                ));
+                break;
+            }
+        }
+    }
+
+    if failure.is_none() {
+        let deadline = Instant::now() + health_gate.timeout;
+        for &index in &upgraded {
+            let node = &node_registry.nodes[index];
+            let rpc_client = create_rpc_client(node.rpc_socket_addr);
+            if let Err(reason) = health_gate
+                .wait_until_healthy(rpc_client.as_ref(), &options.target_version, deadline)
+                .await
+            {
+                failure = Some(format!("{}: {reason}", node.service_name));
+                break;
+            }
+        }
+    }
+
+    let reason = match failure {
+        Some(reason) => reason,
+        None => return UpgradeBatchStatus::Healthy,
+    };
+    if verbosity != VerbosityLevel::Minimal {
+        println!("{} {reason}", "✕".red());
+        println!("Rolling the batch back...");
+    }
+
+    let mut rollback_errors = Vec::new();
+    for &index in &upgraded {
+        let node = &mut node_registry.nodes[index];
+        let previous = match previous_nodes
+            .iter()
+            .find(|previous| previous.service_name == node.service_name)
+        {
+            Some(previous) => previous,
+            None => continue,
+        };
+        let previous_version = match Version::parse(&previous.previous_version) {
+            Ok(version) => version,
+            Err(err) => {
+                rollback_errors.push(format!("{}: {err}", node.service_name));
+                continue;
+            }
+        };
+        let rollback_options = UpgradeOptions {
+            force: true,
+            start_service: true,
+            target_bin_path: previous.previous_bin_path.clone(),
+            target_version: previous_version,
+            ..options.clone()
+        };
+
+        let service = NodeService::new(node, create_rpc_client(node.rpc_socket_addr));
+        let mut service_manager = ServiceManager::new(service, create_service_control(), verbosity);
+        match service_manager.upgrade(rollback_options).await {
+            Ok(UpgradeResult::UpgradedButNotStarted(_, _, err)) | Ok(UpgradeResult::Error(err)) => {
+                rollback_errors.push(format!("{}: {err}", node.service_name));
+            }
+            Ok(_) => {}
+            Err(err) => rollback_errors.push(format!("{}: {err}", node.service_name)),
+        }
+    }
+
+    if rollback_errors.is_empty() {
+        UpgradeBatchStatus::RolledBack(reason)
+    } else {
+        UpgradeBatchStatus::RollbackFailed(reason, rollback_errors.join("; "))
+    }
+}
+
+fn finish(node_registry: &mut NodeRegistry) -> Result<()> {
+    if let Some(rolling_upgrade) = node_registry.rolling_upgrade.as_mut() {
+        rolling_upgrade.finished_at = Some(now());
+    }
+    node_registry.save()?;
+    Ok(())
+}
+
+/// The path the binary a service ran before an upgrade is kept at, alongside the current one.
+pub fn get_previous_bin_path(bin_path: &Path) -> PathBuf {
+    let mut file_name = bin_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".previous");
+    bin_path.with_file_name(file_name)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_fs::prelude::*;
+    use async_trait::async_trait;
+    use libp2p_identity::PeerId;
+    use mockall::mock;
+    use service_manager::ServiceInstallCtx;
+    use sn_service_management::{
+        control::ServiceBackend,
+        error::Result as ServiceControlResult,
+        node::NodeServiceData,
+        rpc::{NetworkInfo, NodeInfo, RecordAddress},
+        ServiceStatus,
+    };
+    use sn_transfers::NanoTokens;
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        str::FromStr,
+    };
+
+    mock! {
+        pub RpcClient {}
+        #[async_trait]
+        impl RpcActions for RpcClient {
+            async fn node_info(&self) -> ServiceControlResult<NodeInfo>;
+            async fn network_info(&self) -> ServiceControlResult<NetworkInfo>;
+            async fn record_addresses(&self) -> ServiceControlResult<Vec<RecordAddress>>;
+            async fn node_restart(&self, delay_millis: u64, retain_peer_id: bool) -> ServiceControlResult<()>;
+            async fn node_stop(&self, delay_millis: u64) -> ServiceControlResult<()>;
+            async fn node_update(&self, delay_millis: u64) -> ServiceControlResult<()>;
+            async fn update_log_level(&self, log_levels: String) -> ServiceControlResult<()>;
+        }
+    }
+
+    mock! {
+        pub ServiceControl {}
+        impl ServiceControl for ServiceControl {
+            fn create_service_user(&self, username: &str) -> ServiceControlResult<()>;
+            fn get_available_port(&self) -> ServiceControlResult<u16>;
+            fn install(&self, install_ctx: ServiceInstallCtx, user_mode: bool) -> ServiceControlResult<()>;
+            fn get_process_pid(&self, bin_path: &Path) -> ServiceControlResult<u32>;
+            fn is_service_process_running(&self, pid: u32) -> bool;
+            fn start(&self, service_name: &str, user_mode: bool) -> ServiceControlResult<()>;
+            fn stop(&self, service_name: &str, user_mode: bool) -> ServiceControlResult<()>;
+            fn uninstall(&self, service_name: &str, user_mode: bool) -> ServiceControlResult<()>;
+            fn wait(&self, delay: u64);
+        }
+    }
+
+    const PEER_ID: &str = "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR";
+
+    /// An RPC client for a node that reports `version` and is otherwise healthy.
+    fn create_rpc_client(version: &'static str) -> Box<dyn RpcActions + Send> {
+        let mut mock_rpc_client = MockRpcClient::new();
+        mock_rpc_client.expect_node_info().returning(move || {
+            Ok(NodeInfo {
+                pid: 2000,
+                peer_id: PeerId::from_str(PEER_ID)?,
+                data_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+                log_path: PathBuf::from("/var/log/safenode/safenode1"),
+                version: version.to_string(),
+                uptime: Duration::from_secs(1),
+            })
+        });
+        mock_rpc_client.expect_network_info().returning(|| {
+            Ok(NetworkInfo {
+                connected_peers: vec![PeerId::random()],
+                listeners: Vec::new(),
+            })
+        });
+        mock_rpc_client.expect_record_addresses().returning(|| {
+            Ok(vec![RecordAddress {
+                key: libp2p::kad::RecordKey::new(&[1u8]),
+            }])
+        });
+        Box::new(mock_rpc_client)
+    }
+
+    fn create_service_control() -> Box<dyn ServiceControl + Send> {
+        let mut mock_service_control = MockServiceControl::new();
+        mock_service_control
+            .expect_is_service_process_running()
+            .returning(|_| true);
+        mock_service_control.expect_stop().returning(|_, _| Ok(()));
+        mock_service_control
+            .expect_uninstall()
+            .returning(|_, _| Ok(()));
+        mock_service_control
+            .expect_install()
+            .returning(|_, _| Ok(()));
+        mock_service_control.expect_start().returning(|_, _| Ok(()));
+        mock_service_control.expect_wait().returning(|_| ());
+        mock_service_control
+            .expect_get_process_pid()
+            .returning(|_| Ok(2000));
+        Box::new(mock_service_control)
+    }
+
+    fn create_node_registry(tmp_data_dir: &assert_fs::TempDir) -> Result<NodeRegistry> {
+        let mut nodes = Vec::new();
+        for number in 1..=2u16 {
+            let node_bin = tmp_data_dir.child(format!("safenode{number}/safenode"));
+            node_bin.write_binary(b"safenode 0.1.0")?;
+            nodes.push(NodeServiceData {
+                connected_peers: None,
+                data_dir_path: PathBuf::from(format!(
+                    "/var/safenode-manager/services/safenode{number}"
+                )),
+                fleet_group: None,
+                genesis: false,
+                home_network: false,
+                last_restart: None,
+                listen_addr: None,
+                local: false,
+                log_dir_path: PathBuf::from(format!("/var/log/safenode/safenode{number}")),
+                metrics_port: None,
+                node_port: None,
+                number,
+                peer_id: Some(PeerId::from_str(PEER_ID)?),
+                pid: Some(1000),
+                restart_count: 0,
+                reward_balance: Some(NanoTokens::zero()),
+                rpc_socket_addr: SocketAddr::new(
+                    IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+                    8080 + number,
+                ),
+                safenode_path: node_bin.to_path_buf(),
+                service_name: format!("safenode{number}"),
+                status: ServiceStatus::Running,
+                user: Some("safe".to_string()),
+                user_mode: false,
+                version: "0.1.0".to_string(),
+            });
+        }
+        Ok(NodeRegistry {
+            bootstrap_peers: Vec::new(),
+            daemon: None,
+            environment_variables: None,
+            faucet: None,
+            nodes,
+            rolling_upgrade: None,
+            save_path: tmp_data_dir.child("node_registry.json").to_path_buf(),
+            service_backend: ServiceBackend::Native,
+        })
+    }
+
+    fn create_options(
+        tmp_data_dir: &assert_fs::TempDir,
+    ) -> Result<(RollingUpgradeOptions, UpgradeOptions)> {
+        let target_node_bin = tmp_data_dir.child("safenode");
+        target_node_bin.write_binary(b"safenode 0.2.0")?;
+        let rolling_options = RollingUpgradeOptions {
+            batch_size: 1,
+            health_gate: HealthGate {
+                min_connected_peers: 1,
+                min_records: 1,
+                timeout: Duration::from_millis(50),
+                poll_interval: Duration::from_millis(10),
+            },
+        };
+        let options = UpgradeOptions {
+            bootstrap_peers: Vec::new(),
+            env_variables: None,
+            force: false,
+            start_service: true,
+            target_bin_path: target_node_bin.to_path_buf(),
+            target_version: Version::parse("0.2.0")?,
+        };
+        Ok((rolling_options, options))
+    }
+
+    #[tokio::test]
+    async fn rolling_upgrade_should_upgrade_each_batch_that_becomes_healthy() -> Result<()> {
+        let tmp_data_dir = assert_fs::TempDir::new()?;
+        let mut node_registry = create_node_registry(&tmp_data_dir)?;
+        let (rolling_options, options) = create_options(&tmp_data_dir)?;
+
+        rolling_upgrade(
+            &mut node_registry,
+            &[0, 1],
+            &rolling_options,
+            options,
+            &|_| create_rpc_client("0.2.0"),
+            &create_service_control,
+            VerbosityLevel::Normal,
+        )
+        .await?;
+
+        for node in &node_registry.nodes {
+            assert_eq!(node.version, "0.2.0");
+            assert_eq!(std::fs::read(&node.safenode_path)?, b"safenode 0.2.0");
+            assert_eq!(
+                std::fs::read(get_previous_bin_path(&node.safenode_path))?,
+                b"safenode 0.1.0"
+            );
+        }
+
+        let record = node_registry.rolling_upgrade.unwrap();
+        assert_eq!(record.target_version, "0.2.0");
+        assert!(record.finished_at.is_some());
+        assert_eq!(record.batches.len(), 2);
+        for (batch, service_name) in record.batches.iter().zip(["safenode1", "safenode2"]) {
+            assert_eq!(batch.status, UpgradeBatchStatus::Healthy);
+            assert_eq!(batch.nodes.len(), 1);
+            assert_eq!(batch.nodes[0].service_name, service_name);
+            assert_eq!(batch.nodes[0].previous_version, "0.1.0");
+        }
+
+        let saved_registry = NodeRegistry::load(&node_registry.save_path)?;
+        assert_eq!(saved_registry.rolling_upgrade, Some(record));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rolling_upgrade_should_roll_back_a_batch_that_does_not_become_healthy() -> Result<()> {
+        let tmp_data_dir = assert_fs::TempDir::new()?;
+        let mut node_registry = create_node_registry(&tmp_data_dir)?;
+        let (rolling_options, options) = create_options(&tmp_data_dir)?;
+
+        // The node keeps reporting the old version, as though the new binary didn't start properly.
+        let result = rolling_upgrade(
+            &mut node_registry,
+            &[0, 1],
+            &rolling_options,
+            options,
+            &|_| create_rpc_client("0.1.0"),
+            &create_service_control,
+            VerbosityLevel::Normal,
+        )
+        .await;
+
+        match result {
+            Ok(()) => panic!("This test should result in an error"),
+            Err(e) => assert!(e
+                .to_string()
+                .starts_with("Batch 1/2 failed its health checks and was rolled back")),
+        }
+
+        let rolled_back_node = &node_registry.nodes[0];
+        assert_eq!(rolled_back_node.version, "0.1.0");
+        assert_eq!(
+            std::fs::read(&rolled_back_node.safenode_path)?,
+            b"safenode 0.1.0"
+        );
+
+        // The upgrade stops at the failed batch, so the next one is left alone.
+        let untouched_node = &node_registry.nodes[1];
+        assert_eq!(untouched_node.version, "0.1.0");
+        assert!(!get_previous_bin_path(&untouched_node.safenode_path).exists());
+
+        let record = node_registry.rolling_upgrade.unwrap();
+        assert!(record.finished_at.is_some());
+        assert_eq!(record.batches.len(), 1);
+        match &record.batches[0].status {
+            UpgradeBatchStatus::RolledBack(reason) => {
+                assert_eq!(
+                    reason,
+                    "safenode1: the node reports version 0.1.0 rather than 0.2.0"
+                );
+            }
+            status => panic!("Expected the batch to be rolled back but it was {status:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn health_gate_should_fail_a_node_with_too_few_connected_peers() -> Result<()> {
+        let health_gate = HealthGate::new(5, 0, Duration::from_secs(1));
+        let rpc_client = create_rpc_client("0.2.0");
+
+        let result = health_gate
+            .check(rpc_client.as_ref(), &Version::parse("0.2.0")?)
+            .await;
+
+        assert_eq!(
+            result,
+            Err("1 connected peers, but at least 5 are required".to_string())
+        );
+
+        Ok(())
+    }
+}
@@ -16,10 +16,10 @@ use color_eyre::{
 };
 use libp2p::PeerId;
 use sn_service_management::{
-    control::{ServiceControl, ServiceController},
-    rpc::RpcClient,
-    NodeRegistry, NodeService, NodeServiceData, ServiceStatus,
+    rpc::RpcClient, NodeRegistry, NodeService, NodeServiceData, ServiceStatus,
 };
+use std::net::SocketAddr;
+use tokio::sync::mpsc;
 
 pub async fn restart_node_service(
     node_registry: &mut NodeRegistry,
@@ -38,12 +38,12 @@ pub async fn restart_node_service(
     let service = NodeService::new(current_node_mut, Box::new(rpc_client));
     let mut service_manager = ServiceManager::new(
         service,
-        Box::new(ServiceController {}),
+        node_registry.service_backend.controller(),
         VerbosityLevel::Normal,
     );
     service_manager.stop().await?;
 
-    let service_control = ServiceController {};
+    let service_control = node_registry.service_backend.controller();
     if retain_peer_id {
         // reuse the same port and root dir to retain peer id.
         service_control
@@ -181,14 +181,19 @@ pub async fn restart_node_service(
         let mut node = NodeServiceData {
             connected_peers: None,
             data_dir_path,
+            fleet_group: None,
             genesis: current_node_clone.genesis,
             home_network: current_node_clone.home_network,
+            last_restart: None,
             listen_addr: None,
             local: current_node_clone.local,
             log_dir_path,
+            metrics_port: None,
+            node_port: None,
             number: new_node_number as u16,
             peer_id: None,
             pid: None,
+            restart_count: 0,
             reward_balance: current_node_clone.reward_balance,
             rpc_socket_addr: current_node_clone.rpc_socket_addr,
             safenode_path,
@@ -203,7 +208,7 @@ pub async fn restart_node_service(
         let service = NodeService::new(&mut node, Box::new(rpc_client));
         let mut service_manager = ServiceManager::new(
             service,
-            Box::new(ServiceController {}),
+            node_registry.service_backend.controller(),
             VerbosityLevel::Normal,
         );
         service_manager.start().await?;
@@ -214,3 +219,54 @@ pub async fn restart_node_service(
 
     Ok(())
 }
+
+/// Forwards the events emitted by each of the given nodes to `sender`, as pairs of the service name
+/// and the event rendered as JSON.
+///
+/// All the nodes are subscribed to before anything is forwarded, so an error is returned if any of
+/// them can't be reached. Forwarding ends when every event stream has closed or the receiving side
+/// is dropped.
+pub async fn stream_node_events(
+    nodes: Vec<(String, SocketAddr)>,
+    sender: mpsc::Sender<(String, String)>,
+) -> Result<()> {
+    let mut streams = Vec::new();
+    for (service_name, rpc_socket_addr) in nodes {
+        let stream = RpcClient::from_socket_addr(rpc_socket_addr)
+            .node_events()
+            .await
+            .map_err(|err| eyre!("Could not subscribe to the events of {service_name}: {err}"))?;
+        streams.push((service_name, stream));
+    }
+
+    let mut tasks = tokio::task::JoinSet::new();
+    for (service_name, mut stream) in streams {
+        let sender = sender.clone();
+        tasks.spawn(async move {
+            loop {
+                let event = tokio::select! {
+                    _ = sender.closed() => break,
+                    message = stream.message() => match message {
+                        Ok(Some(event)) => node_event_to_json(&event.event),
+                        _ => break,
+                    },
+                };
+                if sender.send((service_name.clone(), event)).await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(sender);
+    while tasks.join_next().await.is_some() {}
+
+    Ok(())
+}
+
+/// The node manager doesn't depend on `safenode`, so rather than decoding the `NodeEvent` type, the
+/// MessagePack it is serialized as is rendered as a generic JSON value.
+fn node_event_to_json(bytes: &[u8]) -> String {
+    rmp_serde::from_slice::<serde_json::Value>(bytes)
+        .map(|value| value.to_string())
+        .unwrap_or_else(|_| "\"<undecodable event>\"".to_string())
+}
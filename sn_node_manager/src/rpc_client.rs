@@ -4,14 +4,51 @@ use libp2p_identity::PeerId;
 use sn_service_management::safenode_manager_proto::safe_node_manager_client::SafeNodeManagerClient;
 use sn_service_management::safenode_manager_proto::NodeServiceRestartRequest;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::time::Duration;
-use tonic::transport::Channel;
-use tonic::Request;
+use tonic::codegen::InterceptedService;
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::Interceptor;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Identity};
+use tonic::{Request, Status};
 
-struct DaemonRpcClient {
-    addr: SocketAddr,
-    rpc: SafeNodeManagerClient<Channel>,
+/// The metadata key the daemon's access token is sent under, as `Bearer <token>`.
+pub const DAEMON_AUTH_METADATA_KEY: &str = "authorization";
+
+/// A client for the daemon's RPC service.
+pub type DaemonClient = SafeNodeManagerClient<InterceptedService<Channel, TokenInterceptor>>;
+
+/// How to authenticate with a daemon.
+#[derive(Clone, Debug, Default)]
+pub struct DaemonConnectionOptions {
+    /// The access token the daemon was configured with.
+    pub token: Option<String>,
+    /// A PEM encoded CA certificate for verifying the daemon's certificate.
+    ///
+    /// Setting this enables TLS.
+    pub ca_cert_path: Option<PathBuf>,
+    /// A PEM encoded certificate to present to the daemon for mutual TLS.
+    pub client_cert_path: Option<PathBuf>,
+    /// The PEM encoded private key for the client certificate.
+    pub client_key_path: Option<PathBuf>,
+}
+
+/// Attaches the access token, if there is one, to every request sent to the daemon.
+#[derive(Clone)]
+pub struct TokenInterceptor {
+    token: Option<MetadataValue<Ascii>>,
+}
+
+impl Interceptor for TokenInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(token) = &self.token {
+            request
+                .metadata_mut()
+                .insert(DAEMON_AUTH_METADATA_KEY, token.clone());
+        }
+        Ok(request)
+    }
 }
 
 pub async fn restart_node(
@@ -22,10 +59,13 @@ pub async fn restart_node(
     for peer_id in peer_ids {
         let str_bytes = PeerId::from_str(&peer_id)?.to_bytes();
 
-        let mut daemon_client = get_rpc_client(rpc_server_address).await?;
+        let mut daemon_client = connect_to_daemon(
+            &rpc_server_address.to_string(),
+            &DaemonConnectionOptions::default(),
+        )
+        .await?;
 
         let _response = daemon_client
-            .rpc
             .restart_node_service(Request::new(NodeServiceRestartRequest {
                 peer_id: str_bytes,
                 delay_millis: 0,
@@ -34,30 +74,64 @@ pub async fn restart_node(
             .await
             .map_err(|err| {
                 eyre!(
-                    "Failed to restart node service with {peer_id:?} at {:?} with err: {err:?}",
-                    daemon_client.addr
+                    "Failed to restart node service with {peer_id:?} at {rpc_server_address:?} with err: {err:?}"
                 )
             })?;
     }
     Ok(())
 }
 
-async fn get_rpc_client(socket_addr: SocketAddr) -> Result<DaemonRpcClient> {
-    let endpoint = format!("https://{socket_addr}");
+/// Connect to the daemon at `address`, which is in the form `host:port`.
+///
+/// When TLS is used, the host part is the name the daemon's certificate is verified against.
+pub async fn connect_to_daemon(
+    address: &str,
+    options: &DaemonConnectionOptions,
+) -> Result<DaemonClient> {
+    let token = options
+        .token
+        .as_ref()
+        .map(|token| MetadataValue::from_str(&format!("Bearer {token}")))
+        .transpose()
+        .map_err(|_| eyre!("The daemon access token contains invalid characters"))?;
+
+    let mut endpoint = Channel::from_shared(format!("https://{address}"))?;
+    if let Some(ca_cert_path) = &options.ca_cert_path {
+        let host = address
+            .rsplit_once(':')
+            .map(|(host, _)| host)
+            .unwrap_or(address);
+        let mut tls_config = ClientTlsConfig::new()
+            .domain_name(host)
+            .ca_certificate(Certificate::from_pem(std::fs::read(ca_cert_path)?));
+        match (&options.client_cert_path, &options.client_key_path) {
+            (Some(cert_path), Some(key_path)) => {
+                tls_config = tls_config.identity(Identity::from_pem(
+                    std::fs::read(cert_path)?,
+                    std::fs::read(key_path)?,
+                ));
+            }
+            (None, None) => {}
+            _ => bail!("A client certificate and key must be provided together"),
+        }
+        endpoint = endpoint.tls_config(tls_config)?;
+    }
+
     let mut attempts = 0;
     loop {
-        if let Ok(rpc_client) = SafeNodeManagerClient::connect(endpoint.clone()).await {
-            let rpc_client = DaemonRpcClient {
-                addr: socket_addr,
-                rpc: rpc_client,
-            };
-            return Ok(rpc_client);
+        if let Ok(channel) = endpoint.connect().await {
+            return Ok(SafeNodeManagerClient::with_interceptor(
+                channel,
+                TokenInterceptor {
+                    token: token.clone(),
+                },
+            ));
         }
         attempts += 1;
-        println!("Could not connect to rpc {endpoint:?}. Attempts: {attempts:?}/10");
+        println!("Could not connect to rpc {address:?}. Attempts: {attempts:?}/10");
         tokio::time::sleep(Duration::from_secs(1)).await;
         if attempts >= 10 {
-            bail!("Failed to connect to {endpoint:?} even after 10 retries");
+            bail!("Failed to connect to {address:?} even after 10 retries");
         }
     }
 }
@@ -0,0 +1,677 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+//! Watches running node services and restarts the ones that become unhealthy.
+//!
+//! Only services the registry has as running are supervised, so a service that was deliberately
+//! stopped is left alone.
+
+use crate::{ServiceManager, VerbosityLevel};
+use color_eyre::Result;
+use colored::Colorize;
+use sn_service_management::{
+    control::ServiceControl,
+    node::{NodeRestart, NodeServiceData},
+    rpc::RpcActions,
+    NodeRegistry, NodeService, ServiceStatus,
+};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    path::Path,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::debug;
+
+/// When a node is considered unhealthy and how restarts are applied.
+#[derive(Clone, Debug)]
+pub struct SupervisorPolicy {
+    /// The number of peers a node has to be connected to.
+    pub min_connected_peers: usize,
+    /// Restart a node whose record count hasn't changed for this long.
+    ///
+    /// Record activity isn't checked if this isn't set.
+    pub record_activity_timeout: Option<Duration>,
+    /// The number of checks in a row a node has to fail before it's restarted.
+    pub failure_threshold: u32,
+    /// The time to wait after the first restart before a node can be restarted again. It doubles
+    /// with each restart that doesn't make the node healthy, up to `max_backoff`.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Give up on a node after it's been restarted this many times in a row without becoming
+    /// healthy.
+    pub max_restarts: Option<u32>,
+}
+
+/// What the supervisor knows about a node between checks.
+struct NodeState {
+    consecutive_failures: u32,
+    consecutive_restarts: u32,
+    restart_not_before: Option<Instant>,
+    record_count: Option<usize>,
+    last_record_activity: Instant,
+}
+
+impl NodeState {
+    fn new() -> Self {
+        Self {
+            consecutive_failures: 0,
+            consecutive_restarts: 0,
+            restart_not_before: None,
+            record_count: None,
+            last_record_activity: Instant::now(),
+        }
+    }
+}
+
+pub struct Supervisor {
+    policy: SupervisorPolicy,
+    states: HashMap<String, NodeState>,
+}
+
+impl Supervisor {
+    pub fn new(policy: SupervisorPolicy) -> Self {
+        Self {
+            policy,
+            states: HashMap::new(),
+        }
+    }
+
+    /// Checks each of the services at `service_indices` once, restarting any that are due to be.
+    ///
+    /// The changes are saved on top of the registry on disk, see `save_supervised_nodes`.
+    ///
+    /// Returns the number of services that were restarted.
+    pub async fn supervise(
+        &mut self,
+        node_registry: &mut NodeRegistry,
+        service_indices: &[usize],
+        create_rpc_client: &dyn Fn(SocketAddr) -> Box<dyn RpcActions + Send>,
+        create_service_control: &dyn Fn() -> Box<dyn ServiceControl + Send>,
+        verbosity: VerbosityLevel,
+    ) -> Result<usize> {
+        let mut restarted = 0;
+        let mut supervised = Vec::new();
+        for &index in service_indices {
+            let node = &mut node_registry.nodes[index];
+            if node.status != ServiceStatus::Running {
+                // The state is dropped so a service that's started again begins afresh.
+                self.states.remove(&node.service_name);
+                continue;
+            }
+            supervised.push(index);
+
+            let state = self
+                .states
+                .entry(node.service_name.clone())
+                .or_insert_with(NodeState::new);
+            let rpc_client = create_rpc_client(node.rpc_socket_addr);
+            let service_control = create_service_control();
+            let reason = match check_node(
+                node,
+                state,
+                &self.policy,
+                rpc_client.as_ref(),
+                service_control.as_ref(),
+            )
+            .await
+            {
+                Ok(()) => {
+                    if state.consecutive_restarts > 0 && verbosity != VerbosityLevel::Minimal {
+                        println!("{} {} is healthy again", "✓".green(), node.service_name);
+                    }
+                    state.consecutive_failures = 0;
+                    state.consecutive_restarts = 0;
+                    state.restart_not_before = None;
+                    continue;
+                }
+                Err(reason) => reason,
+            };
+
+            state.consecutive_failures += 1;
+            debug!(
+                "{} failed its health check ({}/{}): {reason}",
+                node.service_name, state.consecutive_failures, self.policy.failure_threshold
+            );
+            if state.consecutive_failures < self.policy.failure_threshold {
+                continue;
+            }
+            if let Some(restart_not_before) = state.restart_not_before {
+                if Instant::now() < restart_not_before {
+                    continue;
+                }
+            }
+            if let Some(max_restarts) = self.policy.max_restarts {
+                if state.consecutive_restarts >= max_restarts {
+                    if state.consecutive_restarts == max_restarts {
+                        println!(
+                            "{} {} is still unhealthy after {max_restarts} restarts and will no \
+                            longer be restarted: {reason}",
+                            "✕".red(),
+                            node.service_name
+                        );
+                        // Move past the limit so the message is only printed once.
+                        state.consecutive_restarts += 1;
+                    }
+                    continue;
+                }
+            }
+
+            // The service may have been stopped or removed since the registry was loaded.
+            if !is_running_in_saved_registry(&node_registry.save_path, &node.service_name)? {
+                debug!(
+                    "{} is no longer running in the saved registry, not restarting it",
+                    node.service_name
+                );
+                continue;
+            }
+            if verbosity != VerbosityLevel::Minimal {
+                println!("Restarting {}: {reason}", node.service_name);
+            }
+            let backoff = self
+                .policy
+                .initial_backoff
+                .saturating_mul(2u32.saturating_pow(state.consecutive_restarts))
+                .min(self.policy.max_backoff);
+            state.consecutive_failures = 0;
+            state.consecutive_restarts += 1;
+            state.restart_not_before = Some(Instant::now() + backoff);
+            state.record_count = None;
+            state.last_record_activity = Instant::now();
+
+            node.restart_count += 1;
+            node.last_restart = Some(NodeRestart {
+                reason: reason.clone(),
+                time: now(),
+            });
+            if let Err(err) = restart_node(node, rpc_client, service_control, verbosity).await {
+                println!(
+                    "{} Failed to restart {}: {err}",
+                    "✕".red(),
+                    node.service_name
+                );
+            }
+            save_supervised_nodes(node_registry, &[index])?;
+            restarted += 1;
+        }
+        save_supervised_nodes(node_registry, &supervised)?;
+        Ok(restarted)
+    }
+}
+
+fn is_running_in_saved_registry(registry_path: &Path, service_name: &str) -> Result<bool> {
+    let saved_registry = NodeRegistry::load(registry_path)?;
+    Ok(saved_registry
+        .nodes
+        .iter()
+        .any(|node| node.service_name == service_name && node.status == ServiceStatus::Running))
+}
+
+/// Saves the changes the supervisor made to the nodes at `service_indices` on top of the registry
+/// as it is on disk now.
+///
+/// Other commands can stop or remove services while a pass runs, so the registry is loaded again
+/// and only the fields the supervisor changes are copied over, to the nodes that are still running
+/// in it.
+fn save_supervised_nodes(node_registry: &NodeRegistry, service_indices: &[usize]) -> Result<()> {
+    let mut saved_registry = NodeRegistry::load(&node_registry.save_path)?;
+    for &index in service_indices {
+        let node = &node_registry.nodes[index];
+        let saved_node = match saved_registry
+            .nodes
+            .iter_mut()
+            .find(|saved_node| saved_node.service_name == node.service_name)
+        {
+            Some(saved_node) if saved_node.status == ServiceStatus::Running => saved_node,
+            _ => {
+                debug!(
+                    "{} is no longer running in the saved registry, dropping the supervisor's changes",
+                    node.service_name
+                );
+                continue;
+            }
+        };
+        saved_node.connected_peers = node.connected_peers.clone();
+        saved_node.last_restart = node.last_restart.clone();
+        saved_node.peer_id = node.peer_id;
+        saved_node.pid = node.pid;
+        saved_node.restart_count = node.restart_count;
+        saved_node.status = node.status.clone();
+    }
+    saved_registry.save()?;
+    Ok(())
+}
+
+/// Returns the reason the node is unhealthy, if it is.
+async fn check_node(
+    node: &mut NodeServiceData,
+    state: &mut NodeState,
+    policy: &SupervisorPolicy,
+    rpc_client: &dyn RpcActions,
+    service_control: &dyn ServiceControl,
+) -> std::result::Result<(), String> {
+    let node_info = rpc_client.node_info().await;
+    match node.pid {
+        Some(pid) if service_control.is_service_process_running(pid) => {}
+        _ => match &node_info {
+            // The service infrastructure may have restarted the process itself, in which case
+            // there's a new PID.
+            Ok(info) if service_control.is_service_process_running(info.pid) => {
+                node.pid = Some(info.pid);
+            }
+            _ => {
+                return Err(match node.pid {
+                    Some(pid) => format!("the process with PID {pid} is not running"),
+                    None => "the process is not running".to_string(),
+                })
+            }
+        },
+    }
+    if let Err(err) = node_info {
+        return Err(format!("node info unavailable: {err}"));
+    }
+
+    let network_info = rpc_client
+        .network_info()
+        .await
+        .map_err(|err| format!("network info unavailable: {err}"))?;
+    let connected_peers = network_info.connected_peers.len();
+    node.connected_peers = Some(network_info.connected_peers);
+    if connected_peers < policy.min_connected_peers {
+        return Err(format!(
+            "{connected_peers} connected peers, but at least {} are required",
+            policy.min_connected_peers
+        ));
+    }
+
+    if let Some(record_activity_timeout) = policy.record_activity_timeout {
+        let record_count = rpc_client
+            .record_addresses()
+            .await
+            .map_err(|err| format!("record addresses unavailable: {err}"))?
+            .len();
+        if state.record_count != Some(record_count) {
+            state.record_count = Some(record_count);
+            state.last_record_activity = Instant::now();
+        } else if state.last_record_activity.elapsed() >= record_activity_timeout {
+            return Err(format!(
+                "no record activity in the last {} seconds",
+                record_activity_timeout.as_secs()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+async fn restart_node(
+    node: &mut NodeServiceData,
+    rpc_client: Box<dyn RpcActions + Send>,
+    service_control: Box<dyn ServiceControl + Send>,
+    verbosity: VerbosityLevel,
+) -> Result<()> {
+    if node.pid.is_none() {
+        // There's nothing to stop, and a running service without a PID can't be started.
+        node.status = ServiceStatus::Stopped;
+    }
+    let service = NodeService::new(node, rpc_client);
+    let mut service_manager = ServiceManager::new(service, service_control, verbosity);
+    service_manager.stop().await?;
+    service_manager.start().await?;
+    Ok(())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use libp2p_identity::PeerId;
+    use mockall::mock;
+    use service_manager::ServiceInstallCtx;
+    use sn_service_management::{
+        control::ServiceBackend,
+        error::Result as ServiceControlResult,
+        rpc::{NetworkInfo, NodeInfo, RecordAddress},
+    };
+    use sn_transfers::NanoTokens;
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        path::PathBuf,
+        str::FromStr,
+    };
+
+    mock! {
+        pub RpcClient {}
+        #[async_trait]
+        impl RpcActions for RpcClient {
+            async fn node_info(&self) -> ServiceControlResult<NodeInfo>;
+            async fn network_info(&self) -> ServiceControlResult<NetworkInfo>;
+            async fn record_addresses(&self) -> ServiceControlResult<Vec<RecordAddress>>;
+            async fn node_restart(&self, delay_millis: u64, retain_peer_id: bool) -> ServiceControlResult<()>;
+            async fn node_stop(&self, delay_millis: u64) -> ServiceControlResult<()>;
+            async fn node_update(&self, delay_millis: u64) -> ServiceControlResult<()>;
+            async fn update_log_level(&self, log_levels: String) -> ServiceControlResult<()>;
+        }
+    }
+
+    mock! {
+        pub ServiceControl {}
+        impl ServiceControl for ServiceControl {
+            fn create_service_user(&self, username: &str) -> ServiceControlResult<()>;
+            fn get_available_port(&self) -> ServiceControlResult<u16>;
+            fn install(&self, install_ctx: ServiceInstallCtx, user_mode: bool) -> ServiceControlResult<()>;
+            fn get_process_pid(&self, bin_path: &Path) -> ServiceControlResult<u32>;
+            fn is_service_process_running(&self, pid: u32) -> bool;
+            fn start(&self, service_name: &str, user_mode: bool) -> ServiceControlResult<()>;
+            fn stop(&self, service_name: &str, user_mode: bool) -> ServiceControlResult<()>;
+            fn uninstall(&self, service_name: &str, user_mode: bool) -> ServiceControlResult<()>;
+            fn wait(&self, delay: u64);
+        }
+    }
+
+    const PEER_ID: &str = "12D3KooWS2tpXGGTmg2AHFiDh57yPQnat49YHnyqoggzXZWpqkCR";
+
+    /// An RPC client for a node connected to `connected_peers` peers, whose process has PID 2000.
+    fn create_rpc_client(connected_peers: usize) -> Box<dyn RpcActions + Send> {
+        let mut mock_rpc_client = MockRpcClient::new();
+        mock_rpc_client.expect_node_info().returning(|| {
+            Ok(NodeInfo {
+                pid: 2000,
+                peer_id: PeerId::from_str(PEER_ID)?,
+                data_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+                log_path: PathBuf::from("/var/log/safenode/safenode1"),
+                version: "0.1.0".to_string(),
+                uptime: Duration::from_secs(1),
+            })
+        });
+        mock_rpc_client.expect_network_info().returning(move || {
+            Ok(NetworkInfo {
+                connected_peers: (0..connected_peers).map(|_| PeerId::random()).collect(),
+                listeners: Vec::new(),
+            })
+        });
+        Box::new(mock_rpc_client)
+    }
+
+    /// Service control where every process is either running or not.
+    fn create_service_control(processes_running: bool) -> Box<dyn ServiceControl + Send> {
+        let mut mock_service_control = MockServiceControl::new();
+        mock_service_control
+            .expect_is_service_process_running()
+            .returning(move |_| processes_running);
+        mock_service_control.expect_stop().returning(|_, _| Ok(()));
+        mock_service_control.expect_start().returning(|_, _| Ok(()));
+        mock_service_control.expect_wait().returning(|_| ());
+        mock_service_control
+            .expect_get_process_pid()
+            .returning(|_| Ok(2000));
+        Box::new(mock_service_control)
+    }
+
+    /// A registry with a single node, saved in `tmp_data_dir`.
+    fn create_node_registry(
+        tmp_data_dir: &assert_fs::TempDir,
+        status: ServiceStatus,
+    ) -> Result<NodeRegistry> {
+        let node_registry = NodeRegistry {
+            bootstrap_peers: Vec::new(),
+            daemon: None,
+            environment_variables: None,
+            faucet: None,
+            nodes: vec![NodeServiceData {
+                connected_peers: None,
+                data_dir_path: PathBuf::from("/var/safenode-manager/services/safenode1"),
+                fleet_group: None,
+                genesis: false,
+                home_network: false,
+                last_restart: None,
+                listen_addr: None,
+                local: false,
+                log_dir_path: PathBuf::from("/var/log/safenode/safenode1"),
+                metrics_port: None,
+                node_port: None,
+                number: 1,
+                peer_id: Some(PeerId::from_str(PEER_ID)?),
+                pid: Some(1000),
+                restart_count: 0,
+                reward_balance: Some(NanoTokens::zero()),
+                rpc_socket_addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8081),
+                safenode_path: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
+                service_name: "safenode1".to_string(),
+                status,
+                user: Some("safe".to_string()),
+                user_mode: false,
+                version: "0.1.0".to_string(),
+            }],
+            rolling_upgrade: None,
+            save_path: tmp_data_dir.join("node_registry.json"),
+            service_backend: ServiceBackend::Native,
+        };
+        node_registry.save()?;
+        Ok(node_registry)
+    }
+
+    fn create_policy() -> SupervisorPolicy {
+        SupervisorPolicy {
+            min_connected_peers: 1,
+            record_activity_timeout: None,
+            failure_threshold: 2,
+            initial_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+            max_restarts: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn supervise_should_not_restart_a_healthy_node() -> Result<()> {
+        let tmp_data_dir = assert_fs::TempDir::new()?;
+        let mut node_registry = create_node_registry(&tmp_data_dir, ServiceStatus::Running)?;
+        let mut supervisor = Supervisor::new(create_policy());
+
+        for _ in 0..3 {
+            let restarted = supervisor
+                .supervise(
+                    &mut node_registry,
+                    &[0],
+                    &|_| create_rpc_client(3),
+                    &|| create_service_control(true),
+                    VerbosityLevel::Normal,
+                )
+                .await?;
+            assert_eq!(restarted, 0);
+        }
+
+        let node = &node_registry.nodes[0];
+        assert_eq!(node.restart_count, 0);
+        assert_eq!(node.last_restart, None);
+        assert_eq!(
+            node.connected_peers.as_ref().map(|peers| peers.len()),
+            Some(3)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn supervise_should_restart_a_node_whose_process_has_died() -> Result<()> {
+        let tmp_data_dir = assert_fs::TempDir::new()?;
+        let mut node_registry = create_node_registry(&tmp_data_dir, ServiceStatus::Running)?;
+        let mut supervisor = Supervisor::new(create_policy());
+
+        // The first failed check is below the threshold.
+        let restarted = supervisor
+            .supervise(
+                &mut node_registry,
+                &[0],
+                &|_| create_rpc_client(3),
+                &|| create_service_control(false),
+                VerbosityLevel::Normal,
+            )
+            .await?;
+        assert_eq!(restarted, 0);
+        assert_eq!(node_registry.nodes[0].restart_count, 0);
+
+        let restarted = supervisor
+            .supervise(
+                &mut node_registry,
+                &[0],
+                &|_| create_rpc_client(3),
+                &|| create_service_control(false),
+                VerbosityLevel::Normal,
+            )
+            .await?;
+        assert_eq!(restarted, 1);
+
+        let node = &node_registry.nodes[0];
+        assert_eq!(node.restart_count, 1);
+        assert_eq!(
+            node.last_restart
+                .as_ref()
+                .map(|restart| restart.reason.as_str()),
+            Some("the process with PID 1000 is not running")
+        );
+        assert_eq!(node.status, ServiceStatus::Running);
+        assert_eq!(node.pid, Some(2000));
+
+        let saved_registry = NodeRegistry::load(&node_registry.save_path)?;
+        assert_eq!(saved_registry.nodes[0].restart_count, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn supervise_should_not_restart_a_node_again_during_its_backoff() -> Result<()> {
+        let tmp_data_dir = assert_fs::TempDir::new()?;
+        let mut node_registry = create_node_registry(&tmp_data_dir, ServiceStatus::Running)?;
+        let mut supervisor = Supervisor::new(SupervisorPolicy {
+            failure_threshold: 1,
+            initial_backoff: Duration::from_secs(3600),
+            max_backoff: Duration::from_secs(3600),
+            ..create_policy()
+        });
+
+        let mut restarted = 0;
+        for _ in 0..3 {
+            restarted += supervisor
+                .supervise(
+                    &mut node_registry,
+                    &[0],
+                    &|_| create_rpc_client(0),
+                    &|| create_service_control(true),
+                    VerbosityLevel::Normal,
+                )
+                .await?;
+        }
+
+        assert_eq!(restarted, 1);
+        assert_eq!(node_registry.nodes[0].restart_count, 1);
+        assert_eq!(
+            node_registry.nodes[0]
+                .last_restart
+                .as_ref()
+                .map(|restart| restart.reason.as_str()),
+            Some("0 connected peers, but at least 1 are required")
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn supervise_should_stop_restarting_a_node_after_the_maximum_restarts() -> Result<()> {
+        let tmp_data_dir = assert_fs::TempDir::new()?;
+        let mut node_registry = create_node_registry(&tmp_data_dir, ServiceStatus::Running)?;
+        let mut supervisor = Supervisor::new(SupervisorPolicy {
+            failure_threshold: 1,
+            max_restarts: Some(2),
+            ..create_policy()
+        });
+
+        for _ in 0..5 {
+            supervisor
+                .supervise(
+                    &mut node_registry,
+                    &[0],
+                    &|_| create_rpc_client(0),
+                    &|| create_service_control(true),
+                    VerbosityLevel::Normal,
+                )
+                .await?;
+        }
+
+        assert_eq!(node_registry.nodes[0].restart_count, 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn supervise_should_ignore_a_stopped_node() -> Result<()> {
+        let tmp_data_dir = assert_fs::TempDir::new()?;
+        let mut node_registry = create_node_registry(&tmp_data_dir, ServiceStatus::Stopped)?;
+        let mut supervisor = Supervisor::new(SupervisorPolicy {
+            failure_threshold: 1,
+            ..create_policy()
+        });
+
+        let restarted = supervisor
+            .supervise(
+                &mut node_registry,
+                &[0],
+                &|_| Box::new(MockRpcClient::new()),
+                &|| Box::new(MockServiceControl::new()),
+                VerbosityLevel::Normal,
+            )
+            .await?;
+
+        assert_eq!(restarted, 0);
+        assert_eq!(node_registry.nodes[0].restart_count, 0);
+        assert_eq!(node_registry.nodes[0].status, ServiceStatus::Stopped);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn supervise_should_not_overwrite_a_node_stopped_during_the_pass() -> Result<()> {
+        let tmp_data_dir = assert_fs::TempDir::new()?;
+        let mut node_registry = create_node_registry(&tmp_data_dir, ServiceStatus::Running)?;
+        let mut supervisor = Supervisor::new(SupervisorPolicy {
+            failure_threshold: 1,
+            ..create_policy()
+        });
+
+        // Another command stops the node after the supervisor loaded the registry.
+        let mut stopped_registry = NodeRegistry::load(&node_registry.save_path)?;
+        stopped_registry.nodes[0].status = ServiceStatus::Stopped;
+        stopped_registry.nodes[0].pid = None;
+        stopped_registry.save()?;
+
+        let restarted = supervisor
+            .supervise(
+                &mut node_registry,
+                &[0],
+                &|_| create_rpc_client(0),
+                &|| create_service_control(true),
+                VerbosityLevel::Normal,
+            )
+            .await?;
+        assert_eq!(restarted, 0);
+
+        let saved_registry = NodeRegistry::load(&node_registry.save_path)?;
+        assert_eq!(saved_registry.nodes[0].status, ServiceStatus::Stopped);
+        assert_eq!(saved_registry.nodes[0].pid, None);
+        assert_eq!(saved_registry.nodes[0].restart_count, 0);
+
+        Ok(())
+    }
+}
@@ -6,15 +6,22 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
+mod container;
+mod process;
+
+pub use container::{ContainerController, ContainerRuntime};
+pub use process::ProcessController;
+
 use crate::error::{Error, Result};
 
+use serde::{Deserialize, Serialize};
 use service_manager::{
     ServiceInstallCtx, ServiceLabel, ServiceLevel, ServiceManager, ServiceStartCtx, ServiceStopCtx,
     ServiceUninstallCtx,
 };
 use std::{
     net::{SocketAddr, TcpListener},
-    path::Path,
+    path::{Path, PathBuf},
 };
 use sysinfo::{Pid, System};
 
@@ -37,6 +44,68 @@ pub trait ServiceControl: Sync {
     fn wait(&self, delay: u64);
 }
 
+/// How the services in a registry are defined and run.
+///
+/// The backend applies to every service in the registry, so it can only be changed when there
+/// are no services.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum ServiceBackend {
+    /// Services are defined with the platform's service manager, e.g., systemd or launchd.
+    #[default]
+    Native,
+    /// Services run as detached processes, supervised by the node manager.
+    ///
+    /// Their definitions, PID files and output are kept in a directory per service under `dir`.
+    Process { dir: PathBuf },
+    /// Services run in containers, which are defined by a file per service under `dir`.
+    ///
+    /// The image only provides the environment; the service's binary and directories are mounted
+    /// into the container at the same paths they have on the host.
+    Container {
+        dir: PathBuf,
+        image: String,
+        runtime: ContainerRuntime,
+    },
+}
+
+impl ServiceBackend {
+    pub fn controller(&self) -> Box<dyn ServiceControl + Send> {
+        match self {
+            ServiceBackend::Native => Box::new(ServiceController {}),
+            ServiceBackend::Process { dir } => Box::new(ProcessController { dir: dir.clone() }),
+            ServiceBackend::Container {
+                dir,
+                image,
+                runtime,
+            } => Box::new(ContainerController {
+                dir: dir.clone(),
+                image: image.clone(),
+                runtime: *runtime,
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for ServiceBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServiceBackend::Native => write!(f, "native service manager"),
+            ServiceBackend::Process { dir } => {
+                write!(f, "detached processes ({})", dir.to_string_lossy())
+            }
+            ServiceBackend::Container {
+                dir,
+                image,
+                runtime,
+            } => write!(
+                f,
+                "{runtime} containers using {image} ({})",
+                dir.to_string_lossy()
+            ),
+        }
+    }
+}
+
 pub struct ServiceController {}
 
 impl ServiceControl for ServiceController {
@@ -237,3 +306,21 @@ impl ServiceControl for ServiceController {
         std::thread::sleep(std::time::Duration::from_millis(delay));
     }
 }
+
+/// Get the user and group IDs of a user account, for running a process as that user.
+#[cfg(unix)]
+fn get_user_ids(username: &str) -> Result<(u32, u32)> {
+    use std::process::Command;
+
+    let get_id = |flag: &str| -> Result<u32> {
+        let output = Command::new("id").arg(flag).arg(username).output()?;
+        if !output.status.success() {
+            return Err(Error::CommandFailed(
+                format!("id {flag} {username}"),
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(std::str::from_utf8(&output.stdout)?.trim().parse()?)
+    };
+    Ok((get_id("-u")?, get_id("-g")?))
+}
@@ -0,0 +1,470 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{ServiceControl, ServiceController};
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use service_manager::ServiceInstallCtx;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum ContainerRuntime {
+    /// Each service is defined by a Compose file, which is run with `docker compose`.
+    #[default]
+    Docker,
+    /// Each service is defined by a Kubernetes pod file, which is run with `podman kube play`.
+    Podman,
+}
+
+impl std::fmt::Display for ContainerRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContainerRuntime::Docker => write!(f, "docker"),
+            ContainerRuntime::Podman => write!(f, "podman"),
+        }
+    }
+}
+
+/// Runs services in containers, for hosts that don't have a service manager.
+///
+/// Each service gets a definition file in its own directory under `dir`. Containers use the host
+/// network, so the ports the services listen on are the same as they would be without one.
+pub struct ContainerController {
+    pub dir: PathBuf,
+    pub image: String,
+    pub runtime: ContainerRuntime,
+}
+
+#[derive(Serialize)]
+struct ComposeFile {
+    services: BTreeMap<String, ComposeService>,
+}
+
+#[derive(Serialize)]
+struct ComposeService {
+    command: Vec<String>,
+    container_name: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    environment: Vec<String>,
+    image: String,
+    network_mode: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+    volumes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    working_dir: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Pod {
+    api_version: String,
+    kind: String,
+    metadata: PodMetadata,
+    spec: PodSpec,
+}
+
+#[derive(Serialize)]
+struct PodMetadata {
+    name: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PodSpec {
+    containers: Vec<PodContainer>,
+    host_network: bool,
+    restart_policy: String,
+    volumes: Vec<PodVolume>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PodContainer {
+    args: Vec<String>,
+    command: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    env: Vec<PodEnvVar>,
+    image: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    security_context: Option<PodSecurityContext>,
+    volume_mounts: Vec<PodVolumeMount>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    working_dir: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+struct PodEnvVar {
+    name: String,
+    value: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PodSecurityContext {
+    run_as_group: u32,
+    run_as_user: u32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PodVolume {
+    host_path: PodHostPath,
+    name: String,
+}
+
+#[derive(Serialize)]
+struct PodHostPath {
+    path: PathBuf,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PodVolumeMount {
+    mount_path: PathBuf,
+    name: String,
+}
+
+impl ContainerController {
+    fn definition_path(&self, service_name: &str) -> PathBuf {
+        let file_name = match self.runtime {
+            ContainerRuntime::Docker => "docker-compose.yml",
+            ContainerRuntime::Podman => "pod.yml",
+        };
+        self.dir.join(service_name).join(file_name)
+    }
+
+    /// Build the definition of the container for a service.
+    ///
+    /// The directory of the binary, the working directory and any existing paths in the
+    /// arguments, which include the data and log directories for a node, are mounted at the same
+    /// paths inside the container, so the arguments can be used as they are.
+    pub fn definition(&self, install_ctx: &ServiceInstallCtx) -> Result<String> {
+        let service_name = install_ctx.label.to_string();
+        let args = install_ctx
+            .args
+            .iter()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        let environment = install_ctx.environment.clone().unwrap_or_default();
+        let user = get_container_user(install_ctx.username.as_deref())?;
+
+        let mut mounts = Vec::new();
+        if let Some(parent) = install_ctx.program.parent() {
+            mounts.push(parent.to_path_buf());
+        }
+        if let Some(working_directory) = &install_ctx.working_directory {
+            mounts.push(working_directory.clone());
+        }
+        for arg in &args {
+            let path = Path::new(arg);
+            if path.is_absolute() && path.exists() {
+                mounts.push(path.to_path_buf());
+            }
+        }
+        mounts.sort();
+        mounts.dedup();
+
+        let definition = match self.runtime {
+            ContainerRuntime::Docker => {
+                let mut command = vec![install_ctx.program.to_string_lossy().to_string()];
+                command.extend(args);
+                let service = ComposeService {
+                    command,
+                    container_name: service_name.clone(),
+                    environment: environment
+                        .into_iter()
+                        .map(|(key, value)| format!("{key}={value}"))
+                        .collect(),
+                    image: self.image.clone(),
+                    network_mode: "host".to_string(),
+                    user: user.map(|(uid, gid)| format!("{uid}:{gid}")),
+                    volumes: mounts
+                        .iter()
+                        .map(|path| {
+                            let path = path.to_string_lossy();
+                            format!("{path}:{path}")
+                        })
+                        .collect(),
+                    working_dir: install_ctx.working_directory.clone(),
+                };
+                serde_yaml::to_string(&ComposeFile {
+                    services: BTreeMap::from([(service_name, service)]),
+                })?
+            }
+            ContainerRuntime::Podman => {
+                let volume_name = |index: usize| format!("volume-{index}");
+                let container = PodContainer {
+                    args,
+                    command: vec![install_ctx.program.to_string_lossy().to_string()],
+                    env: environment
+                        .into_iter()
+                        .map(|(name, value)| PodEnvVar { name, value })
+                        .collect(),
+                    image: self.image.clone(),
+                    name: service_name.clone(),
+                    security_context: user.map(|(uid, gid)| PodSecurityContext {
+                        run_as_group: gid,
+                        run_as_user: uid,
+                    }),
+                    volume_mounts: mounts
+                        .iter()
+                        .enumerate()
+                        .map(|(index, path)| PodVolumeMount {
+                            mount_path: path.clone(),
+                            name: volume_name(index),
+                        })
+                        .collect(),
+                    working_dir: install_ctx.working_directory.clone(),
+                };
+                serde_yaml::to_string(&Pod {
+                    api_version: "v1".to_string(),
+                    kind: "Pod".to_string(),
+                    metadata: PodMetadata { name: service_name },
+                    spec: PodSpec {
+                        containers: vec![container],
+                        host_network: true,
+                        restart_policy: "Never".to_string(),
+                        volumes: mounts
+                            .into_iter()
+                            .enumerate()
+                            .map(|(index, path)| PodVolume {
+                                host_path: PodHostPath { path },
+                                name: volume_name(index),
+                            })
+                            .collect(),
+                    },
+                })?
+            }
+        };
+        Ok(definition)
+    }
+
+    fn run(&self, args: &[&str]) -> Result<()> {
+        let program = self.runtime.to_string();
+        let output = Command::new(&program).args(args).output()?;
+        if !output.status.success() {
+            return Err(Error::CommandFailed(
+                format!("{program} {}", args.join(" ")),
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn up(&self, service_name: &str) -> Result<()> {
+        let path = self.definition_path(service_name);
+        if !path.exists() {
+            return Err(Error::ServiceRemovedManually(service_name.to_string()));
+        }
+        let path = path.to_string_lossy();
+        match self.runtime {
+            ContainerRuntime::Docker => {
+                self.run(&["compose", "-f", &path, "-p", service_name, "up", "-d"])
+            }
+            ContainerRuntime::Podman => self.run(&["kube", "play", "--replace", &path]),
+        }
+    }
+
+    /// Remove the container. The data is kept on the host, so this is also how it's stopped.
+    fn down(&self, service_name: &str) -> Result<()> {
+        let path = self.definition_path(service_name);
+        if !path.exists() {
+            return Err(Error::ServiceRemovedManually(service_name.to_string()));
+        }
+        let path = path.to_string_lossy();
+        match self.runtime {
+            ContainerRuntime::Docker => {
+                self.run(&["compose", "-f", &path, "-p", service_name, "down"])
+            }
+            ContainerRuntime::Podman => self.run(&["kube", "down", &path]),
+        }
+    }
+}
+
+impl ServiceControl for ContainerController {
+    fn create_service_user(&self, username: &str) -> Result<()> {
+        ServiceController {}.create_service_user(username)
+    }
+
+    fn get_available_port(&self) -> Result<u16> {
+        ServiceController {}.get_available_port()
+    }
+
+    fn install(&self, install_ctx: ServiceInstallCtx, _user_mode: bool) -> Result<()> {
+        let service_name = install_ctx.label.to_string();
+        let definition = self.definition(&install_ctx)?;
+        std::fs::create_dir_all(self.dir.join(&service_name))?;
+        std::fs::write(self.definition_path(&service_name), definition)?;
+        Ok(())
+    }
+
+    fn get_process_pid(&self, bin_path: &Path) -> Result<u32> {
+        ServiceController {}.get_process_pid(bin_path)
+    }
+
+    fn is_service_process_running(&self, pid: u32) -> bool {
+        ServiceController {}.is_service_process_running(pid)
+    }
+
+    fn start(&self, service_name: &str, _user_mode: bool) -> Result<()> {
+        self.up(service_name)
+    }
+
+    fn stop(&self, service_name: &str, _user_mode: bool) -> Result<()> {
+        self.down(service_name)
+    }
+
+    fn uninstall(&self, service_name: &str, _user_mode: bool) -> Result<()> {
+        if !self.definition_path(service_name).exists() {
+            return Err(Error::ServiceRemovedManually(service_name.to_string()));
+        }
+        // The container should already be gone, because the service has to be stopped first.
+        let _ = self.down(service_name);
+        std::fs::remove_dir_all(self.dir.join(service_name))?;
+        Ok(())
+    }
+
+    fn wait(&self, delay: u64) {
+        ServiceController {}.wait(delay)
+    }
+}
+
+/// The user and group IDs a container should run as, for the user the service was defined with.
+#[cfg(unix)]
+fn get_container_user(username: Option<&str>) -> Result<Option<(u32, u32)>> {
+    match username {
+        Some(username) => Ok(Some(super::get_user_ids(username)?)),
+        None => Ok(None),
+    }
+}
+
+/// On Windows, containers run as the user the image specifies.
+#[cfg(windows)]
+fn get_container_user(_username: Option<&str>) -> Result<Option<(u32, u32)>> {
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+
+    fn install_ctx(data_dir: &Path) -> Result<ServiceInstallCtx> {
+        Ok(ServiceInstallCtx {
+            label: "safenode1".parse()?,
+            program: PathBuf::from("/var/safenode-manager/services/safenode1/safenode"),
+            args: vec![
+                OsString::from("--root-dir"),
+                data_dir.as_os_str().to_os_string(),
+                OsString::from("--log-output-dest"),
+                OsString::from("/does/not/exist"),
+                OsString::from("--port"),
+                OsString::from("12000"),
+            ],
+            contents: None,
+            username: None,
+            working_directory: None,
+            environment: Some(vec![("SN_LOG".to_string(), "all".to_string())]),
+        })
+    }
+
+    fn controller(runtime: ContainerRuntime) -> ContainerController {
+        ContainerController {
+            dir: PathBuf::from("/var/safenode-manager/containers"),
+            image: "ubuntu:22.04".to_string(),
+            runtime,
+        }
+    }
+
+    #[test]
+    fn docker_definition_should_run_the_binary_with_its_paths_mounted() -> Result<()> {
+        let data_dir = tempfile::tempdir()?;
+        let data_dir_path = data_dir.path().to_string_lossy().to_string();
+        let definition =
+            controller(ContainerRuntime::Docker).definition(&install_ctx(data_dir.path())?)?;
+        let compose: serde_yaml::Value = serde_yaml::from_str(&definition)?;
+        let service = &compose["services"]["safenode1"];
+
+        assert_eq!(service["image"], "ubuntu:22.04");
+        assert_eq!(service["network_mode"], "host");
+        assert_eq!(service["container_name"], "safenode1");
+        assert_eq!(
+            service["command"][0],
+            "/var/safenode-manager/services/safenode1/safenode"
+        );
+        assert_eq!(service["command"][2], data_dir_path.as_str());
+        assert_eq!(service["environment"][0], "SN_LOG=all");
+        assert!(service.get("user").is_none());
+
+        // Only the binary's directory and the paths that exist are mounted.
+        let volumes: Vec<String> = serde_yaml::from_value(service["volumes"].clone())?;
+        let mut expected = vec![
+            format!("{data_dir_path}:{data_dir_path}"),
+            "/var/safenode-manager/services/safenode1:/var/safenode-manager/services/safenode1"
+                .to_string(),
+        ];
+        expected.sort();
+        assert_eq!(volumes, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn podman_definition_should_be_a_pod_on_the_host_network() -> Result<()> {
+        let data_dir = tempfile::tempdir()?;
+        let definition =
+            controller(ContainerRuntime::Podman).definition(&install_ctx(data_dir.path())?)?;
+        let pod: serde_yaml::Value = serde_yaml::from_str(&definition)?;
+
+        assert_eq!(pod["kind"], "Pod");
+        assert_eq!(pod["metadata"]["name"], "safenode1");
+        assert_eq!(pod["spec"]["hostNetwork"], true);
+        assert_eq!(pod["spec"]["restartPolicy"], "Never");
+        let container = &pod["spec"]["containers"][0];
+        assert_eq!(
+            container["command"][0],
+            "/var/safenode-manager/services/safenode1/safenode"
+        );
+        assert_eq!(container["args"][0], "--root-dir");
+        assert_eq!(container["env"][0]["name"], "SN_LOG");
+
+        // Each mount refers to a volume of the pod with the same host path.
+        let mounts = container["volumeMounts"]
+            .as_sequence()
+            .cloned()
+            .unwrap_or_default();
+        let volumes = pod["spec"]["volumes"]
+            .as_sequence()
+            .cloned()
+            .unwrap_or_default();
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts.len(), volumes.len());
+        for (mount, volume) in mounts.iter().zip(volumes.iter()) {
+            assert_eq!(mount["name"], volume["name"]);
+            assert_eq!(mount["mountPath"], volume["hostPath"]["path"]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn definitions_should_be_named_for_the_runtime() {
+        assert_eq!(
+            controller(ContainerRuntime::Docker).definition_path("safenode1"),
+            PathBuf::from("/var/safenode-manager/containers/safenode1/docker-compose.yml")
+        );
+        assert_eq!(
+            controller(ContainerRuntime::Podman).definition_path("safenode1"),
+            PathBuf::from("/var/safenode-manager/containers/safenode1/pod.yml")
+        );
+    }
+}
@@ -0,0 +1,327 @@
+// Copyright (C) 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+use super::{ServiceControl, ServiceController};
+use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use service_manager::ServiceInstallCtx;
+use std::{
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::Duration,
+};
+use sysinfo::{Pid, ProcessRefreshKind, Signal, System, UpdateKind};
+
+/// How long a process has to exit after it's asked to, before it's killed.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What is needed to launch the process for a service.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ProcessDefinition {
+    pub args: Vec<String>,
+    pub environment: Option<Vec<(String, String)>>,
+    pub program: PathBuf,
+    pub username: Option<String>,
+    pub working_directory: Option<PathBuf>,
+}
+
+impl From<ServiceInstallCtx> for ProcessDefinition {
+    fn from(install_ctx: ServiceInstallCtx) -> Self {
+        Self {
+            args: install_ctx
+                .args
+                .iter()
+                .map(|arg| arg.to_string_lossy().to_string())
+                .collect(),
+            environment: install_ctx.environment,
+            program: install_ctx.program,
+            username: install_ctx.username,
+            working_directory: install_ctx.working_directory,
+        }
+    }
+}
+
+/// Runs services as detached processes, for hosts that don't have a service manager.
+///
+/// Each service has a directory under `dir`, with its definition, the PID file for its process,
+/// and the log its standard output and error are redirected to. Processes aren't restarted if
+/// they exit; the `supervise` command can be used for that.
+pub struct ProcessController {
+    pub dir: PathBuf,
+}
+
+impl ProcessController {
+    fn service_dir(&self, service_name: &str) -> PathBuf {
+        self.dir.join(service_name)
+    }
+
+    fn definition_path(&self, service_name: &str) -> PathBuf {
+        self.service_dir(service_name).join("definition.json")
+    }
+
+    fn pid_path(&self, service_name: &str) -> PathBuf {
+        self.service_dir(service_name)
+            .join(format!("{service_name}.pid"))
+    }
+
+    fn output_path(&self, service_name: &str) -> PathBuf {
+        self.service_dir(service_name).join("output.log")
+    }
+
+    fn read_definition(&self, service_name: &str) -> Result<ProcessDefinition> {
+        let path = self.definition_path(service_name);
+        if !path.exists() {
+            return Err(Error::ServiceRemovedManually(service_name.to_string()));
+        }
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    fn read_pid(&self, service_name: &str) -> Option<u32> {
+        std::fs::read_to_string(self.pid_path(service_name))
+            .ok()
+            .and_then(|pid| pid.trim().parse().ok())
+    }
+
+    /// The PID of the running process of the service, if any.
+    fn service_pid(&self, service_name: &str, definition: &ProcessDefinition) -> Option<Pid> {
+        let pid = Pid::from(self.read_pid(service_name)? as usize);
+        if refresh_service_process(&mut System::new(), pid, &definition.program) {
+            Some(pid)
+        } else {
+            None
+        }
+    }
+}
+
+/// Refresh a process, returning whether it is still running the service's `program`.
+///
+/// The PID file can outlive the process, whose PID can then be reused by an unrelated process, so
+/// a PID is only taken to be the service's if the process runs the service's program.
+fn refresh_service_process(system: &mut System, pid: Pid, program: &Path) -> bool {
+    let refresh_kind = ProcessRefreshKind::new().with_exe(UpdateKind::Always);
+    if !system.refresh_process_specifics(pid, refresh_kind) {
+        return false;
+    }
+    match system.process(pid).and_then(|process| process.exe()) {
+        Some(exe) => is_same_program(exe, program),
+        None => false,
+    }
+}
+
+/// Compare the executable of a process to a program path. On Linux, the executable of a process
+/// whose binary was replaced, e.g., by an upgrade, is reported with a ` (deleted)` suffix.
+fn is_same_program(exe: &Path, program: &Path) -> bool {
+    let exe = exe.to_string_lossy();
+    let exe = Path::new(exe.strip_suffix(" (deleted)").unwrap_or(&exe));
+    let canonical = |path: &Path| std::fs::canonicalize(path).unwrap_or(path.to_path_buf());
+    exe == program || canonical(exe) == canonical(program)
+}
+
+impl ServiceControl for ProcessController {
+    fn create_service_user(&self, username: &str) -> Result<()> {
+        ServiceController {}.create_service_user(username)
+    }
+
+    fn get_available_port(&self) -> Result<u16> {
+        ServiceController {}.get_available_port()
+    }
+
+    fn install(&self, install_ctx: ServiceInstallCtx, _user_mode: bool) -> Result<()> {
+        let service_name = install_ctx.label.to_string();
+        std::fs::create_dir_all(self.service_dir(&service_name))?;
+        let definition = ProcessDefinition::from(install_ctx);
+        std::fs::write(
+            self.definition_path(&service_name),
+            serde_json::to_string_pretty(&definition)?,
+        )?;
+        Ok(())
+    }
+
+    fn get_process_pid(&self, bin_path: &Path) -> Result<u32> {
+        ServiceController {}.get_process_pid(bin_path)
+    }
+
+    fn is_service_process_running(&self, pid: u32) -> bool {
+        ServiceController {}.is_service_process_running(pid)
+    }
+
+    fn start(&self, service_name: &str, _user_mode: bool) -> Result<()> {
+        let definition = self.read_definition(service_name)?;
+        if self.service_pid(service_name, &definition).is_some() {
+            return Ok(());
+        }
+
+        let output = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.output_path(service_name))?;
+        let mut command = Command::new(&definition.program);
+        command
+            .args(&definition.args)
+            .stdin(Stdio::null())
+            .stdout(output.try_clone()?)
+            .stderr(output);
+        if let Some(environment) = definition.environment {
+            command.envs(environment);
+        }
+        if let Some(working_directory) = definition.working_directory {
+            command.current_dir(working_directory);
+        }
+
+        // The process is put in its own group, so it doesn't receive signals meant for the node
+        // manager, like the interrupt from Ctrl-C.
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+            if let Some(username) = &definition.username {
+                let (uid, gid) = super::get_user_ids(username)?;
+                command.uid(uid).gid(gid);
+            }
+        }
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            const DETACHED_PROCESS: u32 = 0x00000008;
+            command.creation_flags(DETACHED_PROCESS);
+        }
+
+        let mut child = command.spawn()?;
+        std::fs::write(self.pid_path(service_name), child.id().to_string())?;
+        // If the process exits while the node manager is still running, e.g., as the daemon, it
+        // has to be reaped here. Otherwise that is left to the init process.
+        std::thread::spawn(move || child.wait());
+        Ok(())
+    }
+
+    fn stop(&self, service_name: &str, _user_mode: bool) -> Result<()> {
+        // Without its definition, the process can't be told apart from one that reused its PID,
+        // so it's left alone.
+        let definition = match self.read_definition(service_name) {
+            Ok(definition) => Some(definition),
+            Err(Error::ServiceRemovedManually(_)) => None,
+            Err(err) => return Err(err),
+        };
+        let running = definition.and_then(|definition| {
+            self.service_pid(service_name, &definition)
+                .map(|pid| (pid, definition))
+        });
+        if let Some((pid, definition)) = running {
+            let mut system = System::new();
+            let program = &definition.program;
+            if refresh_service_process(&mut system, pid, program) {
+                if let Some(process) = system.process(pid) {
+                    // Terminating gives the process the chance to shut down cleanly, but it's not
+                    // supported on every platform.
+                    if process.kill_with(Signal::Term).is_none() {
+                        process.kill();
+                    }
+                }
+
+                let mut waited = Duration::ZERO;
+                while refresh_service_process(&mut system, pid, program) && waited < STOP_TIMEOUT {
+                    std::thread::sleep(Duration::from_millis(100));
+                    waited += Duration::from_millis(100);
+                }
+                if refresh_service_process(&mut system, pid, program) {
+                    if let Some(process) = system.process(pid) {
+                        process.kill();
+                    }
+                }
+            }
+        }
+
+        match std::fs::remove_file(self.pid_path(service_name)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn uninstall(&self, service_name: &str, _user_mode: bool) -> Result<()> {
+        if !self.definition_path(service_name).exists() {
+            return Err(Error::ServiceRemovedManually(service_name.to_string()));
+        }
+        std::fs::remove_dir_all(self.service_dir(service_name))?;
+        Ok(())
+    }
+
+    fn wait(&self, delay: u64) {
+        ServiceController {}.wait(delay)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::ffi::OsString;
+
+    fn sleep_ctx(service_name: &str) -> Result<ServiceInstallCtx> {
+        Ok(ServiceInstallCtx {
+            label: service_name.parse()?,
+            program: PathBuf::from("/bin/sleep"),
+            args: vec![OsString::from("30")],
+            contents: None,
+            username: None,
+            working_directory: None,
+            environment: None,
+        })
+    }
+
+    fn is_running(pid: u32) -> bool {
+        System::new().refresh_process(Pid::from(pid as usize))
+    }
+
+    #[test]
+    fn programs_should_match_after_their_binary_is_replaced() {
+        let program = Path::new("/usr/local/bin/safenode");
+        assert!(is_same_program(program, program));
+        assert!(is_same_program(
+            Path::new("/usr/local/bin/safenode (deleted)"),
+            program
+        ));
+        assert!(!is_same_program(Path::new("/usr/bin/bash"), program));
+    }
+
+    #[test]
+    fn a_process_that_reused_the_pid_should_not_be_signalled() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let controller = ProcessController {
+            dir: dir.path().to_path_buf(),
+        };
+        controller.install(sleep_ctx("safenode1")?, false)?;
+
+        // The PID file points to this test process, which doesn't run the service's program.
+        let pid_path = controller.pid_path("safenode1");
+        std::fs::write(&pid_path, std::process::id().to_string())?;
+        controller.stop("safenode1", false)?;
+        assert!(!pid_path.exists());
+
+        std::fs::write(&pid_path, std::process::id().to_string())?;
+        controller.start("safenode1", false)?;
+        let pid = controller
+            .read_pid("safenode1")
+            .ok_or(Error::ServiceRemovedManually("safenode1".to_string()))?;
+        assert_ne!(pid, std::process::id());
+        assert!(is_running(pid));
+
+        // Starting it again keeps the running process.
+        controller.start("safenode1", false)?;
+        assert_eq!(controller.read_pid("safenode1"), Some(pid));
+
+        controller.stop("safenode1", false)?;
+        assert!(!pid_path.exists());
+        let mut waited = Duration::ZERO;
+        while is_running(pid) && waited < STOP_TIMEOUT {
+            std::thread::sleep(Duration::from_millis(100));
+            waited += Duration::from_millis(100);
+        }
+        assert!(!is_running(pid));
+        Ok(())
+    }
+}
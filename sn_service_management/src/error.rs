@@ -15,6 +15,8 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 pub enum Error {
     #[error(transparent)]
     AddrParseError(#[from] std::net::AddrParseError),
+    #[error("The '{0}' command failed: {1}")]
+    CommandFailed(String, String),
     #[error("The endpoint for the daemon has not been set")]
     DaemonEndpointNotSet,
     #[error(transparent)]
@@ -35,6 +37,8 @@ pub enum Error {
     RpcNodeInfoError(String),
     #[error("Could not obtain network info through RPC: {0}")]
     RpcNetworkInfoError(String),
+    #[error("Could not subscribe to node events through RPC: {0}")]
+    RpcNodeEventsError(String),
     #[error("Could not restart node through RPC: {0}")]
     RpcNodeRestartError(String),
     #[error("Could not stop node through RPC: {0}")]
@@ -53,4 +57,6 @@ pub enum Error {
     UserDataDirectoryNotObtainable,
     #[error(transparent)]
     Utf8Error(#[from] std::str::Utf8Error),
+    #[error(transparent)]
+    Yaml(#[from] serde_yaml::Error),
 }
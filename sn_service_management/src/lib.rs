@@ -17,7 +17,10 @@ pub mod safenode_manager_proto {
     tonic::include_proto!("safenode_manager_proto");
 }
 
-use crate::error::{Error, Result};
+use crate::{
+    control::ServiceBackend,
+    error::{Error, Result},
+};
 use async_trait::async_trait;
 use libp2p::Multiaddr;
 use semver::Version;
@@ -80,6 +83,45 @@ pub trait ServiceStateActions {
     fn version(&self) -> String;
 }
 
+/// A record of an upgrade that's applied to services in batches, where each batch has to become
+/// healthy before the next one is upgraded.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RollingUpgrade {
+    pub target_version: String,
+    pub batch_size: usize,
+    /// Unix timestamps, in seconds.
+    pub started_at: u64,
+    pub finished_at: Option<u64>,
+    pub batches: Vec<UpgradeBatch>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UpgradeBatch {
+    pub nodes: Vec<UpgradeBatchNode>,
+    pub status: UpgradeBatchStatus,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UpgradeBatchNode {
+    pub service_name: String,
+    pub previous_version: String,
+    /// A copy of the binary the service ran before the upgrade, used to roll it back.
+    pub previous_bin_path: PathBuf,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum UpgradeBatchStatus {
+    /// The batch is being upgraded or is waiting to become healthy.
+    InProgress,
+    /// Every node in the batch passed the health checks.
+    Healthy,
+    /// The batch failed the health checks, for the given reason, and was rolled back.
+    RolledBack(String),
+    /// The batch failed the health checks, for the first reason given, but it could not be rolled
+    /// back, for the second.
+    RollbackFailed(String, String),
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct StatusSummary {
     pub nodes: Vec<NodeServiceData>,
@@ -94,7 +136,12 @@ pub struct NodeRegistry {
     pub environment_variables: Option<Vec<(String, String)>>,
     pub faucet: Option<FaucetServiceData>,
     pub nodes: Vec<NodeServiceData>,
+    /// The most recent rolling upgrade, including one that's in progress.
+    #[serde(default)]
+    pub rolling_upgrade: Option<RollingUpgrade>,
     pub save_path: PathBuf,
+    #[serde(default)]
+    pub service_backend: ServiceBackend,
 }
 
 impl NodeRegistry {
@@ -118,7 +165,9 @@ impl NodeRegistry {
                 environment_variables: None,
                 faucet: None,
                 nodes: vec![],
+                rolling_upgrade: None,
                 save_path: path.to_path_buf(),
+                service_backend: ServiceBackend::Native,
             });
         }
 
@@ -135,7 +184,9 @@ impl NodeRegistry {
                 environment_variables: None,
                 faucet: None,
                 nodes: vec![],
+                rolling_upgrade: None,
                 save_path: path.to_path_buf(),
+                service_backend: ServiceBackend::Native,
             });
         }
 
@@ -57,13 +57,24 @@ impl<'a> ServiceStateActions for NodeService<'a> {
         if self.service_data.genesis {
             args.push(OsString::from("--first"));
         }
+        if self.service_data.home_network {
+            args.push(OsString::from("--home-network"));
+        }
         if self.service_data.local {
             args.push(OsString::from("--local"));
         }
-        if let Some(node_port) = self.service_data.get_safenode_port() {
+        if let Some(node_port) = self
+            .service_data
+            .node_port
+            .or_else(|| self.service_data.get_safenode_port())
+        {
             args.push(OsString::from("--port"));
             args.push(OsString::from(node_port.to_string()));
         }
+        if let Some(metrics_port) = self.service_data.metrics_port {
+            args.push(OsString::from("--metrics-server-port"));
+            args.push(OsString::from(metrics_port.to_string()));
+        }
 
         if !options.bootstrap_peers.is_empty() {
             let peers_str = options
@@ -155,11 +166,22 @@ pub struct NodeServiceData {
     )]
     pub connected_peers: Option<Vec<PeerId>>,
     pub data_dir_path: PathBuf,
+    /// The fleet file group the service belongs to, if it was added or adopted by `apply`.
+    #[serde(default)]
+    pub fleet_group: Option<String>,
     pub genesis: bool,
     pub home_network: bool,
+    /// The most recent restart by the supervisor.
+    #[serde(default)]
+    pub last_restart: Option<NodeRestart>,
     pub listen_addr: Option<Vec<Multiaddr>>,
     pub local: bool,
     pub log_dir_path: PathBuf,
+    #[serde(default)]
+    pub metrics_port: Option<u16>,
+    /// The port the node was configured to listen on, if it wasn't left to choose one.
+    #[serde(default)]
+    pub node_port: Option<u16>,
     pub number: u16,
     #[serde(
         serialize_with = "serialize_peer_id",
@@ -167,6 +189,9 @@ pub struct NodeServiceData {
     )]
     pub peer_id: Option<PeerId>,
     pub pid: Option<u32>,
+    /// The number of times the supervisor has restarted the node.
+    #[serde(default)]
+    pub restart_count: u32,
     pub reward_balance: Option<NanoTokens>,
     pub rpc_socket_addr: SocketAddr,
     pub safenode_path: PathBuf,
@@ -177,6 +202,14 @@ pub struct NodeServiceData {
     pub version: String,
 }
 
+/// A restart of a node by the supervisor, because it was found to be unhealthy.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NodeRestart {
+    pub reason: String,
+    /// Seconds since the Unix epoch.
+    pub time: u64,
+}
+
 fn serialize_peer_id<S>(value: &Option<PeerId>, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
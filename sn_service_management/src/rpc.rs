@@ -10,12 +10,13 @@ use crate::error::{Error, Result};
 use async_trait::async_trait;
 use libp2p::{kad::RecordKey, Multiaddr, PeerId};
 use sn_protocol::safenode_proto::{
-    safe_node_client::SafeNodeClient, NetworkInfoRequest, NodeInfoRequest, RecordAddressesRequest,
-    RestartRequest, StopRequest, UpdateLogLevelRequest, UpdateRequest,
+    safe_node_client::SafeNodeClient, NetworkInfoRequest, NodeEvent, NodeEventsRequest,
+    NodeInfoRequest, RecordAddressesRequest, RestartRequest, StopRequest, UpdateLogLevelRequest,
+    UpdateRequest,
 };
 use std::{net::SocketAddr, path::PathBuf, str::FromStr};
 use tokio::time::Duration;
-use tonic::Request;
+use tonic::{Request, Streaming};
 use tracing::error;
 
 #[derive(Debug, Clone)]
@@ -69,6 +70,21 @@ impl RpcClient {
         Self { endpoint }
     }
 
+    /// Subscribe to the stream of events emitted by the node.
+    ///
+    /// Each message carries a `NodeEvent` serialized by the node.
+    pub async fn node_events(&self) -> Result<Streaming<NodeEvent>> {
+        let mut client = self.connect_with_retry().await?;
+        let response = client
+            .node_events(Request::new(NodeEventsRequest {}))
+            .await
+            .map_err(|e| {
+                error!("Could not subscribe to node events through RPC: {e:?}");
+                Error::RpcNodeEventsError(e.to_string())
+            })?;
+        Ok(response.into_inner())
+    }
+
     // Connect to the RPC endpoint with retry
     async fn connect_with_retry(&self) -> Result<SafeNodeClient<tonic::transport::Channel>> {
         let mut attempts = 0;
@@ -21,6 +21,32 @@ pub const CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES: &str = "ROYALTY";
 pub const CASHNOTE_PURPOSE_OF_CHANGE: &str = "CHANGE";
 pub const CASHNOTE_PURPOSE_OF_TRANSFER: &str = "TRANSFER";
 
+/// The purpose of the network royalties output of a storage payment, which names the paid data
+/// address after the `CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES` prefix, e.g. `ROYALTY:<hex address>`
+pub fn network_royalties_purpose(data_address: &XorName) -> String {
+    format!(
+        "{CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES}:{}",
+        hex::encode(data_address)
+    )
+}
+
+/// Whether an output with this purpose pays network royalties, naming the paid data address or not
+pub fn is_network_royalties_purpose(purpose: &str) -> bool {
+    purpose == CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES
+        || network_royalties_data_address(purpose).is_some()
+}
+
+/// The data address paid for by a network royalties output, if its purpose names it
+pub fn network_royalties_data_address(purpose: &str) -> Option<XorName> {
+    let hex_address = purpose
+        .strip_prefix(CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES)?
+        .strip_prefix(':')?;
+    let bytes: [u8; xor_name::XOR_NAME_LEN] = hex::decode(hex_address).ok()?.try_into().ok()?;
+    Some(XorName(bytes))
+}
+
+use xor_name::XorName;
+
 pub(crate) use builder::{CashNoteBuilder, TransactionBuilder};
 pub(crate) use transaction::Input;
 
@@ -30,7 +56,9 @@ pub use cashnote::{CashNote, CashNoteOutputDetails};
 pub use nano::NanoTokens;
 pub use reason_hash::Hash;
 pub use signed_spend::{SignedSpend, Spend};
-pub use spend_reason::SpendReason;
+pub use spend_reason::{
+    RewardId, RewardIdKind, RewardTrackingCipher, SpendReason, REWARD_TRACKING_VERSION,
+};
 pub use transaction::Transaction;
 pub use unique_keys::{DerivationIndex, DerivedSecretKey, MainPubkey, MainSecretKey, UniquePubkey};
 
@@ -40,6 +68,32 @@ pub(crate) mod tests {
     use crate::TransferError;
     use transaction::Output;
 
+    #[test]
+    fn network_royalties_purpose_should_name_the_data_address() {
+        let data_address = XorName([7; 32]);
+        let purpose = network_royalties_purpose(&data_address);
+        assert!(is_network_royalties_purpose(&purpose));
+        assert_eq!(network_royalties_data_address(&purpose), Some(data_address));
+
+        // the purpose of the royalties paid before the data address was named
+        assert!(is_network_royalties_purpose(
+            CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES
+        ));
+        assert_eq!(
+            network_royalties_data_address(CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES),
+            None
+        );
+
+        for purpose in [
+            "ROYALTY:",
+            "ROYALTY:zz",
+            "ROYALTYMORE",
+            CASHNOTE_PURPOSE_OF_CHANGE,
+        ] {
+            assert!(!is_network_royalties_purpose(purpose), "{purpose}");
+        }
+    }
+
     #[test]
     fn from_hex_should_deserialize_a_hex_encoded_string_to_a_cashnote() -> Result<(), TransferError>
     {
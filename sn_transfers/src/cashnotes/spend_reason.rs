@@ -9,6 +9,7 @@
 use bls::{Ciphertext, PublicKey, SecretKey};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
 use xor_name::XorName;
 
 use crate::{DerivationIndex, Hash, Result, TransferError};
@@ -27,7 +28,11 @@ pub enum SpendReason {
 
     /// Beta only feature to track rewards
     /// Discord username encrypted to the Foundation's pubkey with a random nonce
+    /// Superseded by `RewardTracking`, kept to read the existing spends
     BetaRewardTracking(DiscordNameCipher),
+    /// Identifier of a declared type (Discord, email, wallet...) to track rewards with
+    /// encrypted to the Foundation's pubkey with a random nonce
+    RewardTracking(RewardTrackingCipher),
 }
 
 impl SpendReason {
@@ -37,14 +42,38 @@ impl SpendReason {
             Self::NetworkData(xor_name) => Hash::hash(xor_name),
             Self::Custom(bytes) => Hash::hash(bytes),
             Self::BetaRewardTracking(cypher) => Hash::hash(&cypher.cipher),
+            Self::RewardTracking(cypher) => cypher.hash(),
         }
     }
 
+    /// Create a reward tracking reason for an identifier in the `RewardId` format, e.g.
+    /// `email:john@doe.com`, a plain name being a Discord username
+    ///
+    /// Discord usernames are still sent as `BetaRewardTracking`, which every peer can read. The
+    /// other types need the `typed-reward-tracking` feature, as peers that don't know the
+    /// `RewardTracking` variant can't read the spends carrying it.
     pub fn create_reward_tracking_reason(input_str: &str) -> Result<Self> {
+        let reward_id = RewardId::from_str(input_str)?;
         let input_pk = crate::NETWORK_ROYALTIES_PK.public_key();
-        Ok(Self::BetaRewardTracking(DiscordNameCipher::create(
-            input_str, input_pk,
-        )?))
+        match reward_id.kind {
+            RewardIdKind::Discord => Ok(Self::BetaRewardTracking(DiscordNameCipher::create(
+                &reward_id.value,
+                input_pk,
+            )?)),
+            kind if kind.can_be_sent() => Ok(Self::RewardTracking(RewardTrackingCipher::create(
+                &reward_id, input_pk,
+            )?)),
+            kind => Err(TransferError::UnsupportedRewardIdKind(kind)),
+        }
+    }
+
+    /// The type of the identifier if this is a reward tracking reason
+    pub fn reward_id_kind(&self) -> Option<RewardIdKind> {
+        match self {
+            Self::BetaRewardTracking(_) => Some(RewardIdKind::Discord),
+            Self::RewardTracking(cypher) => Some(cypher.kind()),
+            Self::None | Self::NetworkData(_) | Self::Custom(_) => None,
+        }
     }
 }
 
@@ -56,6 +85,200 @@ const DERIVATION_INDEX_SIZE: usize = 32;
 const HASH_SIZE: usize = 32;
 const LIMIT_SIZE: usize = HASH_SIZE + DERIVATION_INDEX_SIZE;
 
+/// The current version of the `RewardTrackingCipher` format
+pub const REWARD_TRACKING_VERSION: u8 = 1;
+
+/// The type of the identifier carried by a reward tracking reason
+/// It is encoded as a tag in the cipher, so that the types added later can still be read and counted
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum RewardIdKind {
+    /// Discord username
+    Discord,
+    /// Email address, only its hash is sent
+    Email,
+    /// Address of a wallet outside of the Network
+    Wallet,
+    /// Opaque identifier given by a reward program
+    Opaque,
+    /// A type this version doesn't know, with its tag
+    Other(u8),
+}
+
+impl RewardIdKind {
+    /// The types that can be created by this version
+    pub const ALL: [RewardIdKind; 4] = [Self::Discord, Self::Email, Self::Wallet, Self::Opaque];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Discord => "discord",
+            Self::Email => "email",
+            Self::Wallet => "wallet",
+            Self::Opaque => "id",
+            Self::Other(_) => "other",
+        }
+    }
+
+    /// The tag of the type in the cipher
+    pub fn tag(&self) -> u8 {
+        match self {
+            Self::Discord => 0,
+            Self::Email => 1,
+            Self::Wallet => 2,
+            Self::Opaque => 3,
+            Self::Other(tag) => *tag,
+        }
+    }
+
+    pub fn from_tag(tag: u8) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.tag() == tag)
+            .unwrap_or(Self::Other(tag))
+    }
+
+    /// Whether spends can be tracked with this type. Only Discord usernames can be sent without
+    /// the `typed-reward-tracking` feature.
+    pub fn can_be_sent(&self) -> bool {
+        matches!(self, Self::Discord) || cfg!(feature = "typed-reward-tracking")
+    }
+}
+
+impl fmt::Display for RewardIdKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Other(tag) => write!(f, "{}{tag}", self.as_str()),
+            kind => write!(f, "{}", kind.as_str()),
+        }
+    }
+}
+
+impl FromStr for RewardIdKind {
+    type Err = TransferError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(kind) = Self::ALL.into_iter().find(|kind| kind.as_str() == s) {
+            return Ok(kind);
+        }
+        // the types unknown to this version are written with their tag, e.g. `other42`
+        s.strip_prefix(Self::Other(0).as_str())
+            .and_then(|tag| tag.parse::<u8>().ok())
+            .map(Self::from_tag)
+            .ok_or_else(|| TransferError::InvalidRewardId(format!("unknown identifier type {s:?}")))
+    }
+}
+
+/// An identifier to track rewards with, written as `<type>:<value>`, e.g. `email:john@doe.com`
+/// A value without a known type prefix is a Discord username, as that used to be the only type
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RewardId {
+    pub kind: RewardIdKind,
+    pub value: String,
+}
+
+impl RewardId {
+    pub fn new(kind: RewardIdKind, value: impl Into<String>) -> Self {
+        Self {
+            kind,
+            value: value.into(),
+        }
+    }
+
+    /// The hash of the identifier, emails being case insensitive. Other values, e.g. base58
+    /// wallet addresses, are hashed as given.
+    pub fn hash(&self) -> Hash {
+        match self.kind {
+            RewardIdKind::Email => Hash::hash(self.value.trim().to_lowercase().as_bytes()),
+            RewardIdKind::Discord
+            | RewardIdKind::Wallet
+            | RewardIdKind::Opaque
+            | RewardIdKind::Other(_) => Hash::hash(self.value.as_bytes()),
+        }
+    }
+}
+
+impl fmt::Display for RewardId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            RewardIdKind::Discord => write!(f, "{}", self.value),
+            kind => write!(f, "{kind}:{}", self.value),
+        }
+    }
+}
+
+impl FromStr for RewardId {
+    type Err = TransferError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let reward_id = match s.split_once(':') {
+            Some((kind, value)) => match RewardIdKind::from_str(kind) {
+                Ok(kind) => Self::new(kind, value),
+                Err(_) => Self::new(RewardIdKind::Discord, s),
+            },
+            None => Self::new(RewardIdKind::Discord, s),
+        };
+        if reward_id.value.is_empty() {
+            return Err(TransferError::InvalidRewardId(format!(
+                "empty {} identifier",
+                reward_id.kind
+            )));
+        }
+        Ok(reward_id)
+    }
+}
+
+/// The hash of a `RewardId` encrypted to the Foundation's pubkey with a random nonce, along with the type of the
+/// identifier so that rewards can be reported by type without decrypting them
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct RewardTrackingCipher {
+    /// Version of the format, `REWARD_TRACKING_VERSION` for the ciphers created by this code
+    version: u8,
+    /// The tag of the `RewardIdKind`, unknown tags are kept as they are
+    kind: u8,
+    /// Length of the cipher, hard limited to MAX_U8
+    len: u8,
+    /// Encrypted identifier hash and nonce
+    #[serde(with = "serde_bytes")]
+    cipher: [u8; MAX_CIPHER_SIZE],
+}
+
+impl RewardTrackingCipher {
+    /// Create a new RewardTrackingCipher from an identifier, encrypted to the given pubkey
+    pub fn create(reward_id: &RewardId, foundation_pk: PublicKey) -> Result<Self> {
+        let (len, cipher) = encrypt(&HashWithNonce::new(reward_id.hash()), foundation_pk)
+            .ok_or(TransferError::RewardTrackingCipherTooBig)?;
+        Ok(Self {
+            version: REWARD_TRACKING_VERSION,
+            kind: reward_id.kind.tag(),
+            len,
+            cipher,
+        })
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn kind(&self) -> RewardIdKind {
+        RewardIdKind::from_tag(self.kind)
+    }
+
+    fn hash(&self) -> Hash {
+        let mut bytes = vec![self.version, self.kind, self.len];
+        bytes.extend_from_slice(&self.cipher);
+        Hash::hash(&bytes)
+    }
+
+    /// Recover the identifier hash using the secret key it was encrypted to
+    pub fn decrypt_to_id_hash(&self, sk: &SecretKey) -> Result<Hash> {
+        if self.version != REWARD_TRACKING_VERSION {
+            return Err(TransferError::UnsupportedRewardTrackingVersion(
+                self.version,
+            ));
+        }
+        Ok(decrypt(&self.cipher[0..self.len as usize], sk)?.hash)
+    }
+}
+
 /// Discord username encrypted to the Foundation's pubkey with a random nonce
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct DiscordNameCipher {
@@ -66,18 +289,18 @@ pub struct DiscordNameCipher {
     cipher: [u8; MAX_CIPHER_SIZE],
 }
 
-/// Discord username hash and nonce
+/// Identifier hash and nonce
 /// u256 hash + u256 nonce might be overkill (very big)
-struct DiscordName {
+struct HashWithNonce {
     hash: Hash,
     nonce: DerivationIndex,
 }
 
-impl DiscordName {
-    fn new(user_name: &str) -> Self {
+impl HashWithNonce {
+    fn new(hash: Hash) -> Self {
         let rng = &mut rand::thread_rng();
-        DiscordName {
-            hash: Hash::hash(user_name.as_bytes()),
+        HashWithNonce {
+            hash,
             nonce: DerivationIndex::random(rng),
         }
     }
@@ -100,32 +323,42 @@ impl DiscordName {
     }
 }
 
+/// Encrypt to a fixed size buffer, along with the length used. None if the cipher doesn't fit.
+fn encrypt(hash_with_nonce: &HashWithNonce, pk: PublicKey) -> Option<(u8, [u8; MAX_CIPHER_SIZE])> {
+    let cipher = pk.encrypt(hash_with_nonce.to_sized_bytes());
+    let bytes = cipher.to_bytes();
+    if bytes.len() > MAX_CIPHER_SIZE {
+        return None;
+    }
+    let mut sized = [0; MAX_CIPHER_SIZE];
+    sized[0..bytes.len()].copy_from_slice(&bytes);
+    Some((bytes.len() as u8, sized))
+}
+
+fn decrypt(cipher: &[u8], sk: &SecretKey) -> Result<HashWithNonce> {
+    let cipher = Ciphertext::from_bytes(cipher)?;
+    let decrypted = sk
+        .decrypt(&cipher)
+        .ok_or(TransferError::UserNameDecryptFailed)?;
+    if decrypted.len() < LIMIT_SIZE {
+        return Err(TransferError::UserNameDecryptFailed);
+    }
+    Ok(HashWithNonce::from_bytes(&decrypted))
+}
+
 impl DiscordNameCipher {
     /// Create a new DiscordNameCipher from a Discord username
     /// it is encrypted to the given pubkey
     pub fn create(user_name: &str, foundation_pk: PublicKey) -> Result<Self> {
-        let discord_name = DiscordName::new(user_name);
-        let cipher = foundation_pk.encrypt(discord_name.to_sized_bytes());
-        let bytes = cipher.to_bytes();
-        if bytes.len() > MAX_CIPHER_SIZE {
-            return Err(TransferError::DiscordNameCipherTooBig);
-        }
-        let mut sized = [0; MAX_CIPHER_SIZE];
-        sized[0..bytes.len()].copy_from_slice(&bytes);
-        Ok(Self {
-            len: bytes.len() as u8,
-            cipher: sized,
-        })
+        let discord_name = HashWithNonce::new(Hash::hash(user_name.as_bytes()));
+        let (len, cipher) =
+            encrypt(&discord_name, foundation_pk).ok_or(TransferError::DiscordNameCipherTooBig)?;
+        Ok(Self { len, cipher })
     }
 
     /// Recover a Discord username hash using the secret key it was encrypted to
     pub fn decrypt_to_username_hash(&self, sk: &SecretKey) -> Result<Hash> {
-        let cipher = Ciphertext::from_bytes(&self.cipher[0..self.len as usize])?;
-        let decrypted = sk
-            .decrypt(&cipher)
-            .ok_or(TransferError::UserNameDecryptFailed)?;
-        let discord_name = DiscordName::from_bytes(&decrypted);
-        Ok(discord_name.hash)
+        Ok(decrypt(&self.cipher[0..self.len as usize], sk)?.hash)
     }
 }
 
@@ -158,4 +391,108 @@ mod tests {
 
         assert_ne!(user_name_hash, user_name_hash2);
     }
+
+    #[test]
+    fn test_reward_id_parsing() -> eyre::Result<()> {
+        let discord = RewardId::from_str("JohnDoe#1234")?;
+        assert_eq!(
+            discord,
+            RewardId::new(RewardIdKind::Discord, "JohnDoe#1234")
+        );
+        assert_eq!(discord.to_string(), "JohnDoe#1234");
+
+        let email = RewardId::from_str("email:John@Doe.com")?;
+        assert_eq!(email.kind, RewardIdKind::Email);
+        assert_eq!(email.to_string(), "email:John@Doe.com");
+        assert_eq!(
+            email.hash(),
+            RewardId::from_str("email:john@doe.com")?.hash()
+        );
+
+        // wallet addresses can be case sensitive
+        assert_ne!(
+            RewardId::from_str("wallet:5HueCGU8")?.hash(),
+            RewardId::from_str("wallet:5hueCGU8")?.hash()
+        );
+
+        // an unknown prefix is part of a Discord username
+        let unknown = RewardId::from_str("foo:bar")?;
+        assert_eq!(unknown, RewardId::new(RewardIdKind::Discord, "foo:bar"));
+
+        // the types of later versions are read back from their tag
+        let other = RewardId::new(RewardIdKind::Other(42), "someone");
+        assert_eq!(other.to_string(), "other42:someone");
+        assert_eq!(RewardId::from_str(&other.to_string())?, other);
+
+        assert!(RewardId::from_str("wallet:").is_err());
+        assert!(RewardId::from_str("").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reward_tracking_cyphering() -> eyre::Result<()> {
+        let foundation_sk = SecretKey::random();
+        let foundation_pk = foundation_sk.public_key();
+
+        for input in [
+            "JohnDoe#1234",
+            "email:john@doe.com",
+            "wallet:0xabc",
+            "id:42",
+        ] {
+            let reward_id = RewardId::from_str(input)?;
+            let cipher = RewardTrackingCipher::create(&reward_id, foundation_pk)?;
+            assert_eq!(cipher.decrypt_to_id_hash(&foundation_sk)?, reward_id.hash());
+            let reason = SpendReason::RewardTracking(cipher);
+            assert_eq!(reason.reward_id_kind(), Some(reward_id.kind));
+        }
+
+        // the beta ciphers are still a Discord username
+        let beta = SpendReason::BetaRewardTracking(DiscordNameCipher::create(
+            "JohnDoe#1234",
+            foundation_pk,
+        )?);
+        assert_eq!(beta.reward_id_kind(), Some(RewardIdKind::Discord));
+        Ok(())
+    }
+
+    #[test]
+    fn test_discord_usernames_are_sent_as_beta_reward_tracking() -> eyre::Result<()> {
+        for input in ["JohnDoe#1234", "discord:JohnDoe#1234", "STORAGE"] {
+            let reason = SpendReason::create_reward_tracking_reason(input)?;
+            assert!(
+                matches!(reason, SpendReason::BetaRewardTracking(_)),
+                "{input} is not sent as a beta reason: {reason:?}"
+            );
+        }
+
+        let reason = SpendReason::create_reward_tracking_reason("email:john@doe.com");
+        if cfg!(feature = "typed-reward-tracking") {
+            assert!(matches!(reason, Ok(SpendReason::RewardTracking(_))));
+        } else {
+            assert_eq!(
+                reason,
+                Err(TransferError::UnsupportedRewardIdKind(RewardIdKind::Email))
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_reward_id_kinds_are_kept() -> eyre::Result<()> {
+        let foundation_pk = SecretKey::random().public_key();
+        for kind in RewardIdKind::ALL {
+            assert_eq!(RewardIdKind::from_tag(kind.tag()), kind);
+        }
+
+        // a cipher of a type added by a later version
+        let reward_id = RewardId::new(RewardIdKind::Other(42), "someone");
+        let cipher = RewardTrackingCipher::create(&reward_id, foundation_pk)?;
+        let bytes = rmp_serde::to_vec(&cipher)?;
+        let read_back: RewardTrackingCipher = rmp_serde::from_slice(&bytes)?;
+        assert_eq!(read_back.kind(), RewardIdKind::Other(42));
+        assert_eq!(read_back.kind().to_string(), "other42");
+        assert_eq!(read_back, cipher);
+        Ok(())
+    }
 }
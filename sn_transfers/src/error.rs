@@ -6,7 +6,7 @@
 // KIND, either express or implied. Please review the Licences for the specific language governing
 // permissions and limitations relating to use of the SAFE Network Software.
 
-use crate::{Hash, NanoTokens, UniquePubkey};
+use crate::{Hash, NanoTokens, RewardIdKind, UniquePubkey};
 use thiserror::Error;
 
 /// Specialisation of `std::Result`.
@@ -90,4 +90,14 @@ pub enum TransferError {
     UserNameDecryptFailed,
     #[error("User name encryption failed")]
     DiscordNameCipherTooBig,
+    #[error("Reward tracking identifier encryption failed")]
+    RewardTrackingCipherTooBig,
+    #[error("Invalid reward tracking identifier: {0}")]
+    InvalidRewardId(String),
+    #[error("Unsupported reward tracking reason version: {0}")]
+    UnsupportedRewardTrackingVersion(u8),
+    #[error(
+        "Rewards can't be tracked with {0} identifiers without the typed-reward-tracking feature"
+    )]
+    UnsupportedRewardIdKind(RewardIdKind),
 }
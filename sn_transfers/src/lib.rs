@@ -17,12 +17,16 @@ mod wallet;
 
 pub(crate) use cashnotes::{Input, TransactionBuilder};
 
+pub use cashnotes::{
+    is_network_royalties_purpose, network_royalties_data_address, network_royalties_purpose,
+};
 /// Types used in the public API
 pub use cashnotes::{
     CashNote, CashNoteOutputDetails, DerivationIndex, DerivedSecretKey, Hash, MainPubkey,
-    MainSecretKey, NanoTokens, SignedSpend, Spend, SpendAddress, SpendReason, Transaction,
-    UniquePubkey, UnsignedTransfer, CASHNOTE_PURPOSE_OF_GENESIS,
-    CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES, CASHNOTE_PURPOSE_OF_TRANSFER,
+    MainSecretKey, NanoTokens, RewardId, RewardIdKind, RewardTrackingCipher, SignedSpend, Spend,
+    SpendAddress, SpendReason, Transaction, UniquePubkey, UnsignedTransfer,
+    CASHNOTE_PURPOSE_OF_CHANGE, CASHNOTE_PURPOSE_OF_GENESIS, CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES,
+    CASHNOTE_PURPOSE_OF_TRANSFER, REWARD_TRACKING_VERSION,
 };
 pub use error::{Result, TransferError};
 pub use transfers::{CashNoteRedemption, OfflineTransfer, Transfer};
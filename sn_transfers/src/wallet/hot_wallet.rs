@@ -21,10 +21,11 @@ use super::{
 use crate::{
     calculate_royalties_fee,
     cashnotes::UnsignedTransfer,
+    network_royalties_purpose,
     transfers::{CashNotesAndSecretKey, OfflineTransfer},
     CashNote, CashNoteOutputDetails, CashNoteRedemption, DerivationIndex, DerivedSecretKey,
     MainPubkey, MainSecretKey, NanoTokens, SignedSpend, Spend, SpendReason, Transaction, Transfer,
-    UniquePubkey, WalletError, CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES, NETWORK_ROYALTIES_PK,
+    UniquePubkey, WalletError, NETWORK_ROYALTIES_PK,
 };
 use std::{
     collections::{BTreeMap, BTreeSet, HashSet},
@@ -443,7 +444,7 @@ impl HotWallet {
             let royalties_fee = calculate_royalties_fee(quote.cost);
             let royalties_payee = (
                 royalties_fee,
-                CASHNOTE_PURPOSE_OF_NETWORK_ROYALTIES.to_string(),
+                network_royalties_purpose(xorname),
                 *NETWORK_ROYALTIES_PK,
                 DerivationIndex::random(&mut rng),
             );
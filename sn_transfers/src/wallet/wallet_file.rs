@@ -10,7 +10,7 @@ use super::{
     error::{Error, Result},
     KeyLessWallet,
 };
-use crate::{CashNote, SignedSpend, SpendAddress, UniquePubkey};
+use crate::{CashNote, DerivationIndex, SignedSpend, SpendAddress, UniquePubkey};
 use serde::Serialize;
 use std::{
     collections::BTreeSet,
@@ -23,6 +23,7 @@ const WALLET_FILE_NAME: &str = "wallet";
 const WALLET_LOCK_FILE_NAME: &str = "wallet.lock";
 const CASHNOTES_DIR_NAME: &str = "cash_notes";
 const UNCONFIRMED_TX_NAME: &str = "unconfirmed_spend_requests";
+const DERIVATION_INDEXES_NAME: &str = "derivation_indexes";
 
 /// Writes the `KeyLessWallet` to the specified path.
 pub(super) fn store_wallet(wallet_dir: &Path, wallet: &KeyLessWallet) -> Result<()> {
@@ -83,6 +84,29 @@ pub(super) fn get_unconfirmed_spend_requests(
     Ok(Some(unconfirmed_spend_requests))
 }
 
+/// Writes the derivation indexes of the outputs a watch-only wallet sent to itself to the specified path.
+pub(super) fn store_derivation_indexes(
+    wallet_dir: &Path,
+    derivation_indexes: &BTreeSet<DerivationIndex>,
+) -> Result<()> {
+    let path = wallet_dir.join(DERIVATION_INDEXES_NAME);
+    let mut file = fs::File::create(path)?;
+    let mut serialiser = rmp_serde::encode::Serializer::new(&mut file);
+    derivation_indexes.serialize(&mut serialiser)?;
+    Ok(())
+}
+
+/// Returns the derivation indexes stored at the specified path, empty if the file doesn't exist.
+pub(super) fn get_derivation_indexes(wallet_dir: &Path) -> Result<BTreeSet<DerivationIndex>> {
+    let path = wallet_dir.join(DERIVATION_INDEXES_NAME);
+    if !path.is_file() {
+        return Ok(BTreeSet::new());
+    }
+
+    let file = fs::File::open(&path)?;
+    Ok(rmp_serde::from_read(&file)?)
+}
+
 /// Hex encode and write each `CashNote` to a separate file in respective
 /// recipient public address dir in the created cash_notes dir. Each file is named after the cash_note id.
 pub(super) fn store_created_cash_notes<'a, T>(
@@ -12,14 +12,15 @@ use super::{
     hot_wallet::{TransactionPayeeDetails, WalletExclusiveAccess},
     keys::{get_main_pubkey, store_new_pubkey},
     wallet_file::{
-        load_cash_notes_from_disk, load_created_cash_note, store_created_cash_notes, store_wallet,
-        wallet_lockfile_name,
+        get_derivation_indexes, load_cash_notes_from_disk, load_created_cash_note,
+        store_created_cash_notes, store_derivation_indexes, store_wallet, wallet_lockfile_name,
     },
     KeyLessWallet,
 };
 use crate::{
     transfers::create_unsigned_transfer, wallet::data_payments::PaymentDetails, CashNote,
-    DerivationIndex, MainPubkey, NanoTokens, SpendReason, UniquePubkey, UnsignedTransfer,
+    DerivationIndex, MainPubkey, NanoTokens, SignedSpend, SpendReason, UniquePubkey,
+    UnsignedTransfer,
 };
 #[cfg(not(target_arch = "wasm32"))]
 use fs2::FileExt;
@@ -213,6 +214,86 @@ impl WatchOnlyWallet {
         }
     }
 
+    /// Return the CashNotes held by the wallet that are found in the `cash_notes` dir.
+    pub fn held_cash_notes(&self) -> Vec<CashNote> {
+        self.available_cash_notes()
+            .keys()
+            .filter_map(|id| load_created_cash_note(id, &self.wallet_dir))
+            .collect()
+    }
+
+    /// Return the derivation indexes of the outputs this wallet sent to itself.
+    pub fn known_derivation_indexes(&self) -> Result<BTreeSet<DerivationIndex>> {
+        get_derivation_indexes(&self.wallet_dir)
+    }
+
+    /// Recover the CashNotes created for this wallet by a transaction, from all the spends of that transaction.
+    /// Outputs can only be recovered when their derivation index is known, i.e. the network royalties' public
+    /// indexes or the given `known_indexes`.
+    pub fn recover_cash_notes(
+        &self,
+        parent_spends: &BTreeSet<SignedSpend>,
+        known_indexes: &BTreeSet<DerivationIndex>,
+    ) -> Vec<CashNote> {
+        let Some(parent_tx) = parent_spends.first().map(|s| s.spent_tx()) else {
+            return vec![];
+        };
+        if parent_spends.len() != parent_tx.inputs.len()
+            || parent_spends.iter().any(|s| s.spent_tx() != parent_tx)
+        {
+            warn!("Cannot recover CashNotes without all the spends of their parent transaction");
+            return vec![];
+        }
+
+        let our_keys: BTreeMap<UniquePubkey, DerivationIndex> = parent_spends
+            .iter()
+            .flat_map(|s| s.spend.network_royalties.iter())
+            .chain(known_indexes.iter())
+            .map(|index| (self.main_pubkey.new_unique_pubkey(index), *index))
+            .collect();
+
+        parent_tx
+            .outputs
+            .iter()
+            .filter_map(|output| {
+                let derivation_index = our_keys.get(&output.unique_pubkey)?;
+                Some(CashNote {
+                    unique_pubkey: output.unique_pubkey,
+                    parent_tx: parent_tx.clone(),
+                    parent_spends: parent_spends.clone(),
+                    purpose: output.purpose.clone(),
+                    main_pubkey: self.main_pubkey,
+                    derivation_index: *derivation_index,
+                })
+            })
+            .collect()
+    }
+
+    /// Store the CashNotes found on the Network and remove the ones that were spent,
+    /// then store the updated wallet to disk.
+    /// This function locks the wallet to prevent concurrent processes from writing to it
+    pub fn apply_sync(
+        &mut self,
+        spent_unique_pubkeys: &BTreeSet<UniquePubkey>,
+        found_cash_notes: &[CashNote],
+    ) -> Result<()> {
+        std::fs::create_dir_all(&self.wallet_dir)?;
+        let exclusive_access = self.lock()?;
+        self.reload()?;
+
+        let found_cash_notes: Vec<_> = found_cash_notes
+            .iter()
+            .filter(|cn| !spent_unique_pubkeys.contains(&cn.unique_pubkey()))
+            .collect();
+        for cash_note in found_cash_notes.iter() {
+            store_created_cash_notes([*cash_note], &self.wallet_dir)?;
+        }
+        self.deposit(found_cash_notes)?;
+        self.mark_notes_as_spent(spent_unique_pubkeys);
+
+        self.store(exclusive_access)
+    }
+
     pub fn build_unsigned_transaction(
         &mut self,
         to: Vec<TransactionPayeeDetails>,
@@ -257,6 +338,17 @@ impl WatchOnlyWallet {
             reason_hash,
         )?;
 
+        // keep the derivation indexes of the outputs to ourselves (the change), so that
+        // they can be found on the Network when syncing
+        let mut derivation_indexes = get_derivation_indexes(&self.wallet_dir)?;
+        let new_indexes = unsigned_transfer
+            .output_details
+            .values()
+            .filter(|(_, main_pubkey, _)| *main_pubkey == self.main_pubkey)
+            .map(|(_, _, derivation_index)| *derivation_index);
+        derivation_indexes.extend(new_indexes);
+        store_derivation_indexes(&self.wallet_dir, &derivation_indexes)?;
+
         trace!("Releasing wallet lock"); // by dropping exclusive_access
         std::mem::drop(exclusive_access);
 
@@ -321,10 +413,11 @@ mod tests {
     use crate::{
         genesis::{create_first_cash_note_from_key, GENESIS_CASHNOTE_AMOUNT},
         wallet::KeyLessWallet,
-        MainSecretKey, NanoTokens,
+        MainSecretKey, NanoTokens, SignedSpend, CASHNOTE_PURPOSE_OF_TRANSFER,
     };
     use assert_fs::TempDir;
     use eyre::Result;
+    use std::collections::BTreeSet;
 
     #[test]
     fn watchonly_wallet_basics() -> Result<()> {
@@ -414,4 +507,57 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn watchonly_wallet_sync_recovers_change() -> Result<()> {
+        let main_sk = MainSecretKey::random();
+        let main_pubkey = main_sk.main_pubkey();
+        let wallet_dir = TempDir::new()?;
+        let mut wallet = WatchOnlyWallet::load_from(&wallet_dir, main_pubkey)?;
+
+        let cash_note = create_first_cash_note_from_key(&main_sk)?;
+        wallet.deposit_and_store_to_disk(&vec![cash_note.clone()])?;
+        assert_eq!(1, wallet.held_cash_notes().len());
+
+        let amount = NanoTokens::from(1_000);
+        let recipient = MainSecretKey::random().main_pubkey();
+        let unsigned_transfer = wallet.build_unsigned_transaction(
+            vec![(CASHNOTE_PURPOSE_OF_TRANSFER.to_string(), amount, recipient)],
+            None,
+        )?;
+        let known_indexes = wallet.known_derivation_indexes()?;
+        assert_eq!(1, known_indexes.len());
+
+        // the spends as signed offline and found on the Network
+        let signed_spends: BTreeSet<SignedSpend> = unsigned_transfer
+            .spends
+            .into_iter()
+            .map(|(spend, index)| SignedSpend {
+                derived_key_sig: main_sk
+                    .derive_key(&index)
+                    .sign(&spend.to_bytes_for_signing()),
+                spend,
+            })
+            .collect();
+
+        // nothing can be recovered without the known indexes
+        assert!(wallet
+            .recover_cash_notes(&signed_spends, &BTreeSet::new())
+            .is_empty());
+
+        let recovered = wallet.recover_cash_notes(&signed_spends, &known_indexes);
+        assert_eq!(1, recovered.len());
+        assert_eq!(unsigned_transfer.change_id, recovered[0].unique_pubkey());
+        recovered[0].verify(&main_sk)?;
+
+        let spent = BTreeSet::from([cash_note.unique_pubkey()]);
+        wallet.apply_sync(&spent, &recovered)?;
+        assert_eq!(
+            GENESIS_CASHNOTE_AMOUNT - amount.as_nano(),
+            wallet.balance().as_nano()
+        );
+        assert_eq!(vec![recovered[0].clone()], wallet.held_cash_notes());
+
+        Ok(())
+    }
 }
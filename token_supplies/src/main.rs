@@ -15,14 +15,25 @@ use warp::Filter;
 use dirs_next::home_dir;
 use std::path::PathBuf;
 
-fn data_file_path() -> PathBuf {
+/// The environment variable holding the URL of an auditor, e.g. `http://localhost:4242`
+/// When set, the SNT supply is served from the supply report of that auditor
+const AUDITOR_URL_ENV: &str = "SN_AUDITOR_URL";
+
+fn data_dir_path() -> PathBuf {
     let mut path = home_dir().expect("Could not get home directory");
     path.push(".safe_token_supplies");
     fs::create_dir_all(&path).expect("Failed to create directory");
-    path.push("data.json");
     path
 }
 
+fn data_file_path() -> PathBuf {
+    data_dir_path().join("data.json")
+}
+
+fn supply_report_file_path() -> PathBuf {
+    data_dir_path().join("supply_report.json")
+}
+
 #[derive(Deserialize, Debug, Clone)]
 struct ApiResponse {
     maid_total_circulating_cap: u64,
@@ -64,6 +75,38 @@ async fn scheduled_fetch() {
     }
 }
 
+/// Fetch the supply reconciliation report computed by the auditor from the spend DAG
+async fn fetch_supply_report(auditor_url: &str) -> Result<serde_json::Value, reqwest::Error> {
+    reqwest::get(format!(
+        "{}/api/v1/supply",
+        auditor_url.trim_end_matches('/')
+    ))
+    .await?
+    .error_for_status()?
+    .json::<serde_json::Value>()
+    .await
+}
+
+async fn scheduled_supply_report_fetch(auditor_url: String) {
+    loop {
+        match fetch_supply_report(&auditor_url).await {
+            Ok(report) => match fs::write(supply_report_file_path(), report.to_string()) {
+                Ok(()) => println!("Supply report written to file successfully"),
+                Err(e) => eprintln!("Failed to write supply report to file: {}", e),
+            },
+            Err(e) => eprintln!("Failed to fetch supply report from auditor: {}", e),
+        }
+
+        sleep(Duration::from_secs(3600)).await; // Sleep for 1 hour
+    }
+}
+
+fn read_supply_report_from_file() -> io::Result<serde_json::Value> {
+    let contents = fs::read_to_string(supply_report_file_path())?;
+    let report = serde_json::from_str(&contents)?;
+    Ok(report)
+}
+
 fn write_to_file(data: &SharedData) -> io::Result<()> {
     let json = serde_json::to_string(data)?;
     fs::write(data_file_path(), json)?;
@@ -83,6 +126,12 @@ async fn main() {
     tokio::spawn(async {
         scheduled_fetch().await;
     });
+    match std::env::var(AUDITOR_URL_ENV) {
+        Ok(auditor_url) => {
+            tokio::spawn(scheduled_supply_report_fetch(auditor_url));
+        }
+        Err(_) => println!("{AUDITOR_URL_ENV} is not set, the SNT supply will not be served"),
+    }
 
     let maid_supply = warp::path!("maid").map(|| match read_from_file() {
         Ok(data) => format!("{}", data.maid_supply),
@@ -94,7 +143,28 @@ async fn main() {
         Err(e) => format!("Error reading data: {e}"),
     });
 
-    warp::serve(maid_supply.or(emaid_supply))
-        .run(([0, 0, 0, 0], 3030))
-        .await;
+    let snt_supply = warp::path!("snt").map(|| match read_supply_report_from_file() {
+        Ok(report) => match report.get("circulating") {
+            Some(circulating) => format!("{circulating}"),
+            None => "Error reading data: no circulating supply in report".to_string(),
+        },
+        Err(e) => format!("Error reading data: {e}"),
+    });
+
+    let supply_report = warp::path!("supply").map(|| match read_supply_report_from_file() {
+        Ok(report) => warp::reply::with_status(report.to_string(), warp::http::StatusCode::OK),
+        Err(e) => warp::reply::with_status(
+            format!("Error reading data: {e}"),
+            warp::http::StatusCode::SERVICE_UNAVAILABLE,
+        ),
+    });
+
+    warp::serve(
+        maid_supply
+            .or(emaid_supply)
+            .or(snt_supply)
+            .or(supply_report),
+    )
+    .run(([0, 0, 0, 0], 3030))
+    .await;
 }